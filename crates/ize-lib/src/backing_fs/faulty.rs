@@ -0,0 +1,571 @@
+//! Fault-injecting [`BackingFs`] decorator.
+//!
+//! [`FaultyBackingFs`] wraps another backing store and, according to a list of
+//! [`Fault`] rules, makes selected calls fail with a chosen `errno`, truncates
+//! reads and writes, or delays them.  Everything else is forwarded untouched.
+//! It exists so error paths — a full disk, `EIO` halfway through a write — can
+//! be exercised deterministically in tests.
+//!
+//! ```
+//! use std::path::Path;
+//! use ize_lib::backing_fs::{BackingFs, Fault, FaultyBackingFs, FsMethod, MemBackingFs};
+//!
+//! let fs = FaultyBackingFs::new(MemBackingFs::new());
+//! fs.inject(Fault::errno(FsMethod::Mkdir, libc::ENOSPC).on_path("^full/"));
+//!
+//! assert!(fs.mkdir(Path::new("ok"), 0o755).is_ok());
+//! fs.mkdir(Path::new("full"), 0o755).unwrap();
+//! let err = fs.mkdir(Path::new("full/sub"), 0o755).unwrap_err();
+//! assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+//! ```
+
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use regex::Regex;
+
+use super::{BackingFs, DirEntry};
+
+// ---------------------------------------------------------------------------
+// FsMethod
+// ---------------------------------------------------------------------------
+
+/// The [`BackingFs`] method a [`Fault`] applies to.
+///
+/// `close_fd` cannot report errors and is never faulted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FsMethod {
+    Stat,
+    Fstat,
+    OpenFile,
+    Pread,
+    Pwrite,
+    Fsync,
+    Ftruncate,
+    Mkdir,
+    Rmdir,
+    Readdir,
+    Unlink,
+    Rename,
+    Symlink,
+    Readlink,
+    Chmod,
+    Chown,
+    Utimens,
+    Access,
+    Statvfs,
+}
+
+// ---------------------------------------------------------------------------
+// Fault
+// ---------------------------------------------------------------------------
+
+/// What happens when a [`Fault`] fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultAction {
+    /// Fail the call with this `errno` without reaching the inner store.
+    Errno(i32),
+    /// Cap `pread`/`pwrite` at this many bytes.  Other methods are unaffected.
+    ShortIo(usize),
+    /// Sleep for this long, then forward the call.
+    Delay(Duration),
+}
+
+/// A single fault-injection rule.
+///
+/// A fault matches a call when the method matches (or the fault applies to
+/// any method), and, if a path pattern is set, the call's path matches it.
+/// fd-based methods match against the path the fd was opened with; `rename`
+/// matches if either path does; `statvfs` has no path and never matches a
+/// path-qualified fault.
+#[derive(Debug, Clone)]
+pub struct Fault {
+    method: Option<FsMethod>,
+    path: Option<Regex>,
+    action: FaultAction,
+    skip: usize,
+    times: Option<usize>,
+}
+
+impl Fault {
+    /// Create a fault with an explicit action for `method`.
+    pub fn new(method: FsMethod, action: FaultAction) -> Self {
+        Self {
+            method: Some(method),
+            path: None,
+            action,
+            skip: 0,
+            times: None,
+        }
+    }
+
+    /// Fail `method` with `errno`.
+    pub fn errno(method: FsMethod, errno: i32) -> Self {
+        Self::new(method, FaultAction::Errno(errno))
+    }
+
+    /// Fail every method with `errno`.
+    pub fn any_errno(errno: i32) -> Self {
+        Self {
+            method: None,
+            ..Self::errno(FsMethod::Stat, errno)
+        }
+    }
+
+    /// Cap `pread` or `pwrite` transfers at `max_bytes`.
+    pub fn short_io(method: FsMethod, max_bytes: usize) -> Self {
+        Self::new(method, FaultAction::ShortIo(max_bytes))
+    }
+
+    /// Delay `method` by `latency` before forwarding it.
+    pub fn delay(method: FsMethod, latency: Duration) -> Self {
+        Self::new(method, FaultAction::Delay(latency))
+    }
+
+    /// Only match calls whose relative path matches the regex `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    pub fn on_path(mut self, pattern: &str) -> Self {
+        let regex = Regex::new(pattern)
+            .unwrap_or_else(|e| panic!("invalid fault path pattern {:?}: {}", pattern, e));
+        self.path = Some(regex);
+        self
+    }
+
+    /// Let the first `n` matching calls through before firing.
+    pub fn after(mut self, n: usize) -> Self {
+        self.skip = n;
+        self
+    }
+
+    /// Fire at most `n` times, then stop matching.
+    pub fn times(mut self, n: usize) -> Self {
+        self.times = Some(n);
+        self
+    }
+
+    /// Whether this fault applies to a call of `method` on `paths`.
+    fn matches(&self, method: FsMethod, paths: &[&Path]) -> bool {
+        if self.method.is_some_and(|m| m != method) {
+            return false;
+        }
+        match &self.path {
+            None => true,
+            Some(re) => paths
+                .iter()
+                .any(|p| p.to_str().is_some_and(|s| re.is_match(s))),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// FaultyBackingFs
+// ---------------------------------------------------------------------------
+
+/// A [`BackingFs`] that injects faults into calls to an inner store.
+///
+/// Faults can be added and cleared at any time through `&self`, so a test can
+/// keep an `Arc` to the decorator after handing it to a filesystem.  When
+/// several faults match a call the one injected first wins.
+pub struct FaultyBackingFs<B: BackingFs> {
+    inner: B,
+    faults: Mutex<Vec<Fault>>,
+    /// Paths of fds opened through this decorator, for matching fd-based calls.
+    fd_paths: Mutex<HashMap<RawFd, PathBuf>>,
+    /// Number of faults that fired (delays included).
+    injected: AtomicUsize,
+}
+
+impl<B: BackingFs> FaultyBackingFs<B> {
+    /// Wrap `inner` with no faults configured.
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            faults: Mutex::new(Vec::new()),
+            fd_paths: Mutex::new(HashMap::new()),
+            injected: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add a fault rule.
+    pub fn inject(&self, fault: Fault) {
+        self.faults.lock().unwrap().push(fault);
+    }
+
+    /// Builder-style variant of [`inject`](Self::inject).
+    pub fn with_fault(self, fault: Fault) -> Self {
+        self.inject(fault);
+        self
+    }
+
+    /// Remove all fault rules.
+    pub fn clear(&self) {
+        self.faults.lock().unwrap().clear();
+    }
+
+    /// Number of times a fault has fired so far.
+    pub fn injected_count(&self) -> usize {
+        self.injected.load(Ordering::SeqCst)
+    }
+
+    /// Get a reference to the wrapped store.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    // -- Internal helpers ---------------------------------------------------
+
+    /// Find the fault (if any) that fires for this call and consume one of
+    /// its remaining uses.
+    fn fire(&self, method: FsMethod, paths: &[&Path]) -> Option<FaultAction> {
+        let mut faults = self.faults.lock().unwrap();
+        for fault in faults.iter_mut() {
+            if !fault.matches(method, paths) {
+                continue;
+            }
+            if fault.skip > 0 {
+                fault.skip -= 1;
+                continue;
+            }
+            match fault.times {
+                Some(0) => continue,
+                Some(ref mut n) => *n -= 1,
+                None => {}
+            }
+            self.injected.fetch_add(1, Ordering::SeqCst);
+            return Some(fault.action);
+        }
+        None
+    }
+
+    /// Apply errno and delay faults for a call; returns the short-I/O cap if
+    /// one fired.
+    fn check(&self, method: FsMethod, paths: &[&Path]) -> io::Result<Option<usize>> {
+        match self.fire(method, paths) {
+            Some(FaultAction::Errno(errno)) => Err(io::Error::from_raw_os_error(errno)),
+            Some(FaultAction::ShortIo(max)) => Ok(Some(max)),
+            Some(FaultAction::Delay(latency)) => {
+                thread::sleep(latency);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Path an fd was opened with, or empty if unknown.
+    fn fd_path(&self, fd: RawFd) -> PathBuf {
+        self.fd_paths
+            .lock()
+            .unwrap()
+            .get(&fd)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Like [`check`](Self::check) for fd-based methods.
+    fn check_fd(&self, method: FsMethod, fd: RawFd) -> io::Result<Option<usize>> {
+        let path = self.fd_path(fd);
+        self.check(method, &[&path])
+    }
+}
+
+impl<B: BackingFs> BackingFs for FaultyBackingFs<B> {
+    // -- Stat ---------------------------------------------------------------
+
+    fn stat(&self, rel: &Path) -> io::Result<libc::stat> {
+        self.check(FsMethod::Stat, &[rel])?;
+        self.inner.stat(rel)
+    }
+
+    fn fstat(&self, fd: RawFd) -> io::Result<libc::stat> {
+        self.check_fd(FsMethod::Fstat, fd)?;
+        self.inner.fstat(fd)
+    }
+
+    // -- File I/O -----------------------------------------------------------
+
+    fn open_file(&self, rel: &Path, flags: i32, mode: u32) -> io::Result<RawFd> {
+        self.check(FsMethod::OpenFile, &[rel])?;
+        let fd = self.inner.open_file(rel, flags, mode)?;
+        self.fd_paths.lock().unwrap().insert(fd, rel.to_path_buf());
+        Ok(fd)
+    }
+
+    fn pread(&self, fd: RawFd, buf: &mut [u8], offset: i64) -> io::Result<usize> {
+        let len = match self.check_fd(FsMethod::Pread, fd)? {
+            Some(max) => buf.len().min(max),
+            None => buf.len(),
+        };
+        self.inner.pread(fd, &mut buf[..len], offset)
+    }
+
+    fn pwrite(&self, fd: RawFd, data: &[u8], offset: i64) -> io::Result<usize> {
+        let len = match self.check_fd(FsMethod::Pwrite, fd)? {
+            Some(max) => data.len().min(max),
+            None => data.len(),
+        };
+        self.inner.pwrite(fd, &data[..len], offset)
+    }
+
+    fn fsync(&self, fd: RawFd) -> io::Result<()> {
+        self.check_fd(FsMethod::Fsync, fd)?;
+        self.inner.fsync(fd)
+    }
+
+    fn close_fd(&self, fd: RawFd) {
+        self.fd_paths.lock().unwrap().remove(&fd);
+        self.inner.close_fd(fd)
+    }
+
+    fn ftruncate(&self, fd: RawFd, size: u64) -> io::Result<()> {
+        self.check_fd(FsMethod::Ftruncate, fd)?;
+        self.inner.ftruncate(fd, size)
+    }
+
+    // -- Directory ops ------------------------------------------------------
+
+    fn mkdir(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        self.check(FsMethod::Mkdir, &[rel])?;
+        self.inner.mkdir(rel, mode)
+    }
+
+    fn rmdir(&self, rel: &Path) -> io::Result<()> {
+        self.check(FsMethod::Rmdir, &[rel])?;
+        self.inner.rmdir(rel)
+    }
+
+    fn readdir(&self, rel: &Path) -> io::Result<Vec<DirEntry>> {
+        self.check(FsMethod::Readdir, &[rel])?;
+        self.inner.readdir(rel)
+    }
+
+    // -- Path ops -----------------------------------------------------------
+
+    fn unlink(&self, rel: &Path) -> io::Result<()> {
+        self.check(FsMethod::Unlink, &[rel])?;
+        self.inner.unlink(rel)
+    }
+
+    fn rename(&self, old: &Path, new: &Path) -> io::Result<()> {
+        self.check(FsMethod::Rename, &[old, new])?;
+        self.inner.rename(old, new)
+    }
+
+    fn symlink(&self, target: &Path, rel: &Path) -> io::Result<()> {
+        self.check(FsMethod::Symlink, &[rel])?;
+        self.inner.symlink(target, rel)
+    }
+
+    fn readlink(&self, rel: &Path) -> io::Result<PathBuf> {
+        self.check(FsMethod::Readlink, &[rel])?;
+        self.inner.readlink(rel)
+    }
+
+    // -- Metadata -----------------------------------------------------------
+
+    fn chmod(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        self.check(FsMethod::Chmod, &[rel])?;
+        self.inner.chmod(rel, mode)
+    }
+
+    fn chown(&self, rel: &Path, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+        self.check(FsMethod::Chown, &[rel])?;
+        self.inner.chown(rel, uid, gid)
+    }
+
+    fn utimens(
+        &self,
+        rel: &Path,
+        atime: &libc::timespec,
+        mtime: &libc::timespec,
+    ) -> io::Result<()> {
+        self.check(FsMethod::Utimens, &[rel])?;
+        self.inner.utimens(rel, atime, mtime)
+    }
+
+    fn access(&self, rel: &Path, mask: i32) -> io::Result<()> {
+        self.check(FsMethod::Access, &[rel])?;
+        self.inner.access(rel, mask)
+    }
+
+    // -- Filesystem ---------------------------------------------------------
+
+    fn statvfs(&self) -> io::Result<libc::statvfs> {
+        self.check(FsMethod::Statvfs, &[])?;
+        self.inner.statvfs()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backing_fs::MemBackingFs;
+    use std::time::Instant;
+
+    fn faulty() -> FaultyBackingFs<MemBackingFs> {
+        FaultyBackingFs::new(MemBackingFs::new())
+    }
+
+    fn errno_of<T: std::fmt::Debug>(r: io::Result<T>) -> i32 {
+        r.unwrap_err().raw_os_error().unwrap()
+    }
+
+    #[test]
+    fn no_faults_forwards_everything() {
+        let fs = faulty();
+        fs.mkdir(Path::new("d"), 0o755).unwrap();
+        let fd = fs
+            .open_file(Path::new("d/f"), libc::O_CREAT | libc::O_RDWR, 0o644)
+            .unwrap();
+        assert_eq!(fs.pwrite(fd, b"hello", 0).unwrap(), 5);
+        fs.close_fd(fd);
+        assert_eq!(fs.inner().read_all(Path::new("d/f")).unwrap(), b"hello");
+        assert_eq!(fs.injected_count(), 0);
+    }
+
+    #[test]
+    fn errno_fault_blocks_call() {
+        let fs = faulty().with_fault(Fault::errno(FsMethod::Mkdir, libc::EIO));
+
+        assert_eq!(errno_of(fs.mkdir(Path::new("d"), 0o755)), libc::EIO);
+        assert_eq!(
+            errno_of(fs.inner().stat(Path::new("d"))),
+            libc::ENOENT,
+            "failed mkdir must not reach the inner store"
+        );
+        assert_eq!(fs.injected_count(), 1);
+    }
+
+    #[test]
+    fn fault_only_hits_its_method() {
+        let fs = faulty().with_fault(Fault::errno(FsMethod::Unlink, libc::EIO));
+        fs.mkdir(Path::new("d"), 0o755).unwrap();
+        fs.rmdir(Path::new("d")).unwrap();
+        assert_eq!(fs.injected_count(), 0);
+    }
+
+    #[test]
+    fn path_pattern_limits_fault() {
+        let fs = faulty().with_fault(Fault::errno(FsMethod::Mkdir, libc::ENOSPC).on_path(r"^big"));
+
+        fs.mkdir(Path::new("small"), 0o755).unwrap();
+        assert_eq!(errno_of(fs.mkdir(Path::new("big"), 0o755)), libc::ENOSPC);
+    }
+
+    #[test]
+    fn fd_methods_match_open_path() {
+        let fs = faulty().with_fault(Fault::errno(FsMethod::Pwrite, libc::EIO).on_path(r"\.log$"));
+
+        let log = fs
+            .open_file(Path::new("a.log"), libc::O_CREAT | libc::O_RDWR, 0o644)
+            .unwrap();
+        let txt = fs
+            .open_file(Path::new("a.txt"), libc::O_CREAT | libc::O_RDWR, 0o644)
+            .unwrap();
+
+        assert_eq!(errno_of(fs.pwrite(log, b"x", 0)), libc::EIO);
+        assert_eq!(fs.pwrite(txt, b"x", 0).unwrap(), 1);
+
+        fs.close_fd(log);
+        fs.close_fd(txt);
+    }
+
+    #[test]
+    fn rename_matches_either_path() {
+        let fs = faulty().with_fault(Fault::errno(FsMethod::Rename, libc::EXDEV).on_path("^dst$"));
+        fs.mkdir(Path::new("src"), 0o755).unwrap();
+
+        assert_eq!(
+            errno_of(fs.rename(Path::new("src"), Path::new("dst"))),
+            libc::EXDEV
+        );
+        fs.rename(Path::new("src"), Path::new("other")).unwrap();
+    }
+
+    #[test]
+    fn short_write_truncates_data() {
+        let fs = faulty().with_fault(Fault::short_io(FsMethod::Pwrite, 3));
+        let fd = fs
+            .open_file(Path::new("f"), libc::O_CREAT | libc::O_RDWR, 0o644)
+            .unwrap();
+
+        assert_eq!(fs.pwrite(fd, b"hello", 0).unwrap(), 3);
+        fs.close_fd(fd);
+        assert_eq!(fs.inner().read_all(Path::new("f")).unwrap(), b"hel");
+    }
+
+    #[test]
+    fn short_read_truncates_buffer() {
+        let fs = faulty().with_fault(Fault::short_io(FsMethod::Pread, 2));
+        let fd = fs
+            .open_file(Path::new("f"), libc::O_CREAT | libc::O_RDWR, 0o644)
+            .unwrap();
+        fs.pwrite(fd, b"hello", 0).unwrap();
+
+        let mut buf = [0u8; 5];
+        assert_eq!(fs.pread(fd, &mut buf, 0).unwrap(), 2);
+        assert_eq!(&buf[..2], b"he");
+        fs.close_fd(fd);
+    }
+
+    #[test]
+    fn after_and_times_bound_the_fault() {
+        let fs = faulty().with_fault(Fault::errno(FsMethod::Mkdir, libc::EIO).after(1).times(1));
+
+        fs.mkdir(Path::new("a"), 0o755).unwrap();
+        assert_eq!(errno_of(fs.mkdir(Path::new("b"), 0o755)), libc::EIO);
+        fs.mkdir(Path::new("c"), 0o755).unwrap();
+        assert_eq!(fs.injected_count(), 1);
+    }
+
+    #[test]
+    fn any_errno_hits_every_method() {
+        let fs = faulty().with_fault(Fault::any_errno(libc::EIO));
+        assert_eq!(errno_of(fs.stat(Path::new(""))), libc::EIO);
+        assert_eq!(errno_of(fs.statvfs()), libc::EIO);
+        assert_eq!(errno_of(fs.readdir(Path::new(""))), libc::EIO);
+    }
+
+    #[test]
+    fn path_fault_never_matches_statvfs() {
+        let fs = faulty().with_fault(Fault::errno(FsMethod::Statvfs, libc::EIO).on_path(".*"));
+        fs.statvfs().unwrap();
+    }
+
+    #[test]
+    fn first_injected_fault_wins() {
+        let fs = faulty()
+            .with_fault(Fault::errno(FsMethod::Mkdir, libc::ENOSPC))
+            .with_fault(Fault::errno(FsMethod::Mkdir, libc::EIO));
+        assert_eq!(errno_of(fs.mkdir(Path::new("d"), 0o755)), libc::ENOSPC);
+    }
+
+    #[test]
+    fn clear_removes_faults() {
+        let fs = faulty().with_fault(Fault::errno(FsMethod::Mkdir, libc::EIO));
+        fs.clear();
+        fs.mkdir(Path::new("d"), 0o755).unwrap();
+    }
+
+    #[test]
+    fn delay_then_forward() {
+        let fs = faulty().with_fault(Fault::delay(FsMethod::Mkdir, Duration::from_millis(20)));
+
+        let start = Instant::now();
+        fs.mkdir(Path::new("d"), 0o755).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(fs.inner().stat(Path::new("d")).is_ok());
+    }
+}
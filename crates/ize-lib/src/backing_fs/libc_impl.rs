@@ -16,7 +16,7 @@ use std::io;
 use std::os::unix::ffi::OsStrExt;
 use std::os::unix::ffi::OsStringExt;
 use std::os::unix::io::RawFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use super::{BackingFs, DirEntry};
 
@@ -342,6 +342,36 @@ impl BackingFs for LibcBackingFs {
 
     // -- Metadata -----------------------------------------------------------
 
+    fn symlink(&self, target: &Path, rel: &Path) -> io::Result<()> {
+        let c_target = CString::new(target.as_os_str().as_bytes())
+            .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+        let c_path = rel_cpath(rel)?;
+        let rc = unsafe { libc::symlinkat(c_target.as_ptr(), self.base_fd, c_path.as_ptr()) };
+        if rc < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn readlink(&self, rel: &Path) -> io::Result<PathBuf> {
+        let c_path = rel_cpath(rel)?;
+        let mut buf = vec![0u8; libc::PATH_MAX as usize];
+        let n = unsafe {
+            libc::readlinkat(
+                self.base_fd,
+                c_path.as_ptr(),
+                buf.as_mut_ptr() as *mut libc::c_char,
+                buf.len(),
+            )
+        };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        buf.truncate(n as usize);
+        Ok(PathBuf::from(OsString::from_vec(buf)))
+    }
+
     fn chmod(&self, rel: &Path, mode: u32) -> io::Result<()> {
         let c_path = rel_cpath(rel)?;
         let rc = unsafe { libc::fchmodat(self.base_fd, c_path.as_ptr(), mode as libc::mode_t, 0) };
@@ -709,6 +739,27 @@ mod tests {
         assert!(names.contains(&"b.txt".to_string()), "entries: {:?}", names);
     }
 
+    #[test]
+    fn symlink_and_readlink() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (_dir, backing) = make_backing(tmp.path());
+
+        backing
+            .symlink(Path::new("missing-target"), Path::new("link"))
+            .unwrap();
+
+        let st = backing.stat(Path::new("link")).unwrap();
+        assert_eq!(st.st_mode & libc::S_IFMT, libc::S_IFLNK);
+        assert_eq!(
+            backing.readlink(Path::new("link")).unwrap(),
+            Path::new("missing-target")
+        );
+        assert_eq!(
+            fs::read_link(tmp.path().join("link")).unwrap(),
+            Path::new("missing-target")
+        );
+    }
+
     #[test]
     fn chown_doesnt_panic() {
         // chown may fail if not root, but it shouldn't panic.
@@ -0,0 +1,1531 @@
+//! In-memory [`BackingFs`](super::BackingFs) implementation.
+//!
+//! [`MemBackingFs`] keeps the whole tree — inodes, file contents, open file
+//! descriptors and permission bits — in process memory.  It exists so the
+//! passthrough filesystem, observers and recorder can be exercised in unit
+//! tests without touching the host disk or requiring FUSE.
+//!
+//! # Semantics
+//!
+//! The implementation aims to return the same `errno` values Linux returns
+//! for the same call sequence:
+//!
+//! * Path resolution requires search (`x`) permission on every ancestor and
+//!   fails with `ENOTDIR` when a non-final component is not a directory.
+//! * Permission checks follow the owner / group / other classes of the
+//!   configured caller credentials; uid 0 bypasses read/write checks.
+//! * Unlinked files stay readable through already-open descriptors until the
+//!   last descriptor is closed.
+//! * An optional byte capacity makes writes fail with `ENOSPC` and is
+//!   reflected in [`statvfs`](super::BackingFs::statvfs).
+//!
+//! Symlinks are stored and reported by `stat`, `readdir` and `readlink` but
+//! never followed: opening one fails with `ELOOP` (as with `O_NOFOLLOW`) and
+//! using one as a directory component fails with `ENOTDIR`.  Under FUSE the
+//! kernel resolves symlinks itself, so the passthrough never asks a backing
+//! store to follow one.  Hard links and device nodes are not modelled.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{BackingFs, DirEntry};
+
+// ---------------------------------------------------------------------------
+// Constants
+// ---------------------------------------------------------------------------
+
+/// Inode number of the backing root.
+const ROOT_INO: u64 = 1;
+
+/// Block size reported by `stat` and `statvfs`.
+const BLOCK_SIZE: u64 = 4096;
+
+/// Maximum length of a single path component.
+const NAME_MAX: usize = 255;
+
+/// First file descriptor handed out by [`MemBackingFs::open_file`].
+const FIRST_FD: RawFd = 3;
+
+/// Default capacity when none is configured (1 GiB).
+const DEFAULT_CAPACITY: u64 = 1 << 30;
+
+/// Default inode limit when none is configured.
+const DEFAULT_MAX_INODES: u64 = 1 << 20;
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Build an `io::Error` from a raw errno value.
+fn errno(code: i32) -> io::Error {
+    io::Error::from_raw_os_error(code)
+}
+
+/// Current wall-clock time as a `timespec`.
+fn now() -> libc::timespec {
+    let d = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    libc::timespec {
+        tv_sec: d.as_secs() as libc::time_t,
+        tv_nsec: d.subsec_nanos() as libc::c_long,
+    }
+}
+
+/// Resolve a `utimensat`-style timespec (honouring `UTIME_NOW` / `UTIME_OMIT`).
+fn resolve_time(ts: &libc::timespec, current: libc::timespec) -> libc::timespec {
+    match ts.tv_nsec {
+        libc::UTIME_NOW => now(),
+        libc::UTIME_OMIT => current,
+        _ => *ts,
+    }
+}
+
+/// Split a relative path into its normal components.
+///
+/// `.` components are skipped.  Absolute paths and `..` are rejected with
+/// `EINVAL` — every caller of [`BackingFs`] passes paths that are already
+/// normalised relative to the root.
+fn components(rel: &Path) -> io::Result<Vec<&OsStr>> {
+    let mut out = Vec::new();
+    for comp in rel.components() {
+        match comp {
+            Component::Normal(name) => {
+                if name.as_bytes().len() > NAME_MAX {
+                    return Err(errno(libc::ENAMETOOLONG));
+                }
+                out.push(name);
+            }
+            Component::CurDir => {}
+            _ => return Err(errno(libc::EINVAL)),
+        }
+    }
+    Ok(out)
+}
+
+// ---------------------------------------------------------------------------
+// Node
+// ---------------------------------------------------------------------------
+
+/// Content of an inode.
+#[derive(Debug)]
+enum NodeKind {
+    File(Vec<u8>),
+    Dir(BTreeMap<OsString, u64>),
+    Symlink(PathBuf),
+}
+
+/// A single inode in the in-memory tree.
+#[derive(Debug)]
+struct Node {
+    kind: NodeKind,
+    /// Permission bits only (`0o7777`); the type comes from `kind`.
+    perm: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u64,
+    atime: libc::timespec,
+    mtime: libc::timespec,
+    ctime: libc::timespec,
+}
+
+impl Node {
+    fn new(kind: NodeKind, perm: u32, uid: u32, gid: u32) -> Self {
+        let t = now();
+        let nlink = match kind {
+            NodeKind::Dir(_) => 2,
+            NodeKind::File(_) | NodeKind::Symlink(_) => 1,
+        };
+        Self {
+            kind,
+            perm: perm & 0o7777,
+            uid,
+            gid,
+            nlink,
+            atime: t,
+            mtime: t,
+            ctime: t,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        matches!(self.kind, NodeKind::Dir(_))
+    }
+
+    fn size(&self) -> u64 {
+        match &self.kind {
+            NodeKind::File(data) => data.len() as u64,
+            NodeKind::Dir(_) => BLOCK_SIZE,
+            NodeKind::Symlink(target) => target.as_os_str().len() as u64,
+        }
+    }
+
+    fn dtype(&self) -> u8 {
+        match self.kind {
+            NodeKind::File(_) => libc::DT_REG,
+            NodeKind::Dir(_) => libc::DT_DIR,
+            NodeKind::Symlink(_) => libc::DT_LNK,
+        }
+    }
+
+    fn to_stat(&self, ino: u64) -> libc::stat {
+        // SAFETY: `libc::stat` is a plain C struct; all-zero is a valid value.
+        let mut st: libc::stat = unsafe { std::mem::zeroed() };
+        let fmt = match self.kind {
+            NodeKind::File(_) => libc::S_IFREG,
+            NodeKind::Dir(_) => libc::S_IFDIR,
+            NodeKind::Symlink(_) => libc::S_IFLNK,
+        };
+        let size = self.size();
+        st.st_ino = ino;
+        st.st_mode = fmt | self.perm as libc::mode_t;
+        st.st_nlink = self.nlink as _;
+        st.st_uid = self.uid;
+        st.st_gid = self.gid;
+        st.st_size = size as libc::off_t;
+        st.st_blksize = BLOCK_SIZE as _;
+        st.st_blocks = size.div_ceil(512) as _;
+        st.st_atime = self.atime.tv_sec;
+        st.st_atime_nsec = self.atime.tv_nsec as _;
+        st.st_mtime = self.mtime.tv_sec;
+        st.st_mtime_nsec = self.mtime.tv_nsec as _;
+        st.st_ctime = self.ctime.tv_sec;
+        st.st_ctime_nsec = self.ctime.tv_nsec as _;
+        st
+    }
+}
+
+/// An open file description.
+#[derive(Debug, Clone, Copy)]
+struct OpenFd {
+    ino: u64,
+    flags: i32,
+}
+
+impl OpenFd {
+    fn readable(&self) -> bool {
+        self.flags & libc::O_ACCMODE != libc::O_WRONLY
+    }
+
+    fn writable(&self) -> bool {
+        self.flags & libc::O_ACCMODE != libc::O_RDONLY
+    }
+}
+
+// ---------------------------------------------------------------------------
+// State
+// ---------------------------------------------------------------------------
+
+/// Everything behind the [`MemBackingFs`] lock.
+#[derive(Debug)]
+struct State {
+    nodes: HashMap<u64, Node>,
+    fds: HashMap<RawFd, OpenFd>,
+    next_ino: u64,
+    next_fd: RawFd,
+    /// Sum of all regular-file sizes.
+    used_bytes: u64,
+}
+
+impl State {
+    fn node(&self, ino: u64) -> io::Result<&Node> {
+        self.nodes.get(&ino).ok_or_else(|| errno(libc::ENOENT))
+    }
+
+    fn node_mut(&mut self, ino: u64) -> io::Result<&mut Node> {
+        self.nodes.get_mut(&ino).ok_or_else(|| errno(libc::ENOENT))
+    }
+
+    fn open_fd(&self, fd: RawFd) -> io::Result<OpenFd> {
+        self.fds.get(&fd).copied().ok_or_else(|| errno(libc::EBADF))
+    }
+
+    fn alloc_ino(&mut self) -> u64 {
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        ino
+    }
+
+    /// Drop an inode once it has neither links nor open descriptors.
+    fn maybe_reap(&mut self, ino: u64) {
+        let unlinked = self.nodes.get(&ino).is_some_and(|n| n.nlink == 0);
+        let open = self.fds.values().any(|f| f.ino == ino);
+        if unlinked && !open {
+            if let Some(node) = self.nodes.remove(&ino) {
+                if let NodeKind::File(data) = node.kind {
+                    self.used_bytes -= data.len() as u64;
+                }
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// MemBackingFs
+// ---------------------------------------------------------------------------
+
+/// A [`BackingFs`] that stores everything in memory.
+///
+/// # Construction
+///
+/// * [`new`](Self::new) — empty tree owned by the calling process's
+///   effective uid/gid.
+/// * [`with_credentials`](Self::with_credentials) — act as a specific
+///   uid/gid for permission checks (e.g. to test `EACCES` paths while the
+///   test suite runs as root).
+/// * [`with_capacity`](Self::with_capacity) — limit the number of bytes that
+///   may be stored before writes fail with `ENOSPC`.
+///
+/// All state lives behind a single mutex, so the type is `Send + Sync` and
+/// every operation is atomic with respect to the others.
+#[derive(Debug)]
+pub struct MemBackingFs {
+    state: Mutex<State>,
+    /// Caller uid used for ownership of new nodes and permission checks.
+    uid: u32,
+    /// Caller gid used for ownership of new nodes and permission checks.
+    gid: u32,
+    /// Maximum number of file-content bytes.
+    capacity: u64,
+    /// Maximum number of inodes.
+    max_inodes: u64,
+}
+
+impl Default for MemBackingFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemBackingFs {
+    /// Create an empty in-memory tree owned by the current effective
+    /// uid/gid.
+    pub fn new() -> Self {
+        // SAFETY: geteuid/getegid cannot fail.
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        Self::with_credentials(uid, gid)
+    }
+
+    /// Create an empty in-memory tree that performs permission checks as
+    /// `uid`/`gid`.  The root directory is owned by the same credentials.
+    pub fn with_credentials(uid: u32, gid: u32) -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(
+            ROOT_INO,
+            Node::new(NodeKind::Dir(BTreeMap::new()), 0o755, uid, gid),
+        );
+        Self {
+            state: Mutex::new(State {
+                nodes,
+                fds: HashMap::new(),
+                next_ino: ROOT_INO + 1,
+                next_fd: FIRST_FD,
+                used_bytes: 0,
+            }),
+            uid,
+            gid,
+            capacity: DEFAULT_CAPACITY,
+            max_inodes: DEFAULT_MAX_INODES,
+        }
+    }
+
+    /// Limit the total number of file-content bytes the tree may hold.
+    pub fn with_capacity(mut self, bytes: u64) -> Self {
+        self.capacity = bytes;
+        self
+    }
+
+    /// Limit the total number of inodes (files + directories, including the
+    /// root) the tree may hold.
+    pub fn with_max_inodes(mut self, inodes: u64) -> Self {
+        self.max_inodes = inodes;
+        self
+    }
+
+    /// Number of file descriptors currently open.
+    ///
+    /// Useful for asserting that callers close everything they open.
+    pub fn open_fd_count(&self) -> usize {
+        self.state.lock().unwrap().fds.len()
+    }
+
+    /// Read the full content of a regular file.
+    ///
+    /// A convenience for test assertions — equivalent to an
+    /// `open_file` / `pread` / `close_fd` sequence but without permission
+    /// checks.
+    pub fn read_all(&self, rel: &Path) -> io::Result<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        let ino = self.lookup(&state, rel, false)?;
+        match &state.node(ino)?.kind {
+            NodeKind::File(data) => Ok(data.clone()),
+            NodeKind::Dir(_) => Err(errno(libc::EISDIR)),
+            NodeKind::Symlink(_) => Err(errno(libc::ELOOP)),
+        }
+    }
+
+    // -- Permission helpers -------------------------------------------------
+
+    /// Check `mask` (`R_OK | W_OK | X_OK`) against a node for the caller.
+    fn check_perm(&self, node: &Node, mask: i32) -> io::Result<()> {
+        if mask == libc::F_OK {
+            return Ok(());
+        }
+        if self.uid == 0 {
+            // root bypasses read/write checks; execute needs at least one
+            // x bit on regular files.
+            if mask & libc::X_OK != 0 && !node.is_dir() && node.perm & 0o111 == 0 {
+                return Err(errno(libc::EACCES));
+            }
+            return Ok(());
+        }
+        let class = if node.uid == self.uid {
+            (node.perm >> 6) & 0o7
+        } else if node.gid == self.gid {
+            (node.perm >> 3) & 0o7
+        } else {
+            node.perm & 0o7
+        };
+        let wanted = (if mask & libc::R_OK != 0 { 0o4 } else { 0 })
+            | (if mask & libc::W_OK != 0 { 0o2 } else { 0 })
+            | (if mask & libc::X_OK != 0 { 0o1 } else { 0 });
+        if class & wanted == wanted {
+            Ok(())
+        } else {
+            Err(errno(libc::EACCES))
+        }
+    }
+
+    /// Only the owner (or root) may change mode bits and timestamps.
+    fn check_owner(&self, node: &Node) -> io::Result<()> {
+        if self.uid == 0 || node.uid == self.uid {
+            Ok(())
+        } else {
+            Err(errno(libc::EPERM))
+        }
+    }
+
+    // -- Path resolution ----------------------------------------------------
+
+    /// Walk `names` from the root, checking search permission on every
+    /// directory traversed.
+    fn walk(&self, state: &State, names: &[&OsStr], check: bool) -> io::Result<u64> {
+        let mut ino = ROOT_INO;
+        for name in names {
+            let node = state.node(ino)?;
+            let NodeKind::Dir(children) = &node.kind else {
+                return Err(errno(libc::ENOTDIR));
+            };
+            if check {
+                self.check_perm(node, libc::X_OK)?;
+            }
+            ino = *children.get(*name).ok_or_else(|| errno(libc::ENOENT))?;
+        }
+        Ok(ino)
+    }
+
+    /// Resolve a relative path to an inode number.
+    fn lookup(&self, state: &State, rel: &Path, check: bool) -> io::Result<u64> {
+        let names = components(rel)?;
+        self.walk(state, &names, check)
+    }
+
+    /// Resolve the parent directory of `rel` and return it with the final
+    /// component.  Fails with `EINVAL` for the root itself.
+    fn lookup_parent<'p>(&self, state: &State, rel: &'p Path) -> io::Result<(u64, &'p OsStr)> {
+        let names = components(rel)?;
+        let (last, parents) = names.split_last().ok_or_else(|| errno(libc::EINVAL))?;
+        let parent = self.walk(state, parents, true)?;
+        let node = state.node(parent)?;
+        if !node.is_dir() {
+            return Err(errno(libc::ENOTDIR));
+        }
+        self.check_perm(node, libc::X_OK)?;
+        Ok((parent, last))
+    }
+
+    /// Look up `name` inside directory `parent`.
+    fn child(state: &State, parent: u64, name: &OsStr) -> Option<u64> {
+        match &state.nodes.get(&parent)?.kind {
+            NodeKind::Dir(children) => children.get(name).copied(),
+            NodeKind::File(_) | NodeKind::Symlink(_) => None,
+        }
+    }
+
+    /// Insert a new node under `parent` and return its inode number.
+    fn link_new(
+        &self,
+        state: &mut State,
+        parent: u64,
+        name: &OsStr,
+        kind: NodeKind,
+        mode: u32,
+    ) -> io::Result<u64> {
+        if state.nodes.len() as u64 >= self.max_inodes {
+            return Err(errno(libc::ENOSPC));
+        }
+        let is_dir = matches!(kind, NodeKind::Dir(_));
+        let ino = state.alloc_ino();
+        state
+            .nodes
+            .insert(ino, Node::new(kind, mode, self.uid, self.gid));
+        let t = now();
+        let p = state.node_mut(parent)?;
+        if let NodeKind::Dir(children) = &mut p.kind {
+            children.insert(name.to_os_string(), ino);
+        }
+        if is_dir {
+            p.nlink += 1;
+        }
+        p.mtime = t;
+        p.ctime = t;
+        Ok(ino)
+    }
+
+    /// Remove `name` from directory `parent` (the entry must exist).
+    fn unlink_entry(state: &mut State, parent: u64, name: &OsStr) -> Option<u64> {
+        let t = now();
+        let p = state.nodes.get_mut(&parent)?;
+        let NodeKind::Dir(children) = &mut p.kind else {
+            return None;
+        };
+        let ino = children.remove(name)?;
+        p.mtime = t;
+        p.ctime = t;
+        Some(ino)
+    }
+
+    /// Is `ino` equal to, or a descendant of, directory `ancestor`?
+    fn is_within(state: &State, ancestor: u64, ino: u64) -> bool {
+        if ancestor == ino {
+            return true;
+        }
+        match state.nodes.get(&ancestor).map(|n| &n.kind) {
+            Some(NodeKind::Dir(children)) => children
+                .values()
+                .any(|&child| Self::is_within(state, child, ino)),
+            _ => false,
+        }
+    }
+}
+
+impl BackingFs for MemBackingFs {
+    // -- Stat ---------------------------------------------------------------
+
+    fn stat(&self, rel: &Path) -> io::Result<libc::stat> {
+        let state = self.state.lock().unwrap();
+        let ino = self.lookup(&state, rel, true)?;
+        Ok(state.node(ino)?.to_stat(ino))
+    }
+
+    fn fstat(&self, fd: RawFd) -> io::Result<libc::stat> {
+        let state = self.state.lock().unwrap();
+        let open = state.open_fd(fd)?;
+        Ok(state.node(open.ino)?.to_stat(open.ino))
+    }
+
+    // -- File I/O -----------------------------------------------------------
+
+    fn open_file(&self, rel: &Path, flags: i32, mode: u32) -> io::Result<RawFd> {
+        let mut state = self.state.lock().unwrap();
+        let accmode = flags & libc::O_ACCMODE;
+
+        let (ino, created) = if rel.as_os_str().is_empty() {
+            (ROOT_INO, false)
+        } else {
+            let (parent, name) = self.lookup_parent(&state, rel)?;
+            match Self::child(&state, parent, name) {
+                Some(_) if flags & libc::O_CREAT != 0 && flags & libc::O_EXCL != 0 => {
+                    return Err(errno(libc::EEXIST));
+                }
+                Some(ino) => (ino, false),
+                None if flags & libc::O_CREAT != 0 => {
+                    self.check_perm(state.node(parent)?, libc::W_OK | libc::X_OK)?;
+                    let kind = NodeKind::File(Vec::new());
+                    (self.link_new(&mut state, parent, name, kind, mode)?, true)
+                }
+                None => return Err(errno(libc::ENOENT)),
+            }
+        };
+
+        let node = state.node(ino)?;
+        if matches!(node.kind, NodeKind::Symlink(_)) {
+            return Err(errno(libc::ELOOP));
+        }
+        if node.is_dir() {
+            if accmode != libc::O_RDONLY {
+                return Err(errno(libc::EISDIR));
+            }
+        } else if flags & libc::O_DIRECTORY != 0 {
+            return Err(errno(libc::ENOTDIR));
+        }
+
+        // A freshly created file may be opened with any access mode,
+        // regardless of the permission bits it was created with.
+        if !created {
+            let mut mask = 0;
+            if accmode != libc::O_WRONLY {
+                mask |= libc::R_OK;
+            }
+            if accmode != libc::O_RDONLY || flags & libc::O_TRUNC != 0 {
+                mask |= libc::W_OK;
+            }
+            self.check_perm(node, mask)?;
+        }
+
+        if flags & libc::O_TRUNC != 0 && accmode != libc::O_RDONLY {
+            let t = now();
+            let node = state.node_mut(ino)?;
+            let mut freed = 0;
+            if let NodeKind::File(data) = &mut node.kind {
+                freed = data.len() as u64;
+                data.clear();
+            }
+            node.mtime = t;
+            node.ctime = t;
+            state.used_bytes -= freed;
+        }
+
+        let fd = state.next_fd;
+        state.next_fd += 1;
+        state.fds.insert(fd, OpenFd { ino, flags });
+        Ok(fd)
+    }
+
+    fn pread(&self, fd: RawFd, buf: &mut [u8], offset: i64) -> io::Result<usize> {
+        if offset < 0 {
+            return Err(errno(libc::EINVAL));
+        }
+        let mut state = self.state.lock().unwrap();
+        let open = state.open_fd(fd)?;
+        if !open.readable() {
+            return Err(errno(libc::EBADF));
+        }
+        let node = state.node_mut(open.ino)?;
+        let data = match &node.kind {
+            NodeKind::File(data) => data,
+            NodeKind::Dir(_) => return Err(errno(libc::EISDIR)),
+            NodeKind::Symlink(_) => return Err(errno(libc::EINVAL)),
+        };
+        let start = (offset as usize).min(data.len());
+        let n = buf.len().min(data.len() - start);
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        node.atime = now();
+        Ok(n)
+    }
+
+    fn pwrite(&self, fd: RawFd, data: &[u8], offset: i64) -> io::Result<usize> {
+        if offset < 0 {
+            return Err(errno(libc::EINVAL));
+        }
+        let mut state = self.state.lock().unwrap();
+        let open = state.open_fd(fd)?;
+        if !open.writable() {
+            return Err(errno(libc::EBADF));
+        }
+        let used = state.used_bytes;
+        let node = state.node_mut(open.ino)?;
+        let NodeKind::File(content) = &mut node.kind else {
+            return Err(errno(libc::EISDIR));
+        };
+        let offset = if open.flags & libc::O_APPEND != 0 {
+            content.len()
+        } else {
+            offset as usize
+        };
+        let end = offset + data.len();
+        let grow = (end as u64).saturating_sub(content.len() as u64);
+        if used + grow > self.capacity {
+            return Err(errno(libc::ENOSPC));
+        }
+        if end > content.len() {
+            content.resize(end, 0);
+        }
+        content[offset..end].copy_from_slice(data);
+        let t = now();
+        node.mtime = t;
+        node.ctime = t;
+        state.used_bytes += grow;
+        Ok(data.len())
+    }
+
+    fn fsync(&self, fd: RawFd) -> io::Result<()> {
+        let state = self.state.lock().unwrap();
+        state.open_fd(fd).map(|_| ())
+    }
+
+    fn close_fd(&self, fd: RawFd) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(open) = state.fds.remove(&fd) {
+            state.maybe_reap(open.ino);
+        }
+    }
+
+    fn ftruncate(&self, fd: RawFd, size: u64) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let open = state.open_fd(fd)?;
+        if !open.writable() {
+            return Err(errno(libc::EINVAL));
+        }
+        let used = state.used_bytes;
+        let node = state.node_mut(open.ino)?;
+        let NodeKind::File(content) = &mut node.kind else {
+            return Err(errno(libc::EISDIR));
+        };
+        let old = content.len() as u64;
+        if size > old && used + (size - old) > self.capacity {
+            return Err(errno(libc::ENOSPC));
+        }
+        content.resize(size as usize, 0);
+        let t = now();
+        node.mtime = t;
+        node.ctime = t;
+        state.used_bytes = used - old + size;
+        Ok(())
+    }
+
+    // -- Directory ops ------------------------------------------------------
+
+    fn mkdir(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if rel.as_os_str().is_empty() {
+            return Err(errno(libc::EEXIST));
+        }
+        let (parent, name) = self.lookup_parent(&state, rel)?;
+        if Self::child(&state, parent, name).is_some() {
+            return Err(errno(libc::EEXIST));
+        }
+        self.check_perm(state.node(parent)?, libc::W_OK | libc::X_OK)?;
+        self.link_new(
+            &mut state,
+            parent,
+            name,
+            NodeKind::Dir(BTreeMap::new()),
+            mode,
+        )?;
+        Ok(())
+    }
+
+    fn rmdir(&self, rel: &Path) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if rel.as_os_str().is_empty() {
+            return Err(errno(libc::EBUSY));
+        }
+        let (parent, name) = self.lookup_parent(&state, rel)?;
+        let ino = Self::child(&state, parent, name).ok_or_else(|| errno(libc::ENOENT))?;
+        match &state.node(ino)?.kind {
+            NodeKind::Dir(children) if !children.is_empty() => {
+                return Err(errno(libc::ENOTEMPTY));
+            }
+            NodeKind::Dir(_) => {}
+            NodeKind::File(_) | NodeKind::Symlink(_) => return Err(errno(libc::ENOTDIR)),
+        }
+        self.check_perm(state.node(parent)?, libc::W_OK | libc::X_OK)?;
+        Self::unlink_entry(&mut state, parent, name);
+        state.node_mut(parent)?.nlink -= 1;
+        state.node_mut(ino)?.nlink = 0;
+        state.maybe_reap(ino);
+        Ok(())
+    }
+
+    fn readdir(&self, rel: &Path) -> io::Result<Vec<DirEntry>> {
+        let state = self.state.lock().unwrap();
+        let names = components(rel)?;
+        let ino = self.walk(&state, &names, true)?;
+        let parent_ino = match names.split_last() {
+            Some((_, parents)) => self.walk(&state, parents, false)?,
+            None => ROOT_INO,
+        };
+        let node = state.node(ino)?;
+        let NodeKind::Dir(children) = &node.kind else {
+            return Err(errno(libc::ENOTDIR));
+        };
+        self.check_perm(node, libc::R_OK)?;
+
+        let mut entries = vec![
+            DirEntry {
+                ino,
+                dtype: libc::DT_DIR,
+                name: OsString::from("."),
+            },
+            DirEntry {
+                ino: parent_ino,
+                dtype: libc::DT_DIR,
+                name: OsString::from(".."),
+            },
+        ];
+        for (name, &child) in children {
+            entries.push(DirEntry {
+                ino: child,
+                dtype: state.node(child)?.dtype(),
+                name: name.clone(),
+            });
+        }
+        Ok(entries)
+    }
+
+    // -- Path ops -----------------------------------------------------------
+
+    fn unlink(&self, rel: &Path) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if rel.as_os_str().is_empty() {
+            return Err(errno(libc::EISDIR));
+        }
+        let (parent, name) = self.lookup_parent(&state, rel)?;
+        let ino = Self::child(&state, parent, name).ok_or_else(|| errno(libc::ENOENT))?;
+        if state.node(ino)?.is_dir() {
+            return Err(errno(libc::EISDIR));
+        }
+        self.check_perm(state.node(parent)?, libc::W_OK | libc::X_OK)?;
+        Self::unlink_entry(&mut state, parent, name);
+        let node = state.node_mut(ino)?;
+        node.nlink -= 1;
+        node.ctime = now();
+        state.maybe_reap(ino);
+        Ok(())
+    }
+
+    fn rename(&self, old: &Path, new: &Path) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if old.as_os_str().is_empty() || new.as_os_str().is_empty() {
+            return Err(errno(libc::EBUSY));
+        }
+        let (old_parent, old_name) = self.lookup_parent(&state, old)?;
+        let (new_parent, new_name) = self.lookup_parent(&state, new)?;
+        let ino = Self::child(&state, old_parent, old_name).ok_or_else(|| errno(libc::ENOENT))?;
+        self.check_perm(state.node(old_parent)?, libc::W_OK | libc::X_OK)?;
+        self.check_perm(state.node(new_parent)?, libc::W_OK | libc::X_OK)?;
+
+        let moving_dir = state.node(ino)?.is_dir();
+        if moving_dir && Self::is_within(&state, ino, new_parent) {
+            return Err(errno(libc::EINVAL));
+        }
+
+        if let Some(target) = Self::child(&state, new_parent, new_name) {
+            if target == ino {
+                return Ok(());
+            }
+            match (&state.node(target)?.kind, moving_dir) {
+                (NodeKind::Dir(children), true) if !children.is_empty() => {
+                    return Err(errno(libc::ENOTEMPTY));
+                }
+                (NodeKind::Dir(_), false) => return Err(errno(libc::EISDIR)),
+                (NodeKind::File(_) | NodeKind::Symlink(_), true) => {
+                    return Err(errno(libc::ENOTDIR));
+                }
+                _ => {}
+            }
+            Self::unlink_entry(&mut state, new_parent, new_name);
+            if moving_dir {
+                state.node_mut(new_parent)?.nlink -= 1;
+            }
+            state.node_mut(target)?.nlink = 0;
+            state.maybe_reap(target);
+        }
+
+        Self::unlink_entry(&mut state, old_parent, old_name);
+        let t = now();
+        let p = state.node_mut(new_parent)?;
+        if let NodeKind::Dir(children) = &mut p.kind {
+            children.insert(new_name.to_os_string(), ino);
+        }
+        p.mtime = t;
+        p.ctime = t;
+        if moving_dir && old_parent != new_parent {
+            state.node_mut(old_parent)?.nlink -= 1;
+            state.node_mut(new_parent)?.nlink += 1;
+        }
+        state.node_mut(ino)?.ctime = t;
+        Ok(())
+    }
+
+    fn symlink(&self, target: &Path, rel: &Path) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if rel.as_os_str().is_empty() {
+            return Err(errno(libc::EEXIST));
+        }
+        let (parent, name) = self.lookup_parent(&state, rel)?;
+        if Self::child(&state, parent, name).is_some() {
+            return Err(errno(libc::EEXIST));
+        }
+        if target.as_os_str().is_empty() {
+            return Err(errno(libc::ENOENT));
+        }
+        self.check_perm(state.node(parent)?, libc::W_OK | libc::X_OK)?;
+        let kind = NodeKind::Symlink(target.to_path_buf());
+        self.link_new(&mut state, parent, name, kind, 0o777)?;
+        Ok(())
+    }
+
+    fn readlink(&self, rel: &Path) -> io::Result<PathBuf> {
+        let state = self.state.lock().unwrap();
+        let ino = self.lookup(&state, rel, true)?;
+        match &state.node(ino)?.kind {
+            NodeKind::Symlink(target) => Ok(target.clone()),
+            _ => Err(errno(libc::EINVAL)),
+        }
+    }
+
+    // -- Metadata -----------------------------------------------------------
+
+    fn chmod(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let ino = self.lookup(&state, rel, true)?;
+        self.check_owner(state.node(ino)?)?;
+        let node = state.node_mut(ino)?;
+        node.perm = mode & 0o7777;
+        node.ctime = now();
+        Ok(())
+    }
+
+    fn chown(&self, rel: &Path, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let ino = self.lookup(&state, rel, true)?;
+        let node = state.node(ino)?;
+        if self.uid != 0 {
+            // Unprivileged callers may only "change" the owner to itself and
+            // the group to their own group, and only on files they own.
+            let uid_ok = uid.is_none_or(|u| u == node.uid);
+            let gid_ok =
+                gid.is_none_or(|g| g == node.gid || (g == self.gid && node.uid == self.uid));
+            if !uid_ok || !gid_ok {
+                return Err(errno(libc::EPERM));
+            }
+        }
+        let node = state.node_mut(ino)?;
+        if let Some(u) = uid {
+            node.uid = u;
+        }
+        if let Some(g) = gid {
+            node.gid = g;
+        }
+        node.ctime = now();
+        Ok(())
+    }
+
+    fn utimens(
+        &self,
+        rel: &Path,
+        atime: &libc::timespec,
+        mtime: &libc::timespec,
+    ) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let ino = self.lookup(&state, rel, true)?;
+        let node = state.node(ino)?;
+        // Setting both times to "now" only needs write access; explicit
+        // timestamps require ownership.
+        let only_now = [atime, mtime]
+            .iter()
+            .all(|t| t.tv_nsec == libc::UTIME_NOW || t.tv_nsec == libc::UTIME_OMIT);
+        if only_now {
+            self.check_owner(node)
+                .or_else(|_| self.check_perm(node, libc::W_OK))?;
+        } else {
+            self.check_owner(node)?;
+        }
+        let node = state.node_mut(ino)?;
+        node.atime = resolve_time(atime, node.atime);
+        node.mtime = resolve_time(mtime, node.mtime);
+        node.ctime = now();
+        Ok(())
+    }
+
+    fn access(&self, rel: &Path, mask: i32) -> io::Result<()> {
+        let state = self.state.lock().unwrap();
+        let ino = self.lookup(&state, rel, true)?;
+        self.check_perm(state.node(ino)?, mask)
+    }
+
+    // -- Filesystem ---------------------------------------------------------
+
+    fn statvfs(&self) -> io::Result<libc::statvfs> {
+        let state = self.state.lock().unwrap();
+        // SAFETY: `libc::statvfs` is a plain C struct; all-zero is valid.
+        let mut buf: libc::statvfs = unsafe { std::mem::zeroed() };
+        let total_blocks = self.capacity / BLOCK_SIZE;
+        let used_blocks = state.used_bytes.div_ceil(BLOCK_SIZE);
+        let free_blocks = total_blocks.saturating_sub(used_blocks);
+        let free_inodes = self.max_inodes.saturating_sub(state.nodes.len() as u64);
+        buf.f_bsize = BLOCK_SIZE as _;
+        buf.f_frsize = BLOCK_SIZE as _;
+        buf.f_blocks = total_blocks as _;
+        buf.f_bfree = free_blocks as _;
+        buf.f_bavail = free_blocks as _;
+        buf.f_files = self.max_inodes as _;
+        buf.f_ffree = free_inodes as _;
+        buf.f_favail = free_inodes as _;
+        buf.f_namemax = NAME_MAX as _;
+        Ok(buf)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Helper: create a file with the given content and close it.
+    fn write_file(backing: &MemBackingFs, rel: &str, content: &[u8]) {
+        let fd = backing
+            .open_file(
+                Path::new(rel),
+                libc::O_CREAT | libc::O_WRONLY | libc::O_TRUNC,
+                0o644,
+            )
+            .expect("open_file create");
+        backing.pwrite(fd, content, 0).expect("pwrite");
+        backing.close_fd(fd);
+    }
+
+    fn names(entries: &[DirEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .filter(|e| e.name != "." && e.name != "..")
+            .map(|e| e.name.to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn stat_root() {
+        let backing = MemBackingFs::new();
+        let st = backing.stat(Path::new("")).expect("stat root");
+        assert_eq!(st.st_mode & libc::S_IFMT, libc::S_IFDIR);
+        assert_eq!(st.st_ino, ROOT_INO);
+    }
+
+    #[test]
+    fn open_write_read_close() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "hello.txt", b"hello backing_fs");
+
+        let fd = backing
+            .open_file(Path::new("hello.txt"), libc::O_RDONLY, 0)
+            .expect("open_file read");
+        let mut buf = vec![0u8; 64];
+        let n = backing.pread(fd, &mut buf, 0).expect("pread");
+        assert_eq!(&buf[..n], b"hello backing_fs");
+        backing.close_fd(fd);
+        assert_eq!(backing.open_fd_count(), 0);
+    }
+
+    #[test]
+    fn pread_past_eof_returns_zero() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "short.txt", b"abc");
+
+        let fd = backing
+            .open_file(Path::new("short.txt"), libc::O_RDONLY, 0)
+            .unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(backing.pread(fd, &mut buf, 100).unwrap(), 0);
+        backing.close_fd(fd);
+    }
+
+    #[test]
+    fn pwrite_past_eof_leaves_hole() {
+        let backing = MemBackingFs::new();
+        let fd = backing
+            .open_file(Path::new("sparse"), libc::O_CREAT | libc::O_RDWR, 0o644)
+            .unwrap();
+        backing.pwrite(fd, b"end", 10).unwrap();
+        assert_eq!(backing.fstat(fd).unwrap().st_size, 13);
+        backing.close_fd(fd);
+
+        let content = backing.read_all(Path::new("sparse")).unwrap();
+        assert_eq!(&content[..10], &[0u8; 10]);
+        assert_eq!(&content[10..], b"end");
+    }
+
+    #[test]
+    fn open_missing_without_create_fails() {
+        let backing = MemBackingFs::new();
+        let err = backing
+            .open_file(Path::new("nope"), libc::O_RDONLY, 0)
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+    }
+
+    #[test]
+    fn open_excl_existing_fails() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "x", b"");
+        let err = backing
+            .open_file(
+                Path::new("x"),
+                libc::O_CREAT | libc::O_EXCL | libc::O_WRONLY,
+                0o644,
+            )
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EEXIST));
+    }
+
+    #[test]
+    fn open_trunc_clears_content() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "t.txt", b"some content");
+        let fd = backing
+            .open_file(Path::new("t.txt"), libc::O_WRONLY | libc::O_TRUNC, 0)
+            .unwrap();
+        assert_eq!(backing.fstat(fd).unwrap().st_size, 0);
+        backing.close_fd(fd);
+    }
+
+    #[test]
+    fn open_directory_for_write_fails() {
+        let backing = MemBackingFs::new();
+        backing.mkdir(Path::new("d"), 0o755).unwrap();
+        let err = backing
+            .open_file(Path::new("d"), libc::O_WRONLY, 0)
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EISDIR));
+    }
+
+    #[test]
+    fn access_mode_is_enforced_on_fds() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "ro.txt", b"data");
+
+        let fd = backing
+            .open_file(Path::new("ro.txt"), libc::O_RDONLY, 0)
+            .unwrap();
+        let err = backing.pwrite(fd, b"x", 0).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+        let err = backing.ftruncate(fd, 0).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+        backing.close_fd(fd);
+
+        let fd = backing
+            .open_file(Path::new("ro.txt"), libc::O_WRONLY, 0)
+            .unwrap();
+        let mut buf = [0u8; 4];
+        let err = backing.pread(fd, &mut buf, 0).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+        backing.close_fd(fd);
+    }
+
+    #[test]
+    fn closed_fd_is_ebadf() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "f", b"");
+        let fd = backing
+            .open_file(Path::new("f"), libc::O_RDONLY, 0)
+            .unwrap();
+        backing.close_fd(fd);
+        let err = backing.fstat(fd).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+    }
+
+    #[test]
+    fn ftruncate_extends_and_shrinks() {
+        let backing = MemBackingFs::new();
+        let fd = backing
+            .open_file(Path::new("trunc.txt"), libc::O_CREAT | libc::O_RDWR, 0o644)
+            .unwrap();
+        backing.pwrite(fd, b"hello world", 0).unwrap();
+
+        backing.ftruncate(fd, 5).unwrap();
+        assert_eq!(backing.fstat(fd).unwrap().st_size, 5);
+
+        backing.ftruncate(fd, 100).unwrap();
+        assert_eq!(backing.fstat(fd).unwrap().st_size, 100);
+        backing.close_fd(fd);
+
+        let content = backing.read_all(Path::new("trunc.txt")).unwrap();
+        assert_eq!(&content[..5], b"hello");
+        assert!(content[5..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn mkdir_and_readdir() {
+        let backing = MemBackingFs::new();
+        backing.mkdir(Path::new("subdir"), 0o755).unwrap();
+        write_file(&backing, "subdir/a.txt", b"a");
+        write_file(&backing, "subdir/b.txt", b"b");
+
+        let root = backing.readdir(Path::new("")).unwrap();
+        assert_eq!(names(&root), vec!["subdir"]);
+        assert!(root
+            .iter()
+            .any(|e| e.name == "." && e.dtype == libc::DT_DIR));
+
+        let sub = backing.readdir(Path::new("subdir")).unwrap();
+        assert_eq!(names(&sub), vec!["a.txt", "b.txt"]);
+        assert!(sub
+            .iter()
+            .all(|e| e.name == "." || e.name == ".." || e.dtype == libc::DT_REG));
+    }
+
+    #[test]
+    fn mkdir_existing_fails() {
+        let backing = MemBackingFs::new();
+        backing.mkdir(Path::new("d"), 0o755).unwrap();
+        let err = backing.mkdir(Path::new("d"), 0o755).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EEXIST));
+    }
+
+    #[test]
+    fn path_through_file_is_enotdir() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "file", b"");
+        let err = backing.stat(Path::new("file/child")).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOTDIR));
+        let err = backing.mkdir(Path::new("file/child"), 0o755).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOTDIR));
+    }
+
+    #[test]
+    fn rmdir_non_empty_fails() {
+        let backing = MemBackingFs::new();
+        backing.mkdir(Path::new("d"), 0o755).unwrap();
+        write_file(&backing, "d/f", b"");
+        let err = backing.rmdir(Path::new("d")).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOTEMPTY));
+
+        backing.unlink(Path::new("d/f")).unwrap();
+        backing.rmdir(Path::new("d")).unwrap();
+        assert!(backing.stat(Path::new("d")).is_err());
+    }
+
+    #[test]
+    fn unlink_and_rmdir_check_type() {
+        let backing = MemBackingFs::new();
+        backing.mkdir(Path::new("d"), 0o755).unwrap();
+        write_file(&backing, "f", b"");
+        assert_eq!(
+            backing.unlink(Path::new("d")).unwrap_err().raw_os_error(),
+            Some(libc::EISDIR)
+        );
+        assert_eq!(
+            backing.rmdir(Path::new("f")).unwrap_err().raw_os_error(),
+            Some(libc::ENOTDIR)
+        );
+    }
+
+    #[test]
+    fn unlinked_file_stays_readable_through_open_fd() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "ghost.txt", b"boo");
+
+        let fd = backing
+            .open_file(Path::new("ghost.txt"), libc::O_RDONLY, 0)
+            .unwrap();
+        backing.unlink(Path::new("ghost.txt")).unwrap();
+        assert!(backing.stat(Path::new("ghost.txt")).is_err());
+
+        let mut buf = [0u8; 3];
+        assert_eq!(backing.pread(fd, &mut buf, 0).unwrap(), 3);
+        assert_eq!(&buf, b"boo");
+        assert_eq!(backing.fstat(fd).unwrap().st_nlink, 0);
+        backing.close_fd(fd);
+    }
+
+    #[test]
+    fn rename_file() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "old.txt", b"data");
+        backing
+            .rename(Path::new("old.txt"), Path::new("new.txt"))
+            .unwrap();
+        assert!(backing.stat(Path::new("old.txt")).is_err());
+        assert_eq!(backing.read_all(Path::new("new.txt")).unwrap(), b"data");
+    }
+
+    #[test]
+    fn rename_replaces_existing_file() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "a", b"aaa");
+        write_file(&backing, "b", b"b");
+        let ino = backing.stat(Path::new("a")).unwrap().st_ino;
+
+        backing.rename(Path::new("a"), Path::new("b")).unwrap();
+        let st = backing.stat(Path::new("b")).unwrap();
+        assert_eq!(st.st_ino, ino);
+        assert_eq!(st.st_size, 3);
+    }
+
+    #[test]
+    fn rename_directory_keeps_children() {
+        let backing = MemBackingFs::new();
+        backing.mkdir(Path::new("src"), 0o755).unwrap();
+        write_file(&backing, "src/f", b"x");
+        backing.mkdir(Path::new("dst"), 0o755).unwrap();
+
+        backing
+            .rename(Path::new("src"), Path::new("dst/moved"))
+            .unwrap();
+        assert_eq!(backing.read_all(Path::new("dst/moved/f")).unwrap(), b"x");
+        assert_eq!(backing.stat(Path::new("dst")).unwrap().st_nlink, 3);
+        assert_eq!(backing.stat(Path::new("")).unwrap().st_nlink, 3);
+    }
+
+    #[test]
+    fn rename_type_mismatches() {
+        let backing = MemBackingFs::new();
+        backing.mkdir(Path::new("d"), 0o755).unwrap();
+        backing.mkdir(Path::new("full"), 0o755).unwrap();
+        write_file(&backing, "full/x", b"");
+        write_file(&backing, "f", b"");
+
+        let code = |old: &str, new: &str| {
+            backing
+                .rename(Path::new(old), Path::new(new))
+                .unwrap_err()
+                .raw_os_error()
+        };
+        assert_eq!(code("f", "d"), Some(libc::EISDIR));
+        assert_eq!(code("d", "f"), Some(libc::ENOTDIR));
+        assert_eq!(code("d", "full"), Some(libc::ENOTEMPTY));
+        assert_eq!(code("d", "d/inner"), Some(libc::EINVAL));
+        assert_eq!(code("missing", "other"), Some(libc::ENOENT));
+    }
+
+    #[test]
+    fn chmod_file() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "mode.txt", b"x");
+        backing.chmod(Path::new("mode.txt"), 0o600).unwrap();
+        let st = backing.stat(Path::new("mode.txt")).unwrap();
+        assert_eq!(st.st_mode & 0o777, 0o600);
+        assert_eq!(st.st_mode & libc::S_IFMT, libc::S_IFREG);
+    }
+
+    #[test]
+    fn permissions_are_enforced_for_unprivileged_caller() {
+        let backing = MemBackingFs::with_credentials(1000, 1000);
+        write_file(&backing, "secret", b"s");
+        backing.chmod(Path::new("secret"), 0o200).unwrap();
+
+        let err = backing
+            .open_file(Path::new("secret"), libc::O_RDONLY, 0)
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EACCES));
+        assert!(backing.access(Path::new("secret"), libc::W_OK).is_ok());
+        assert_eq!(
+            backing
+                .access(Path::new("secret"), libc::R_OK)
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::EACCES)
+        );
+
+        // No write permission on the directory: cannot create or unlink.
+        backing.mkdir(Path::new("locked"), 0o755).unwrap();
+        write_file(&backing, "locked/f", b"");
+        backing.chmod(Path::new("locked"), 0o555).unwrap();
+        let err = backing.unlink(Path::new("locked/f")).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EACCES));
+        let err = backing
+            .open_file(Path::new("locked/g"), libc::O_CREAT | libc::O_WRONLY, 0o644)
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EACCES));
+
+        // No search permission: lookups beneath the directory fail.
+        backing.chmod(Path::new("locked"), 0o600).unwrap();
+        let err = backing.stat(Path::new("locked/f")).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EACCES));
+    }
+
+    #[test]
+    fn root_bypasses_permission_bits() {
+        let backing = MemBackingFs::with_credentials(0, 0);
+        write_file(&backing, "f", b"data");
+        backing.chmod(Path::new("f"), 0o000).unwrap();
+        let fd = backing.open_file(Path::new("f"), libc::O_RDWR, 0).unwrap();
+        backing.close_fd(fd);
+        assert!(backing.access(Path::new("f"), libc::X_OK).is_err());
+    }
+
+    #[test]
+    fn chown_requires_privilege() {
+        let backing = MemBackingFs::with_credentials(1000, 1000);
+        write_file(&backing, "own.txt", b"x");
+        backing.chown(Path::new("own.txt"), None, None).unwrap();
+        let err = backing
+            .chown(Path::new("own.txt"), Some(0), None)
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+        let root = MemBackingFs::with_credentials(0, 0);
+        write_file(&root, "own.txt", b"x");
+        root.chown(Path::new("own.txt"), Some(42), Some(43))
+            .unwrap();
+        let st = root.stat(Path::new("own.txt")).unwrap();
+        assert_eq!((st.st_uid, st.st_gid), (42, 43));
+    }
+
+    #[test]
+    fn utimens_sets_times() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "time.txt", b"t");
+
+        let atime = libc::timespec {
+            tv_sec: 1_000_000,
+            tv_nsec: 0,
+        };
+        let mtime = libc::timespec {
+            tv_sec: 2_000_000,
+            tv_nsec: 0,
+        };
+        backing
+            .utimens(Path::new("time.txt"), &atime, &mtime)
+            .unwrap();
+        let st = backing.stat(Path::new("time.txt")).unwrap();
+        assert_eq!(st.st_atime, 1_000_000);
+        assert_eq!(st.st_mtime, 2_000_000);
+
+        let omit = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_OMIT,
+        };
+        backing
+            .utimens(Path::new("time.txt"), &omit, &omit)
+            .unwrap();
+        assert_eq!(
+            backing.stat(Path::new("time.txt")).unwrap().st_mtime,
+            2_000_000
+        );
+    }
+
+    #[test]
+    fn capacity_limits_writes() {
+        let backing = MemBackingFs::new().with_capacity(8);
+        let fd = backing
+            .open_file(Path::new("big"), libc::O_CREAT | libc::O_RDWR, 0o644)
+            .unwrap();
+        backing.pwrite(fd, b"12345678", 0).unwrap();
+        let err = backing.pwrite(fd, b"9", 8).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+        // Overwriting in place does not need more space.
+        backing.pwrite(fd, b"abcd", 0).unwrap();
+        backing.close_fd(fd);
+
+        // Deleting the file frees the space again.
+        backing.unlink(Path::new("big")).unwrap();
+        let vfs = backing.statvfs().unwrap();
+        assert_eq!(vfs.f_bfree, vfs.f_blocks);
+    }
+
+    #[test]
+    fn statvfs_reports_usage() {
+        let backing = MemBackingFs::new()
+            .with_capacity(1 << 20)
+            .with_max_inodes(10);
+        let before = backing.statvfs().unwrap();
+        assert_eq!(before.f_bsize, BLOCK_SIZE as _);
+        assert_eq!(before.f_files, 10);
+        assert_eq!(before.f_ffree, 9);
+
+        write_file(&backing, "f", &[1u8; 5000]);
+        let after = backing.statvfs().unwrap();
+        assert_eq!(before.f_bfree - after.f_bfree, 2);
+        assert_eq!(after.f_ffree, 8);
+    }
+
+    #[test]
+    fn inode_limit_is_enforced() {
+        let backing = MemBackingFs::new().with_max_inodes(2);
+        backing.mkdir(Path::new("one"), 0o755).unwrap();
+        let err = backing.mkdir(Path::new("two"), 0o755).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+    }
+
+    #[test]
+    fn fsync_on_open_file() {
+        let backing = MemBackingFs::new();
+        let fd = backing
+            .open_file(Path::new("sync.txt"), libc::O_CREAT | libc::O_WRONLY, 0o644)
+            .unwrap();
+        backing.fsync(fd).unwrap();
+        backing.close_fd(fd);
+        assert_eq!(
+            backing.fsync(fd).unwrap_err().raw_os_error(),
+            Some(libc::EBADF)
+        );
+    }
+
+    #[test]
+    fn nested_directory_operations() {
+        let backing = MemBackingFs::new();
+        backing.mkdir(Path::new("a"), 0o755).unwrap();
+        backing.mkdir(Path::new("a/b"), 0o755).unwrap();
+        backing.mkdir(Path::new("a/b/c"), 0o755).unwrap();
+        write_file(&backing, "a/b/c/deep.txt", b"deep");
+
+        assert_eq!(
+            backing.stat(Path::new("a/b/c/deep.txt")).unwrap().st_size,
+            4
+        );
+        let entries = backing.readdir(Path::new("a/b/c")).unwrap();
+        assert_eq!(names(&entries), vec!["deep.txt"]);
+        let dotdot = entries.iter().find(|e| e.name == "..").unwrap();
+        assert_eq!(dotdot.ino, backing.stat(Path::new("a/b")).unwrap().st_ino);
+
+        backing.unlink(Path::new("a/b/c/deep.txt")).unwrap();
+        backing.rmdir(Path::new("a/b/c")).unwrap();
+        backing.rmdir(Path::new("a/b")).unwrap();
+        backing.rmdir(Path::new("a")).unwrap();
+        assert!(names(&backing.readdir(Path::new("")).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn symlinks_are_stored_not_followed() {
+        let backing = MemBackingFs::new();
+        write_file(&backing, "target", b"data");
+        backing
+            .symlink(Path::new("target"), Path::new("link"))
+            .unwrap();
+
+        let st = backing.stat(Path::new("link")).unwrap();
+        assert_eq!(st.st_mode & libc::S_IFMT, libc::S_IFLNK);
+        assert_eq!(st.st_size, 6);
+        assert_eq!(
+            backing.readlink(Path::new("link")).unwrap(),
+            Path::new("target")
+        );
+        assert_eq!(
+            backing
+                .open_file(Path::new("link"), libc::O_RDONLY, 0)
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::ELOOP)
+        );
+        assert_eq!(
+            backing
+                .readlink(Path::new("target"))
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::EINVAL)
+        );
+        assert_eq!(
+            backing
+                .symlink(Path::new("elsewhere"), Path::new("target"))
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::EEXIST)
+        );
+
+        let entries = backing.readdir(Path::new("")).unwrap();
+        let link = entries.iter().find(|e| e.name == "link").unwrap();
+        assert_eq!(link.dtype, libc::DT_LNK);
+
+        backing.unlink(Path::new("link")).unwrap();
+        assert_eq!(backing.read_all(Path::new("target")).unwrap(), b"data");
+    }
+
+    #[test]
+    fn shared_through_arc() {
+        let backing = std::sync::Arc::new(MemBackingFs::new());
+        let as_trait: &dyn BackingFs = &backing;
+        as_trait.mkdir(Path::new("via_arc"), 0o755).unwrap();
+        assert!(backing.stat(Path::new("via_arc")).is_ok());
+    }
+}
@@ -9,16 +9,29 @@
 //!
 //! [`RawFd`] values returned by [`BackingFs::open_file`] are owned by the
 //! caller, who is responsible for closing them via [`BackingFs::close_fd`].
+//!
+//! # Implementations
+//!
+//! * [`LibcBackingFs`] — raw `*at()` syscalls against a pre-opened directory
+//!   fd; used for real mounts.
+//! * [`MemBackingFs`] — a fully in-memory tree for hermetic tests.
+//! * [`FaultyBackingFs`] — wraps another implementation and injects errors,
+//!   short I/O and latency, for error-path tests.
 
+pub mod faulty;
 pub mod libc_impl;
+pub mod mem_impl;
 
 use std::ffi::OsString;
 use std::io;
 use std::os::unix::io::RawFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 // Re-exports
+pub use faulty::{Fault, FaultAction, FaultyBackingFs, FsMethod};
 pub use libc_impl::LibcBackingFs;
+pub use mem_impl::MemBackingFs;
 
 // ---------------------------------------------------------------------------
 // DirEntry
@@ -108,6 +121,15 @@ pub trait BackingFs: Send + Sync {
     /// store root.
     fn rename(&self, old: &Path, new: &Path) -> io::Result<()>;
 
+    /// Create a symbolic link at `rel` pointing to `target`.
+    ///
+    /// `target` is stored verbatim; it is neither resolved nor required to
+    /// exist.
+    fn symlink(&self, target: &Path, rel: &Path) -> io::Result<()>;
+
+    /// Read the target of the symbolic link at `rel`.
+    fn readlink(&self, rel: &Path) -> io::Result<PathBuf>;
+
     // -- Metadata -----------------------------------------------------------
 
     /// Change file mode bits.
@@ -132,3 +154,96 @@ pub trait BackingFs: Send + Sync {
     /// Return filesystem statistics for the backing store.
     fn statvfs(&self) -> io::Result<libc::statvfs>;
 }
+
+// ---------------------------------------------------------------------------
+// Shared backings
+// ---------------------------------------------------------------------------
+
+/// Forward every call through an [`Arc`], so one backing store can be handed
+/// to the filesystem while a test (or observer) keeps its own handle to it.
+impl<B: BackingFs + ?Sized> BackingFs for Arc<B> {
+    fn stat(&self, rel: &Path) -> io::Result<libc::stat> {
+        (**self).stat(rel)
+    }
+
+    fn fstat(&self, fd: RawFd) -> io::Result<libc::stat> {
+        (**self).fstat(fd)
+    }
+
+    fn open_file(&self, rel: &Path, flags: i32, mode: u32) -> io::Result<RawFd> {
+        (**self).open_file(rel, flags, mode)
+    }
+
+    fn pread(&self, fd: RawFd, buf: &mut [u8], offset: i64) -> io::Result<usize> {
+        (**self).pread(fd, buf, offset)
+    }
+
+    fn pwrite(&self, fd: RawFd, data: &[u8], offset: i64) -> io::Result<usize> {
+        (**self).pwrite(fd, data, offset)
+    }
+
+    fn fsync(&self, fd: RawFd) -> io::Result<()> {
+        (**self).fsync(fd)
+    }
+
+    fn close_fd(&self, fd: RawFd) {
+        (**self).close_fd(fd)
+    }
+
+    fn ftruncate(&self, fd: RawFd, size: u64) -> io::Result<()> {
+        (**self).ftruncate(fd, size)
+    }
+
+    fn mkdir(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        (**self).mkdir(rel, mode)
+    }
+
+    fn rmdir(&self, rel: &Path) -> io::Result<()> {
+        (**self).rmdir(rel)
+    }
+
+    fn readdir(&self, rel: &Path) -> io::Result<Vec<DirEntry>> {
+        (**self).readdir(rel)
+    }
+
+    fn unlink(&self, rel: &Path) -> io::Result<()> {
+        (**self).unlink(rel)
+    }
+
+    fn rename(&self, old: &Path, new: &Path) -> io::Result<()> {
+        (**self).rename(old, new)
+    }
+
+    fn symlink(&self, target: &Path, rel: &Path) -> io::Result<()> {
+        (**self).symlink(target, rel)
+    }
+
+    fn readlink(&self, rel: &Path) -> io::Result<PathBuf> {
+        (**self).readlink(rel)
+    }
+
+    fn chmod(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        (**self).chmod(rel, mode)
+    }
+
+    fn chown(&self, rel: &Path, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+        (**self).chown(rel, uid, gid)
+    }
+
+    fn utimens(
+        &self,
+        rel: &Path,
+        atime: &libc::timespec,
+        mtime: &libc::timespec,
+    ) -> io::Result<()> {
+        (**self).utimens(rel, atime, mtime)
+    }
+
+    fn access(&self, rel: &Path, mask: i32) -> io::Result<()> {
+        (**self).access(rel, mask)
+    }
+
+    fn statvfs(&self) -> io::Result<libc::statvfs> {
+        (**self).statvfs()
+    }
+}
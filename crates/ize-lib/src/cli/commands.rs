@@ -31,14 +31,42 @@ pub enum Commands {
         /// Custom channel name (default: "main")
         #[arg(long, short)]
         channel: Option<String>,
+
+        /// Encrypt the project's history at rest
+        ///
+        /// Without `--keyfile` or `--passphrase`, a key is generated into
+        /// `~/.config/ize/keys/<uuid>.key`.
+        #[arg(long)]
+        encrypt: bool,
+
+        /// Key file to encrypt with, generated if it doesn't exist
+        #[arg(
+            long,
+            value_name = "FILE",
+            requires = "encrypt",
+            conflicts_with = "passphrase"
+        )]
+        keyfile: Option<PathBuf>,
+
+        /// Derive the key from a passphrase, taken from IZE_PASSPHRASE or
+        /// asked for
+        #[arg(long, requires = "encrypt")]
+        passphrase: bool,
     },
 
     /// Mount a tracked directory with version control
     ///
     /// Mounts a FUSE filesystem at the source directory location,
     /// backed by the versioned working copy in the central store.
+    ///
+    /// Past changes and other channels can be browsed read-only under the
+    /// virtual `.ize/` directory at the mount root.
+    ///
+    /// With `--at`, DIRECTORY is instead an empty mount point that shows the
+    /// project read-only as it was at the given change or time.
     Mount {
-        /// Directory to mount (must be initialized with `ize init`)
+        /// Directory to mount (must be initialized with `ize init`), or the
+        /// mount point when `--at` is given
         #[arg(value_name = "DIRECTORY")]
         directory: PathBuf,
 
@@ -46,6 +74,21 @@ pub enum Commands {
         #[arg(long)]
         read_only: bool,
 
+        /// Mount a read-only view of history at a change hash (or unique
+        /// prefix), a tag or a timestamp such as "2024-05-01 14:30"
+        #[arg(long, value_name = "HASH|TAG|TIMESTAMP")]
+        at: Option<String>,
+
+        /// Tracked directory to take history from with `--at` (defaults to
+        /// the current directory)
+        #[arg(long, short, value_name = "PROJECT", requires = "at")]
+        project: Option<PathBuf>,
+
+        /// List the `.ize/` history directory in the mount root (it can be
+        /// opened by path either way; overrides `mount.show_history_dir`)
+        #[arg(long, conflicts_with = "at")]
+        show_history_dir: bool,
+
         /// Run in foreground (don't daemonize)
         #[arg(long, short)]
         foreground: bool,
@@ -67,6 +110,10 @@ pub enum Commands {
         /// Show detailed information
         #[arg(long, short)]
         verbose: bool,
+
+        /// Show how history is stored and what block deltas saved
+        #[arg(long)]
+        stats: bool,
     },
 
     /// List all tracked projects
@@ -77,8 +124,12 @@ pub enum Commands {
     },
 
     /// View version history of a file
+    ///
+    /// Lists the changes touching the file, newest first, with their author
+    /// and whether their signature checks out.
     History {
-        /// Path to the file to show history for
+        /// Path to the file to show history for, relative to the project
+        /// root or absolute
         #[arg(value_name = "FILE_PATH")]
         file_path: PathBuf,
 
@@ -89,21 +140,238 @@ pub enum Commands {
         /// Show detailed information
         #[arg(long)]
         verbose: bool,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// List the changes of a channel, newest first
+    ///
+    /// Filters combine; `--format json` and `--format jsonl` print one object
+    /// per change for scripts.
+    Log {
+        /// Only list changes recorded at or after this time, e.g.
+        /// "2024-05-01 14:30"
+        #[arg(long, value_name = "TIME")]
+        since: Option<String>,
+
+        /// Only list changes recorded at or before this time
+        #[arg(long, value_name = "TIME")]
+        until: Option<String>,
+
+        /// Only list changes touching a file matching this glob, e.g.
+        /// "src/**/*.rs"
+        #[arg(long, value_name = "GLOB")]
+        path: Option<String>,
+
+        /// Only list changes by an author whose name or email contains this
+        #[arg(long)]
+        author: Option<String>,
+
+        /// List this channel (default: current channel)
+        #[arg(long)]
+        channel: Option<String>,
+
+        /// Number of changes to list (default: all)
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Output format
+        #[arg(long, default_value = "table", value_parser = ["table", "json", "jsonl"])]
+        format: String,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Show what a single change did, file by file
+    ///
+    /// Prints the change's message, author, time and signature, then the
+    /// lines it removed and added in each file.
+    Show {
+        /// Change hash or tag; a hash prefix must be unique across all
+        /// channels
+        #[arg(value_name = "HASH")]
+        hash: String,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Show which change wrote each line of a file
+    ///
+    /// Each line is listed with its change, when it was written to the
+    /// millisecond and, if the mount saw it, the process that wrote it.
+    Blame {
+        /// Path to the file, relative to the project root or absolute
+        #[arg(value_name = "FILE_PATH")]
+        file_path: PathBuf,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Search file contents across every past version
+    ///
+    /// Finds lines as they were written, deleted ones included, and prints
+    /// the change that wrote each one.
+    Grep {
+        /// Regular expression to search for
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
+
+        /// Only search changes recorded at or after this time, e.g.
+        /// "2024-05-01 14:30"
+        #[arg(long, value_name = "TIME")]
+        since: Option<String>,
+
+        /// Only search files matching this glob, e.g. "src/**/*.rs"
+        #[arg(long, value_name = "GLOB")]
+        path: Option<String>,
+
+        /// Search this channel (default: current channel)
+        #[arg(long)]
+        channel: Option<String>,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
     },
 
     /// Restore a file to a previous version
+    ///
+    /// The restored content is recorded as a new change.
     Restore {
-        /// Path to the file to restore
+        /// Path to the file to restore, relative to the project root or
+        /// absolute
         #[arg(value_name = "FILE_PATH")]
         file_path: PathBuf,
 
-        /// Version to restore to
-        #[arg(long)]
-        version: usize,
+        /// Version to restore to: a change hash (or unique prefix), a tag or
+        /// a timestamp
+        #[arg(long, value_name = "HASH|TAG|TIMESTAMP")]
+        version: String,
 
         /// Don't prompt for confirmation
         #[arg(long)]
         force: bool,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Apply a single change from another channel to the current one
+    ///
+    /// Changes it depends on are applied too. Competing edits end up as
+    /// conflicts, marked in the affected files.
+    Apply {
+        /// Change hash, or a prefix unique across all channels
+        #[arg(value_name = "HASH")]
+        hash: String,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Take back recently recorded changes
+    ///
+    /// Unrecords changes from the current channel and restores the working
+    /// copy. The undone changes stay on a new `undone/...` channel, so the
+    /// undo can itself be undone with `ize channel merge` or `ize apply`.
+    Undo {
+        /// Number of changes to undo, or a change hash (prefix) to undo
+        /// along with everything after it (default: 1)
+        #[arg(value_name = "N|HASH", conflicts_with = "since")]
+        target: Option<String>,
+
+        /// Undo every change recorded at or after this time, e.g.
+        /// "2024-05-01 14:30"
+        #[arg(long, value_name = "TIME")]
+        since: Option<String>,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Squash everything recorded since the last checkpoint into one change
+    ///
+    /// The squashed changes are archived on an `archive/...` channel unless
+    /// `--drop` is given or the project's `[checkpoint]` policy is "drop".
+    Checkpoint {
+        /// Message of the checkpoint change
+        #[arg(long, short)]
+        message: String,
+
+        /// Keep the squashed changes on an archive channel
+        #[arg(long, conflicts_with = "drop")]
+        archive: bool,
+
+        /// Discard the squashed changes
+        #[arg(long)]
+        drop: bool,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Compact old history and delete unused change files
+    ///
+    /// Keeps every change of the last day, one per hour for 30 days and one
+    /// per day before that, unless the project's `[retention]` section says
+    /// otherwise. Tagged states are kept.
+    Gc {
+        /// Show what would be compacted and reclaimed, changing nothing
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// List unresolved conflicts in the current channel
+    ///
+    /// Conflicted files carry markers around each side. Edit them into the
+    /// wanted shape, markers included; the edit is recorded as the resolution.
+    Conflicts {
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Show or change who changes are recorded as
+    ///
+    /// The global identity lives in the user config directory
+    /// (`~/.config/ize/identity.toml` on Linux); a project's `[identity]`
+    /// section overrides it field by field.
+    Identity {
+        #[command(subcommand)]
+        action: IdentityAction,
+    },
+
+    /// Show or rotate the key an encrypted project is sealed with
+    ///
+    /// Opening an encrypted project needs its key: the key file it names
+    /// (or the one in IZE_KEY_FILE), or the passphrase in IZE_PASSPHRASE.
+    Key {
+        #[command(subcommand)]
+        action: KeyAction,
+    },
+
+    /// Name states of a channel, to come back to them later
+    ///
+    /// A tag can be given wherever a change hash is accepted.
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
     },
 
     /// Create a new channel (branch)
@@ -159,11 +427,23 @@ pub enum ChannelAction {
     },
 
     /// Switch to a different channel
+    ///
+    /// Rewrites the working copy to match NAME. Refuses if files have
+    /// unrecorded changes unless `--stash` or `--force` is given. If the
+    /// project is mounted, the running mount performs the switch.
     Switch {
         /// Name of the channel to switch to
         #[arg(value_name = "NAME")]
         name: String,
 
+        /// Record unrecorded changes on a new `stash/...` channel first
+        #[arg(long, conflicts_with = "force")]
+        stash: bool,
+
+        /// Overwrite unrecorded changes
+        #[arg(long)]
+        force: bool,
+
         /// Directory of the project (default: current directory)
         #[arg(long, short)]
         directory: Option<PathBuf>,
@@ -183,4 +463,141 @@ pub enum ChannelAction {
         #[arg(long, short)]
         directory: Option<PathBuf>,
     },
+
+    /// Merge another channel into the current one
+    ///
+    /// Applies every change of FROM the current channel doesn't have yet and
+    /// updates the working copy. Competing edits end up as conflicts, marked
+    /// in the affected files.
+    Merge {
+        /// Channel to take changes from
+        #[arg(value_name = "FROM")]
+        from: String,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TagAction {
+    /// Tag the current channel's latest state
+    Create {
+        /// Name of the tag
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Note to keep with the tag
+        #[arg(long, short)]
+        message: Option<String>,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// List all tags
+    List {
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Delete a tag; the changes it named are kept
+    Delete {
+        /// Name of the tag
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Show a tag and the change it points at
+    Show {
+        /// Name of the tag
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IdentityAction {
+    /// Show the identity changes are recorded as
+    Show {
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Change the global identity, or a project's with `--project`
+    Set {
+        /// Author name
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Author email
+        #[arg(long)]
+        email: Option<String>,
+
+        /// Secret ed25519 key file
+        #[arg(long, value_name = "FILE", conflicts_with = "generate_key")]
+        key: Option<PathBuf>,
+
+        /// Generate a new key and use it
+        #[arg(long)]
+        generate_key: bool,
+
+        /// Sign recorded changes
+        #[arg(long, conflicts_with = "no_sign")]
+        sign: bool,
+
+        /// Stop signing recorded changes
+        #[arg(long)]
+        no_sign: bool,
+
+        /// Change the project's identity instead of the global one
+        #[arg(long)]
+        project: bool,
+
+        /// Directory of the project, with `--project` (default: current
+        /// directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KeyAction {
+    /// Show which key the project is sealed with and where it is read from
+    Show {
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
+
+    /// Seal the project's history under a new key
+    ///
+    /// By default a new key replaces the current key file. The project must
+    /// not be mounted.
+    Rotate {
+        /// Key file to seal with from now on, generated if it doesn't exist
+        #[arg(long, value_name = "FILE", conflicts_with = "passphrase")]
+        keyfile: Option<PathBuf>,
+
+        /// Derive the new key from a passphrase, taken from
+        /// IZE_NEW_PASSPHRASE or asked for
+        #[arg(long)]
+        passphrase: bool,
+
+        /// Directory of the project (default: current directory)
+        #[arg(long, short)]
+        directory: Option<PathBuf>,
+    },
 }
@@ -0,0 +1,647 @@
+//! The individual conformance checks.
+//!
+//! Each check gets a target and an empty scratch directory, and returns on
+//! the first deviation.  Expected errno values follow Linux where POSIX
+//! leaves a choice (e.g. `EISDIR` for `unlink` of a directory), except where
+//! both answers are common enough that either is accepted.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::{FileKind, Outcome, PosixTarget};
+
+/// Why a check stopped early.
+enum Stop {
+    Fail(String),
+    Skip(String),
+}
+
+type CheckResult = Result<(), Stop>;
+
+/// A named conformance check.
+#[derive(Clone, Copy)]
+pub struct Check {
+    pub name: &'static str,
+    /// Group the check belongs to, e.g. `"rename"`.
+    pub area: &'static str,
+    func: fn(&dyn PosixTarget, &Path) -> CheckResult,
+}
+
+impl Check {
+    /// Run the check inside `scratch`, which must exist and be empty.
+    pub fn run(&self, target: &dyn PosixTarget, scratch: &Path) -> Outcome {
+        match (self.func)(target, scratch) {
+            Ok(()) => Outcome::Passed,
+            Err(Stop::Fail(msg)) => Outcome::Failed(msg),
+            Err(Stop::Skip(why)) => Outcome::Skipped(why),
+        }
+    }
+}
+
+impl std::fmt::Debug for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.area, self.name)
+    }
+}
+
+macro_rules! checks {
+    ($($area:literal => [$($name:ident),* $(,)?]),* $(,)?) => {
+        /// Every check, in the order [`run`](super::run) executes them.
+        pub const CHECKS: &[Check] = &[
+            $($(Check { name: stringify!($name), area: $area, func: $name },)*)*
+        ];
+    };
+}
+
+checks! {
+    "file" => [create_write_read, create_exclusive, create_truncates, overwrite_in_place, read_past_eof],
+    "dir" => [mkdir_readdir, unlink_file, unlink_errors, rmdir_errors],
+    "rename" => [rename_file, rename_replaces_file, rename_directory, rename_errors],
+    "truncate" => [truncate_shrink, truncate_extend_zero_fills, truncate_to_zero],
+    "sparse" => [sparse_hole_reads_zero, sparse_extend_by_write],
+    "perm" => [create_mode, chmod_mode_bits, permission_denied],
+    "symlink" => [symlink_readlink, symlink_dangling, symlink_exists, symlink_rename, symlink_unlink_keeps_target],
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Attach `what` to an unexpected error; unsupported operations skip.
+fn op<T>(what: impl std::fmt::Display, result: io::Result<T>) -> Result<T, Stop> {
+    result.map_err(|e| match e.raw_os_error() {
+        Some(libc::ENOSYS) | Some(libc::EOPNOTSUPP) => {
+            Stop::Skip(format!("{} unsupported: {}", what, e))
+        }
+        _ => Stop::Fail(format!("{}: {}", what, e)),
+    })
+}
+
+/// Require `result` to fail with one of `errnos`.
+fn expect_err<T: std::fmt::Debug>(
+    what: impl std::fmt::Display,
+    result: io::Result<T>,
+    errnos: &[i32],
+) -> CheckResult {
+    let expected = errnos
+        .iter()
+        .map(|e| io::Error::from_raw_os_error(*e).to_string())
+        .collect::<Vec<_>>()
+        .join(" or ");
+    match result {
+        Ok(value) => Err(Stop::Fail(format!(
+            "{}: succeeded with {:?}, expected {}",
+            what, value, expected
+        ))),
+        Err(e)
+            if matches!(
+                e.raw_os_error(),
+                Some(libc::ENOSYS) | Some(libc::EOPNOTSUPP)
+            ) =>
+        {
+            Err(Stop::Skip(format!("{} unsupported: {}", what, e)))
+        }
+        Err(e) if e.raw_os_error().is_some_and(|n| errnos.contains(&n)) => Ok(()),
+        Err(e) => Err(Stop::Fail(format!(
+            "{}: got {}, expected {}",
+            what, e, expected
+        ))),
+    }
+}
+
+fn ensure_eq<T: PartialEq + std::fmt::Debug>(what: &str, got: T, expected: T) -> CheckResult {
+    if got == expected {
+        Ok(())
+    } else {
+        Err(Stop::Fail(format!(
+            "{}: got {:?}, expected {:?}",
+            what, got, expected
+        )))
+    }
+}
+
+/// Create (or truncate) `rel` and fill it with `data`.
+fn write_file(t: &dyn PosixTarget, rel: &Path, data: &[u8]) -> CheckResult {
+    op(format!("create {:?}", rel), t.create(rel, 0o644, false))?;
+    if !data.is_empty() {
+        let n = op(format!("write {:?}", rel), t.write_at(rel, 0, data))?;
+        ensure_eq(&format!("bytes written to {:?}", rel), n, data.len())?;
+    }
+    Ok(())
+}
+
+/// Whole-file contents, using the size from `stat`.
+fn read_file(t: &dyn PosixTarget, rel: &Path) -> Result<Vec<u8>, Stop> {
+    let size = op(format!("stat {:?}", rel), t.stat(rel))?.size;
+    // Ask for one byte more than the size so trailing garbage shows up.
+    op(
+        format!("read {:?}", rel),
+        t.read_at(rel, 0, size as usize + 1),
+    )
+}
+
+fn ensure_content(t: &dyn PosixTarget, rel: &Path, expected: &[u8]) -> CheckResult {
+    let got = read_file(t, rel)?;
+    if got == expected {
+        Ok(())
+    } else {
+        Err(Stop::Fail(format!(
+            "content of {:?}: got {:?}, expected {:?}",
+            rel,
+            String::from_utf8_lossy(&got),
+            String::from_utf8_lossy(expected)
+        )))
+    }
+}
+
+fn ensure_missing(t: &dyn PosixTarget, rel: &Path) -> CheckResult {
+    expect_err(format!("stat {:?}", rel), t.stat(rel), &[libc::ENOENT])
+}
+
+fn ensure_kind(t: &dyn PosixTarget, rel: &Path, kind: FileKind) -> CheckResult {
+    let stat = op(format!("stat {:?}", rel), t.stat(rel))?;
+    ensure_eq(&format!("kind of {:?}", rel), stat.kind, kind)
+}
+
+fn names(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+// ---------------------------------------------------------------------------
+// Files
+// ---------------------------------------------------------------------------
+
+fn create_write_read(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("hello.txt");
+    write_file(t, &file, b"hello world")?;
+    ensure_kind(t, &file, FileKind::File)?;
+    ensure_eq("size", op("stat", t.stat(&file))?.size, 11)?;
+    ensure_content(t, &file, b"hello world")?;
+    ensure_eq(
+        "partial read",
+        op("read_at", t.read_at(&file, 6, 5))?,
+        b"world".to_vec(),
+    )
+}
+
+fn create_exclusive(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("once");
+    op("first exclusive create", t.create(&file, 0o644, true))?;
+    expect_err(
+        "second exclusive create",
+        t.create(&file, 0o644, true),
+        &[libc::EEXIST],
+    )?;
+    op("mkdir", t.mkdir(&dir.join("sub"), 0o755))?;
+    expect_err(
+        "exclusive create over a directory",
+        t.create(&dir.join("sub"), 0o644, true),
+        &[libc::EEXIST],
+    )
+}
+
+fn create_truncates(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("f");
+    write_file(t, &file, b"previous contents")?;
+    op("re-create", t.create(&file, 0o644, false))?;
+    ensure_eq("size after re-create", op("stat", t.stat(&file))?.size, 0)
+}
+
+fn overwrite_in_place(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("f");
+    write_file(t, &file, b"hello world")?;
+    op("overwrite", t.write_at(&file, 6, b"WORLD"))?;
+    ensure_content(t, &file, b"hello WORLD")?;
+    op("append", t.write_at(&file, 11, b"!"))?;
+    ensure_content(t, &file, b"hello WORLD!")
+}
+
+fn read_past_eof(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("f");
+    write_file(t, &file, b"abc")?;
+    ensure_eq(
+        "read at EOF",
+        op("read", t.read_at(&file, 3, 8))?,
+        Vec::new(),
+    )?;
+    ensure_eq(
+        "read past EOF",
+        op("read", t.read_at(&file, 100, 8))?,
+        Vec::new(),
+    )?;
+    ensure_eq(
+        "read across EOF",
+        op("read", t.read_at(&file, 1, 8))?,
+        b"bc".to_vec(),
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Directories
+// ---------------------------------------------------------------------------
+
+fn mkdir_readdir(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let sub = dir.join("d");
+    op("mkdir", t.mkdir(&sub, 0o755))?;
+    ensure_kind(t, &sub, FileKind::Dir)?;
+    ensure_eq("empty readdir", op("readdir", t.readdir(&sub))?, Vec::new())?;
+
+    write_file(t, &sub.join("b"), b"")?;
+    write_file(t, &sub.join("a"), b"")?;
+    op("nested mkdir", t.mkdir(&sub.join("c"), 0o755))?;
+    ensure_eq(
+        "readdir",
+        op("readdir", t.readdir(&sub))?,
+        names(&["a", "b", "c"]),
+    )?;
+
+    expect_err("mkdir existing", t.mkdir(&sub, 0o755), &[libc::EEXIST])?;
+    expect_err(
+        "mkdir under missing parent",
+        t.mkdir(&dir.join("missing/d"), 0o755),
+        &[libc::ENOENT],
+    )?;
+    expect_err(
+        "mkdir under a file",
+        t.mkdir(&sub.join("a/x"), 0o755),
+        &[libc::ENOTDIR],
+    )
+}
+
+fn unlink_file(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("f");
+    write_file(t, &file, b"data")?;
+    write_file(t, &dir.join("other"), b"")?;
+    op("unlink", t.unlink(&file))?;
+    ensure_missing(t, &file)?;
+    ensure_eq("readdir", op("readdir", t.readdir(dir))?, names(&["other"]))
+}
+
+fn unlink_errors(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    op("mkdir", t.mkdir(&dir.join("d"), 0o755))?;
+    expect_err(
+        "unlink missing",
+        t.unlink(&dir.join("missing")),
+        &[libc::ENOENT],
+    )?;
+    // POSIX allows EPERM; Linux answers EISDIR.
+    expect_err(
+        "unlink directory",
+        t.unlink(&dir.join("d")),
+        &[libc::EISDIR, libc::EPERM],
+    )?;
+    ensure_kind(t, &dir.join("d"), FileKind::Dir)
+}
+
+fn rmdir_errors(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let sub = dir.join("d");
+    op("mkdir", t.mkdir(&sub, 0o755))?;
+    write_file(t, &sub.join("f"), b"")?;
+
+    expect_err(
+        "rmdir non-empty",
+        t.rmdir(&sub),
+        &[libc::ENOTEMPTY, libc::EEXIST],
+    )?;
+    expect_err("rmdir file", t.rmdir(&sub.join("f")), &[libc::ENOTDIR])?;
+    expect_err(
+        "rmdir missing",
+        t.rmdir(&dir.join("missing")),
+        &[libc::ENOENT],
+    )?;
+
+    op("unlink", t.unlink(&sub.join("f")))?;
+    op("rmdir empty", t.rmdir(&sub))?;
+    ensure_missing(t, &sub)
+}
+
+// ---------------------------------------------------------------------------
+// Renames
+// ---------------------------------------------------------------------------
+
+fn rename_file(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let (a, b) = (dir.join("a"), dir.join("b"));
+    write_file(t, &a, b"contents")?;
+    op("rename", t.rename(&a, &b))?;
+    ensure_missing(t, &a)?;
+    ensure_content(t, &b, b"contents")?;
+
+    op("mkdir", t.mkdir(&dir.join("d"), 0o755))?;
+    let moved = dir.join("d/c");
+    op("rename into subdirectory", t.rename(&b, &moved))?;
+    ensure_content(t, &moved, b"contents")?;
+    ensure_eq("readdir", op("readdir", t.readdir(dir))?, names(&["d"]))
+}
+
+fn rename_replaces_file(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    // The write-temp-then-rename pattern editors use to save atomically.
+    let (tmp, target) = (dir.join("file.tmp"), dir.join("file"));
+    write_file(t, &target, b"old contents, longer")?;
+    write_file(t, &tmp, b"new")?;
+    op("rename over existing", t.rename(&tmp, &target))?;
+    ensure_missing(t, &tmp)?;
+    ensure_content(t, &target, b"new")?;
+    ensure_eq("readdir", op("readdir", t.readdir(dir))?, names(&["file"]))
+}
+
+fn rename_directory(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let (from, to) = (dir.join("src"), dir.join("dst"));
+    op("mkdir", t.mkdir(&from, 0o755))?;
+    op("mkdir nested", t.mkdir(&from.join("inner"), 0o755))?;
+    write_file(t, &from.join("inner/f"), b"nested")?;
+
+    op("rename directory", t.rename(&from, &to))?;
+    ensure_missing(t, &from)?;
+    ensure_content(t, &to.join("inner/f"), b"nested")?;
+
+    // Renaming over an empty directory replaces it.
+    let empty = dir.join("empty");
+    op("mkdir empty", t.mkdir(&empty, 0o755))?;
+    op("rename over empty directory", t.rename(&to, &empty))?;
+    ensure_content(t, &empty.join("inner/f"), b"nested")?;
+    ensure_eq("readdir", op("readdir", t.readdir(dir))?, names(&["empty"]))
+}
+
+fn rename_errors(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let (file, d, full) = (dir.join("f"), dir.join("d"), dir.join("full"));
+    write_file(t, &file, b"x")?;
+    op("mkdir", t.mkdir(&d, 0o755))?;
+    op("mkdir", t.mkdir(&full, 0o755))?;
+    write_file(t, &full.join("f"), b"")?;
+
+    expect_err(
+        "rename missing",
+        t.rename(&dir.join("missing"), &dir.join("x")),
+        &[libc::ENOENT],
+    )?;
+    expect_err(
+        "rename file over directory",
+        t.rename(&file, &d),
+        &[libc::EISDIR],
+    )?;
+    expect_err(
+        "rename directory over file",
+        t.rename(&d, &file),
+        &[libc::ENOTDIR],
+    )?;
+    expect_err(
+        "rename directory over non-empty directory",
+        t.rename(&d, &full),
+        &[libc::ENOTEMPTY, libc::EEXIST],
+    )?;
+    expect_err(
+        "rename directory into itself",
+        t.rename(&full, &full.join("inside")),
+        &[libc::EINVAL],
+    )?;
+
+    // Nothing moved.
+    ensure_content(t, &file, b"x")?;
+    ensure_eq(
+        "readdir",
+        op("readdir", t.readdir(dir))?,
+        names(&["d", "f", "full"]),
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Truncation
+// ---------------------------------------------------------------------------
+
+fn truncate_shrink(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("f");
+    write_file(t, &file, b"0123456789")?;
+    op("truncate", t.truncate(&file, 4))?;
+    ensure_eq("size", op("stat", t.stat(&file))?.size, 4)?;
+    ensure_content(t, &file, b"0123")
+}
+
+fn truncate_extend_zero_fills(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("f");
+    write_file(t, &file, b"ab")?;
+    op("truncate", t.truncate(&file, 6))?;
+    ensure_eq("size", op("stat", t.stat(&file))?.size, 6)?;
+    ensure_content(t, &file, b"ab\0\0\0\0")?;
+
+    // Shrinking and re-extending must not resurrect the old bytes.
+    write_file(t, &file, b"secret")?;
+    op("shrink", t.truncate(&file, 1))?;
+    op("extend", t.truncate(&file, 6))?;
+    ensure_content(t, &file, b"s\0\0\0\0\0")
+}
+
+fn truncate_to_zero(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("f");
+    write_file(t, &file, b"some data")?;
+    op("truncate", t.truncate(&file, 0))?;
+    ensure_content(t, &file, b"")?;
+    expect_err(
+        "truncate missing",
+        t.truncate(&dir.join("missing"), 0),
+        &[libc::ENOENT],
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Sparse files
+// ---------------------------------------------------------------------------
+
+/// Far enough out to span several blocks on any backing store.
+const HOLE: u64 = 1 << 20;
+
+fn sparse_hole_reads_zero(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("sparse");
+    op("create", t.create(&file, 0o644, false))?;
+    op("write past hole", t.write_at(&file, HOLE, b"tail"))?;
+    ensure_eq("size", op("stat", t.stat(&file))?.size, HOLE + 4)?;
+
+    let head = op("read hole", t.read_at(&file, 0, 4096))?;
+    if head.len() != 4096 || head.iter().any(|b| *b != 0) {
+        return Err(Stop::Fail("hole did not read back as zeros".into()));
+    }
+    let middle = op("read hole", t.read_at(&file, HOLE / 2, 16))?;
+    ensure_eq("middle of hole", middle, vec![0u8; 16])?;
+    ensure_eq(
+        "data after hole",
+        op("read tail", t.read_at(&file, HOLE, 16))?,
+        b"tail".to_vec(),
+    )
+}
+
+fn sparse_extend_by_write(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("f");
+    write_file(t, &file, b"head")?;
+    op("write past EOF", t.write_at(&file, 10, b"tail"))?;
+    ensure_content(t, &file, b"head\0\0\0\0\0\0tail")
+}
+
+// ---------------------------------------------------------------------------
+// Permissions
+// ---------------------------------------------------------------------------
+
+fn create_mode(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    // 0o600 and 0o700 survive any common umask.
+    let file = dir.join("f");
+    op("create", t.create(&file, 0o600, true))?;
+    ensure_eq("file mode", op("stat", t.stat(&file))?.perm, 0o600)?;
+
+    let sub = dir.join("d");
+    op("mkdir", t.mkdir(&sub, 0o700))?;
+    ensure_eq("directory mode", op("stat", t.stat(&sub))?.perm, 0o700)
+}
+
+fn chmod_mode_bits(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let file = dir.join("script.sh");
+    write_file(t, &file, b"#!/bin/sh\n")?;
+    for mode in [0o755, 0o640, 0o400, 0o644] {
+        op(format!("chmod {:o}", mode), t.chmod(&file, mode))?;
+        ensure_eq(
+            &format!("mode after chmod {:o}", mode),
+            op("stat", t.stat(&file))?.perm,
+            mode,
+        )?;
+    }
+
+    let sub = dir.join("d");
+    op("mkdir", t.mkdir(&sub, 0o755))?;
+    op("chmod directory", t.chmod(&sub, 0o711))?;
+    ensure_eq("directory mode", op("stat", t.stat(&sub))?.perm, 0o711)?;
+    ensure_kind(t, &sub, FileKind::Dir)?;
+
+    expect_err(
+        "chmod missing",
+        t.chmod(&dir.join("missing"), 0o644),
+        &[libc::ENOENT],
+    )
+}
+
+fn permission_denied(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    if t.privileged() {
+        return Err(Stop::Skip("caller bypasses permission bits".into()));
+    }
+
+    let file = dir.join("f");
+    write_file(t, &file, b"guarded")?;
+
+    op("chmod 0400", t.chmod(&file, 0o400))?;
+    expect_err(
+        "write to read-only file",
+        t.write_at(&file, 0, b"x"),
+        &[libc::EACCES],
+    )?;
+    expect_err(
+        "truncate read-only file",
+        t.truncate(&file, 0),
+        &[libc::EACCES],
+    )?;
+    ensure_content(t, &file, b"guarded")?;
+
+    op("chmod 0200", t.chmod(&file, 0o200))?;
+    expect_err(
+        "read write-only file",
+        t.read_at(&file, 0, 8),
+        &[libc::EACCES],
+    )?;
+    op("chmod 0644", t.chmod(&file, 0o644))?;
+
+    let locked = dir.join("locked");
+    op("mkdir", t.mkdir(&locked, 0o755))?;
+    write_file(t, &locked.join("inside"), b"")?;
+    op("chmod dir 0555", t.chmod(&locked, 0o555))?;
+    expect_err(
+        "create in read-only directory",
+        t.create(&locked.join("new"), 0o644, true),
+        &[libc::EACCES],
+    )?;
+    expect_err(
+        "unlink in read-only directory",
+        t.unlink(&locked.join("inside")),
+        &[libc::EACCES],
+    )?;
+    op("chmod dir 0755", t.chmod(&locked, 0o755))
+}
+
+// ---------------------------------------------------------------------------
+// Symlinks
+// ---------------------------------------------------------------------------
+
+fn symlink_readlink(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    write_file(t, &dir.join("target.txt"), b"pointed at")?;
+    let link = dir.join("link");
+    op("symlink", t.symlink(Path::new("target.txt"), &link))?;
+
+    ensure_kind(t, &link, FileKind::Symlink)?;
+    ensure_eq(
+        "readlink",
+        op("readlink", t.readlink(&link))?,
+        PathBuf::from("target.txt"),
+    )?;
+    ensure_eq(
+        "symlink size",
+        op("stat", t.stat(&link))?.size,
+        "target.txt".len() as u64,
+    )?;
+    ensure_eq(
+        "readdir",
+        op("readdir", t.readdir(dir))?,
+        names(&["link", "target.txt"]),
+    )?;
+    expect_err(
+        "readlink on a regular file",
+        t.readlink(&dir.join("target.txt")),
+        &[libc::EINVAL],
+    )
+}
+
+fn symlink_dangling(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let link = dir.join("dangling");
+    let target = Path::new("../nowhere/at/all");
+    op("symlink", t.symlink(target, &link))?;
+    ensure_kind(t, &link, FileKind::Symlink)?;
+    ensure_eq(
+        "readlink",
+        op("readlink", t.readlink(&link))?,
+        target.to_path_buf(),
+    )
+}
+
+fn symlink_exists(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    write_file(t, &dir.join("f"), b"")?;
+    op("mkdir", t.mkdir(&dir.join("d"), 0o755))?;
+    op("symlink", t.symlink(Path::new("f"), &dir.join("l")))?;
+
+    for name in ["f", "d", "l"] {
+        expect_err(
+            format!("symlink over existing {}", name),
+            t.symlink(Path::new("x"), &dir.join(name)),
+            &[libc::EEXIST],
+        )?;
+    }
+    ensure_eq(
+        "original link untouched",
+        op("readlink", t.readlink(&dir.join("l")))?,
+        PathBuf::from("f"),
+    )
+}
+
+fn symlink_rename(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let (from, to) = (dir.join("l1"), dir.join("l2"));
+    op("symlink", t.symlink(Path::new("somewhere"), &from))?;
+    op("rename symlink", t.rename(&from, &to))?;
+    ensure_missing(t, &from)?;
+    ensure_kind(t, &to, FileKind::Symlink)?;
+    ensure_eq(
+        "readlink after rename",
+        op("readlink", t.readlink(&to))?,
+        PathBuf::from("somewhere"),
+    )
+}
+
+fn symlink_unlink_keeps_target(t: &dyn PosixTarget, dir: &Path) -> CheckResult {
+    let (target, link) = (dir.join("target"), dir.join("link"));
+    write_file(t, &target, b"survives")?;
+    op("symlink", t.symlink(Path::new("target"), &link))?;
+    op("unlink symlink", t.unlink(&link))?;
+    ensure_missing(t, &link)?;
+    ensure_content(t, &target, b"survives")
+}
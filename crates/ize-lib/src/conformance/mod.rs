@@ -0,0 +1,341 @@
+//! POSIX conformance suite for backing stores and filesystems.
+//!
+//! The same set of checks runs against any layer of the stack, so a new
+//! [`BackingFs`](crate::backing_fs::BackingFs) implementation, a change to
+//! the passthrough, or a real mount can all be held to one definition of
+//! "behaves like a POSIX filesystem".
+//!
+//! # Targets
+//!
+//! A [`PosixTarget`] is a path-based view of the thing under test:
+//!
+//! * [`BackingFsTarget`] — calls a `BackingFs` directly
+//!   (e.g. [`LibcBackingFs`](crate::backing_fs::LibcBackingFs) or
+//!   [`MemBackingFs`](crate::backing_fs::MemBackingFs)).
+//! * [`FsOpsTarget`] — drives an [`FsOps`](crate::filesystems::FsOps)
+//!   filesystem in-process, resolving paths with `lookup` the way the kernel
+//!   does.
+//! * [`MountedTarget`] — plain `std::fs` calls under a directory, which may
+//!   be a live FUSE mount.
+//!
+//! # Checks
+//!
+//! Checks cover file I/O, directories, renames, truncation, sparse files,
+//! permissions and symlinks.  Each one runs in its own scratch directory
+//! under the target root, which is removed afterwards.  A check is
+//! *skipped* rather than failed when the target answers `ENOSYS` or
+//! `EOPNOTSUPP`, or when it needs an unprivileged caller and the target
+//! reports [`PosixTarget::privileged`].
+//!
+//! ```
+//! use ize_lib::backing_fs::MemBackingFs;
+//! use ize_lib::conformance::{self, BackingFsTarget};
+//!
+//! let report = conformance::run(&BackingFsTarget::new(MemBackingFs::new()));
+//! assert!(report.is_success(), "{}", report);
+//! ```
+
+mod checks;
+mod targets;
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use checks::{Check, CHECKS};
+pub use targets::{BackingFsTarget, FsOpsTarget, MountedTarget};
+
+// ---------------------------------------------------------------------------
+// Target abstraction
+// ---------------------------------------------------------------------------
+
+/// Kind of a directory entry, as seen without following symlinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+impl FileKind {
+    /// Classify a `st_mode` value.
+    pub fn from_mode(mode: u32) -> Self {
+        match mode & libc::S_IFMT {
+            libc::S_IFREG => FileKind::File,
+            libc::S_IFDIR => FileKind::Dir,
+            libc::S_IFLNK => FileKind::Symlink,
+            _ => FileKind::Other,
+        }
+    }
+}
+
+/// The subset of `stat` the checks look at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stat {
+    pub kind: FileKind,
+    /// Size in bytes.
+    pub size: u64,
+    /// Permission bits (`0o7777`).
+    pub perm: u32,
+}
+
+/// Path-based operations the conformance checks are written against.
+///
+/// All paths are relative to the target root.  Errors carry the raw `errno`
+/// (`io::Error::raw_os_error`) so checks can assert on exact codes.
+pub trait PosixTarget {
+    /// Human-readable name used in reports.
+    fn describe(&self) -> String;
+
+    /// Whether the caller bypasses permission bits (i.e. runs as root).
+    ///
+    /// Defaults to checking the effective uid of this process.
+    fn privileged(&self) -> bool {
+        euid_is_root()
+    }
+
+    /// Create a regular file, truncating an existing one unless `exclusive`
+    /// is set, in which case an existing entry fails with `EEXIST`.
+    fn create(&self, rel: &Path, mode: u32, exclusive: bool) -> io::Result<()>;
+
+    /// Write `data` at `offset` into an existing file.
+    fn write_at(&self, rel: &Path, offset: u64, data: &[u8]) -> io::Result<usize>;
+
+    /// Read up to `len` bytes at `offset`; fewer at end of file.
+    fn read_at(&self, rel: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Truncate or extend a file to `size` bytes.
+    fn truncate(&self, rel: &Path, size: u64) -> io::Result<()>;
+
+    /// `lstat`.
+    fn stat(&self, rel: &Path) -> io::Result<Stat>;
+
+    fn mkdir(&self, rel: &Path, mode: u32) -> io::Result<()>;
+
+    fn rmdir(&self, rel: &Path) -> io::Result<()>;
+
+    fn unlink(&self, rel: &Path) -> io::Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Entry names in a directory, sorted, without `.` and `..`.
+    fn readdir(&self, rel: &Path) -> io::Result<Vec<String>>;
+
+    fn chmod(&self, rel: &Path, mode: u32) -> io::Result<()>;
+
+    fn symlink(&self, target: &Path, rel: &Path) -> io::Result<()>;
+
+    fn readlink(&self, rel: &Path) -> io::Result<PathBuf>;
+}
+
+// ---------------------------------------------------------------------------
+// Reports
+// ---------------------------------------------------------------------------
+
+/// Result of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+/// One check's name and outcome.
+#[derive(Debug, Clone)]
+pub struct CheckReport {
+    pub name: &'static str,
+    pub area: &'static str,
+    pub outcome: Outcome,
+}
+
+/// Outcomes of a suite run against one target.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub target: String,
+    pub checks: Vec<CheckReport>,
+}
+
+impl Report {
+    /// True if no check failed (skips are allowed).
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none()
+    }
+
+    pub fn passed(&self) -> impl Iterator<Item = &CheckReport> {
+        self.checks
+            .iter()
+            .filter(|c| matches!(c.outcome, Outcome::Passed))
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &CheckReport> {
+        self.checks
+            .iter()
+            .filter(|c| matches!(c.outcome, Outcome::Failed(_)))
+    }
+
+    pub fn skipped(&self) -> impl Iterator<Item = &CheckReport> {
+        self.checks
+            .iter()
+            .filter(|c| matches!(c.outcome, Outcome::Skipped(_)))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "conformance: {}", self.target)?;
+        for check in &self.checks {
+            match &check.outcome {
+                Outcome::Passed => writeln!(f, "  PASS {}/{}", check.area, check.name)?,
+                Outcome::Failed(msg) => {
+                    writeln!(f, "  FAIL {}/{}: {}", check.area, check.name, msg)?
+                }
+                Outcome::Skipped(why) => {
+                    writeln!(f, "  SKIP {}/{}: {}", check.area, check.name, why)?
+                }
+            }
+        }
+        write!(
+            f,
+            "{} passed, {} failed, {} skipped",
+            self.passed().count(),
+            self.failed().count(),
+            self.skipped().count()
+        )
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Running
+// ---------------------------------------------------------------------------
+
+/// Run every check in [`CHECKS`] against `target`.
+pub fn run(target: &dyn PosixTarget) -> Report {
+    run_checks(target, CHECKS)
+}
+
+/// Run the given checks against `target`.
+///
+/// Each check gets a fresh scratch directory named after it; the directory
+/// is removed afterwards whether the check passed or not.
+pub fn run_checks(target: &dyn PosixTarget, checks: &[Check]) -> Report {
+    let checks = checks
+        .iter()
+        .map(|check| {
+            let scratch = PathBuf::from(format!("ize-conformance-{}", check.name));
+            let _ = remove_tree(target, &scratch);
+            let outcome = match target.mkdir(&scratch, 0o755) {
+                Ok(()) => check.run(target, &scratch),
+                Err(e) => Outcome::Failed(format!("creating scratch dir {:?}: {}", scratch, e)),
+            };
+            let _ = remove_tree(target, &scratch);
+            CheckReport {
+                name: check.name,
+                area: check.area,
+                outcome,
+            }
+        })
+        .collect();
+
+    Report {
+        target: target.describe(),
+        checks,
+    }
+}
+
+fn euid_is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Recursively remove `rel`, restoring write/search permission on
+/// directories a check may have locked down.
+fn remove_tree(target: &dyn PosixTarget, rel: &Path) -> io::Result<()> {
+    match target.stat(rel)?.kind {
+        FileKind::Dir => {
+            let _ = target.chmod(rel, 0o700);
+            for name in target.readdir(rel)? {
+                remove_tree(target, &rel.join(name))?;
+            }
+            target.rmdir(rel)
+        }
+        _ => target.unlink(rel),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backing_fs::{LibcBackingFs, MemBackingFs};
+    use crate::filesystems::passthrough_fd::FdPassthroughFS;
+    use crate::filesystems::ObservingFS;
+
+    fn assert_conforms(target: &dyn PosixTarget) {
+        let report = run(target);
+        assert!(report.is_success(), "{}", report);
+        assert_eq!(report.checks.len(), CHECKS.len());
+    }
+
+    #[test]
+    fn mem_backing_conforms() {
+        assert_conforms(&BackingFsTarget::new(MemBackingFs::new()));
+    }
+
+    #[test]
+    fn mem_backing_enforces_permissions_for_unprivileged_caller() {
+        let target =
+            BackingFsTarget::new(MemBackingFs::with_credentials(1000, 1000)).with_privileged(false);
+        let report = run(&target);
+        assert!(report.is_success(), "{}", report);
+        assert_eq!(report.skipped().count(), 0, "{}", report);
+    }
+
+    #[test]
+    fn libc_backing_conforms() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backing = LibcBackingFs::open_dir(tmp.path()).unwrap();
+        assert_conforms(&BackingFsTarget::new(backing));
+    }
+
+    #[test]
+    fn passthrough_over_mem_conforms() {
+        let fs = FdPassthroughFS::new(MemBackingFs::new(), PathBuf::from("/conformance"));
+        assert_conforms(&FsOpsTarget::new(fs));
+    }
+
+    #[test]
+    fn observed_passthrough_over_libc_conforms() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backing = LibcBackingFs::open_dir(tmp.path()).unwrap();
+        let fs = ObservingFS::new(FdPassthroughFS::new(backing, tmp.path().to_path_buf()));
+        assert_conforms(&FsOpsTarget::new(fs));
+    }
+
+    #[test]
+    fn plain_directory_conforms() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_conforms(&MountedTarget::new(tmp.path()));
+    }
+
+    #[test]
+    fn scratch_directories_are_removed() {
+        let backing = std::sync::Arc::new(MemBackingFs::new());
+        run(&BackingFsTarget::new(backing.clone()));
+        let entries = crate::backing_fs::BackingFs::readdir(&*backing, Path::new("")).unwrap();
+        let names: Vec<_> = entries
+            .iter()
+            .filter(|e| e.name != "." && e.name != "..")
+            .collect();
+        assert!(names.is_empty(), "{:?}", names);
+    }
+
+    #[test]
+    fn failures_and_skips_are_reported() {
+        // A read-only passthrough fails every mutating check with EROFS.
+        let mut fs = FdPassthroughFS::new(MemBackingFs::new(), PathBuf::from("/ro"));
+        fs.set_read_only(true);
+        let report = run(&FsOpsTarget::new(fs));
+        assert!(!report.is_success());
+        assert!(report.to_string().contains("FAIL"));
+    }
+}
@@ -0,0 +1,540 @@
+//! [`PosixTarget`] adapters for each layer of the stack.
+
+use std::ffi::OsStr;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, FileExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use fuser::{FileAttr, FileType};
+
+use super::{FileKind, PosixTarget, Stat};
+use crate::backing_fs::BackingFs;
+use crate::filesystems::{FsOps, OpResult, SetAttr};
+
+/// Inode FUSE assigns to the mount root.
+const FUSE_ROOT_ID: u64 = 1;
+
+/// Largest single read or write issued in one call, mirroring FUSE's default
+/// `max_read`/`max_write`.
+const IO_CHUNK: usize = 128 * 1024;
+
+// ---------------------------------------------------------------------------
+// BackingFs
+// ---------------------------------------------------------------------------
+
+/// Runs checks directly against a [`BackingFs`].
+pub struct BackingFsTarget<B: BackingFs> {
+    backing: B,
+    privileged: bool,
+}
+
+impl<B: BackingFs> BackingFsTarget<B> {
+    pub fn new(backing: B) -> Self {
+        Self {
+            backing,
+            privileged: super::euid_is_root(),
+        }
+    }
+
+    /// Override whether the backing bypasses permission bits.
+    ///
+    /// Needed for stores that do their own permission checks with configured
+    /// credentials, such as `MemBackingFs::with_credentials`.
+    pub fn with_privileged(mut self, privileged: bool) -> Self {
+        self.privileged = privileged;
+        self
+    }
+
+    fn with_fd<T>(
+        &self,
+        rel: &Path,
+        flags: i32,
+        f: impl FnOnce(i32) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let fd = self.backing.open_file(rel, flags, 0)?;
+        let result = f(fd);
+        self.backing.close_fd(fd);
+        result
+    }
+}
+
+impl<B: BackingFs> PosixTarget for BackingFsTarget<B> {
+    fn describe(&self) -> String {
+        format!("BackingFs ({})", std::any::type_name::<B>())
+    }
+
+    fn privileged(&self) -> bool {
+        self.privileged
+    }
+
+    fn create(&self, rel: &Path, mode: u32, exclusive: bool) -> io::Result<()> {
+        let excl = if exclusive {
+            libc::O_EXCL
+        } else {
+            libc::O_TRUNC
+        };
+        let fd = self
+            .backing
+            .open_file(rel, libc::O_CREAT | libc::O_WRONLY | excl, mode)?;
+        self.backing.close_fd(fd);
+        Ok(())
+    }
+
+    fn write_at(&self, rel: &Path, offset: u64, data: &[u8]) -> io::Result<usize> {
+        self.with_fd(rel, libc::O_WRONLY, |fd| {
+            let mut written = 0;
+            while written < data.len() {
+                let end = (written + IO_CHUNK).min(data.len());
+                let n = self.backing.pwrite(
+                    fd,
+                    &data[written..end],
+                    (offset + written as u64) as i64,
+                )?;
+                if n == 0 {
+                    break;
+                }
+                written += n;
+            }
+            Ok(written)
+        })
+    }
+
+    fn read_at(&self, rel: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.with_fd(rel, libc::O_RDONLY, |fd| {
+            let mut out = vec![0u8; len];
+            let mut filled = 0;
+            while filled < len {
+                let end = (filled + IO_CHUNK).min(len);
+                let n = self.backing.pread(
+                    fd,
+                    &mut out[filled..end],
+                    (offset + filled as u64) as i64,
+                )?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            out.truncate(filled);
+            Ok(out)
+        })
+    }
+
+    fn truncate(&self, rel: &Path, size: u64) -> io::Result<()> {
+        self.with_fd(rel, libc::O_WRONLY, |fd| self.backing.ftruncate(fd, size))
+    }
+
+    fn stat(&self, rel: &Path) -> io::Result<Stat> {
+        let st = self.backing.stat(rel)?;
+        Ok(Stat {
+            kind: FileKind::from_mode(st.st_mode),
+            size: st.st_size as u64,
+            perm: st.st_mode & 0o7777,
+        })
+    }
+
+    fn mkdir(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        self.backing.mkdir(rel, mode)
+    }
+
+    fn rmdir(&self, rel: &Path) -> io::Result<()> {
+        self.backing.rmdir(rel)
+    }
+
+    fn unlink(&self, rel: &Path) -> io::Result<()> {
+        self.backing.unlink(rel)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.backing.rename(from, to)
+    }
+
+    fn readdir(&self, rel: &Path) -> io::Result<Vec<String>> {
+        Ok(sorted_names(
+            self.backing
+                .readdir(rel)?
+                .iter()
+                .map(|e| e.name.as_os_str()),
+        ))
+    }
+
+    fn chmod(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        self.backing.chmod(rel, mode)
+    }
+
+    fn symlink(&self, target: &Path, rel: &Path) -> io::Result<()> {
+        self.backing.symlink(target, rel)
+    }
+
+    fn readlink(&self, rel: &Path) -> io::Result<PathBuf> {
+        self.backing.readlink(rel)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// FsOps
+// ---------------------------------------------------------------------------
+
+/// Runs checks against an [`FsOps`] filesystem without mounting it.
+///
+/// Paths are resolved one component at a time with `lookup`, starting from
+/// the root inode, and files are opened and released around every read and
+/// write — the same call pattern the kernel produces for a short-lived
+/// process.
+pub struct FsOpsTarget<F: FsOps> {
+    fs: Mutex<F>,
+}
+
+impl<F: FsOps> FsOpsTarget<F> {
+    /// Wrap `fs`, calling its `init` hook first.
+    pub fn new(mut fs: F) -> Self {
+        let _ = fs.init();
+        Self { fs: Mutex::new(fs) }
+    }
+
+    /// Give the filesystem back, e.g. to inspect recorded state.
+    pub fn into_inner(self) -> F {
+        self.fs.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn with_fs<T>(&self, f: impl FnOnce(&mut F) -> OpResult<T>) -> io::Result<T> {
+        let mut fs = self.fs.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut fs).map_err(io::Error::from_raw_os_error)
+    }
+}
+
+/// Resolve `rel` to an inode by walking `lookup` from the root.
+fn resolve<F: FsOps>(fs: &mut F, rel: &Path) -> OpResult<u64> {
+    let mut ino = FUSE_ROOT_ID;
+    for component in rel.components() {
+        match component {
+            Component::Normal(name) => ino = fs.lookup(ino, name)?.ino,
+            Component::CurDir => {}
+            _ => return Err(libc::EINVAL),
+        }
+    }
+    Ok(ino)
+}
+
+/// Resolve the parent of `rel` and return it with the final name.
+fn resolve_parent<'a, F: FsOps>(fs: &mut F, rel: &'a Path) -> OpResult<(u64, &'a OsStr)> {
+    let name = rel.file_name().ok_or(libc::EINVAL)?;
+    let parent = resolve(fs, rel.parent().unwrap_or(Path::new("")))?;
+    Ok((parent, name))
+}
+
+fn open_with<F: FsOps, T>(
+    fs: &mut F,
+    rel: &Path,
+    flags: i32,
+    f: impl FnOnce(&mut F, u64, u64) -> OpResult<T>,
+) -> OpResult<T> {
+    let ino = resolve(fs, rel)?;
+    let fh = fs.open(ino, flags)?;
+    let result = f(fs, ino, fh);
+    let _ = fs.release(ino, fh);
+    result
+}
+
+fn attr_to_stat(attr: &FileAttr) -> Stat {
+    Stat {
+        kind: match attr.kind {
+            FileType::RegularFile => FileKind::File,
+            FileType::Directory => FileKind::Dir,
+            FileType::Symlink => FileKind::Symlink,
+            _ => FileKind::Other,
+        },
+        size: attr.size,
+        perm: u32::from(attr.perm),
+    }
+}
+
+impl<F: FsOps> PosixTarget for FsOpsTarget<F> {
+    fn describe(&self) -> String {
+        format!("FsOps ({})", std::any::type_name::<F>())
+    }
+
+    fn create(&self, rel: &Path, mode: u32, exclusive: bool) -> io::Result<()> {
+        self.with_fs(|fs| {
+            let (parent, name) = resolve_parent(fs, rel)?;
+            let flags = libc::O_CREAT
+                | libc::O_WRONLY
+                | if exclusive {
+                    libc::O_EXCL
+                } else {
+                    libc::O_TRUNC
+                };
+            match fs.create(parent, name, mode, flags) {
+                Ok((attr, fh)) => {
+                    let _ = fs.release(attr.ino, fh);
+                    Ok(())
+                }
+                // The kernel only sends CREATE for names it found missing;
+                // an existing file is opened (and truncated) instead.
+                Err(libc::EEXIST) if !exclusive => {
+                    let ino = fs.lookup(parent, name)?.ino;
+                    fs.setattr(
+                        ino,
+                        &SetAttr {
+                            size: Some(0),
+                            ..SetAttr::default()
+                        },
+                    )
+                    .map(drop)
+                }
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    fn write_at(&self, rel: &Path, offset: u64, data: &[u8]) -> io::Result<usize> {
+        self.with_fs(|fs| {
+            open_with(fs, rel, libc::O_WRONLY, |fs, ino, fh| {
+                let mut written = 0;
+                for chunk in data.chunks(IO_CHUNK) {
+                    let n = fs.write(ino, fh, (offset + written as u64) as i64, chunk)? as usize;
+                    written += n;
+                    if n < chunk.len() {
+                        break;
+                    }
+                }
+                Ok(written)
+            })
+        })
+    }
+
+    fn read_at(&self, rel: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.with_fs(|fs| {
+            open_with(fs, rel, libc::O_RDONLY, |fs, ino, fh| {
+                let mut out = Vec::with_capacity(len);
+                while out.len() < len {
+                    let want = (len - out.len()).min(IO_CHUNK) as u32;
+                    let chunk = fs.read(ino, fh, (offset + out.len() as u64) as i64, want)?;
+                    if chunk.is_empty() {
+                        break;
+                    }
+                    out.extend_from_slice(&chunk);
+                }
+                Ok(out)
+            })
+        })
+    }
+
+    fn truncate(&self, rel: &Path, size: u64) -> io::Result<()> {
+        self.with_fs(|fs| {
+            let ino = resolve(fs, rel)?;
+            fs.setattr(
+                ino,
+                &SetAttr {
+                    size: Some(size),
+                    ..SetAttr::default()
+                },
+            )
+            .map(drop)
+        })
+    }
+
+    fn stat(&self, rel: &Path) -> io::Result<Stat> {
+        self.with_fs(|fs| {
+            let ino = resolve(fs, rel)?;
+            fs.getattr(ino, None).map(|attr| attr_to_stat(&attr))
+        })
+    }
+
+    fn mkdir(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        self.with_fs(|fs| {
+            let (parent, name) = resolve_parent(fs, rel)?;
+            fs.mkdir(parent, name, mode).map(drop)
+        })
+    }
+
+    fn rmdir(&self, rel: &Path) -> io::Result<()> {
+        self.with_fs(|fs| {
+            let (parent, name) = resolve_parent(fs, rel)?;
+            fs.rmdir(parent, name)
+        })
+    }
+
+    fn unlink(&self, rel: &Path) -> io::Result<()> {
+        self.with_fs(|fs| {
+            let (parent, name) = resolve_parent(fs, rel)?;
+            fs.unlink(parent, name)
+        })
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.with_fs(|fs| {
+            let (parent, name) = resolve_parent(fs, from)?;
+            let (new_parent, new_name) = resolve_parent(fs, to)?;
+            fs.rename(parent, name, new_parent, new_name)
+        })
+    }
+
+    fn readdir(&self, rel: &Path) -> io::Result<Vec<String>> {
+        self.with_fs(|fs| {
+            let ino = resolve(fs, rel)?;
+            let fh = fs.opendir(ino, libc::O_RDONLY)?;
+            let entries = fs.readdir(ino, fh, 0);
+            let _ = fs.releasedir(ino, fh);
+            Ok(sorted_names(entries?.iter().map(|e| e.name.as_os_str())))
+        })
+    }
+
+    fn chmod(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        self.with_fs(|fs| {
+            let ino = resolve(fs, rel)?;
+            fs.setattr(
+                ino,
+                &SetAttr {
+                    mode: Some(mode),
+                    ..SetAttr::default()
+                },
+            )
+            .map(drop)
+        })
+    }
+
+    fn symlink(&self, target: &Path, rel: &Path) -> io::Result<()> {
+        self.with_fs(|fs| {
+            let (parent, name) = resolve_parent(fs, rel)?;
+            fs.symlink(parent, name, target).map(drop)
+        })
+    }
+
+    fn readlink(&self, rel: &Path) -> io::Result<PathBuf> {
+        self.with_fs(|fs| {
+            let ino = resolve(fs, rel)?;
+            let target = fs.readlink(ino)?;
+            Ok(PathBuf::from(OsStr::from_bytes(&target)))
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Mounted path
+// ---------------------------------------------------------------------------
+
+/// Runs checks with ordinary `std::fs` calls under a directory.
+///
+/// Point it at a live FUSE mount to test the whole stack through the kernel,
+/// or at a plain directory to check the suite itself against the host
+/// filesystem.
+pub struct MountedTarget {
+    root: PathBuf,
+}
+
+impl MountedTarget {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, rel: &Path) -> PathBuf {
+        self.root.join(rel)
+    }
+}
+
+impl PosixTarget for MountedTarget {
+    fn describe(&self) -> String {
+        format!("mounted path {}", self.root.display())
+    }
+
+    fn create(&self, rel: &Path, mode: u32, exclusive: bool) -> io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).mode(mode);
+        if exclusive {
+            options.create_new(true);
+        } else {
+            options.create(true).truncate(true);
+        }
+        options.open(self.path(rel)).map(drop)
+    }
+
+    fn write_at(&self, rel: &Path, offset: u64, data: &[u8]) -> io::Result<usize> {
+        let file = OpenOptions::new().write(true).open(self.path(rel))?;
+        file.write_all_at(data, offset)?;
+        Ok(data.len())
+    }
+
+    fn read_at(&self, rel: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let file = File::open(self.path(rel))?;
+        let mut out = vec![0u8; len];
+        let mut filled = 0;
+        while filled < len {
+            let n = file.read_at(&mut out[filled..], offset + filled as u64)?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        out.truncate(filled);
+        Ok(out)
+    }
+
+    fn truncate(&self, rel: &Path, size: u64) -> io::Result<()> {
+        OpenOptions::new()
+            .write(true)
+            .open(self.path(rel))?
+            .set_len(size)
+    }
+
+    fn stat(&self, rel: &Path) -> io::Result<Stat> {
+        let meta = fs::symlink_metadata(self.path(rel))?;
+        Ok(Stat {
+            kind: FileKind::from_mode(meta.mode()),
+            size: meta.len(),
+            perm: meta.mode() & 0o7777,
+        })
+    }
+
+    fn mkdir(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        DirBuilder::new().mode(mode).create(self.path(rel))
+    }
+
+    fn rmdir(&self, rel: &Path) -> io::Result<()> {
+        fs::remove_dir(self.path(rel))
+    }
+
+    fn unlink(&self, rel: &Path) -> io::Result<()> {
+        fs::remove_file(self.path(rel))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(self.path(from), self.path(to))
+    }
+
+    fn readdir(&self, rel: &Path) -> io::Result<Vec<String>> {
+        let entries = fs::read_dir(self.path(rel))?
+            .map(|e| e.map(|e| e.file_name()))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(sorted_names(entries.iter().map(|n| n.as_os_str())))
+    }
+
+    fn chmod(&self, rel: &Path, mode: u32) -> io::Result<()> {
+        fs::set_permissions(self.path(rel), fs::Permissions::from_mode(mode))
+    }
+
+    fn symlink(&self, target: &Path, rel: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, self.path(rel))
+    }
+
+    fn readlink(&self, rel: &Path) -> io::Result<PathBuf> {
+        fs::read_link(self.path(rel))
+    }
+}
+
+/// Entry names without `.` and `..`, sorted.
+fn sorted_names<'a>(names: impl Iterator<Item = &'a OsStr>) -> Vec<String> {
+    let mut names: Vec<String> = names
+        .filter(|n| n.as_bytes() != b"." && n.as_bytes() != b"..")
+        .map(|n| n.to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
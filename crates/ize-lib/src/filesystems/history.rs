@@ -0,0 +1,901 @@
+//! Virtual `.ize/` history directory.
+//!
+//! [`HistoryFS`] wraps the live filesystem and synthesizes a read-only
+//! directory at the mount root, much like ZFS's `.zfs/snapshot`:
+//!
+//! ```text
+//! .ize/
+//! ├── log                  # one line per change, newest first
+//! ├── changes/<hash>/...   # the tree right after each change
+//! └── channels/<name>/...  # the tree at each channel's latest change
+//! ```
+//!
+//! Editors, `diff -r` and `grep -r` can reach history without leaving the
+//! mount.  Everything else is passed to the inner filesystem untouched.
+//!
+//! # Inodes
+//!
+//! Virtual entries live in the upper half of the inode space (bit 63 set),
+//! which real filesystems do not hand out.  Each change or channel tree is a
+//! [`SnapshotFS`] in its own 32-bit inode slot, built from the
+//! [`HistorySource`] the first time it is entered and then kept: a change
+//! never changes, and a channel tree is replaced (its old inodes answering
+//! `ESTALE`) once the channel moves on.
+//!
+//! `.ize` is left out of the root listing unless [`HistoryFS::with_visible`]
+//! is set, so tools that walk the tree do not descend into all of history.
+//! A real `.ize` entry in the inner filesystem is shadowed.
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use fuser::{FileAttr, FileType, MountOption, Session};
+use log::{debug, warn};
+
+use super::observing::ObservingFS;
+use super::ops::{impl_filesystem_via_fs_ops, Caller, FsOps, OpResult, ReaddirEntry, SetAttr};
+use super::passthrough_fd::FdPassthroughFS;
+use super::snapshot::SnapshotFS;
+use crate::backing_fs::BackingFs;
+
+// ---------------------------------------------------------------------------
+// Constants
+// ---------------------------------------------------------------------------
+
+/// Name of the virtual directory at the mount root.
+pub const HISTORY_DIR: &str = ".ize";
+
+/// FUSE always uses inode 1 for the root directory.
+const FUSE_ROOT_ID: u64 = 1;
+
+/// Set on every inode this layer synthesizes.
+const VIRTUAL: u64 = 1 << 63;
+
+const IZE_ROOT: u64 = VIRTUAL | 1;
+const CHANGES_DIR: u64 = VIRTUAL | 2;
+const CHANNELS_DIR: u64 = VIRTUAL | 3;
+const LOG_FILE: u64 = VIRTUAL | 4;
+
+/// Tree slots start above the fixed entries; the low bits are the inode
+/// within the tree's [`SnapshotFS`].
+const TREE_SHIFT: u32 = 32;
+const TREE_INO_MASK: u64 = (1 << TREE_SHIFT) - 1;
+
+// ---------------------------------------------------------------------------
+// HistorySource
+// ---------------------------------------------------------------------------
+
+/// Where [`HistoryFS`] gets its content from.
+///
+/// Change and channel names are used verbatim as directory names.  Errors
+/// of kind [`io::ErrorKind::NotFound`] surface as `ENOENT`.
+pub trait HistorySource: Send {
+    /// Contents of `.ize/log`.
+    fn log(&self) -> io::Result<String>;
+
+    /// Names listed under `.ize/changes/`.
+    fn changes(&self) -> io::Result<Vec<String>>;
+
+    /// Names listed under `.ize/channels/`.
+    fn channels(&self) -> io::Result<Vec<String>>;
+
+    /// The latest change on `channel`, or `None` if it has none yet.
+    ///
+    /// A channel's tree is rebuilt whenever this changes.
+    fn channel_head(&self, channel: &str) -> io::Result<Option<String>>;
+
+    /// Fill `tree` with the files as they were right after `change`.
+    fn load_change(&self, change: &str, tree: &mut SnapshotFS) -> io::Result<()>;
+
+    /// Fill `tree` with the files at the latest change on `channel`.
+    fn load_channel(&self, channel: &str, tree: &mut SnapshotFS) -> io::Result<()>;
+}
+
+// ---------------------------------------------------------------------------
+// Trees
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum TreeKey {
+    Change(String),
+    /// Channel name and the head the tree was built at.
+    Channel(String, Option<String>),
+}
+
+struct Tree {
+    key: TreeKey,
+    /// Inode of the directory the tree is listed in.
+    parent: u64,
+    /// Built on first use.
+    fs: Option<SnapshotFS>,
+    /// Superseded by a newer tree for the same channel.
+    stale: bool,
+}
+
+/// Which layer an inode belongs to.
+enum Node {
+    Inner,
+    Fixed(u64),
+    Tree(usize, u64),
+}
+
+fn classify(ino: u64) -> Node {
+    if ino & VIRTUAL == 0 {
+        return Node::Inner;
+    }
+    match (ino & !VIRTUAL) >> TREE_SHIFT {
+        0 => Node::Fixed(ino),
+        slot => Node::Tree((slot - 1) as usize, ino & TREE_INO_MASK),
+    }
+}
+
+fn tree_ino(slot: usize, ino: u64) -> u64 {
+    VIRTUAL | ((slot as u64 + 1) << TREE_SHIFT) | ino
+}
+
+fn errno(e: io::Error) -> libc::c_int {
+    e.raw_os_error().unwrap_or(match e.kind() {
+        io::ErrorKind::NotFound => libc::ENOENT,
+        _ => libc::EIO,
+    })
+}
+
+/// Number a directory listing and resume it after `offset`.
+fn listing(entries: Vec<(u64, FileType, OsString)>, offset: i64) -> Vec<ReaddirEntry> {
+    entries
+        .into_iter()
+        .enumerate()
+        .skip(offset.max(0) as usize)
+        .map(|(i, (ino, kind, name))| ReaddirEntry {
+            ino,
+            offset: (i + 1) as i64,
+            kind,
+            name,
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// HistoryFS
+// ---------------------------------------------------------------------------
+
+/// Wraps a filesystem and adds a read-only `.ize/` history directory at its
+/// root.
+pub struct HistoryFS<F: FsOps, S: HistorySource> {
+    inner: F,
+    source: S,
+    /// List `.ize` in the root directory.
+    visible: bool,
+    /// Last rendered `.ize/log`, refreshed on lookup.
+    log: Vec<u8>,
+    trees: Vec<Tree>,
+    by_key: HashMap<TreeKey, usize>,
+    created: SystemTime,
+    uid: u32,
+    gid: u32,
+}
+
+impl<F: FsOps, S: HistorySource> HistoryFS<F, S> {
+    /// Wrap `inner`, serving history from `source`.
+    pub fn new(inner: F, source: S) -> Self {
+        // SAFETY: getuid/getgid have no preconditions and cannot fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            inner,
+            source,
+            visible: false,
+            log: Vec::new(),
+            trees: Vec::new(),
+            by_key: HashMap::new(),
+            created: SystemTime::now(),
+            uid,
+            gid,
+        }
+    }
+
+    /// List `.ize` in the root directory instead of only resolving it by name.
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    /// Get a reference to the inner filesystem.
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner filesystem.
+    pub fn inner_mut(&mut self) -> &mut F {
+        &mut self.inner
+    }
+
+    // -- Internal helpers ---------------------------------------------------
+
+    fn is_history_dir(parent: u64, name: &OsStr) -> bool {
+        parent == FUSE_ROOT_ID && name == HISTORY_DIR
+    }
+
+    /// Refuse mutations of `.ize` itself or anything below it.
+    fn guard(parent: u64, name: &OsStr) -> OpResult<()> {
+        if parent & VIRTUAL != 0 || Self::is_history_dir(parent, name) {
+            Err(libc::EROFS)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn refresh_log(&mut self) {
+        match self.source.log() {
+            Ok(log) => self.log = log.into_bytes(),
+            Err(e) => warn!("HistoryFS: failed to render {}/log: {}", HISTORY_DIR, e),
+        }
+    }
+
+    /// Slot for `key`, allocating an unbuilt one if needed.
+    fn slot_for(&mut self, key: TreeKey, parent: u64) -> usize {
+        if let Some(&slot) = self.by_key.get(&key) {
+            return slot;
+        }
+        if let TreeKey::Channel(name, _) = &key {
+            let superseded: Vec<TreeKey> = self
+                .by_key
+                .keys()
+                .filter(|k| matches!(k, TreeKey::Channel(n, _) if n == name))
+                .cloned()
+                .collect();
+            for old in superseded {
+                if let Some(slot) = self.by_key.remove(&old) {
+                    let tree = &mut self.trees[slot];
+                    tree.stale = true;
+                    tree.fs = None;
+                }
+            }
+        }
+        self.trees.push(Tree {
+            key: key.clone(),
+            parent,
+            fs: None,
+            stale: false,
+        });
+        let slot = self.trees.len() - 1;
+        self.by_key.insert(key, slot);
+        slot
+    }
+
+    /// The filesystem for a tree slot, building it on first use.
+    fn tree_fs(&mut self, slot: usize) -> OpResult<&mut SnapshotFS> {
+        let tree = self.trees.get_mut(slot).ok_or(libc::ENOENT)?;
+        if tree.stale {
+            return Err(libc::ESTALE);
+        }
+        if tree.fs.is_none() {
+            debug!("HistoryFS: building tree {:?}", tree.key);
+            let mut fs = SnapshotFS::new(PathBuf::from(HISTORY_DIR));
+            match &tree.key {
+                TreeKey::Change(change) => self.source.load_change(change, &mut fs),
+                TreeKey::Channel(channel, _) => self.source.load_channel(channel, &mut fs),
+            }
+            .map_err(errno)?;
+            fs.init()?;
+            tree.fs = Some(fs);
+        }
+        Ok(tree.fs.as_mut().expect("tree was just built"))
+    }
+
+    fn fixed_attr(&self, ino: u64) -> OpResult<FileAttr> {
+        let (kind, perm, size) = match ino {
+            IZE_ROOT | CHANGES_DIR | CHANNELS_DIR => (FileType::Directory, 0o555, 0),
+            LOG_FILE => (FileType::RegularFile, 0o444, self.log.len() as u64),
+            _ => return Err(libc::ENOENT),
+        };
+        Ok(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: self.created,
+            mtime: self.created,
+            ctime: self.created,
+            crtime: self.created,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+
+    fn tree_attr(&mut self, slot: usize, ino: u64) -> OpResult<FileAttr> {
+        // A tree's root can be stat'ed without building it, so `ls -l` on
+        // `.ize/changes` stays cheap.
+        let unbuilt = self.trees.get(slot).is_some_and(|t| t.fs.is_none());
+        let mut attr = if ino == FUSE_ROOT_ID && unbuilt {
+            FileAttr {
+                ino,
+                ..self.fixed_attr(CHANGES_DIR)?
+            }
+        } else {
+            self.tree_fs(slot)?.getattr(ino, None)?
+        };
+        attr.ino = tree_ino(slot, attr.ino);
+        Ok(attr)
+    }
+
+    fn virtual_lookup(&mut self, parent: u64, name: &OsStr) -> OpResult<FileAttr> {
+        match classify(parent) {
+            Node::Fixed(IZE_ROOT) => match name.to_str() {
+                Some("changes") => self.fixed_attr(CHANGES_DIR),
+                Some("channels") => self.fixed_attr(CHANNELS_DIR),
+                Some("log") => {
+                    self.refresh_log();
+                    self.fixed_attr(LOG_FILE)
+                }
+                _ => Err(libc::ENOENT),
+            },
+            Node::Fixed(CHANGES_DIR) => {
+                let name = name.to_str().ok_or(libc::ENOENT)?;
+                if !self
+                    .source
+                    .changes()
+                    .map_err(errno)?
+                    .iter()
+                    .any(|c| c == name)
+                {
+                    return Err(libc::ENOENT);
+                }
+                let slot = self.slot_for(TreeKey::Change(name.to_string()), CHANGES_DIR);
+                self.tree_attr(slot, FUSE_ROOT_ID)
+            }
+            Node::Fixed(CHANNELS_DIR) => {
+                let name = name.to_str().ok_or(libc::ENOENT)?;
+                let head = self.source.channel_head(name).map_err(errno)?;
+                let slot = self.slot_for(TreeKey::Channel(name.to_string(), head), CHANNELS_DIR);
+                self.tree_attr(slot, FUSE_ROOT_ID)
+            }
+            Node::Fixed(LOG_FILE) => Err(libc::ENOTDIR),
+            Node::Tree(slot, ino) => {
+                let mut attr = self.tree_fs(slot)?.lookup(ino, name)?;
+                attr.ino = tree_ino(slot, attr.ino);
+                Ok(attr)
+            }
+            Node::Fixed(_) | Node::Inner => Err(libc::ENOENT),
+        }
+    }
+
+    fn virtual_readdir(&mut self, ino: u64, offset: i64) -> OpResult<Vec<ReaddirEntry>> {
+        let dot = |ino, parent| {
+            vec![
+                (ino, FileType::Directory, OsString::from(".")),
+                (parent, FileType::Directory, OsString::from("..")),
+            ]
+        };
+        let entries = match classify(ino) {
+            Node::Fixed(IZE_ROOT) => {
+                let mut entries = dot(IZE_ROOT, FUSE_ROOT_ID);
+                entries.push((CHANGES_DIR, FileType::Directory, "changes".into()));
+                entries.push((CHANNELS_DIR, FileType::Directory, "channels".into()));
+                entries.push((LOG_FILE, FileType::RegularFile, "log".into()));
+                entries
+            }
+            Node::Fixed(CHANGES_DIR) => {
+                let mut entries = dot(CHANGES_DIR, IZE_ROOT);
+                for change in self.source.changes().map_err(errno)? {
+                    let slot = self.slot_for(TreeKey::Change(change.clone()), CHANGES_DIR);
+                    entries.push((
+                        tree_ino(slot, FUSE_ROOT_ID),
+                        FileType::Directory,
+                        change.into(),
+                    ));
+                }
+                entries
+            }
+            Node::Fixed(CHANNELS_DIR) => {
+                let mut entries = dot(CHANNELS_DIR, IZE_ROOT);
+                for channel in self.source.channels().map_err(errno)? {
+                    let head = self.source.channel_head(&channel).map_err(errno)?;
+                    let key = TreeKey::Channel(channel.clone(), head);
+                    let slot = self.slot_for(key, CHANNELS_DIR);
+                    entries.push((
+                        tree_ino(slot, FUSE_ROOT_ID),
+                        FileType::Directory,
+                        channel.into(),
+                    ));
+                }
+                entries
+            }
+            Node::Fixed(LOG_FILE) => return Err(libc::ENOTDIR),
+            Node::Tree(slot, dir) => {
+                let parent = self.trees[slot].parent;
+                let entries = self.tree_fs(slot)?.readdir(dir, 0, offset)?;
+                return Ok(entries
+                    .into_iter()
+                    .map(|mut entry| {
+                        entry.ino = if dir == FUSE_ROOT_ID && entry.name == ".." {
+                            parent
+                        } else {
+                            tree_ino(slot, entry.ino)
+                        };
+                        entry
+                    })
+                    .collect());
+            }
+            Node::Fixed(_) | Node::Inner => return Err(libc::ENOENT),
+        };
+        Ok(listing(entries, offset))
+    }
+
+    fn root_readdir(&mut self, fh: u64, offset: i64) -> OpResult<Vec<ReaddirEntry>> {
+        let shadowed = |entry: &ReaddirEntry| entry.name != HISTORY_DIR;
+        if !self.visible {
+            let entries = self.inner.readdir(FUSE_ROOT_ID, fh, offset)?;
+            return Ok(entries.into_iter().filter(shadowed).collect());
+        }
+
+        // `.ize` goes after the inner entries, so the whole listing is needed
+        // to know its offset.
+        let mut entries: Vec<ReaddirEntry> = self
+            .inner
+            .readdir(FUSE_ROOT_ID, fh, 0)?
+            .into_iter()
+            .filter(shadowed)
+            .collect();
+        let next = entries.iter().map(|e| e.offset).max().unwrap_or(0) + 1;
+        entries.push(ReaddirEntry {
+            ino: IZE_ROOT,
+            offset: next,
+            kind: FileType::Directory,
+            name: OsString::from(HISTORY_DIR),
+        });
+        Ok(entries.into_iter().filter(|e| e.offset > offset).collect())
+    }
+}
+
+impl<B: BackingFs + 'static, S: HistorySource + 'static>
+    HistoryFS<ObservingFS<FdPassthroughFS<B>>, S>
+{
+    /// Mount the filesystem; blocks until it is unmounted.
+    pub fn mount(self) -> io::Result<()> {
+        self.into_session()?.run()
+    }
+
+    /// Mount the filesystem without serving it yet.
+    ///
+    /// Lets the caller take a [`fuser::Notifier`] for cache invalidation before
+    /// [`Session::run`] blocks until unmount.
+    pub fn into_session(self) -> io::Result<Session<Self>> {
+        let passthrough = self.inner.inner();
+        let mut options = vec![
+            MountOption::FSName("ize".to_string()),
+            MountOption::AutoUnmount,
+            MountOption::AllowOther,
+        ];
+        if passthrough.is_read_only() {
+            options.push(MountOption::RO);
+        }
+
+        let mount_point = passthrough.mount_point().to_path_buf();
+        Session::new(self, mount_point, &options)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// FsOps implementation
+// ---------------------------------------------------------------------------
+
+impl<F: FsOps, S: HistorySource> FsOps for HistoryFS<F, S> {
+    fn init(&mut self) -> OpResult<()> {
+        self.inner.init()
+    }
+
+    fn destroy(&mut self) {
+        self.inner.destroy()
+    }
+
+    fn set_caller(&mut self, caller: Caller) {
+        self.inner.set_caller(caller)
+    }
+
+    fn lookup(&mut self, parent: u64, name: &OsStr) -> OpResult<FileAttr> {
+        if Self::is_history_dir(parent, name) {
+            return self.fixed_attr(IZE_ROOT);
+        }
+        match classify(parent) {
+            Node::Inner => self.inner.lookup(parent, name),
+            _ => self.virtual_lookup(parent, name),
+        }
+    }
+
+    fn getattr(&mut self, ino: u64, fh: Option<u64>) -> OpResult<FileAttr> {
+        match classify(ino) {
+            Node::Inner => self.inner.getattr(ino, fh),
+            Node::Fixed(ino) => self.fixed_attr(ino),
+            Node::Tree(slot, ino) => self.tree_attr(slot, ino),
+        }
+    }
+
+    fn setattr(&mut self, ino: u64, attr: &SetAttr) -> OpResult<FileAttr> {
+        match classify(ino) {
+            Node::Inner => self.inner.setattr(ino, attr),
+            _ => Err(libc::EROFS),
+        }
+    }
+
+    fn readdir(&mut self, ino: u64, fh: u64, offset: i64) -> OpResult<Vec<ReaddirEntry>> {
+        match classify(ino) {
+            Node::Inner if ino == FUSE_ROOT_ID => self.root_readdir(fh, offset),
+            Node::Inner => self.inner.readdir(ino, fh, offset),
+            _ => self.virtual_readdir(ino, offset),
+        }
+    }
+
+    fn open(&mut self, ino: u64, flags: i32) -> OpResult<u64> {
+        let node = classify(ino);
+        if !matches!(node, Node::Inner)
+            && flags & (libc::O_WRONLY | libc::O_RDWR | libc::O_APPEND | libc::O_TRUNC) != 0
+        {
+            return Err(libc::EROFS);
+        }
+        match node {
+            Node::Inner => self.inner.open(ino, flags),
+            Node::Fixed(LOG_FILE) => Ok(0),
+            Node::Fixed(_) => Err(libc::EISDIR),
+            Node::Tree(slot, ino) => self.tree_fs(slot)?.open(ino, flags),
+        }
+    }
+
+    fn read(&mut self, ino: u64, fh: u64, offset: i64, size: u32) -> OpResult<Vec<u8>> {
+        match classify(ino) {
+            Node::Inner => self.inner.read(ino, fh, offset, size),
+            Node::Fixed(LOG_FILE) => {
+                let start = (offset.max(0) as usize).min(self.log.len());
+                let end = start.saturating_add(size as usize).min(self.log.len());
+                Ok(self.log[start..end].to_vec())
+            }
+            Node::Fixed(_) => Err(libc::EISDIR),
+            Node::Tree(slot, ino) => self.tree_fs(slot)?.read(ino, fh, offset, size),
+        }
+    }
+
+    fn write(&mut self, ino: u64, fh: u64, offset: i64, data: &[u8]) -> OpResult<u32> {
+        match classify(ino) {
+            Node::Inner => self.inner.write(ino, fh, offset, data),
+            _ => Err(libc::EROFS),
+        }
+    }
+
+    fn create(
+        &mut self,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        flags: i32,
+    ) -> OpResult<(FileAttr, u64)> {
+        Self::guard(parent, name)?;
+        self.inner.create(parent, name, mode, flags)
+    }
+
+    fn mkdir(&mut self, parent: u64, name: &OsStr, mode: u32) -> OpResult<FileAttr> {
+        Self::guard(parent, name)?;
+        self.inner.mkdir(parent, name, mode)
+    }
+
+    fn unlink(&mut self, parent: u64, name: &OsStr) -> OpResult<()> {
+        Self::guard(parent, name)?;
+        self.inner.unlink(parent, name)
+    }
+
+    fn rmdir(&mut self, parent: u64, name: &OsStr) -> OpResult<()> {
+        Self::guard(parent, name)?;
+        self.inner.rmdir(parent, name)
+    }
+
+    fn rename(
+        &mut self,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+    ) -> OpResult<()> {
+        Self::guard(parent, name)?;
+        Self::guard(newparent, newname)?;
+        self.inner.rename(parent, name, newparent, newname)
+    }
+
+    fn symlink(&mut self, parent: u64, name: &OsStr, target: &Path) -> OpResult<FileAttr> {
+        Self::guard(parent, name)?;
+        self.inner.symlink(parent, name, target)
+    }
+
+    fn readlink(&mut self, ino: u64) -> OpResult<Vec<u8>> {
+        match classify(ino) {
+            Node::Inner => self.inner.readlink(ino),
+            _ => Err(libc::EINVAL),
+        }
+    }
+
+    fn flush(&mut self, ino: u64, fh: u64) -> OpResult<()> {
+        match classify(ino) {
+            Node::Inner => self.inner.flush(ino, fh),
+            _ => Ok(()),
+        }
+    }
+
+    fn release(&mut self, ino: u64, fh: u64) -> OpResult<()> {
+        match classify(ino) {
+            Node::Inner => self.inner.release(ino, fh),
+            _ => Ok(()),
+        }
+    }
+
+    fn fsync(&mut self, ino: u64, fh: u64, datasync: bool) -> OpResult<()> {
+        match classify(ino) {
+            Node::Inner => self.inner.fsync(ino, fh, datasync),
+            _ => Ok(()),
+        }
+    }
+
+    fn access(&mut self, ino: u64, mask: i32) -> OpResult<()> {
+        if matches!(classify(ino), Node::Inner) {
+            return self.inner.access(ino, mask);
+        }
+        self.getattr(ino, None)?;
+        if mask & libc::W_OK != 0 {
+            return Err(libc::EROFS);
+        }
+        Ok(())
+    }
+
+    fn statfs(&mut self, ino: u64) -> OpResult<libc::statvfs> {
+        match classify(ino) {
+            Node::Inner => self.inner.statfs(ino),
+            _ => self.inner.statfs(FUSE_ROOT_ID),
+        }
+    }
+
+    fn opendir(&mut self, ino: u64, flags: i32) -> OpResult<u64> {
+        match classify(ino) {
+            Node::Inner => self.inner.opendir(ino, flags),
+            Node::Fixed(LOG_FILE) => Err(libc::ENOTDIR),
+            Node::Fixed(ino) => self.fixed_attr(ino).map(|_| 0),
+            Node::Tree(slot, ino) => self.tree_fs(slot)?.opendir(ino, flags),
+        }
+    }
+
+    fn releasedir(&mut self, ino: u64, fh: u64) -> OpResult<()> {
+        match classify(ino) {
+            Node::Inner => self.inner.releasedir(ino, fh),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl_filesystem_via_fs_ops!(impl<F: FsOps, S: HistorySource> for HistoryFS<F, S>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backing_fs::{BackingFs, MemBackingFs};
+    use std::cell::Cell;
+    use std::collections::BTreeMap;
+
+    /// History with fixed changes and channels whose heads can be moved.
+    #[derive(Default)]
+    struct FakeHistory {
+        changes: BTreeMap<String, Vec<(&'static str, &'static [u8])>>,
+        channels: BTreeMap<String, String>,
+        loads: Cell<usize>,
+    }
+
+    impl FakeHistory {
+        fn sample() -> Self {
+            let mut history = Self::default();
+            history
+                .changes
+                .insert("AAAA".into(), vec![("a.txt", b"one\n")]);
+            history.changes.insert(
+                "BBBB".into(),
+                vec![("a.txt", b"one\ntwo\n"), ("src/lib.rs", b"// lib\n")],
+            );
+            history.channels.insert("main".into(), "BBBB".into());
+            history.channels.insert("old".into(), "AAAA".into());
+            history
+        }
+
+        fn fill(&self, change: &str, tree: &mut SnapshotFS) -> io::Result<()> {
+            self.loads.set(self.loads.get() + 1);
+            let files = self
+                .changes
+                .get(change)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+            for (path, content) in files {
+                tree.add_file(path, content.to_vec());
+            }
+            Ok(())
+        }
+    }
+
+    impl HistorySource for FakeHistory {
+        fn log(&self) -> io::Result<String> {
+            Ok(self
+                .changes
+                .keys()
+                .rev()
+                .map(|c| format!("{}\n", c))
+                .collect())
+        }
+
+        fn changes(&self) -> io::Result<Vec<String>> {
+            Ok(self.changes.keys().cloned().collect())
+        }
+
+        fn channels(&self) -> io::Result<Vec<String>> {
+            Ok(self.channels.keys().cloned().collect())
+        }
+
+        fn channel_head(&self, channel: &str) -> io::Result<Option<String>> {
+            self.channels
+                .get(channel)
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+
+        fn load_change(&self, change: &str, tree: &mut SnapshotFS) -> io::Result<()> {
+            self.fill(change, tree)
+        }
+
+        fn load_channel(&self, channel: &str, tree: &mut SnapshotFS) -> io::Result<()> {
+            let head = self.channel_head(channel)?.unwrap_or_default();
+            self.fill(&head, tree)
+        }
+    }
+
+    type TestFS = HistoryFS<FdPassthroughFS<MemBackingFs>, FakeHistory>;
+
+    fn make_fs() -> TestFS {
+        let backing = MemBackingFs::new();
+        let fd = backing
+            .open_file(Path::new("live.txt"), libc::O_CREAT | libc::O_WRONLY, 0o644)
+            .unwrap();
+        backing.close_fd(fd);
+        let inner = FdPassthroughFS::new(backing, PathBuf::from("/mnt"));
+        let mut fs = HistoryFS::new(inner, FakeHistory::sample());
+        fs.init().unwrap();
+        fs
+    }
+
+    fn names(entries: &[ReaddirEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|e| e.name.to_string_lossy().into_owned())
+            .filter(|n| n != "." && n != "..")
+            .collect()
+    }
+
+    fn resolve(fs: &mut TestFS, path: &str) -> OpResult<FileAttr> {
+        let mut ino = FUSE_ROOT_ID;
+        let mut attr = None;
+        for name in path.split('/') {
+            let found = fs.lookup(ino, OsStr::new(name))?;
+            ino = found.ino;
+            attr = Some(found);
+        }
+        Ok(attr.expect("non-empty path"))
+    }
+
+    fn read_all(fs: &mut TestFS, path: &str) -> Vec<u8> {
+        let attr = resolve(fs, path).unwrap();
+        let fh = fs.open(attr.ino, libc::O_RDONLY).unwrap();
+        fs.read(attr.ino, fh, 0, 4096).unwrap()
+    }
+
+    #[test]
+    fn hidden_from_root_listing_by_default() {
+        let mut fs = make_fs();
+        let root = fs.readdir(FUSE_ROOT_ID, 0, 0).unwrap();
+        assert_eq!(names(&root), vec!["live.txt"]);
+
+        let ize = fs.lookup(FUSE_ROOT_ID, OsStr::new(HISTORY_DIR)).unwrap();
+        assert_eq!(ize.kind, FileType::Directory);
+        let listing = fs.readdir(ize.ino, 0, 0).unwrap();
+        assert_eq!(names(&listing), vec!["changes", "channels", "log"]);
+    }
+
+    #[test]
+    fn visible_listing_resumes_after_history_dir() {
+        let mut fs = make_fs().with_visible(true);
+        let root = fs.readdir(FUSE_ROOT_ID, 0, 0).unwrap();
+        assert_eq!(names(&root), vec!["live.txt", HISTORY_DIR]);
+
+        let last = root.last().unwrap();
+        assert_eq!(last.ino, IZE_ROOT);
+        assert!(fs.readdir(FUSE_ROOT_ID, 0, last.offset).unwrap().is_empty());
+    }
+
+    #[test]
+    fn log_and_change_trees_are_readable() {
+        let mut fs = make_fs();
+        assert_eq!(read_all(&mut fs, ".ize/log"), b"BBBB\nAAAA\n");
+
+        let changes = resolve(&mut fs, ".ize/changes").unwrap();
+        let listing = fs.readdir(changes.ino, 0, 0).unwrap();
+        assert_eq!(names(&listing), vec!["AAAA", "BBBB"]);
+        // Listing and stat'ing change directories does not build them.
+        resolve(&mut fs, ".ize/changes/AAAA").unwrap();
+        assert_eq!(fs.source.loads.get(), 0);
+
+        assert_eq!(read_all(&mut fs, ".ize/changes/AAAA/a.txt"), b"one\n");
+        assert_eq!(
+            read_all(&mut fs, ".ize/changes/BBBB/src/lib.rs"),
+            b"// lib\n"
+        );
+        assert_eq!(
+            resolve(&mut fs, ".ize/changes/CCCC").map(|_| ()),
+            Err(libc::ENOENT)
+        );
+
+        let tree = resolve(&mut fs, ".ize/changes/BBBB").unwrap();
+        let entries = fs.readdir(tree.ino, 0, 0).unwrap();
+        let dotdot = entries.iter().find(|e| e.name == "..").unwrap();
+        assert_eq!(dotdot.ino, changes.ino);
+        assert_eq!(names(&entries), vec!["a.txt", "src"]);
+    }
+
+    #[test]
+    fn channel_tree_follows_head() {
+        let mut fs = make_fs();
+        assert_eq!(read_all(&mut fs, ".ize/channels/old/a.txt"), b"one\n");
+        let stale = resolve(&mut fs, ".ize/channels/old/a.txt").unwrap();
+
+        fs.source.channels.insert("old".into(), "BBBB".into());
+        assert_eq!(read_all(&mut fs, ".ize/channels/old/a.txt"), b"one\ntwo\n");
+        assert_eq!(fs.getattr(stale.ino, None).map(|_| ()), Err(libc::ESTALE));
+        assert_eq!(
+            resolve(&mut fs, ".ize/channels/missing").map(|_| ()),
+            Err(libc::ENOENT)
+        );
+    }
+
+    #[test]
+    fn history_is_read_only() {
+        let mut fs = make_fs();
+        let ize = resolve(&mut fs, HISTORY_DIR).unwrap();
+        let file = resolve(&mut fs, ".ize/changes/AAAA/a.txt").unwrap();
+
+        assert_eq!(
+            fs.mkdir(FUSE_ROOT_ID, OsStr::new(HISTORY_DIR), 0o755)
+                .map(|_| ()),
+            Err(libc::EROFS)
+        );
+        assert_eq!(
+            fs.rmdir(FUSE_ROOT_ID, OsStr::new(HISTORY_DIR)),
+            Err(libc::EROFS)
+        );
+        assert_eq!(
+            fs.create(ize.ino, OsStr::new("x"), 0o644, 0).map(|_| ()),
+            Err(libc::EROFS)
+        );
+        assert_eq!(fs.open(file.ino, libc::O_WRONLY), Err(libc::EROFS));
+        assert_eq!(fs.write(file.ino, 0, 0, b"x"), Err(libc::EROFS));
+        assert_eq!(
+            fs.rename(
+                FUSE_ROOT_ID,
+                OsStr::new("live.txt"),
+                ize.ino,
+                OsStr::new("live.txt")
+            ),
+            Err(libc::EROFS)
+        );
+        assert_eq!(fs.access(file.ino, libc::W_OK), Err(libc::EROFS));
+        assert_eq!(fs.access(file.ino, libc::R_OK), Ok(()));
+
+        // The live tree is still writable.
+        fs.create(FUSE_ROOT_ID, OsStr::new("new.txt"), 0o644, 0)
+            .unwrap();
+    }
+}
@@ -1,9 +1,15 @@
 pub mod error;
+pub mod history;
 pub mod observing;
+pub mod ops;
 pub mod passthrough;
 pub mod passthrough_fd;
+pub mod snapshot;
 
 // Re-export key types for convenience
-pub use observing::{FsObserver, ObservingFS};
+pub use history::{HistoryFS, HistorySource, HISTORY_DIR};
+pub use observing::{FsObserver, MutationGate, MutationPause, ObservingFS};
+pub use ops::{Caller, FsOps, OpResult, ReaddirEntry, SetAttr};
 pub use passthrough::PassthroughFS;
-pub use passthrough_fd::FdPassthroughFS;
+pub use passthrough_fd::{invalidate_kernel_cache, FdPassthroughFS};
+pub use snapshot::SnapshotFS;
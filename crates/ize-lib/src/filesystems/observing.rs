@@ -2,7 +2,10 @@
 //!
 //! This module provides the Observer pattern for filesystem operations:
 //! - `FsObserver` trait: Receives notifications about filesystem mutations
-//! - `ObservingFS<F>`: Wraps any `Filesystem` and notifies observers of mutations
+//! - `ObservingFS<F>`: Wraps any `FsOps` filesystem and notifies observers of
+//!   mutations once they have succeeded
+//! - `MutationGate`: Lets another thread pause mutations, e.g. while the
+//!   backing store is swapped out from under the mount
 //!
 //! The key insight is that we're not "fanning out" filesystem operations - we're
 //! observing them. The actual filesystem operation only happens once in the inner
@@ -24,7 +27,7 @@
 //! │                          │                               │
 //! │                          ▼                               │
 //! │  ┌─────────────────────────────────────────────────┐    │
-//! │  │              inner: F (e.g., PassthroughFS)     │    │
+//! │  │           inner: F (e.g., FdPassthroughFS)      │    │
 //! │  └─────────────────────────────────────────────────┘    │
 //! └─────────────────────────────────────────────────────────┘
 //! ```
@@ -35,18 +38,22 @@ use std::path::Path;
 use std::sync::Arc;
 use std::time::SystemTime;
 
-use fuser::{
-    Filesystem, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
-    ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, Request, TimeOrNow,
-};
+use fuser::{FileAttr, FileType, MountOption, TimeOrNow};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-use super::passthrough::PassthroughFS;
+use super::ops::{impl_filesystem_via_fs_ops, Caller, FsOps, OpResult, ReaddirEntry, SetAttr};
+use super::passthrough_fd::FdPassthroughFS;
+use crate::backing_fs::BackingFs;
 
 /// Observer trait for filesystem mutations.
 ///
 /// Implementors receive notifications about filesystem changes but do NOT handle
 /// FUSE replies - that's the inner filesystem's job.
 ///
+/// Hooks run after the operation has succeeded, so the filesystem already
+/// reflects the change: a renamed entry is found at its new name, an unlinked
+/// one is gone.
+///
 /// All methods have default empty implementations so observers can choose which
 /// operations they care about.
 ///
@@ -55,13 +62,20 @@ use super::passthrough::PassthroughFS;
 /// Observers must be `Send + Sync` as they may be called from multiple threads.
 /// Implementations should be non-blocking - use channels or queues for async work.
 pub trait FsObserver: Send + Sync {
+    /// Called before each mutation with the process requesting it.
+    ///
+    /// The notification for the mutation itself, if it succeeds, follows
+    /// before the next `on_caller`.
+    fn on_caller(&self, _caller: Caller) {}
+
     /// Called when a write operation occurs.
     ///
     /// # Arguments
     /// * `ino` - Inode number of the file
     /// * `fh` - File handle
     /// * `offset` - Byte offset where write begins
-    /// * `data` - The data being written
+    /// * `data` - The bytes actually written (a prefix of the request on a
+    ///   short write)
     fn on_write(&self, _ino: u64, _fh: u64, _offset: i64, _data: &[u8]) {}
 
     /// Called when a file is created.
@@ -78,7 +92,8 @@ pub trait FsObserver: Send + Sync {
     /// # Arguments
     /// * `parent` - Parent directory inode
     /// * `name` - Name of the file being deleted
-    fn on_unlink(&self, _parent: u64, _name: &OsStr) {}
+    /// * `kind` - What the entry was, looked up before it was removed
+    fn on_unlink(&self, _parent: u64, _name: &OsStr, _kind: FileType) {}
 
     /// Called when a directory is created.
     ///
@@ -142,47 +157,79 @@ pub trait FsObserver: Send + Sync {
 
 /// A filesystem wrapper that notifies observers of mutation operations.
 ///
-/// `ObservingFS` wraps any filesystem implementing `fuser::Filesystem` and
-/// adds observation capabilities. Observers are notified of mutations BEFORE
-/// the operation is delegated to the inner filesystem.
+/// `ObservingFS` wraps any filesystem implementing [`FsOps`] and adds
+/// observation capabilities. Observers are notified of a mutation only AFTER
+/// the inner filesystem has carried it out successfully, so a failed write,
+/// rename or unlink never reaches them.
 ///
 /// Read-only operations (lookup, getattr, read, readdir, etc.) are passed
 /// directly to the inner filesystem without observer notification.
 ///
 /// # Type Parameters
 ///
-/// * `F` - The inner filesystem type (must implement `Filesystem`)
+/// * `F` - The inner filesystem type (must implement `FsOps`)
 ///
 /// # Example
 ///
 /// ```no_run
 /// # use std::sync::Arc;
 /// # use std::path::PathBuf;
-/// # use ize_lib::filesystems::passthrough::PassthroughFS;
+/// # use ize_lib::backing_fs::LibcBackingFs;
+/// # use ize_lib::filesystems::passthrough_fd::FdPassthroughFS;
 /// # use ize_lib::filesystems::observing::{ObservingFS, FsObserver};
 /// # fn main() -> std::io::Result<()> {
 /// # let source_dir = PathBuf::from("/tmp/source");
 /// # let mount_point = PathBuf::from("/tmp/mount");
-/// let passthrough = PassthroughFS::new(&source_dir, &mount_point)?;
+/// let backing = LibcBackingFs::open_dir(&source_dir)?;
+/// let passthrough = FdPassthroughFS::new(backing, mount_point);
 /// let mut observing = ObservingFS::new(passthrough);
 ///
 /// // Add an observer (implement FsObserver trait)
 /// // let my_observer = Arc::new(MyObserver::new());
 /// // observing.add_observer(my_observer);
 ///
-/// // Mount - observers will be notified of all mutations
-/// // fuser::mount2(observing, mount_point, &options)?;
+/// // Mount - observers will be notified of all successful mutations
+/// // observing.mount()?;
 /// # Ok(())
 /// # }
 /// ```
-pub struct ObservingFS<F: Filesystem> {
+pub struct ObservingFS<F: FsOps> {
     /// The wrapped inner filesystem
     inner: F,
     /// List of observers to notify on mutations
     observers: Vec<Arc<dyn FsObserver>>,
+    /// Held shared by every mutation, exclusively while paused
+    gate: MutationGate,
+}
+
+/// Pauses mutations through an [`ObservingFS`] from another thread.
+///
+/// While a [`MutationPause`] is alive, every mutating operation waits before
+/// touching the inner filesystem, so observers see nothing new and the
+/// backing store can be changed underneath without racing user writes.
+/// Reads keep being served.
+#[derive(Clone, Default)]
+pub struct MutationGate(Arc<RwLock<()>>);
+
+/// Keeps mutations paused until dropped; see [`MutationGate::pause`].
+pub struct MutationPause<'a> {
+    _guard: RwLockWriteGuard<'a, ()>,
+}
+
+impl MutationGate {
+    /// Wait for in-flight mutations to finish and hold off new ones.
+    pub fn pause(&self) -> MutationPause<'_> {
+        MutationPause {
+            _guard: self.0.write(),
+        }
+    }
+
+    fn enter(&self) -> RwLockReadGuard<'_, ()> {
+        self.0.read()
+    }
 }
 
-impl<F: Filesystem> ObservingFS<F> {
+impl<F: FsOps> ObservingFS<F> {
     /// Create a new observing filesystem wrapper.
     ///
     /// # Arguments
@@ -191,6 +238,7 @@ impl<F: Filesystem> ObservingFS<F> {
         Self {
             inner,
             observers: Vec::new(),
+            gate: MutationGate::default(),
         }
     }
 
@@ -201,6 +249,11 @@ impl<F: Filesystem> ObservingFS<F> {
         self.observers.push(observer);
     }
 
+    /// Get a handle for pausing mutations while the filesystem is mounted.
+    pub fn gate(&self) -> MutationGate {
+        self.gate.clone()
+    }
+
     /// Get a reference to the inner filesystem.
     pub fn inner(&self) -> &F {
         &self.inner
@@ -212,10 +265,10 @@ impl<F: Filesystem> ObservingFS<F> {
     }
 }
 
-impl ObservingFS<PassthroughFS> {
+impl<B: BackingFs + 'static> ObservingFS<FdPassthroughFS<B>> {
     /// Mount the observing filesystem.
     ///
-    /// This delegates to the inner PassthroughFS for mount point and read-only settings.
+    /// This delegates to the inner FdPassthroughFS for mount point and read-only settings.
     pub fn mount(self) -> io::Result<()> {
         let mut options = vec![
             MountOption::FSName("ize".to_string()),
@@ -233,7 +286,7 @@ impl ObservingFS<PassthroughFS> {
     }
 }
 
-impl<F: Filesystem> ObservingFS<F> {
+impl<F: FsOps> ObservingFS<F> {
     /// Notify all observers of a write operation.
     fn notify_write(&self, ino: u64, fh: u64, offset: i64, data: &[u8]) {
         for observer in &self.observers {
@@ -242,23 +295,23 @@ impl<F: Filesystem> ObservingFS<F> {
     }
 
     /// Notify all observers of a create operation.
-    fn notify_create(&self, parent: u64, name: &OsStr, mode: u32) {
+    fn notify_create(&self, parent: u64, name: &OsStr, mode: u32, result_ino: Option<u64>) {
         for observer in &self.observers {
-            observer.on_create(parent, name, mode, None);
+            observer.on_create(parent, name, mode, result_ino);
         }
     }
 
     /// Notify all observers of an unlink operation.
-    fn notify_unlink(&self, parent: u64, name: &OsStr) {
+    fn notify_unlink(&self, parent: u64, name: &OsStr, kind: FileType) {
         for observer in &self.observers {
-            observer.on_unlink(parent, name);
+            observer.on_unlink(parent, name, kind);
         }
     }
 
     /// Notify all observers of a mkdir operation.
-    fn notify_mkdir(&self, parent: u64, name: &OsStr, mode: u32) {
+    fn notify_mkdir(&self, parent: u64, name: &OsStr, mode: u32, result_ino: Option<u64>) {
         for observer in &self.observers {
-            observer.on_mkdir(parent, name, mode, None);
+            observer.on_mkdir(parent, name, mode, result_ino);
         }
     }
 
@@ -315,238 +368,174 @@ fn time_or_now_to_system_time(t: TimeOrNow) -> Option<SystemTime> {
     }
 }
 
-impl<F: Filesystem> Filesystem for ObservingFS<F> {
+impl<F: FsOps> FsOps for ObservingFS<F> {
     // =========================================================================
     // Lifecycle methods - delegate directly
     // =========================================================================
 
-    fn init(
-        &mut self,
-        req: &Request<'_>,
-        config: &mut fuser::KernelConfig,
-    ) -> Result<(), libc::c_int> {
-        self.inner.init(req, config)
+    fn init(&mut self) -> OpResult<()> {
+        self.inner.init()
     }
 
     fn destroy(&mut self) {
         self.inner.destroy()
     }
 
+    fn set_caller(&mut self, caller: Caller) {
+        for observer in &self.observers {
+            observer.on_caller(caller);
+        }
+        self.inner.set_caller(caller)
+    }
+
     // =========================================================================
     // Read-only operations - delegate directly, no observation needed
     // =========================================================================
 
-    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
-        self.inner.lookup(req, parent, name, reply)
+    fn lookup(&mut self, parent: u64, name: &OsStr) -> OpResult<FileAttr> {
+        self.inner.lookup(parent, name)
     }
 
-    fn getattr(&mut self, req: &Request<'_>, ino: u64, fh: Option<u64>, reply: ReplyAttr) {
-        self.inner.getattr(req, ino, fh, reply)
+    fn getattr(&mut self, ino: u64, fh: Option<u64>) -> OpResult<FileAttr> {
+        self.inner.getattr(ino, fh)
     }
 
-    fn readdir(
-        &mut self,
-        req: &Request<'_>,
-        ino: u64,
-        fh: u64,
-        offset: i64,
-        reply: ReplyDirectory,
-    ) {
-        self.inner.readdir(req, ino, fh, offset, reply)
+    fn readdir(&mut self, ino: u64, fh: u64, offset: i64) -> OpResult<Vec<ReaddirEntry>> {
+        self.inner.readdir(ino, fh, offset)
     }
 
-    fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
-        self.inner.open(req, ino, flags, reply)
+    fn open(&mut self, ino: u64, flags: i32) -> OpResult<u64> {
+        self.inner.open(ino, flags)
     }
 
-    fn read(
-        &mut self,
-        req: &Request<'_>,
-        ino: u64,
-        fh: u64,
-        offset: i64,
-        size: u32,
-        flags: i32,
-        lock_owner: Option<u64>,
-        reply: ReplyData,
-    ) {
-        self.inner
-            .read(req, ino, fh, offset, size, flags, lock_owner, reply)
+    fn read(&mut self, ino: u64, fh: u64, offset: i64, size: u32) -> OpResult<Vec<u8>> {
+        self.inner.read(ino, fh, offset, size)
     }
 
-    fn flush(&mut self, req: &Request<'_>, ino: u64, fh: u64, lock_owner: u64, reply: ReplyEmpty) {
-        self.inner.flush(req, ino, fh, lock_owner, reply)
+    fn flush(&mut self, ino: u64, fh: u64) -> OpResult<()> {
+        self.inner.flush(ino, fh)
     }
 
-    fn release(
-        &mut self,
-        req: &Request<'_>,
-        ino: u64,
-        fh: u64,
-        flags: i32,
-        lock_owner: Option<u64>,
-        flush: bool,
-        reply: ReplyEmpty,
-    ) {
-        self.inner
-            .release(req, ino, fh, flags, lock_owner, flush, reply)
+    fn release(&mut self, ino: u64, fh: u64) -> OpResult<()> {
+        self.inner.release(ino, fh)
     }
 
-    fn fsync(&mut self, req: &Request<'_>, ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
-        self.inner.fsync(req, ino, fh, datasync, reply)
+    fn fsync(&mut self, ino: u64, fh: u64, datasync: bool) -> OpResult<()> {
+        self.inner.fsync(ino, fh, datasync)
     }
 
-    fn opendir(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
-        self.inner.opendir(req, ino, flags, reply)
+    fn opendir(&mut self, ino: u64, flags: i32) -> OpResult<u64> {
+        self.inner.opendir(ino, flags)
     }
 
-    fn releasedir(&mut self, req: &Request<'_>, ino: u64, fh: u64, flags: i32, reply: ReplyEmpty) {
-        self.inner.releasedir(req, ino, fh, flags, reply)
+    fn releasedir(&mut self, ino: u64, fh: u64) -> OpResult<()> {
+        self.inner.releasedir(ino, fh)
     }
 
-    fn access(&mut self, req: &Request<'_>, ino: u64, mask: i32, reply: ReplyEmpty) {
-        self.inner.access(req, ino, mask, reply)
+    fn access(&mut self, ino: u64, mask: i32) -> OpResult<()> {
+        self.inner.access(ino, mask)
     }
 
-    fn statfs(&mut self, req: &Request<'_>, ino: u64, reply: ReplyStatfs) {
-        self.inner.statfs(req, ino, reply)
+    fn statfs(&mut self, ino: u64) -> OpResult<libc::statvfs> {
+        self.inner.statfs(ino)
     }
 
     // =========================================================================
-    // Mutation operations - notify observers BEFORE delegating
+    // Mutation operations - delegate, then notify observers on success
     // =========================================================================
 
-    fn write(
-        &mut self,
-        req: &Request<'_>,
-        ino: u64,
-        fh: u64,
-        offset: i64,
-        data: &[u8],
-        write_flags: u32,
-        flags: i32,
-        lock_owner: Option<u64>,
-        reply: ReplyWrite,
-    ) {
-        // Notify observers first
-        self.notify_write(ino, fh, offset, data);
-
-        // Delegate to inner filesystem
-        self.inner.write(
-            req,
-            ino,
-            fh,
-            offset,
-            data,
-            write_flags,
-            flags,
-            lock_owner,
-            reply,
-        )
+    fn write(&mut self, ino: u64, fh: u64, offset: i64, data: &[u8]) -> OpResult<u32> {
+        let _open = self.gate.enter();
+        let written = self.inner.write(ino, fh, offset, data)?;
+
+        // Only the bytes that actually landed are reported; a short write
+        // leaves the rest for the caller to retry.
+        let written_len = (written as usize).min(data.len());
+        self.notify_write(ino, fh, offset, &data[..written_len]);
+        Ok(written)
     }
 
     fn create(
         &mut self,
-        req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         mode: u32,
-        umask: u32,
         flags: i32,
-        reply: ReplyCreate,
-    ) {
-        // Notify observers first
-        self.notify_create(parent, name, mode);
-
-        // Delegate to inner filesystem
-        self.inner
-            .create(req, parent, name, mode, umask, flags, reply)
-    }
-
-    fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
-        // Notify observers first
-        self.notify_unlink(parent, name);
-
-        // Delegate to inner filesystem
-        self.inner.unlink(req, parent, name, reply)
+    ) -> OpResult<(FileAttr, u64)> {
+        let _open = self.gate.enter();
+        let (attr, fh) = self.inner.create(parent, name, mode, flags)?;
+        self.notify_create(parent, name, mode, Some(attr.ino));
+        Ok((attr, fh))
+    }
+
+    fn unlink(&mut self, parent: u64, name: &OsStr) -> OpResult<()> {
+        let _open = self.gate.enter();
+        // Once unlinked the entry can't be inspected, so observers are told
+        // what it was (a symlink or a file) from a lookup made beforehand.
+        let kind = self
+            .inner
+            .lookup(parent, name)
+            .map_or(FileType::RegularFile, |attr| attr.kind);
+        self.inner.unlink(parent, name)?;
+        self.notify_unlink(parent, name, kind);
+        Ok(())
     }
 
-    fn mkdir(
-        &mut self,
-        req: &Request<'_>,
-        parent: u64,
-        name: &OsStr,
-        mode: u32,
-        umask: u32,
-        reply: ReplyEntry,
-    ) {
-        // Notify observers first
-        self.notify_mkdir(parent, name, mode);
-
-        // Delegate to inner filesystem
-        self.inner.mkdir(req, parent, name, mode, umask, reply)
+    fn mkdir(&mut self, parent: u64, name: &OsStr, mode: u32) -> OpResult<FileAttr> {
+        let _open = self.gate.enter();
+        let attr = self.inner.mkdir(parent, name, mode)?;
+        self.notify_mkdir(parent, name, mode, Some(attr.ino));
+        Ok(attr)
     }
 
-    fn rmdir(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
-        // Notify observers first
+    fn rmdir(&mut self, parent: u64, name: &OsStr) -> OpResult<()> {
+        let _open = self.gate.enter();
+        self.inner.rmdir(parent, name)?;
         self.notify_rmdir(parent, name);
-
-        // Delegate to inner filesystem
-        self.inner.rmdir(req, parent, name, reply)
+        Ok(())
     }
 
     fn rename(
         &mut self,
-        req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         newparent: u64,
         newname: &OsStr,
-        flags: u32,
-        reply: ReplyEmpty,
-    ) {
-        // Notify observers first
+    ) -> OpResult<()> {
+        let _open = self.gate.enter();
+        self.inner.rename(parent, name, newparent, newname)?;
         self.notify_rename(parent, name, newparent, newname);
+        Ok(())
+    }
 
-        // Delegate to inner filesystem
-        self.inner
-            .rename(req, parent, name, newparent, newname, flags, reply)
+    fn setattr(&mut self, ino: u64, attr: &SetAttr) -> OpResult<FileAttr> {
+        let _open = self.gate.enter();
+        let result = self.inner.setattr(ino, attr)?;
+
+        // Convert TimeOrNow to SystemTime for observers
+        let atime_st = attr.atime.and_then(time_or_now_to_system_time);
+        let mtime_st = attr.mtime.and_then(time_or_now_to_system_time);
+        self.notify_setattr(ino, attr.size, attr.mode, atime_st, mtime_st);
+        Ok(result)
     }
 
-    fn setattr(
-        &mut self,
-        req: &Request<'_>,
-        ino: u64,
-        mode: Option<u32>,
-        uid: Option<u32>,
-        gid: Option<u32>,
-        size: Option<u64>,
-        atime: Option<TimeOrNow>,
-        mtime: Option<TimeOrNow>,
-        ctime: Option<SystemTime>,
-        fh: Option<u64>,
-        crtime: Option<SystemTime>,
-        chgtime: Option<SystemTime>,
-        bkuptime: Option<SystemTime>,
-        flags: Option<u32>,
-        reply: ReplyAttr,
-    ) {
-        // Notify observers first (convert TimeOrNow to SystemTime)
-        let atime_st = atime.and_then(time_or_now_to_system_time);
-        let mtime_st = mtime.and_then(time_or_now_to_system_time);
-        self.notify_setattr(ino, size, mode, atime_st, mtime_st);
+    fn symlink(&mut self, parent: u64, name: &OsStr, target: &Path) -> OpResult<FileAttr> {
+        let _open = self.gate.enter();
+        let attr = self.inner.symlink(parent, name, target)?;
+        self.notify_symlink(parent, name, target);
+        Ok(attr)
+    }
 
-        // Delegate to inner filesystem
-        self.inner.setattr(
-            req, ino, mode, uid, gid, size, atime, mtime, ctime, fh, crtime, chgtime, bkuptime,
-            flags, reply,
-        )
+    fn readlink(&mut self, ino: u64) -> OpResult<Vec<u8>> {
+        self.inner.readlink(ino)
     }
 
-    // Note: symlink and link are not implemented in PassthroughFS currently,
-    // but we include the observer hooks for future use.
+    // Note: link is not part of FsOps currently, but the observer hook
+    // exists for future use.
 }
 
+impl_filesystem_via_fs_ops!(impl<F: FsOps> for ObservingFS<F>);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -614,7 +603,7 @@ mod tests {
             self.create_count.fetch_add(1, Ordering::SeqCst);
         }
 
-        fn on_unlink(&self, _parent: u64, _name: &OsStr) {
+        fn on_unlink(&self, _parent: u64, _name: &OsStr, _kind: FileType) {
             self.unlink_count.fetch_add(1, Ordering::SeqCst);
         }
 
@@ -645,8 +634,8 @@ mod tests {
     /// A minimal mock filesystem for testing
     struct MockFilesystem;
 
-    impl Filesystem for MockFilesystem {
-        // All methods use default implementations (which do nothing)
+    impl FsOps for MockFilesystem {
+        // All methods use default implementations (which return ENOSYS)
     }
 
     #[test]
@@ -703,7 +692,7 @@ mod tests {
         let observer = Arc::new(CountingObserver::new());
         observing.add_observer(observer.clone());
 
-        observing.notify_create(1, OsStr::new("test.txt"), 0o644);
+        observing.notify_create(1, OsStr::new("test.txt"), 0o644, None);
 
         assert_eq!(observer.create_count(), 1);
     }
@@ -716,7 +705,7 @@ mod tests {
         let observer = Arc::new(CountingObserver::new());
         observing.add_observer(observer.clone());
 
-        observing.notify_unlink(1, OsStr::new("test.txt"));
+        observing.notify_unlink(1, OsStr::new("test.txt"), FileType::RegularFile);
 
         assert_eq!(observer.unlink_count(), 1);
     }
@@ -729,7 +718,7 @@ mod tests {
         let observer = Arc::new(CountingObserver::new());
         observing.add_observer(observer.clone());
 
-        observing.notify_mkdir(1, OsStr::new("test_dir"), 0o755);
+        observing.notify_mkdir(1, OsStr::new("test_dir"), 0o755, None);
 
         assert_eq!(observer.mkdir_count(), 1);
     }
@@ -791,4 +780,153 @@ mod tests {
         assert_eq!(observer1.write_count(), 1);
         assert_eq!(observer2.write_count(), 1);
     }
+
+    /// A filesystem whose mutations all fail with a fixed errno, or succeed
+    /// with writes capped at `write_limit` bytes.
+    struct ScriptedFilesystem {
+        fail_with: Option<libc::c_int>,
+        write_limit: usize,
+    }
+
+    impl ScriptedFilesystem {
+        fn failing(errno: libc::c_int) -> Self {
+            Self {
+                fail_with: Some(errno),
+                write_limit: usize::MAX,
+            }
+        }
+
+        fn short_writes(limit: usize) -> Self {
+            Self {
+                fail_with: None,
+                write_limit: limit,
+            }
+        }
+
+        fn outcome(&self) -> OpResult<()> {
+            match self.fail_with {
+                Some(errno) => Err(errno),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl FsOps for ScriptedFilesystem {
+        fn write(&mut self, _ino: u64, _fh: u64, _offset: i64, data: &[u8]) -> OpResult<u32> {
+            self.outcome()?;
+            Ok(data.len().min(self.write_limit) as u32)
+        }
+
+        fn unlink(&mut self, _parent: u64, _name: &OsStr) -> OpResult<()> {
+            self.outcome()
+        }
+
+        fn rmdir(&mut self, _parent: u64, _name: &OsStr) -> OpResult<()> {
+            self.outcome()
+        }
+
+        fn rename(
+            &mut self,
+            _parent: u64,
+            _name: &OsStr,
+            _newparent: u64,
+            _newname: &OsStr,
+        ) -> OpResult<()> {
+            self.outcome()
+        }
+    }
+
+    /// Captures the payload of every write notification.
+    #[derive(Default)]
+    struct WriteCapture {
+        writes: std::sync::Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl FsObserver for WriteCapture {
+        fn on_write(&self, _ino: u64, _fh: u64, _offset: i64, data: &[u8]) {
+            self.writes.lock().unwrap().push(data.to_vec());
+        }
+    }
+
+    #[test]
+    fn test_failed_mutations_not_observed() {
+        let mut observing = ObservingFS::new(ScriptedFilesystem::failing(libc::EIO));
+        let observer = Arc::new(CountingObserver::new());
+        observing.add_observer(observer.clone());
+
+        assert_eq!(observing.write(2, 1, 0, b"hello"), Err(libc::EIO));
+        assert_eq!(observing.unlink(1, OsStr::new("a")), Err(libc::EIO));
+        assert_eq!(observing.rmdir(1, OsStr::new("d")), Err(libc::EIO));
+        assert_eq!(
+            observing.rename(1, OsStr::new("a"), 1, OsStr::new("b")),
+            Err(libc::EIO)
+        );
+
+        assert_eq!(observer.write_count(), 0);
+        assert_eq!(observer.unlink_count(), 0);
+        assert_eq!(observer.rmdir_count(), 0);
+        assert_eq!(observer.rename_count(), 0);
+    }
+
+    #[test]
+    fn test_unimplemented_mutations_not_observed() {
+        let mut observing = ObservingFS::new(MockFilesystem);
+        let observer = Arc::new(CountingObserver::new());
+        observing.add_observer(observer.clone());
+
+        assert!(observing.create(1, OsStr::new("f"), 0o644, 0).is_err());
+        assert!(observing.mkdir(1, OsStr::new("d"), 0o755).is_err());
+        assert!(observing.setattr(2, &SetAttr::default()).is_err());
+
+        assert_eq!(observer.create_count(), 0);
+        assert_eq!(observer.mkdir_count(), 0);
+        assert_eq!(observer.setattr_count(), 0);
+    }
+
+    #[test]
+    fn test_successful_mutations_observed() {
+        let mut observing = ObservingFS::new(ScriptedFilesystem::short_writes(usize::MAX));
+        let observer = Arc::new(CountingObserver::new());
+        observing.add_observer(observer.clone());
+
+        assert_eq!(observing.write(2, 1, 0, b"hello"), Ok(5));
+        observing.unlink(1, OsStr::new("a")).unwrap();
+        observing
+            .rename(1, OsStr::new("b"), 1, OsStr::new("c"))
+            .unwrap();
+
+        assert_eq!(observer.write_count(), 1);
+        assert_eq!(observer.unlink_count(), 1);
+        assert_eq!(observer.rename_count(), 1);
+    }
+
+    #[test]
+    fn test_short_write_reports_only_written_bytes() {
+        let mut observing = ObservingFS::new(ScriptedFilesystem::short_writes(3));
+        let capture = Arc::new(WriteCapture::default());
+        observing.add_observer(capture.clone());
+
+        assert_eq!(observing.write(2, 1, 0, b"hello"), Ok(3));
+
+        assert_eq!(*capture.writes.lock().unwrap(), vec![b"hel".to_vec()]);
+    }
+
+    #[test]
+    fn test_paused_mutations_wait_for_resume() {
+        let mut observing = ObservingFS::new(ScriptedFilesystem::short_writes(usize::MAX));
+        let observer = Arc::new(CountingObserver::new());
+        observing.add_observer(observer.clone());
+        let gate = observing.gate();
+
+        let pause = gate.pause();
+        let writer = std::thread::spawn(move || {
+            observing.write(2, 1, 0, b"hello").unwrap();
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(observer.write_count(), 0);
+
+        drop(pause);
+        writer.join().unwrap();
+        assert_eq!(observer.write_count(), 1);
+    }
 }
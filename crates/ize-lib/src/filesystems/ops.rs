@@ -0,0 +1,675 @@
+//! Request-free filesystem operations.
+//!
+//! fuser's `Request` and `Reply*` types can only be constructed by fuser
+//! itself, so a filesystem written directly against [`fuser::Filesystem`] can
+//! only be driven through a real mount, and a wrapper that delegates a call has
+//! no way to learn whether it succeeded.
+//!
+//! [`FsOps`] is the same surface with plain arguments and `Result` returns.
+//! Filesystems implement it once; [`impl_filesystem_via_fs_ops!`] then
+//! generates the [`Filesystem`](fuser::Filesystem) impl that forwards every
+//! FUSE callback and sends the result back through its reply.  Wrappers such
+//! as [`ObservingFS`](super::ObservingFS) compose at the `FsOps` level, and
+//! tests can call it directly without mounting anything.
+
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::time::Duration;
+
+use fuser::{
+    FileAttr, FileType, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry,
+    ReplyOpen, ReplyStatfs, ReplyWrite, TimeOrNow,
+};
+
+// ---------------------------------------------------------------------------
+// Types
+// ---------------------------------------------------------------------------
+
+/// TTL for cached attributes and entries (1 second).
+pub(crate) const TTL: Duration = Duration::from_secs(1);
+
+/// Result of a filesystem operation; the error is a raw `errno` value.
+pub type OpResult<T> = Result<T, libc::c_int>;
+
+/// Attribute changes requested by a `setattr` call.
+///
+/// A `None` field means "leave unchanged".
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SetAttr {
+    /// New permission bits.
+    pub mode: Option<u32>,
+    /// New owner.
+    pub uid: Option<u32>,
+    /// New group.
+    pub gid: Option<u32>,
+    /// New size (truncate or extend).
+    pub size: Option<u64>,
+    /// New access time.
+    pub atime: Option<TimeOrNow>,
+    /// New modification time.
+    pub mtime: Option<TimeOrNow>,
+    /// Open file handle the change was issued through, if any.
+    pub fh: Option<u64>,
+}
+
+/// The process behind a FUSE request, as reported by the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Caller {
+    /// Effective user ID.
+    pub uid: u32,
+    /// Effective group ID.
+    pub gid: u32,
+    /// Process ID (of the thread that issued the request).
+    pub pid: u32,
+}
+
+impl From<&fuser::Request<'_>> for Caller {
+    fn from(req: &fuser::Request<'_>) -> Self {
+        Self {
+            uid: req.uid(),
+            gid: req.gid(),
+            pid: req.pid(),
+        }
+    }
+}
+
+/// A single entry returned by [`FsOps::readdir`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReaddirEntry {
+    /// Inode number of the entry.
+    pub ino: u64,
+    /// Offset of the *next* entry, to be passed back to resume listing.
+    pub offset: i64,
+    /// File type of the entry.
+    pub kind: FileType,
+    /// Entry name.
+    pub name: OsString,
+}
+
+// ---------------------------------------------------------------------------
+// FsOps trait
+// ---------------------------------------------------------------------------
+
+/// Filesystem operations without fuser request or reply objects.
+///
+/// Mirrors the subset of [`fuser::Filesystem`] that ize's filesystems
+/// implement.  Every operation defaults to `ENOSYS`.
+#[allow(unused_variables)]
+pub trait FsOps {
+    /// Called once when the filesystem is mounted.
+    fn init(&mut self) -> OpResult<()> {
+        Ok(())
+    }
+
+    /// Called once when the filesystem is unmounted.
+    fn destroy(&mut self) {}
+
+    /// Called with the requesting process before each mutating operation
+    /// (write, create, mkdir, unlink, rmdir, rename, setattr, symlink).
+    fn set_caller(&mut self, caller: Caller) {}
+
+    /// Look up a directory entry by name.
+    fn lookup(&mut self, parent: u64, name: &OsStr) -> OpResult<FileAttr> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Get file attributes, preferring the open handle `fh` when given.
+    fn getattr(&mut self, ino: u64, fh: Option<u64>) -> OpResult<FileAttr> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Change file attributes and return the updated ones.
+    fn setattr(&mut self, ino: u64, attr: &SetAttr) -> OpResult<FileAttr> {
+        Err(libc::ENOSYS)
+    }
+
+    /// List a directory, starting after `offset`.
+    fn readdir(&mut self, ino: u64, fh: u64, offset: i64) -> OpResult<Vec<ReaddirEntry>> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Open a file and return a new file handle.
+    fn open(&mut self, ino: u64, flags: i32) -> OpResult<u64> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Read up to `size` bytes at `offset`.
+    fn read(&mut self, ino: u64, fh: u64, offset: i64, size: u32) -> OpResult<Vec<u8>> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Write `data` at `offset` and return the number of bytes written.
+    fn write(&mut self, ino: u64, fh: u64, offset: i64, data: &[u8]) -> OpResult<u32> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Create and open a file, returning its attributes and a file handle.
+    fn create(
+        &mut self,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        flags: i32,
+    ) -> OpResult<(FileAttr, u64)> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Create a directory.
+    fn mkdir(&mut self, parent: u64, name: &OsStr, mode: u32) -> OpResult<FileAttr> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Remove a non-directory entry.
+    fn unlink(&mut self, parent: u64, name: &OsStr) -> OpResult<()> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Remove an empty directory.
+    fn rmdir(&mut self, parent: u64, name: &OsStr) -> OpResult<()> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Rename an entry, replacing the destination if it exists.
+    fn rename(
+        &mut self,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+    ) -> OpResult<()> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Create a symbolic link named `name` pointing at `target`.
+    fn symlink(&mut self, parent: u64, name: &OsStr, target: &Path) -> OpResult<FileAttr> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Read the target of a symbolic link.
+    fn readlink(&mut self, ino: u64) -> OpResult<Vec<u8>> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Called on each `close()` of a file descriptor.
+    fn flush(&mut self, ino: u64, fh: u64) -> OpResult<()> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Release a file handle once every descriptor using it is closed.
+    fn release(&mut self, ino: u64, fh: u64) -> OpResult<()> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Flush file data (and metadata unless `datasync`) to stable storage.
+    fn fsync(&mut self, ino: u64, fh: u64, datasync: bool) -> OpResult<()> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Check access permissions, as `access(2)`.
+    fn access(&mut self, ino: u64, mask: i32) -> OpResult<()> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Return filesystem statistics.
+    fn statfs(&mut self, ino: u64) -> OpResult<libc::statvfs> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Open a directory and return a directory handle.
+    fn opendir(&mut self, ino: u64, flags: i32) -> OpResult<u64> {
+        Err(libc::ENOSYS)
+    }
+
+    /// Release a directory handle.
+    fn releasedir(&mut self, ino: u64, fh: u64) -> OpResult<()> {
+        Err(libc::ENOSYS)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Reply plumbing
+// ---------------------------------------------------------------------------
+
+/// Send an [`OpResult`] through the matching fuser reply.
+pub(crate) trait SendReply<T> {
+    fn send(self, result: OpResult<T>);
+}
+
+impl SendReply<FileAttr> for ReplyEntry {
+    fn send(self, result: OpResult<FileAttr>) {
+        match result {
+            Ok(attr) => self.entry(&TTL, &attr, 0),
+            Err(errno) => self.error(errno),
+        }
+    }
+}
+
+impl SendReply<FileAttr> for ReplyAttr {
+    fn send(self, result: OpResult<FileAttr>) {
+        match result {
+            Ok(attr) => self.attr(&TTL, &attr),
+            Err(errno) => self.error(errno),
+        }
+    }
+}
+
+impl SendReply<Vec<ReaddirEntry>> for ReplyDirectory {
+    fn send(mut self, result: OpResult<Vec<ReaddirEntry>>) {
+        match result {
+            Ok(entries) => {
+                for entry in &entries {
+                    if self.add(entry.ino, entry.offset, entry.kind, &entry.name) {
+                        break;
+                    }
+                }
+                self.ok();
+            }
+            Err(errno) => self.error(errno),
+        }
+    }
+}
+
+impl SendReply<u64> for ReplyOpen {
+    fn send(self, result: OpResult<u64>) {
+        match result {
+            Ok(fh) => self.opened(fh, 0),
+            Err(errno) => self.error(errno),
+        }
+    }
+}
+
+impl SendReply<Vec<u8>> for ReplyData {
+    fn send(self, result: OpResult<Vec<u8>>) {
+        match result {
+            Ok(data) => self.data(&data),
+            Err(errno) => self.error(errno),
+        }
+    }
+}
+
+impl SendReply<u32> for ReplyWrite {
+    fn send(self, result: OpResult<u32>) {
+        match result {
+            Ok(written) => self.written(written),
+            Err(errno) => self.error(errno),
+        }
+    }
+}
+
+impl SendReply<(FileAttr, u64)> for ReplyCreate {
+    fn send(self, result: OpResult<(FileAttr, u64)>) {
+        match result {
+            Ok((attr, fh)) => self.created(&TTL, &attr, 0, fh, 0),
+            Err(errno) => self.error(errno),
+        }
+    }
+}
+
+impl SendReply<()> for ReplyEmpty {
+    fn send(self, result: OpResult<()>) {
+        match result {
+            Ok(()) => self.ok(),
+            Err(errno) => self.error(errno),
+        }
+    }
+}
+
+impl SendReply<libc::statvfs> for ReplyStatfs {
+    fn send(self, result: OpResult<libc::statvfs>) {
+        match result {
+            Ok(st) => self.statfs(
+                st.f_blocks,
+                st.f_bfree,
+                st.f_bavail,
+                st.f_files,
+                st.f_ffree,
+                st.f_bsize as u32,
+                st.f_namemax as u32,
+                st.f_frsize as u32,
+            ),
+            Err(errno) => self.error(errno),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Filesystem adapter
+// ---------------------------------------------------------------------------
+
+/// Implement [`fuser::Filesystem`] for a type by forwarding every callback to
+/// its [`FsOps`] impl.
+///
+/// ```ignore
+/// impl_filesystem_via_fs_ops!(impl<B: BackingFs> for FdPassthroughFS<B>);
+/// ```
+macro_rules! impl_filesystem_via_fs_ops {
+    (impl<$($gp:ident: $bound:path),*> for $ty:ty) => {
+        impl<$($gp: $bound),*> fuser::Filesystem for $ty {
+            fn init(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                _config: &mut fuser::KernelConfig,
+            ) -> Result<(), libc::c_int> {
+                $crate::filesystems::ops::FsOps::init(self)
+            }
+
+            fn destroy(&mut self) {
+                $crate::filesystems::ops::FsOps::destroy(self)
+            }
+
+            fn lookup(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                parent: u64,
+                name: &std::ffi::OsStr,
+                reply: fuser::ReplyEntry,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::lookup(self, parent, name))
+            }
+
+            fn getattr(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                fh: Option<u64>,
+                reply: fuser::ReplyAttr,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::getattr(self, ino, fh))
+            }
+
+            fn setattr(
+                &mut self,
+                req: &fuser::Request<'_>,
+                ino: u64,
+                mode: Option<u32>,
+                uid: Option<u32>,
+                gid: Option<u32>,
+                size: Option<u64>,
+                atime: Option<fuser::TimeOrNow>,
+                mtime: Option<fuser::TimeOrNow>,
+                _ctime: Option<std::time::SystemTime>,
+                fh: Option<u64>,
+                _crtime: Option<std::time::SystemTime>,
+                _chgtime: Option<std::time::SystemTime>,
+                _bkuptime: Option<std::time::SystemTime>,
+                _flags: Option<u32>,
+                reply: fuser::ReplyAttr,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                $crate::filesystems::ops::FsOps::set_caller(self, req.into());
+                let attr = $crate::filesystems::ops::SetAttr {
+                    mode,
+                    uid,
+                    gid,
+                    size,
+                    atime,
+                    mtime,
+                    fh,
+                };
+                reply.send($crate::filesystems::ops::FsOps::setattr(self, ino, &attr))
+            }
+
+            fn readdir(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                fh: u64,
+                offset: i64,
+                reply: fuser::ReplyDirectory,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::readdir(self, ino, fh, offset))
+            }
+
+            fn open(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                flags: i32,
+                reply: fuser::ReplyOpen,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::open(self, ino, flags))
+            }
+
+            fn read(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                fh: u64,
+                offset: i64,
+                size: u32,
+                _flags: i32,
+                _lock_owner: Option<u64>,
+                reply: fuser::ReplyData,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::read(self, ino, fh, offset, size))
+            }
+
+            fn write(
+                &mut self,
+                req: &fuser::Request<'_>,
+                ino: u64,
+                fh: u64,
+                offset: i64,
+                data: &[u8],
+                _write_flags: u32,
+                _flags: i32,
+                _lock_owner: Option<u64>,
+                reply: fuser::ReplyWrite,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                $crate::filesystems::ops::FsOps::set_caller(self, req.into());
+                reply.send($crate::filesystems::ops::FsOps::write(self, ino, fh, offset, data))
+            }
+
+            fn create(
+                &mut self,
+                req: &fuser::Request<'_>,
+                parent: u64,
+                name: &std::ffi::OsStr,
+                mode: u32,
+                _umask: u32,
+                flags: i32,
+                reply: fuser::ReplyCreate,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                $crate::filesystems::ops::FsOps::set_caller(self, req.into());
+                reply.send($crate::filesystems::ops::FsOps::create(
+                    self, parent, name, mode, flags,
+                ))
+            }
+
+            fn mkdir(
+                &mut self,
+                req: &fuser::Request<'_>,
+                parent: u64,
+                name: &std::ffi::OsStr,
+                mode: u32,
+                _umask: u32,
+                reply: fuser::ReplyEntry,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                $crate::filesystems::ops::FsOps::set_caller(self, req.into());
+                reply.send($crate::filesystems::ops::FsOps::mkdir(self, parent, name, mode))
+            }
+
+            fn unlink(
+                &mut self,
+                req: &fuser::Request<'_>,
+                parent: u64,
+                name: &std::ffi::OsStr,
+                reply: fuser::ReplyEmpty,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                $crate::filesystems::ops::FsOps::set_caller(self, req.into());
+                reply.send($crate::filesystems::ops::FsOps::unlink(self, parent, name))
+            }
+
+            fn rmdir(
+                &mut self,
+                req: &fuser::Request<'_>,
+                parent: u64,
+                name: &std::ffi::OsStr,
+                reply: fuser::ReplyEmpty,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                $crate::filesystems::ops::FsOps::set_caller(self, req.into());
+                reply.send($crate::filesystems::ops::FsOps::rmdir(self, parent, name))
+            }
+
+            fn rename(
+                &mut self,
+                req: &fuser::Request<'_>,
+                parent: u64,
+                name: &std::ffi::OsStr,
+                newparent: u64,
+                newname: &std::ffi::OsStr,
+                _flags: u32,
+                reply: fuser::ReplyEmpty,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                $crate::filesystems::ops::FsOps::set_caller(self, req.into());
+                reply.send($crate::filesystems::ops::FsOps::rename(
+                    self, parent, name, newparent, newname,
+                ))
+            }
+
+            fn symlink(
+                &mut self,
+                req: &fuser::Request<'_>,
+                parent: u64,
+                link_name: &std::ffi::OsStr,
+                target: &std::path::Path,
+                reply: fuser::ReplyEntry,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                $crate::filesystems::ops::FsOps::set_caller(self, req.into());
+                reply.send($crate::filesystems::ops::FsOps::symlink(
+                    self, parent, link_name, target,
+                ))
+            }
+
+            fn readlink(&mut self, _req: &fuser::Request<'_>, ino: u64, reply: fuser::ReplyData) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::readlink(self, ino))
+            }
+
+            fn flush(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                fh: u64,
+                _lock_owner: u64,
+                reply: fuser::ReplyEmpty,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::flush(self, ino, fh))
+            }
+
+            fn release(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                fh: u64,
+                _flags: i32,
+                _lock_owner: Option<u64>,
+                _flush: bool,
+                reply: fuser::ReplyEmpty,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::release(self, ino, fh))
+            }
+
+            fn fsync(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                fh: u64,
+                datasync: bool,
+                reply: fuser::ReplyEmpty,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::fsync(self, ino, fh, datasync))
+            }
+
+            fn access(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                mask: i32,
+                reply: fuser::ReplyEmpty,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::access(self, ino, mask))
+            }
+
+            fn statfs(&mut self, _req: &fuser::Request<'_>, ino: u64, reply: fuser::ReplyStatfs) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::statfs(self, ino))
+            }
+
+            fn opendir(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                flags: i32,
+                reply: fuser::ReplyOpen,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::opendir(self, ino, flags))
+            }
+
+            fn releasedir(
+                &mut self,
+                _req: &fuser::Request<'_>,
+                ino: u64,
+                fh: u64,
+                _flags: i32,
+                reply: fuser::ReplyEmpty,
+            ) {
+                use $crate::filesystems::ops::SendReply;
+                reply.send($crate::filesystems::ops::FsOps::releasedir(self, ino, fh))
+            }
+        }
+    };
+}
+
+pub(crate) use impl_filesystem_via_fs_ops;
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Unimplemented;
+
+    impl FsOps for Unimplemented {}
+
+    #[test]
+    fn defaults_report_enosys() {
+        let mut fs = Unimplemented;
+        assert_eq!(fs.init(), Ok(()));
+        assert_eq!(fs.lookup(1, OsStr::new("x")).unwrap_err(), libc::ENOSYS);
+        assert_eq!(fs.write(1, 1, 0, b"data").unwrap_err(), libc::ENOSYS);
+        assert_eq!(
+            fs.rename(1, OsStr::new("a"), 1, OsStr::new("b")),
+            Err(libc::ENOSYS)
+        );
+    }
+
+    #[test]
+    fn set_attr_default_changes_nothing() {
+        let attr = SetAttr::default();
+        assert!(attr.mode.is_none());
+        assert!(attr.size.is_none());
+        assert!(attr.atime.is_none());
+        assert!(attr.fh.is_none());
+    }
+}
@@ -1,7 +1,8 @@
 //! fd-based passthrough FUSE filesystem.
 //!
-//! [`FdPassthroughFS`] implements the fuser [`Filesystem`] trait by delegating
-//! all underlying I/O through a [`BackingFs`] implementation.  Because the
+//! [`FdPassthroughFS`] implements [`FsOps`] (and, through it, the fuser
+//! [`Filesystem`](fuser::Filesystem) trait) by delegating all underlying I/O
+//! through a [`BackingFs`] implementation.  Because the
 //! backing implementation (e.g. [`LibcBackingFs`](crate::backing_fs::LibcBackingFs))
 //! operates against a pre-opened directory fd, the FUSE layer never re-enters
 //! itself — eliminating the deadlock that plagues naive passthrough mounts.
@@ -12,20 +13,18 @@
 //! inside [`ObservingFS`](super::ObservingFS).
 
 use std::collections::HashMap;
-use std::ffi::{OsStr, OsString};
-use std::os::unix::ffi::OsStrExt;
+use std::ffi::OsStr;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::os::unix::io::RawFd;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, UNIX_EPOCH};
 
-use fuser::{
-    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory,
-    ReplyEmpty, ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, Request, TimeOrNow,
-};
+use fuser::{FileAttr, FileType, MountOption, Notifier, TimeOrNow};
 use log::{debug, error, info, warn};
 
+use super::ops::{impl_filesystem_via_fs_ops, FsOps, OpResult, ReaddirEntry, SetAttr};
 use crate::backing_fs::{BackingFs, DirEntry};
 use crate::vcs::IgnoreFilter;
 
@@ -33,9 +32,6 @@ use crate::vcs::IgnoreFilter;
 // Constants
 // ---------------------------------------------------------------------------
 
-/// TTL for cached attributes (1 second).
-const TTL: Duration = Duration::from_secs(1);
-
 /// FUSE always uses inode 1 for the root directory.
 const FUSE_ROOT_ID: u64 = 1;
 
@@ -48,6 +44,34 @@ const FUSE_ROOT_ID: u64 = 1;
 /// This can be shared with observers for path resolution.
 pub type InodeMap = Arc<RwLock<HashMap<u64, PathBuf>>>;
 
+/// Drop whatever the kernel has cached for the inodes in `inodes`: their
+/// attributes, page cache and directory entries.
+///
+/// Needed after the backing directory changed behind the mount's back, such
+/// as by a channel checkout. Don't call it from inside a request handler; the
+/// kernel may wait on the request being handled.
+///
+/// Returns the number of inodes the kernel still knew about.
+pub fn invalidate_kernel_cache(notifier: &Notifier, inodes: &InodeMap) -> usize {
+    let map = inodes.read().unwrap();
+    let by_path: HashMap<&Path, u64> = map.iter().map(|(ino, p)| (p.as_path(), *ino)).collect();
+
+    let mut invalidated = 0;
+    for (&ino, path) in map.iter() {
+        if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+            if let Some(&parent_ino) = by_path.get(parent) {
+                // ENOENT just means the kernel had already forgotten it
+                let _ = notifier.inval_entry(parent_ino, name);
+            }
+        }
+        match notifier.inval_inode(ino, 0, 0) {
+            Ok(()) => invalidated += 1,
+            Err(e) => debug!("inval_inode({}) for {:?}: {}", ino, path, e),
+        }
+    }
+    invalidated
+}
+
 // ---------------------------------------------------------------------------
 // OpenFile
 // ---------------------------------------------------------------------------
@@ -255,6 +279,11 @@ impl<B: BackingFs> FdPassthroughFS<B> {
         self.read_only = read_only;
     }
 
+    /// Check if the filesystem is in read-only mode.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     /// Mount the filesystem.
     pub fn mount(self) -> std::io::Result<()> {
         let mut options = vec![
@@ -346,15 +375,11 @@ impl<B: BackingFs> FdPassthroughFS<B> {
 }
 
 // ---------------------------------------------------------------------------
-// Filesystem implementation
+// FsOps implementation
 // ---------------------------------------------------------------------------
 
-impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
-    fn init(
-        &mut self,
-        _req: &Request<'_>,
-        _config: &mut fuser::KernelConfig,
-    ) -> Result<(), libc::c_int> {
+impl<B: BackingFs> FsOps for FdPassthroughFS<B> {
+    fn init(&mut self) -> OpResult<()> {
         info!(
             "FdPassthroughFS initialised — mount_point={:?}",
             self.mount_point
@@ -368,36 +393,28 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
 
     // -- lookup -------------------------------------------------------------
 
-    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+    fn lookup(&mut self, parent: u64, name: &OsStr) -> OpResult<FileAttr> {
         debug!("lookup(parent={}, name={:?})", parent, name);
 
-        let parent_rel = match self.get_rel_path(parent) {
-            Some(p) => p,
-            None => {
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
-
+        let parent_rel = self.get_rel_path(parent).ok_or(libc::ENOENT)?;
         let child_rel = parent_rel.join(name);
 
         match self.backing.stat(&child_rel) {
             Ok(st) => {
                 let ino = st.st_ino;
                 self.register_inode(ino, child_rel);
-                let attr = Self::stat_to_attr(&st, ino);
-                reply.entry(&TTL, &attr, 0);
+                Ok(Self::stat_to_attr(&st, ino))
             }
             Err(e) => {
                 debug!("lookup: stat failed for {:?}: {}", child_rel, e);
-                reply.error(e.raw_os_error().unwrap_or(libc::ENOENT));
+                Err(e.raw_os_error().unwrap_or(libc::ENOENT))
             }
         }
     }
 
     // -- getattr ------------------------------------------------------------
 
-    fn getattr(&mut self, _req: &Request<'_>, ino: u64, fh: Option<u64>, reply: ReplyAttr) {
+    fn getattr(&mut self, ino: u64, fh: Option<u64>) -> OpResult<FileAttr> {
         debug!("getattr(ino={}, fh={:?})", ino, fh);
 
         // Try fstat on an open fd first (cheaper, no path resolution needed).
@@ -411,8 +428,7 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                         } else {
                             st.st_ino
                         };
-                        reply.attr(&TTL, &Self::stat_to_attr(&st, returned_ino));
-                        return;
+                        return Ok(Self::stat_to_attr(&st, returned_ino));
                     }
                     Err(e) => {
                         debug!(
@@ -425,14 +441,10 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
         }
 
         // Fall back to path-based stat.
-        let rel = match self.get_rel_path(ino) {
-            Some(p) => p,
-            None => {
-                error!("getattr: inode {} not found", ino);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let rel = self.get_rel_path(ino).ok_or_else(|| {
+            error!("getattr: inode {} not found", ino);
+            libc::ENOENT
+        })?;
 
         match self.backing.stat(&rel) {
             Ok(st) => {
@@ -441,116 +453,88 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                 } else {
                     st.st_ino
                 };
-                reply.attr(&TTL, &Self::stat_to_attr(&st, returned_ino));
+                Ok(Self::stat_to_attr(&st, returned_ino))
             }
             Err(e) => {
                 error!("getattr: stat failed for {:?}: {}", rel, e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
 
     // -- setattr ------------------------------------------------------------
 
-    fn setattr(
-        &mut self,
-        _req: &Request<'_>,
-        ino: u64,
-        mode: Option<u32>,
-        uid: Option<u32>,
-        gid: Option<u32>,
-        size: Option<u64>,
-        atime: Option<TimeOrNow>,
-        mtime: Option<TimeOrNow>,
-        _ctime: Option<SystemTime>,
-        fh: Option<u64>,
-        _crtime: Option<SystemTime>,
-        _chgtime: Option<SystemTime>,
-        _bkuptime: Option<SystemTime>,
-        _flags: Option<u32>,
-        reply: ReplyAttr,
-    ) {
+    fn setattr(&mut self, ino: u64, attr: &SetAttr) -> OpResult<FileAttr> {
         debug!(
             "setattr(ino={}, mode={:?}, uid={:?}, gid={:?}, size={:?}, fh={:?})",
-            ino, mode, uid, gid, size, fh
+            ino, attr.mode, attr.uid, attr.gid, attr.size, attr.fh
         );
 
         if self.read_only {
-            reply.error(libc::EROFS);
-            return;
+            return Err(libc::EROFS);
         }
 
-        let rel = match self.get_rel_path(ino) {
-            Some(p) => p,
-            None => {
-                error!("setattr: inode {} not found", ino);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let rel = self.get_rel_path(ino).ok_or_else(|| {
+            error!("setattr: inode {} not found", ino);
+            libc::ENOENT
+        })?;
 
         // -- Handle truncate (size change) --
-        if let Some(new_size) = size {
+        if let Some(new_size) = attr.size {
             // Prefer using an existing file handle if available.
-            let truncated = if let Some(fh_val) = fh {
-                let handles = self.open_files.read().unwrap();
-                if let Some(ofile) = handles.get(&fh_val) {
-                    match self.backing.ftruncate(ofile.fd, new_size) {
-                        Ok(()) => true,
-                        Err(e) => {
-                            error!("setattr: ftruncate via fh failed: {}", e);
-                            reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                            return;
+            let truncated = match attr.fh {
+                Some(fh_val) => {
+                    let handles = self.open_files.read().unwrap();
+                    match handles.get(&fh_val) {
+                        Some(ofile) => {
+                            self.backing.ftruncate(ofile.fd, new_size).map_err(|e| {
+                                error!("setattr: ftruncate via fh failed: {}", e);
+                                e.raw_os_error().unwrap_or(libc::EIO)
+                            })?;
+                            true
                         }
+                        None => false,
                     }
-                } else {
-                    false
                 }
-            } else {
-                false
+                None => false,
             };
 
             // Fall back to open-truncate-close if no fh was usable.
             if !truncated {
-                match self.backing.open_file(&rel, libc::O_WRONLY, 0) {
-                    Ok(fd) => {
-                        let result = self.backing.ftruncate(fd, new_size);
-                        self.backing.close_fd(fd);
-                        if let Err(e) = result {
-                            error!("setattr: ftruncate via path failed: {}", e);
-                            reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                            return;
-                        }
-                    }
-                    Err(e) => {
+                let fd = self
+                    .backing
+                    .open_file(&rel, libc::O_WRONLY, 0)
+                    .map_err(|e| {
                         error!("setattr: open for truncate failed: {}", e);
-                        reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                        return;
-                    }
-                }
+                        e.raw_os_error().unwrap_or(libc::EIO)
+                    })?;
+                let result = self.backing.ftruncate(fd, new_size);
+                self.backing.close_fd(fd);
+                result.map_err(|e| {
+                    error!("setattr: ftruncate via path failed: {}", e);
+                    e.raw_os_error().unwrap_or(libc::EIO)
+                })?;
             }
         }
 
         // -- Handle mode change --
-        if let Some(new_mode) = mode {
-            if let Err(e) = self.backing.chmod(&rel, new_mode) {
+        if let Some(new_mode) = attr.mode {
+            self.backing.chmod(&rel, new_mode).map_err(|e| {
                 error!("setattr: chmod failed: {}", e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                return;
-            }
+                e.raw_os_error().unwrap_or(libc::EIO)
+            })?;
         }
 
         // -- Handle uid/gid change --
-        if uid.is_some() || gid.is_some() {
-            if let Err(e) = self.backing.chown(&rel, uid, gid) {
+        if attr.uid.is_some() || attr.gid.is_some() {
+            self.backing.chown(&rel, attr.uid, attr.gid).map_err(|e| {
                 error!("setattr: chown failed: {}", e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                return;
-            }
+                e.raw_os_error().unwrap_or(libc::EIO)
+            })?;
         }
 
         // -- Handle atime/mtime change --
-        if atime.is_some() || mtime.is_some() {
+        if attr.atime.is_some() || attr.mtime.is_some() {
             let to_timespec = |t: Option<TimeOrNow>| -> libc::timespec {
                 match t {
                     Some(TimeOrNow::SpecificTime(st)) => {
@@ -570,14 +554,15 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                     },
                 }
             };
-            let atime_ts = to_timespec(atime);
-            let mtime_ts = to_timespec(mtime);
-
-            if let Err(e) = self.backing.utimens(&rel, &atime_ts, &mtime_ts) {
-                error!("setattr: utimens failed: {}", e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                return;
-            }
+            let atime_ts = to_timespec(attr.atime);
+            let mtime_ts = to_timespec(attr.mtime);
+
+            self.backing
+                .utimens(&rel, &atime_ts, &mtime_ts)
+                .map_err(|e| {
+                    error!("setattr: utimens failed: {}", e);
+                    e.raw_os_error().unwrap_or(libc::EIO)
+                })?;
         }
 
         // -- Return updated attrs --
@@ -588,50 +573,33 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                 } else {
                     st.st_ino
                 };
-                reply.attr(&TTL, &Self::stat_to_attr(&st, returned_ino));
+                Ok(Self::stat_to_attr(&st, returned_ino))
             }
             Err(e) => {
                 error!("setattr: failed to re-stat after changes: {}", e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
 
     // -- readdir ------------------------------------------------------------
 
-    fn readdir(
-        &mut self,
-        _req: &Request<'_>,
-        ino: u64,
-        _fh: u64,
-        offset: i64,
-        mut reply: ReplyDirectory,
-    ) {
+    fn readdir(&mut self, ino: u64, _fh: u64, offset: i64) -> OpResult<Vec<ReaddirEntry>> {
         debug!("readdir(ino={}, offset={})", ino, offset);
 
-        let rel = match self.get_rel_path(ino) {
-            Some(p) => p,
-            None => {
-                error!("readdir: inode {} not found", ino);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let rel = self.get_rel_path(ino).ok_or_else(|| {
+            error!("readdir: inode {} not found", ino);
+            libc::ENOENT
+        })?;
 
-        let entries = match self.backing.readdir(&rel) {
-            Ok(e) => e,
-            Err(e) => {
-                error!("readdir: failed for {:?}: {}", rel, e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                return;
-            }
-        };
+        let entries = self.backing.readdir(&rel).map_err(|e| {
+            error!("readdir: failed for {:?}: {}", rel, e);
+            e.raw_os_error().unwrap_or(libc::EIO)
+        })?;
 
         // Build the full entry list with stable offsets.
-        let mut all: Vec<(u64, FileType, OsString)> = Vec::with_capacity(entries.len());
-        for entry in &entries {
-            let ft = Self::dtype_to_filetype(entry.dtype);
-
+        let mut all = Vec::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
             // Register inode mappings for children (skip . and ..).
             let name_bytes = entry.name.as_bytes();
             if name_bytes != b"." && name_bytes != b".." {
@@ -639,38 +607,33 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                 self.register_inode(entry.ino, child_rel);
             }
 
-            all.push((entry.ino, ft, entry.name.clone()));
+            all.push(ReaddirEntry {
+                ino: entry.ino,
+                offset: (i + 1) as i64,
+                kind: Self::dtype_to_filetype(entry.dtype),
+                name: entry.name.clone(),
+            });
         }
 
-        for (i, (entry_ino, kind, name)) in all.iter().enumerate().skip(offset as usize) {
-            if reply.add(*entry_ino, (i + 1) as i64, *kind, OsStr::new(name)) {
-                break;
-            }
-        }
-        reply.ok();
+        Ok(all.into_iter().skip(offset as usize).collect())
     }
 
     // -- open ---------------------------------------------------------------
 
-    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+    fn open(&mut self, ino: u64, flags: i32) -> OpResult<u64> {
         debug!("open(ino={}, flags=0x{:x})", ino, flags);
 
         // Check read-only mode for write-ish flags.
         if self.read_only
             && (flags & (libc::O_WRONLY | libc::O_RDWR | libc::O_APPEND | libc::O_TRUNC) != 0)
         {
-            reply.error(libc::EROFS);
-            return;
+            return Err(libc::EROFS);
         }
 
-        let rel = match self.get_rel_path(ino) {
-            Some(p) => p,
-            None => {
-                error!("open: inode {} not found", ino);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let rel = self.get_rel_path(ino).ok_or_else(|| {
+            error!("open: inode {} not found", ino);
+            libc::ENOENT
+        })?;
 
         // Strip O_CREAT — open() should not create; that's create()'s job.
         let open_flags = flags & !libc::O_CREAT;
@@ -687,91 +650,59 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                     },
                 );
                 debug!("open: fh={} fd={}", fh, fd);
-                reply.opened(fh, 0);
+                Ok(fh)
             }
             Err(e) => {
                 error!("open: failed for {:?}: {}", rel, e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
 
     // -- read ---------------------------------------------------------------
 
-    fn read(
-        &mut self,
-        _req: &Request<'_>,
-        _ino: u64,
-        fh: u64,
-        offset: i64,
-        size: u32,
-        _flags: i32,
-        _lock_owner: Option<u64>,
-        reply: ReplyData,
-    ) {
+    fn read(&mut self, _ino: u64, fh: u64, offset: i64, size: u32) -> OpResult<Vec<u8>> {
         debug!("read(fh={}, offset={}, size={})", fh, offset, size);
 
         let handles = self.open_files.read().unwrap();
-        let ofile = match handles.get(&fh) {
-            Some(f) => f,
-            None => {
-                warn!("read: fh {} not found", fh);
-                reply.error(libc::EBADF);
-                return;
-            }
-        };
+        let ofile = handles.get(&fh).ok_or_else(|| {
+            warn!("read: fh {} not found", fh);
+            libc::EBADF
+        })?;
 
         let mut buf = vec![0u8; size as usize];
         match self.backing.pread(ofile.fd, &mut buf, offset) {
             Ok(n) => {
                 buf.truncate(n);
-                reply.data(&buf);
+                Ok(buf)
             }
             Err(e) => {
                 error!("read: pread failed: {}", e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
 
     // -- write --------------------------------------------------------------
 
-    fn write(
-        &mut self,
-        _req: &Request<'_>,
-        _ino: u64,
-        fh: u64,
-        offset: i64,
-        data: &[u8],
-        _write_flags: u32,
-        _flags: i32,
-        _lock_owner: Option<u64>,
-        reply: ReplyWrite,
-    ) {
+    fn write(&mut self, _ino: u64, fh: u64, offset: i64, data: &[u8]) -> OpResult<u32> {
         debug!("write(fh={}, offset={}, len={})", fh, offset, data.len());
 
         if self.read_only {
-            reply.error(libc::EROFS);
-            return;
+            return Err(libc::EROFS);
         }
 
         let handles = self.open_files.read().unwrap();
-        let ofile = match handles.get(&fh) {
-            Some(f) => f,
-            None => {
-                warn!("write: fh {} not found", fh);
-                reply.error(libc::EBADF);
-                return;
-            }
-        };
+        let ofile = handles.get(&fh).ok_or_else(|| {
+            warn!("write: fh {} not found", fh);
+            libc::EBADF
+        })?;
 
         match self.backing.pwrite(ofile.fd, data, offset) {
-            Ok(n) => {
-                reply.written(n as u32);
-            }
+            Ok(n) => Ok(n as u32),
             Err(e) => {
                 error!("write: pwrite failed: {}", e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
@@ -780,141 +711,113 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
 
     fn create(
         &mut self,
-        _req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         mode: u32,
-        _umask: u32,
         flags: i32,
-        reply: ReplyCreate,
-    ) {
+    ) -> OpResult<(FileAttr, u64)> {
         debug!(
             "create(parent={}, name={:?}, mode=0o{:o}, flags=0x{:x})",
             parent, name, mode, flags
         );
 
         if self.read_only {
-            reply.error(libc::EROFS);
-            return;
+            return Err(libc::EROFS);
         }
 
-        let parent_rel = match self.get_rel_path(parent) {
-            Some(p) => p,
-            None => {
-                error!("create: parent inode {} not found", parent);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let parent_rel = self.get_rel_path(parent).ok_or_else(|| {
+            error!("create: parent inode {} not found", parent);
+            libc::ENOENT
+        })?;
 
         let child_rel = parent_rel.join(name);
         let open_flags = flags | libc::O_CREAT;
 
-        match self.backing.open_file(&child_rel, open_flags, mode) {
-            Ok(fd) => {
-                // Stat the newly created file to get its inode.
-                match self.backing.fstat(fd) {
-                    Ok(st) => {
-                        let ino = st.st_ino;
-                        self.register_inode(ino, child_rel.clone());
-
-                        let fh = self.alloc_fh();
-                        self.open_files.write().unwrap().insert(
-                            fh,
-                            OpenFile {
-                                fd,
-                                rel_path: child_rel,
-                                flags,
-                            },
-                        );
+        let fd = self
+            .backing
+            .open_file(&child_rel, open_flags, mode)
+            .map_err(|e| {
+                error!("create: open_file failed for {:?}: {}", child_rel, e);
+                e.raw_os_error().unwrap_or(libc::EIO)
+            })?;
 
-                        let attr = Self::stat_to_attr(&st, ino);
-                        debug!("create: ino={}, fh={}", ino, fh);
-                        reply.created(&TTL, &attr, 0, fh, 0);
-                    }
-                    Err(e) => {
-                        self.backing.close_fd(fd);
-                        error!("create: fstat after create failed: {}", e);
-                        reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                    }
-                }
+        // Stat the newly created file to get its inode.
+        match self.backing.fstat(fd) {
+            Ok(st) => {
+                let ino = st.st_ino;
+                self.register_inode(ino, child_rel.clone());
+
+                let fh = self.alloc_fh();
+                self.open_files.write().unwrap().insert(
+                    fh,
+                    OpenFile {
+                        fd,
+                        rel_path: child_rel,
+                        flags,
+                    },
+                );
+
+                debug!("create: ino={}, fh={}", ino, fh);
+                Ok((Self::stat_to_attr(&st, ino), fh))
             }
             Err(e) => {
-                error!("create: open_file failed for {:?}: {}", child_rel, e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                self.backing.close_fd(fd);
+                error!("create: fstat after create failed: {}", e);
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
 
     // -- mkdir --------------------------------------------------------------
 
-    fn mkdir(
-        &mut self,
-        _req: &Request<'_>,
-        parent: u64,
-        name: &OsStr,
-        mode: u32,
-        _umask: u32,
-        reply: ReplyEntry,
-    ) {
+    fn mkdir(&mut self, parent: u64, name: &OsStr, mode: u32) -> OpResult<FileAttr> {
         debug!(
             "mkdir(parent={}, name={:?}, mode=0o{:o})",
             parent, name, mode
         );
 
         if self.read_only {
-            reply.error(libc::EROFS);
-            return;
+            return Err(libc::EROFS);
         }
 
-        let parent_rel = match self.get_rel_path(parent) {
-            Some(p) => p,
-            None => {
-                error!("mkdir: parent inode {} not found", parent);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let parent_rel = self.get_rel_path(parent).ok_or_else(|| {
+            error!("mkdir: parent inode {} not found", parent);
+            libc::ENOENT
+        })?;
 
         let child_rel = parent_rel.join(name);
 
-        if let Err(e) = self.backing.mkdir(&child_rel, mode) {
+        self.backing.mkdir(&child_rel, mode).map_err(|e| {
             error!("mkdir: failed for {:?}: {}", child_rel, e);
-            reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-            return;
-        }
+            e.raw_os_error().unwrap_or(libc::EIO)
+        })?;
 
         match self.backing.stat(&child_rel) {
             Ok(st) => {
                 let ino = st.st_ino;
                 self.register_inode(ino, child_rel);
-                reply.entry(&TTL, &Self::stat_to_attr(&st, ino), 0);
+                Ok(Self::stat_to_attr(&st, ino))
             }
             Err(e) => {
                 error!("mkdir: stat after mkdir failed: {}", e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
 
     // -- unlink -------------------------------------------------------------
 
-    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+    fn unlink(&mut self, parent: u64, name: &OsStr) -> OpResult<()> {
         debug!("unlink(parent={}, name={:?})", parent, name);
 
         if self.read_only {
-            reply.error(libc::EROFS);
-            return;
+            return Err(libc::EROFS);
         }
 
-        let parent_rel = match self.get_rel_path(parent) {
-            Some(p) => p,
-            None => {
-                error!("unlink: parent inode {} not found", parent);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let parent_rel = self.get_rel_path(parent).ok_or_else(|| {
+            error!("unlink: parent inode {} not found", parent);
+            libc::ENOENT
+        })?;
 
         let child_rel = parent_rel.join(name);
 
@@ -926,33 +829,28 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                 if let Some(ino) = maybe_ino {
                     self.inode_to_path.write().unwrap().remove(&ino);
                 }
-                reply.ok();
+                Ok(())
             }
             Err(e) => {
                 error!("unlink: failed for {:?}: {}", child_rel, e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
 
     // -- rmdir --------------------------------------------------------------
 
-    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+    fn rmdir(&mut self, parent: u64, name: &OsStr) -> OpResult<()> {
         debug!("rmdir(parent={}, name={:?})", parent, name);
 
         if self.read_only {
-            reply.error(libc::EROFS);
-            return;
+            return Err(libc::EROFS);
         }
 
-        let parent_rel = match self.get_rel_path(parent) {
-            Some(p) => p,
-            None => {
-                error!("rmdir: parent inode {} not found", parent);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let parent_rel = self.get_rel_path(parent).ok_or_else(|| {
+            error!("rmdir: parent inode {} not found", parent);
+            libc::ENOENT
+        })?;
 
         let child_rel = parent_rel.join(name);
 
@@ -964,11 +862,11 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                 if let Some(ino) = maybe_ino {
                     self.inode_to_path.write().unwrap().remove(&ino);
                 }
-                reply.ok();
+                Ok(())
             }
             Err(e) => {
                 error!("rmdir: failed for {:?}: {}", child_rel, e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
@@ -977,40 +875,28 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
 
     fn rename(
         &mut self,
-        _req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         newparent: u64,
         newname: &OsStr,
-        _flags: u32,
-        reply: ReplyEmpty,
-    ) {
+    ) -> OpResult<()> {
         debug!(
             "rename(parent={}, name={:?}, newparent={}, newname={:?})",
             parent, name, newparent, newname
         );
 
         if self.read_only {
-            reply.error(libc::EROFS);
-            return;
+            return Err(libc::EROFS);
         }
 
-        let old_parent_rel = match self.get_rel_path(parent) {
-            Some(p) => p,
-            None => {
-                error!("rename: old parent inode {} not found", parent);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
-        let new_parent_rel = match self.get_rel_path(newparent) {
-            Some(p) => p,
-            None => {
-                error!("rename: new parent inode {} not found", newparent);
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let old_parent_rel = self.get_rel_path(parent).ok_or_else(|| {
+            error!("rename: old parent inode {} not found", parent);
+            libc::ENOENT
+        })?;
+        let new_parent_rel = self.get_rel_path(newparent).ok_or_else(|| {
+            error!("rename: new parent inode {} not found", newparent);
+            libc::ENOENT
+        })?;
 
         let old_rel = old_parent_rel.join(name);
         let new_rel = new_parent_rel.join(newname);
@@ -1023,25 +909,71 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                 if let Some(ino) = maybe_ino {
                     self.register_inode(ino, new_rel);
                 }
-                reply.ok();
+                Ok(())
             }
             Err(e) => {
                 error!("rename: failed {:?} -> {:?}: {}", old_rel, new_rel, e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
+            }
+        }
+    }
+
+    // -- symlink ------------------------------------------------------------
+
+    fn symlink(&mut self, parent: u64, name: &OsStr, target: &Path) -> OpResult<FileAttr> {
+        debug!(
+            "symlink(parent={}, name={:?}, target={:?})",
+            parent, name, target
+        );
+
+        if self.read_only {
+            return Err(libc::EROFS);
+        }
+
+        let parent_rel = self.get_rel_path(parent).ok_or_else(|| {
+            error!("symlink: parent inode {} not found", parent);
+            libc::ENOENT
+        })?;
+
+        let child_rel = parent_rel.join(name);
+
+        self.backing.symlink(target, &child_rel).map_err(|e| {
+            error!("symlink: failed for {:?}: {}", child_rel, e);
+            e.raw_os_error().unwrap_or(libc::EIO)
+        })?;
+
+        match self.backing.stat(&child_rel) {
+            Ok(st) => {
+                let ino = st.st_ino;
+                self.register_inode(ino, child_rel);
+                Ok(Self::stat_to_attr(&st, ino))
+            }
+            Err(e) => {
+                error!("symlink: stat after symlink failed: {}", e);
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
+            }
+        }
+    }
+
+    // -- readlink -----------------------------------------------------------
+
+    fn readlink(&mut self, ino: u64) -> OpResult<Vec<u8>> {
+        debug!("readlink(ino={})", ino);
+
+        let rel = self.get_rel_path(ino).ok_or(libc::ENOENT)?;
+
+        match self.backing.readlink(&rel) {
+            Ok(target) => Ok(target.into_os_string().into_vec()),
+            Err(e) => {
+                debug!("readlink: failed for {:?}: {}", rel, e);
+                Err(e.raw_os_error().unwrap_or(libc::EIO))
             }
         }
     }
 
     // -- flush --------------------------------------------------------------
 
-    fn flush(
-        &mut self,
-        _req: &Request<'_>,
-        _ino: u64,
-        fh: u64,
-        _lock_owner: u64,
-        reply: ReplyEmpty,
-    ) {
+    fn flush(&mut self, _ino: u64, fh: u64) -> OpResult<()> {
         debug!("flush(fh={})", fh);
 
         let handles = self.open_files.read().unwrap();
@@ -1050,131 +982,88 @@ impl<B: BackingFs> Filesystem for FdPassthroughFS<B> {
                 // EBADF can happen if the fd was already closed, treat as OK.
                 if e.raw_os_error() != Some(libc::EBADF) {
                     error!("flush: fsync failed: {}", e);
-                    reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                    return;
+                    return Err(e.raw_os_error().unwrap_or(libc::EIO));
                 }
             }
         }
-        reply.ok();
+        Ok(())
     }
 
     // -- release ------------------------------------------------------------
 
-    fn release(
-        &mut self,
-        _req: &Request<'_>,
-        _ino: u64,
-        fh: u64,
-        _flags: i32,
-        _lock_owner: Option<u64>,
-        _flush: bool,
-        reply: ReplyEmpty,
-    ) {
+    fn release(&mut self, _ino: u64, fh: u64) -> OpResult<()> {
         debug!("release(fh={})", fh);
 
         if let Some(ofile) = self.open_files.write().unwrap().remove(&fh) {
             self.backing.close_fd(ofile.fd);
         }
-        reply.ok();
+        Ok(())
     }
 
     // -- fsync --------------------------------------------------------------
 
-    fn fsync(
-        &mut self,
-        _req: &Request<'_>,
-        _ino: u64,
-        fh: u64,
-        _datasync: bool,
-        reply: ReplyEmpty,
-    ) {
+    fn fsync(&mut self, _ino: u64, fh: u64, _datasync: bool) -> OpResult<()> {
         debug!("fsync(fh={})", fh);
 
         let handles = self.open_files.read().unwrap();
-        match handles.get(&fh) {
-            Some(ofile) => match self.backing.fsync(ofile.fd) {
-                Ok(()) => reply.ok(),
-                Err(e) => {
-                    error!("fsync: failed: {}", e);
-                    reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                }
-            },
-            None => {
-                warn!("fsync: fh {} not found", fh);
-                reply.error(libc::EBADF);
-            }
-        }
+        let ofile = handles.get(&fh).ok_or_else(|| {
+            warn!("fsync: fh {} not found", fh);
+            libc::EBADF
+        })?;
+
+        self.backing.fsync(ofile.fd).map_err(|e| {
+            error!("fsync: failed: {}", e);
+            e.raw_os_error().unwrap_or(libc::EIO)
+        })
     }
 
     // -- access -------------------------------------------------------------
 
-    fn access(&mut self, _req: &Request<'_>, ino: u64, mask: i32, reply: ReplyEmpty) {
+    fn access(&mut self, ino: u64, mask: i32) -> OpResult<()> {
         debug!("access(ino={}, mask=0x{:x})", ino, mask);
 
-        let rel = match self.get_rel_path(ino) {
-            Some(p) => p,
-            None => {
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
+        let rel = self.get_rel_path(ino).ok_or(libc::ENOENT)?;
 
-        match self.backing.access(&rel, mask) {
-            Ok(()) => reply.ok(),
-            Err(e) => reply.error(e.raw_os_error().unwrap_or(libc::EACCES)),
-        }
+        self.backing
+            .access(&rel, mask)
+            .map_err(|e| e.raw_os_error().unwrap_or(libc::EACCES))
     }
 
     // -- statfs -------------------------------------------------------------
 
-    fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: ReplyStatfs) {
+    fn statfs(&mut self, _ino: u64) -> OpResult<libc::statvfs> {
         debug!("statfs");
 
-        match self.backing.statvfs() {
-            Ok(stfs) => {
-                reply.statfs(
-                    stfs.f_blocks,
-                    stfs.f_bfree,
-                    stfs.f_bavail,
-                    stfs.f_files,
-                    stfs.f_ffree,
-                    stfs.f_bsize as u32,
-                    stfs.f_namemax as u32,
-                    stfs.f_frsize as u32,
-                );
-            }
-            Err(e) => {
-                error!("statfs: failed: {}", e);
-                reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-            }
-        }
+        self.backing.statvfs().map_err(|e| {
+            error!("statfs: failed: {}", e);
+            e.raw_os_error().unwrap_or(libc::EIO)
+        })
     }
 
     // -- opendir / releasedir -----------------------------------------------
 
-    fn opendir(&mut self, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+    fn opendir(&mut self, ino: u64, _flags: i32) -> OpResult<u64> {
         debug!("opendir(ino={})", ino);
         // Just verify the inode is known.
         if ino == FUSE_ROOT_ID || self.get_rel_path(ino).is_some() {
-            reply.opened(0, 0);
+            Ok(0)
         } else {
-            reply.error(libc::ENOENT);
+            Err(libc::ENOENT)
         }
     }
 
-    fn releasedir(
-        &mut self,
-        _req: &Request<'_>,
-        _ino: u64,
-        _fh: u64,
-        _flags: i32,
-        reply: ReplyEmpty,
-    ) {
+    fn releasedir(&mut self, _ino: u64, _fh: u64) -> OpResult<()> {
         debug!("releasedir");
-        reply.ok();
+        Ok(())
     }
 }
 
+// ---------------------------------------------------------------------------
+// Filesystem implementation
+// ---------------------------------------------------------------------------
+
+impl_filesystem_via_fs_ops!(impl<B: BackingFs> for FdPassthroughFS<B>);
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -1357,4 +1246,18 @@ mod tests {
         assert!(fs.is_vcs_path(Path::new(".jj")));
         assert!(fs.is_vcs_path(Path::new(".jj/repo")));
     }
+
+    #[test]
+    fn vcs_detection_over_mem_backing() {
+        use crate::backing_fs::MemBackingFs;
+
+        let backing = MemBackingFs::new();
+        backing.mkdir(Path::new(".git"), 0o755).unwrap();
+        backing.mkdir(Path::new("src"), 0o755).unwrap();
+
+        let fs = FdPassthroughFS::new(backing, PathBuf::from("/nonexistent/mnt"));
+        assert_eq!(fs.detected_vcs(), vec![".git".to_string()]);
+        assert!(fs.is_vcs_path(Path::new(".git/HEAD")));
+        assert!(!fs.is_vcs_path(Path::new("src")));
+    }
 }
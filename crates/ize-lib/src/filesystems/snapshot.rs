@@ -0,0 +1,466 @@
+//! Read-only in-memory filesystem for historical views.
+//!
+//! [`SnapshotFS`] serves a fixed tree of files and directories built up
+//! front with [`SnapshotFS::add_file`] / [`SnapshotFS::add_dir`].  It backs
+//! `ize mount --at`, which fills it from a Pijul snapshot, but knows nothing
+//! about Pijul itself: every mutating operation answers `EROFS` and nothing
+//! touches disk, so it can be mounted anywhere alongside the live mount.
+
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fuser::{FileAttr, FileType, MountOption};
+use log::debug;
+
+use super::ops::{impl_filesystem_via_fs_ops, FsOps, OpResult, ReaddirEntry, SetAttr};
+
+// ---------------------------------------------------------------------------
+// Constants
+// ---------------------------------------------------------------------------
+
+/// FUSE always uses inode 1 for the root directory.
+const FUSE_ROOT_ID: u64 = 1;
+
+/// Permission bits for files; nothing in a snapshot is writable.
+const FILE_PERM: u16 = 0o444;
+
+/// Permission bits for directories.
+const DIR_PERM: u16 = 0o555;
+
+/// Block size reported by `getattr` and `statfs`.
+const BLOCK_SIZE: u32 = 4096;
+
+// ---------------------------------------------------------------------------
+// Node
+// ---------------------------------------------------------------------------
+
+enum NodeKind {
+    File(Vec<u8>),
+    Dir(BTreeMap<OsString, u64>),
+}
+
+struct Node {
+    parent: u64,
+    kind: NodeKind,
+}
+
+// ---------------------------------------------------------------------------
+// SnapshotFS
+// ---------------------------------------------------------------------------
+
+/// A read-only filesystem over a tree held in memory.
+///
+/// Inode `n` is stored at index `n - 1`, so the root is always inode 1.
+pub struct SnapshotFS {
+    nodes: Vec<Node>,
+    mount_point: PathBuf,
+    mtime: SystemTime,
+    uid: u32,
+    gid: u32,
+}
+
+impl SnapshotFS {
+    /// Create an empty snapshot filesystem to be mounted at `mount_point`.
+    pub fn new(mount_point: PathBuf) -> Self {
+        // SAFETY: getuid/getgid have no preconditions and cannot fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            nodes: vec![Node {
+                parent: FUSE_ROOT_ID,
+                kind: NodeKind::Dir(BTreeMap::new()),
+            }],
+            mount_point,
+            mtime: UNIX_EPOCH,
+            uid,
+            gid,
+        }
+    }
+
+    /// Report `mtime` as the modification time of every entry.
+    pub fn with_mtime(mut self, mtime: SystemTime) -> Self {
+        self.set_mtime(mtime);
+        self
+    }
+
+    /// Set the modification time reported for every entry.
+    pub fn set_mtime(&mut self, mtime: SystemTime) {
+        self.mtime = mtime;
+    }
+
+    /// Add a file at the `/`-separated relative `path`, creating any missing
+    /// parent directories.  An existing file at that path is replaced.
+    pub fn add_file(&mut self, path: &str, content: Vec<u8>) {
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((dir, name)) => (self.ensure_dir(dir), name),
+            None => (FUSE_ROOT_ID, path),
+        };
+        match self.child(parent, OsStr::new(name)) {
+            Some(ino) => self.nodes[(ino - 1) as usize].kind = NodeKind::File(content),
+            None => {
+                self.insert(parent, name, NodeKind::File(content));
+            }
+        }
+    }
+
+    /// Add a directory (and its parents) at the `/`-separated relative `path`.
+    pub fn add_dir(&mut self, path: &str) {
+        self.ensure_dir(path);
+    }
+
+    /// Get the mount point.
+    pub fn mount_point(&self) -> &Path {
+        &self.mount_point
+    }
+
+    /// Mount the filesystem read-only; blocks until it is unmounted.
+    pub fn mount(self) -> std::io::Result<()> {
+        let options = vec![
+            MountOption::FSName("ize-at".to_string()),
+            MountOption::AutoUnmount,
+            MountOption::AllowOther,
+            MountOption::RO,
+        ];
+        let mount_point = self.mount_point.clone();
+        fuser::mount2(self, mount_point, &options)?;
+        Ok(())
+    }
+
+    // -- Internal helpers ---------------------------------------------------
+
+    fn node(&self, ino: u64) -> OpResult<&Node> {
+        ino.checked_sub(1)
+            .and_then(|i| self.nodes.get(i as usize))
+            .ok_or(libc::ENOENT)
+    }
+
+    fn child(&self, parent: u64, name: &OsStr) -> Option<u64> {
+        match &self.node(parent).ok()?.kind {
+            NodeKind::Dir(children) => children.get(name).copied(),
+            NodeKind::File(_) => None,
+        }
+    }
+
+    fn insert(&mut self, parent: u64, name: &str, kind: NodeKind) -> u64 {
+        self.nodes.push(Node { parent, kind });
+        let ino = self.nodes.len() as u64;
+        if let NodeKind::Dir(children) = &mut self.nodes[(parent - 1) as usize].kind {
+            children.insert(OsString::from(name), ino);
+        }
+        ino
+    }
+
+    /// Walk `path` from the root, creating directories as needed.
+    fn ensure_dir(&mut self, path: &str) -> u64 {
+        let mut ino = FUSE_ROOT_ID;
+        for component in Path::new(path).components() {
+            let Component::Normal(name) = component else {
+                continue;
+            };
+            ino = match self.child(ino, name) {
+                Some(child) => child,
+                None => self.insert(ino, &name.to_string_lossy(), NodeKind::Dir(BTreeMap::new())),
+            };
+        }
+        ino
+    }
+
+    fn attr(&self, ino: u64) -> OpResult<FileAttr> {
+        let node = self.node(ino)?;
+        let (kind, perm, size, nlink) = match &node.kind {
+            NodeKind::File(content) => (FileType::RegularFile, FILE_PERM, content.len() as u64, 1),
+            NodeKind::Dir(children) => {
+                (FileType::Directory, DIR_PERM, 0, 2 + children.len() as u32)
+            }
+        };
+        Ok(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: self.mtime,
+            mtime: self.mtime,
+            ctime: self.mtime,
+            crtime: self.mtime,
+            kind,
+            perm,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// FsOps implementation
+// ---------------------------------------------------------------------------
+
+impl FsOps for SnapshotFS {
+    fn init(&mut self) -> OpResult<()> {
+        debug!(
+            "SnapshotFS::init: {} entries at {:?}",
+            self.nodes.len(),
+            self.mount_point
+        );
+        Ok(())
+    }
+
+    fn lookup(&mut self, parent: u64, name: &OsStr) -> OpResult<FileAttr> {
+        match &self.node(parent)?.kind {
+            NodeKind::Dir(children) => {
+                let ino = children.get(name).copied().ok_or(libc::ENOENT)?;
+                self.attr(ino)
+            }
+            NodeKind::File(_) => Err(libc::ENOTDIR),
+        }
+    }
+
+    fn getattr(&mut self, ino: u64, _fh: Option<u64>) -> OpResult<FileAttr> {
+        self.attr(ino)
+    }
+
+    fn setattr(&mut self, _ino: u64, _attr: &SetAttr) -> OpResult<FileAttr> {
+        Err(libc::EROFS)
+    }
+
+    fn readdir(&mut self, ino: u64, _fh: u64, offset: i64) -> OpResult<Vec<ReaddirEntry>> {
+        let node = self.node(ino)?;
+        let NodeKind::Dir(children) = &node.kind else {
+            return Err(libc::ENOTDIR);
+        };
+
+        let mut entries = vec![
+            (ino, FileType::Directory, OsString::from(".")),
+            (node.parent, FileType::Directory, OsString::from("..")),
+        ];
+        for (name, &child) in children {
+            let kind = match self.node(child)?.kind {
+                NodeKind::File(_) => FileType::RegularFile,
+                NodeKind::Dir(_) => FileType::Directory,
+            };
+            entries.push((child, kind, name.clone()));
+        }
+
+        Ok(entries
+            .into_iter()
+            .enumerate()
+            .skip(offset as usize)
+            .map(|(i, (ino, kind, name))| ReaddirEntry {
+                ino,
+                offset: (i + 1) as i64,
+                kind,
+                name,
+            })
+            .collect())
+    }
+
+    fn open(&mut self, ino: u64, flags: i32) -> OpResult<u64> {
+        if flags & (libc::O_WRONLY | libc::O_RDWR | libc::O_APPEND | libc::O_TRUNC) != 0 {
+            return Err(libc::EROFS);
+        }
+        match self.node(ino)?.kind {
+            NodeKind::File(_) => Ok(0),
+            NodeKind::Dir(_) => Err(libc::EISDIR),
+        }
+    }
+
+    fn read(&mut self, ino: u64, _fh: u64, offset: i64, size: u32) -> OpResult<Vec<u8>> {
+        let NodeKind::File(content) = &self.node(ino)?.kind else {
+            return Err(libc::EISDIR);
+        };
+        let start = (offset.max(0) as usize).min(content.len());
+        let end = start.saturating_add(size as usize).min(content.len());
+        Ok(content[start..end].to_vec())
+    }
+
+    fn write(&mut self, _ino: u64, _fh: u64, _offset: i64, _data: &[u8]) -> OpResult<u32> {
+        Err(libc::EROFS)
+    }
+
+    fn create(
+        &mut self,
+        _parent: u64,
+        _name: &OsStr,
+        _mode: u32,
+        _flags: i32,
+    ) -> OpResult<(FileAttr, u64)> {
+        Err(libc::EROFS)
+    }
+
+    fn mkdir(&mut self, _parent: u64, _name: &OsStr, _mode: u32) -> OpResult<FileAttr> {
+        Err(libc::EROFS)
+    }
+
+    fn unlink(&mut self, _parent: u64, _name: &OsStr) -> OpResult<()> {
+        Err(libc::EROFS)
+    }
+
+    fn rmdir(&mut self, _parent: u64, _name: &OsStr) -> OpResult<()> {
+        Err(libc::EROFS)
+    }
+
+    fn rename(
+        &mut self,
+        _parent: u64,
+        _name: &OsStr,
+        _newparent: u64,
+        _newname: &OsStr,
+    ) -> OpResult<()> {
+        Err(libc::EROFS)
+    }
+
+    fn symlink(&mut self, _parent: u64, _name: &OsStr, _target: &Path) -> OpResult<FileAttr> {
+        Err(libc::EROFS)
+    }
+
+    fn flush(&mut self, _ino: u64, _fh: u64) -> OpResult<()> {
+        Ok(())
+    }
+
+    fn release(&mut self, _ino: u64, _fh: u64) -> OpResult<()> {
+        Ok(())
+    }
+
+    fn access(&mut self, ino: u64, mask: i32) -> OpResult<()> {
+        self.node(ino)?;
+        if mask & libc::W_OK != 0 {
+            return Err(libc::EROFS);
+        }
+        Ok(())
+    }
+
+    fn statfs(&mut self, _ino: u64) -> OpResult<libc::statvfs> {
+        let bytes: u64 = self
+            .nodes
+            .iter()
+            .map(|node| match &node.kind {
+                NodeKind::File(content) => content.len() as u64,
+                NodeKind::Dir(_) => 0,
+            })
+            .sum();
+
+        // SAFETY: statvfs is a plain C struct for which all-zero is valid.
+        let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+        st.f_bsize = BLOCK_SIZE as _;
+        st.f_frsize = BLOCK_SIZE as _;
+        st.f_blocks = bytes.div_ceil(BLOCK_SIZE as u64) as _;
+        st.f_files = self.nodes.len() as _;
+        st.f_namemax = 255;
+        st.f_flag = libc::ST_RDONLY as _;
+        Ok(st)
+    }
+
+    fn opendir(&mut self, ino: u64, _flags: i32) -> OpResult<u64> {
+        match self.node(ino)?.kind {
+            NodeKind::Dir(_) => Ok(0),
+            NodeKind::File(_) => Err(libc::ENOTDIR),
+        }
+    }
+
+    fn releasedir(&mut self, _ino: u64, _fh: u64) -> OpResult<()> {
+        Ok(())
+    }
+}
+
+impl_filesystem_via_fs_ops!(impl<> for SnapshotFS);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SnapshotFS {
+        let mut fs = SnapshotFS::new(PathBuf::from("/snapshot"));
+        fs.add_file("README.md", b"# readme\n".to_vec());
+        fs.add_file("src/lib.rs", b"pub fn f() {}\n".to_vec());
+        fs.add_dir("empty");
+        fs
+    }
+
+    fn names(entries: &[ReaddirEntry]) -> Vec<&OsStr> {
+        entries.iter().map(|e| e.name.as_os_str()).collect()
+    }
+
+    #[test]
+    fn lookup_and_read() {
+        let mut fs = sample();
+        let src = fs.lookup(FUSE_ROOT_ID, OsStr::new("src")).unwrap();
+        assert_eq!(src.kind, FileType::Directory);
+        assert_eq!(src.perm, DIR_PERM);
+
+        let lib = fs.lookup(src.ino, OsStr::new("lib.rs")).unwrap();
+        assert_eq!(lib.kind, FileType::RegularFile);
+        assert_eq!(lib.size, 14);
+        assert_eq!(lib.perm, FILE_PERM);
+
+        let fh = fs.open(lib.ino, libc::O_RDONLY).unwrap();
+        assert_eq!(fs.read(lib.ino, fh, 4, 100).unwrap(), b"fn f() {}\n");
+        assert_eq!(fs.read(lib.ino, fh, 100, 10).unwrap(), b"");
+
+        assert_eq!(
+            fs.lookup(FUSE_ROOT_ID, OsStr::new("missing")),
+            Err(libc::ENOENT)
+        );
+        assert_eq!(fs.lookup(lib.ino, OsStr::new("x")), Err(libc::ENOTDIR));
+    }
+
+    #[test]
+    fn readdir_lists_sorted_entries_with_offsets() {
+        let mut fs = sample();
+        let entries = fs.readdir(FUSE_ROOT_ID, 0, 0).unwrap();
+        assert_eq!(
+            names(&entries),
+            vec![".", "..", "README.md", "empty", "src"]
+        );
+
+        let rest = fs.readdir(FUSE_ROOT_ID, 0, entries[2].offset).unwrap();
+        assert_eq!(names(&rest), vec!["empty", "src"]);
+    }
+
+    #[test]
+    fn add_file_replaces_existing_content() {
+        let mut fs = sample();
+        fs.add_file("README.md", b"new".to_vec());
+        let attr = fs.lookup(FUSE_ROOT_ID, OsStr::new("README.md")).unwrap();
+        assert_eq!(fs.read(attr.ino, 0, 0, 10).unwrap(), b"new");
+        assert_eq!(fs.readdir(FUSE_ROOT_ID, 0, 0).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn mutations_are_rejected() {
+        let mut fs = sample();
+        let readme = fs.lookup(FUSE_ROOT_ID, OsStr::new("README.md")).unwrap();
+
+        assert_eq!(fs.open(readme.ino, libc::O_RDWR), Err(libc::EROFS));
+        assert_eq!(fs.write(readme.ino, 0, 0, b"x"), Err(libc::EROFS));
+        assert_eq!(
+            fs.create(FUSE_ROOT_ID, OsStr::new("new"), 0o644, 0)
+                .map(|_| ()),
+            Err(libc::EROFS)
+        );
+        assert_eq!(
+            fs.mkdir(FUSE_ROOT_ID, OsStr::new("d"), 0o755).map(|_| ()),
+            Err(libc::EROFS)
+        );
+        assert_eq!(
+            fs.unlink(FUSE_ROOT_ID, OsStr::new("README.md")),
+            Err(libc::EROFS)
+        );
+        assert_eq!(
+            fs.setattr(readme.ino, &SetAttr::default()).map(|_| ()),
+            Err(libc::EROFS)
+        );
+        assert_eq!(fs.access(readme.ino, libc::W_OK), Err(libc::EROFS));
+        assert_eq!(fs.access(readme.ino, libc::R_OK), Ok(()));
+    }
+
+    #[test]
+    fn attributes_use_snapshot_mtime() {
+        let when = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let mut fs = sample().with_mtime(when);
+        assert_eq!(fs.getattr(FUSE_ROOT_ID, None).unwrap().mtime, when);
+        assert_eq!(fs.getattr(99, None), Err(libc::ENOENT));
+    }
+}
@@ -0,0 +1,489 @@
+//! Who changes are recorded as
+//!
+//! An identity is a name, an email and optionally an ed25519 key. It comes
+//! from the global `identity.toml` in ize's config directory
+//! (`~/.config/ize/identity.toml` on Linux), overridden field by field by the
+//! `[identity]` section of a project's `meta/project.toml`:
+//!
+//! ```toml
+//! name = "Ada Lovelace"
+//! email = "ada@example.com"
+//! key = "ed25519.key"   # secret key file, relative to the file naming it
+//! sign = true
+//! ```
+//!
+//! Recorded changes list the identity as their author, public key included.
+//! With `sign = true`, each change's hash is also signed and the signature
+//! kept in the change's unhashed section, where Pijul keeps its own.
+//! Verifying checks it against the key in the change's author entry.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Directory under the platform config directory holding ize's settings
+pub const CONFIG_DIR: &str = "ize";
+
+/// Global identity file, inside [`CONFIG_DIR`]
+pub const IDENTITY_FILE: &str = "identity.toml";
+
+/// Default name of a generated secret key file, next to [`IDENTITY_FILE`]
+pub const KEY_FILE: &str = "ed25519.key";
+
+/// Keys of a change author entry
+pub const AUTHOR_NAME: &str = "name";
+pub const AUTHOR_EMAIL: &str = "email";
+pub const AUTHOR_KEY: &str = "key";
+
+/// Key of the signature in a change's unhashed section
+pub const SIGNATURE_FIELD: &str = "signature";
+
+#[derive(Error, Debug)]
+pub enum IdentityError {
+    #[error("IO error on {0}: {1}")]
+    Io(PathBuf, #[source] std::io::Error),
+
+    #[error("Invalid identity settings in {0}: {1}")]
+    Parse(PathBuf, String),
+
+    #[error("Invalid ed25519 key in {0}")]
+    InvalidKey(PathBuf),
+
+    #[error("Key file already exists: {0}")]
+    KeyExists(PathBuf),
+
+    #[error("Signing is enabled but no key is configured\nCreate one with: ize identity set --generate-key")]
+    NoKey,
+
+    #[error("No config directory on this platform")]
+    NoConfigDir,
+}
+
+/// Identity settings as written in `identity.toml` or `[identity]`
+///
+/// Every field is optional so a project can override just some of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentityConfig {
+    /// Author name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Author email
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Secret ed25519 key file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    /// Sign every recorded change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sign: Option<bool>,
+}
+
+impl IdentityConfig {
+    /// Path of the global identity file, if the platform has a config
+    /// directory
+    pub fn global_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(IDENTITY_FILE))
+    }
+
+    /// The global identity settings; empty if there are none
+    pub fn load_global() -> Result<Self, IdentityError> {
+        match Self::global_path() {
+            Some(path) => Self::load(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Read identity settings from a file; empty if the file doesn't exist
+    ///
+    /// A relative `key` is taken relative to the file's directory.
+    pub fn load(path: &Path) -> Result<Self, IdentityError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(IdentityError::Io(path.to_path_buf(), e)),
+        };
+        let config: Self = toml::from_str(&content)
+            .map_err(|e| IdentityError::Parse(path.to_path_buf(), e.to_string()))?;
+        Ok(match path.parent() {
+            Some(dir) => config.relative_to(dir),
+            None => config,
+        })
+    }
+
+    /// Write the settings to a file, creating its directory
+    pub fn save(&self, path: &Path) -> Result<(), IdentityError> {
+        let io = |e| IdentityError::Io(path.to_path_buf(), e);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io)?;
+        }
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| IdentityError::Parse(path.to_path_buf(), e.to_string()))?;
+        std::fs::write(path, content).map_err(io)
+    }
+
+    /// Resolve a relative `key` against `dir`
+    pub fn relative_to(mut self, dir: &Path) -> Self {
+        if let Some(key) = self.key.as_mut() {
+            if key.is_relative() {
+                *key = dir.join(&*key);
+            }
+        }
+        self
+    }
+
+    /// These settings with every field `other` sets replaced
+    pub fn overridden_by(&self, other: &IdentityConfig) -> IdentityConfig {
+        IdentityConfig {
+            name: other.name.clone().or_else(|| self.name.clone()),
+            email: other.email.clone().or_else(|| self.email.clone()),
+            key: other.key.clone().or_else(|| self.key.clone()),
+            sign: other.sign.or(self.sign),
+        }
+    }
+
+    /// Whether no field is set
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Load the key, if any, and build the identity
+    pub fn resolve(&self) -> Result<Identity, IdentityError> {
+        let key = self.key.as_deref().map(load_key).transpose()?;
+        let sign = self.sign.unwrap_or(false);
+        if sign && key.is_none() {
+            return Err(IdentityError::NoKey);
+        }
+        Ok(Identity {
+            name: self.name.clone(),
+            email: self.email.clone(),
+            key,
+            sign,
+        })
+    }
+}
+
+/// A resolved identity, ready to author and sign changes
+#[derive(Clone, Default)]
+pub struct Identity {
+    /// Author name
+    pub name: Option<String>,
+    /// Author email
+    pub email: Option<String>,
+    key: Option<SigningKey>,
+    sign: bool,
+}
+
+impl Identity {
+    /// An identity with just a name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Set the email
+    pub fn with_email(mut self, email: impl Into<String>) -> Self {
+        self.email = Some(email.into());
+        self
+    }
+
+    /// Set the key, and whether changes get signed with it
+    pub fn with_key(mut self, key: SigningKey, sign: bool) -> Self {
+        self.key = Some(key);
+        self.sign = sign;
+        self
+    }
+
+    /// Public key, hex-encoded, if there is a key
+    pub fn public_key(&self) -> Option<String> {
+        self.key
+            .as_ref()
+            .map(|key| to_hex(key.verifying_key().as_bytes()))
+    }
+
+    /// Whether recorded changes get signed
+    pub fn signs(&self) -> bool {
+        self.sign && self.key.is_some()
+    }
+
+    /// The author entry of changes recorded as this identity; `None` for
+    /// an empty identity
+    pub fn author(&self) -> Option<BTreeMap<String, String>> {
+        let mut author = BTreeMap::new();
+        if let Some(name) = &self.name {
+            author.insert(AUTHOR_NAME.to_string(), name.clone());
+        }
+        if let Some(email) = &self.email {
+            author.insert(AUTHOR_EMAIL.to_string(), email.clone());
+        }
+        if let Some(key) = self.public_key() {
+            author.insert(AUTHOR_KEY.to_string(), key);
+        }
+        (!author.is_empty()).then_some(author)
+    }
+
+    /// Sign `message`, hex-encoding the signature; `None` without a key
+    pub fn sign(&self, message: &[u8]) -> Option<String> {
+        self.key
+            .as_ref()
+            .map(|key| to_hex(&key.sign(message).to_bytes()))
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.name, &self.email) {
+            (Some(name), Some(email)) => write!(f, "{} <{}>", name, email),
+            (Some(name), None) => f.write_str(name),
+            (None, Some(email)) => write!(f, "<{}>", email),
+            (None, None) => f.write_str("(anonymous)"),
+        }
+    }
+}
+
+// Keeps the secret key out of logs
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Identity")
+            .field("name", &self.name)
+            .field("email", &self.email)
+            .field("public_key", &self.public_key())
+            .field("sign", &self.sign)
+            .finish()
+    }
+}
+
+/// Whether a change's signature checks out
+///
+/// Serializes as `{"status": "valid", "key": "..."}` and the like.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum SignatureStatus {
+    /// Not looked at (listings only read change headers)
+    Unchecked,
+    /// The change carries no signature
+    Unsigned,
+    /// Signed by the author's key
+    Valid {
+        /// Hex-encoded public key
+        key: String,
+    },
+    /// A signature that doesn't match the author's key, or no key to check
+    /// it against
+    Invalid,
+}
+
+impl SignatureStatus {
+    /// Check `signature` over `message` against the author entries' keys
+    pub fn check<'a>(
+        authors: impl IntoIterator<Item = &'a BTreeMap<String, String>>,
+        message: &[u8],
+        signature: Option<&str>,
+    ) -> Self {
+        let Some(signature) = signature else {
+            return SignatureStatus::Unsigned;
+        };
+        authors
+            .into_iter()
+            .filter_map(|author| author.get(AUTHOR_KEY))
+            .find(|key| verify(key, message, signature))
+            .map_or(SignatureStatus::Invalid, |key| SignatureStatus::Valid {
+                key: key.clone(),
+            })
+    }
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureStatus::Unchecked => f.write_str("unchecked"),
+            SignatureStatus::Unsigned => f.write_str("unsigned"),
+            SignatureStatus::Valid { key } => {
+                write!(f, "good signature by {}", &key[..key.len().min(16)])
+            }
+            SignatureStatus::Invalid => f.write_str("BAD signature"),
+        }
+    }
+}
+
+/// Generate a secret key and write it to `path`, readable by the owner only
+///
+/// Refuses to overwrite an existing file.
+pub fn generate_key(path: &Path) -> Result<SigningKey, IdentityError> {
+    let io = |e| IdentityError::Io(path.to_path_buf(), e);
+    if path.exists() {
+        return Err(IdentityError::KeyExists(path.to_path_buf()));
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(io)?;
+    }
+
+    let mut seed = [0u8; 32];
+    getrandom::getrandom(&mut seed)
+        .map_err(|e| IdentityError::Io(path.to_path_buf(), std::io::Error::other(e)))?;
+    let key = SigningKey::from_bytes(&seed);
+
+    let mut file = std::fs::OpenOptions::new();
+    file.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut file, 0o600);
+    let mut file = file.open(path).map_err(io)?;
+    writeln!(file, "{}", to_hex(&seed)).map_err(io)?;
+    Ok(key)
+}
+
+/// Read a secret key written by [`generate_key`]
+pub fn load_key(path: &Path) -> Result<SigningKey, IdentityError> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| IdentityError::Io(path.to_path_buf(), e))?;
+    from_hex(content.trim())
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .map(|seed| SigningKey::from_bytes(&seed))
+        .ok_or_else(|| IdentityError::InvalidKey(path.to_path_buf()))
+}
+
+/// Check a hex-encoded signature over `message` against a hex-encoded
+/// public key
+pub fn verify(public_key: &str, message: &[u8], signature: &str) -> bool {
+    let key = from_hex(public_key)
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok());
+    let signature = from_hex(signature)
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        .map(|bytes| Signature::from_bytes(&bytes));
+    match (key, signature) {
+        (Some(key), Some(signature)) => key.verify(message, &signature).is_ok(),
+        _ => false,
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_project_settings_override_global() {
+        let global = IdentityConfig {
+            name: Some("Ada".to_string()),
+            email: Some("ada@example.com".to_string()),
+            ..Default::default()
+        };
+        let project = IdentityConfig {
+            email: Some("ada@work.example".to_string()),
+            sign: Some(false),
+            ..Default::default()
+        };
+        let merged = global.overridden_by(&project);
+        assert_eq!(merged.name.as_deref(), Some("Ada"));
+        assert_eq!(merged.email.as_deref(), Some("ada@work.example"));
+        assert_eq!(merged.sign, Some(false));
+        assert!(IdentityConfig::default().is_empty());
+    }
+
+    #[test]
+    fn test_load_resolves_key_relative_to_file() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join(IDENTITY_FILE);
+        assert!(IdentityConfig::load(&path).unwrap().is_empty());
+
+        std::fs::write(&path, "name = \"Ada\"\nkey = \"my.key\"\nsign = true\n").unwrap();
+        let config = IdentityConfig::load(&path).unwrap();
+        assert_eq!(config.key, Some(temp.path().join("my.key")));
+
+        // Signing without a readable key is an error, not silently unsigned
+        assert!(matches!(config.resolve(), Err(IdentityError::Io(..))));
+        generate_key(&temp.path().join("my.key")).unwrap();
+        let identity = config.resolve().unwrap();
+        assert!(identity.signs());
+        assert_eq!(identity.to_string(), "Ada");
+    }
+
+    #[test]
+    fn test_sign_without_key_is_an_error() {
+        let config = IdentityConfig {
+            sign: Some(true),
+            ..Default::default()
+        };
+        assert!(matches!(config.resolve(), Err(IdentityError::NoKey)));
+    }
+
+    #[test]
+    fn test_generated_key_round_trips() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join(KEY_FILE);
+        let key = generate_key(&path).unwrap();
+        assert_eq!(load_key(&path).unwrap().to_bytes(), key.to_bytes());
+        assert!(matches!(
+            generate_key(&path),
+            Err(IdentityError::KeyExists(_))
+        ));
+
+        std::fs::write(&path, "not a key\n").unwrap();
+        assert!(matches!(load_key(&path), Err(IdentityError::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_signature_check() {
+        let temp = TempDir::new().unwrap();
+        let key = generate_key(&temp.path().join(KEY_FILE)).unwrap();
+        let identity = Identity::new("Ada")
+            .with_email("ada@example.com")
+            .with_key(key, true);
+        let author = identity.author().unwrap();
+        assert_eq!(author[AUTHOR_NAME], "Ada");
+        assert_eq!(author[AUTHOR_EMAIL], "ada@example.com");
+        assert_eq!(author.get(AUTHOR_KEY), identity.public_key().as_ref());
+
+        let signature = identity.sign(b"HASH").unwrap();
+        assert_eq!(
+            SignatureStatus::check([&author], b"HASH", Some(&signature)),
+            SignatureStatus::Valid {
+                key: identity.public_key().unwrap()
+            }
+        );
+        assert_eq!(
+            SignatureStatus::check([&author], b"OTHER", Some(&signature)),
+            SignatureStatus::Invalid
+        );
+        assert_eq!(
+            SignatureStatus::check([&author], b"HASH", None),
+            SignatureStatus::Unsigned
+        );
+
+        // A signature by some other key doesn't pass for the author's
+        let other = Identity::default()
+            .with_key(generate_key(&temp.path().join("other.key")).unwrap(), true);
+        assert_eq!(
+            SignatureStatus::check([&author], b"HASH", other.sign(b"HASH").as_deref()),
+            SignatureStatus::Invalid
+        );
+    }
+
+    #[test]
+    fn test_empty_identity_has_no_author() {
+        assert_eq!(Identity::default().author(), None);
+        assert!(!Identity::default().signs());
+        assert_eq!(Identity::default().to_string(), "(anonymous)");
+    }
+}
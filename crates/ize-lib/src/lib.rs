@@ -2,15 +2,21 @@
 // pub mod filesystem;
 pub mod backing_fs;
 pub mod cli;
+pub mod conformance;
 pub mod filesystems;
+pub mod identity;
 pub mod operations;
 pub mod pijul;
 pub mod project;
 pub mod storage;
 pub mod vcs;
 
+pub use identity::{Identity, IdentityConfig, SignatureStatus};
 pub use pijul::{OpcodeError, OpcodeRecordingBackend, PijulBackend, PijulError};
-pub use project::{IzeProject, ProjectError, ProjectInfo, ProjectManager};
+pub use project::{
+    CheckpointSettings, IzeProject, MountSettings, ProjectError, ProjectInfo, ProjectManager,
+};
+pub use storage::{LogStore, MemoryStore, StorageError, VersionStore};
 
 pub fn version() -> &'static str {
     env!("CARGO_PKG_VERSION")
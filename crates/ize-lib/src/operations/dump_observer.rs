@@ -37,6 +37,7 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use fuser::FileType;
 use log::{debug, warn};
 
 use crate::filesystems::observing::FsObserver;
@@ -53,7 +54,7 @@ pub struct DumpObserver {
     inode_map: InodeMap,
 
     /// Root of the source directory — used for real-path lookups
-    /// (e.g. distinguishing symlinks from regular files on unlink).
+    /// (e.g. telling directory renames from file renames).
     source_dir: PathBuf,
 
     /// Monotonic sequence counter so every logged entry gets a number.
@@ -233,7 +234,7 @@ impl FsObserver for DumpObserver {
         ));
     }
 
-    fn on_unlink(&self, parent: u64, name: &OsStr) {
+    fn on_unlink(&self, parent: u64, name: &OsStr, kind: FileType) {
         let path = match self.resolve_with_name(parent, name) {
             Some(p) => p,
             None => {
@@ -245,12 +246,7 @@ impl FsObserver for DumpObserver {
             return;
         }
 
-        let real_path = self.to_real(&path);
-        let is_symlink = std::fs::symlink_metadata(&real_path)
-            .map(|m| m.file_type().is_symlink())
-            .unwrap_or(false);
-
-        let kind = if is_symlink {
+        let kind = if kind == FileType::Symlink {
             "SymlinkDelete"
         } else {
             "FileDelete"
@@ -318,8 +314,9 @@ impl FsObserver for DumpObserver {
             return;
         }
 
-        let real_old = self.to_real(&old_path);
-        let is_dir = std::fs::metadata(&real_old)
+        // Renames are reported after the fact: inspect the destination.
+        let real_new = self.to_real(&new_path);
+        let is_dir = std::fs::metadata(&real_new)
             .map(|m| m.is_dir())
             .unwrap_or(false);
 
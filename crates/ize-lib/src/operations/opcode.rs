@@ -33,6 +33,8 @@
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::filesystems::Caller;
+
 /// A single filesystem operation with all necessary context.
 ///
 /// Opcodes are the fundamental unit of change tracking in Ize. Each opcode
@@ -51,6 +53,9 @@ pub struct Opcode {
 
     /// The operation itself.
     op: Operation,
+
+    /// The process that performed it, when known.
+    caller: Option<Caller>,
 }
 
 impl Opcode {
@@ -65,14 +70,30 @@ impl Opcode {
             .map(|d| d.as_nanos() as u64)
             .unwrap_or(0);
 
-        Self { seq, timestamp, op }
+        Self {
+            seq,
+            timestamp,
+            op,
+            caller: None,
+        }
     }
 
     /// Create a new opcode with a specific timestamp.
     ///
     /// Useful for testing or replaying operations.
     pub fn with_timestamp(seq: u64, timestamp: u64, op: Operation) -> Self {
-        Self { seq, timestamp, op }
+        Self {
+            seq,
+            timestamp,
+            op,
+            caller: None,
+        }
+    }
+
+    /// Attach the process that performed the operation.
+    pub fn with_caller(mut self, caller: Caller) -> Self {
+        self.caller = Some(caller);
+        self
     }
 
     /// Get the sequence number.
@@ -85,6 +106,11 @@ impl Opcode {
         self.timestamp
     }
 
+    /// Get the process that performed the operation, if known.
+    pub fn caller(&self) -> Option<Caller> {
+        self.caller
+    }
+
     /// Get a reference to the operation.
     pub fn op(&self) -> &Operation {
         &self.op
@@ -309,6 +335,26 @@ impl Operation {
         }
     }
 
+    /// Short lowercase name of the operation type, e.g. `"write"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Operation::FileCreate { .. } => "create",
+            Operation::FileWrite { .. } => "write",
+            Operation::FileTruncate { .. } => "truncate",
+            Operation::FileDelete { .. } => "delete",
+            Operation::FileRename { .. } => "rename",
+            Operation::DirCreate { .. } => "mkdir",
+            Operation::DirDelete { .. } => "rmdir",
+            Operation::DirRename { .. } => "dir-rename",
+            Operation::SetPermissions { .. } => "chmod",
+            Operation::SetTimestamps { .. } => "touch",
+            Operation::SetOwnership { .. } => "chown",
+            Operation::SymlinkCreate { .. } => "symlink",
+            Operation::SymlinkDelete { .. } => "symlink-delete",
+            Operation::HardLinkCreate { .. } => "link",
+        }
+    }
+
     /// Check if this operation affects the given path.
     ///
     /// For rename operations, checks both source and destination.
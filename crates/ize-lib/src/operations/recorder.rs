@@ -9,13 +9,15 @@
 //! ```no_run
 //! # use std::sync::Arc;
 //! # use std::path::PathBuf;
-//! # use ize_lib::filesystems::passthrough::PassthroughFS;
+//! # use ize_lib::backing_fs::LibcBackingFs;
+//! # use ize_lib::filesystems::passthrough_fd::FdPassthroughFS;
 //! # use ize_lib::filesystems::observing::ObservingFS;
 //! # use ize_lib::operations::{OpcodeQueue, OpcodeRecorder};
 //! # fn main() -> std::io::Result<()> {
 //! # let source_dir = PathBuf::from("/tmp/source");
 //! # let mount_point = PathBuf::from("/tmp/mount");
-//! let passthrough = PassthroughFS::new(&source_dir, &mount_point)?;
+//! let backing = LibcBackingFs::open_dir(&source_dir)?;
+//! let passthrough = FdPassthroughFS::new(backing, mount_point);
 //! let inode_map = passthrough.inode_map();
 //! let queue = OpcodeQueue::new();
 //!
@@ -34,12 +36,17 @@
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use fuser::FileType;
 use log::{debug, warn};
+use parking_lot::Mutex;
 
+use crate::backing_fs::BackingFs;
 use crate::filesystems::observing::FsObserver;
 use crate::filesystems::passthrough::InodeMap;
+use crate::filesystems::Caller;
 use crate::operations::{Opcode, Operation};
 use crate::vcs::IgnoreFilter;
 
@@ -65,6 +72,13 @@ pub struct OpcodeRecorder {
 
     /// Ignore filters — paths matching any filter are silently dropped.
     ignore_filters: Vec<Box<dyn IgnoreFilter>>,
+
+    /// Optional backing store used for metadata lookups instead of
+    /// `source_dir` on the host filesystem.
+    backing: Option<Arc<dyn BackingFs>>,
+
+    /// Process behind the mutation being observed, stamped on its opcode
+    caller: Mutex<Option<Caller>>,
 }
 
 impl OpcodeRecorder {
@@ -81,6 +95,8 @@ impl OpcodeRecorder {
             next_seq: AtomicU64::new(1),
             sender,
             ignore_filters: Vec::new(),
+            backing: None,
+            caller: Mutex::new(None),
         }
     }
 
@@ -94,6 +110,16 @@ impl OpcodeRecorder {
         self
     }
 
+    /// Perform metadata lookups (directory detection on rename) through a
+    /// [`BackingFs`] rather than through `source_dir`.
+    ///
+    /// Pass the same store the filesystem writes to — e.g. a shared
+    /// [`MemBackingFs`](crate::backing_fs::MemBackingFs) in tests.
+    pub fn with_backing(mut self, backing: Arc<dyn BackingFs>) -> Self {
+        self.backing = Some(backing);
+        self
+    }
+
     /// Generate the next sequence number.
     fn next_seq(&self) -> u64 {
         self.next_seq.fetch_add(1, Ordering::SeqCst)
@@ -120,6 +146,27 @@ impl OpcodeRecorder {
         self.source_dir.join(rel_path)
     }
 
+    /// Return the `S_IFMT` bits of `rel_path` without following symlinks.
+    fn file_type(&self, rel_path: &PathBuf) -> Option<libc::mode_t> {
+        match &self.backing {
+            Some(backing) => backing
+                .stat(rel_path)
+                .ok()
+                .map(|st| st.st_mode & libc::S_IFMT),
+            None => {
+                let meta = std::fs::symlink_metadata(self.to_real(rel_path)).ok()?;
+                let ft = meta.file_type();
+                Some(if ft.is_symlink() {
+                    libc::S_IFLNK
+                } else if ft.is_dir() {
+                    libc::S_IFDIR
+                } else {
+                    libc::S_IFREG
+                })
+            }
+        }
+    }
+
     /// Check whether a path should be ignored (not recorded).
     fn is_ignored(&self, path: &Path) -> bool {
         self.ignore_filters.iter().any(|f| f.should_ignore(path))
@@ -129,7 +176,10 @@ impl OpcodeRecorder {
     fn emit(&self, op: Operation) {
         let seq = self.next_seq();
         debug!("OpcodeRecorder::emit seq={} op={:?}", seq, op);
-        let opcode = Opcode::new(seq, op);
+        let mut opcode = Opcode::new(seq, op);
+        if let Some(caller) = *self.caller.lock() {
+            opcode = opcode.with_caller(caller);
+        }
         if let Err(_opcode) = self.sender.try_send(opcode) {
             warn!("Failed to enqueue opcode: queue at capacity");
             // Fallback: force push to avoid losing the opcode
@@ -139,6 +189,10 @@ impl OpcodeRecorder {
 }
 
 impl FsObserver for OpcodeRecorder {
+    fn on_caller(&self, caller: Caller) {
+        *self.caller.lock() = Some(caller);
+    }
+
     fn on_write(&self, ino: u64, _fh: u64, offset: i64, data: &[u8]) {
         debug!(
             "OpcodeRecorder::on_write(ino={}, offset={}, data_len={})",
@@ -191,7 +245,7 @@ impl FsObserver for OpcodeRecorder {
         });
     }
 
-    fn on_unlink(&self, parent: u64, name: &OsStr) {
+    fn on_unlink(&self, parent: u64, name: &OsStr, kind: FileType) {
         debug!(
             "OpcodeRecorder::on_unlink(parent={}, name={:?})",
             parent, name
@@ -209,17 +263,11 @@ impl FsObserver for OpcodeRecorder {
             return;
         }
 
-        // Check if it's a symlink (use symlink_metadata to not follow symlinks)
-        let real_path = self.to_real(&path);
-        let is_symlink = std::fs::symlink_metadata(&real_path)
-            .map(|m| m.file_type().is_symlink())
-            .unwrap_or(false);
-
         debug!(
-            "OpcodeRecorder::on_unlink resolved path={:?}, is_symlink={}",
-            path, is_symlink
+            "OpcodeRecorder::on_unlink resolved path={:?}, kind={:?}",
+            path, kind
         );
-        if is_symlink {
+        if kind == FileType::Symlink {
             self.emit(Operation::SymlinkDelete { path });
         } else {
             self.emit(Operation::FileDelete { path });
@@ -286,11 +334,8 @@ impl FsObserver for OpcodeRecorder {
             return;
         }
 
-        // Check if source is a directory
-        let real_old = self.to_real(&old_path);
-        let is_dir = std::fs::metadata(&real_old)
-            .map(|m| m.is_dir())
-            .unwrap_or(false);
+        // The rename has already happened, so the entry now lives at new_path
+        let is_dir = self.file_type(&new_path) == Some(libc::S_IFDIR);
 
         if is_dir {
             self.emit(Operation::DirRename { old_path, new_path });
@@ -400,6 +445,8 @@ impl FsObserver for OpcodeRecorder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backing_fs::{Fault, FaultyBackingFs, FsMethod, MemBackingFs};
+    use crate::filesystems::{FdPassthroughFS, FsOps, ObservingFS, SetAttr};
     use crate::operations::OpcodeQueue;
     use crate::vcs::GitBackend;
     use std::collections::HashMap;
@@ -501,9 +548,7 @@ mod tests {
     fn test_on_unlink() {
         let (recorder, queue) = setup_test_recorder();
 
-        // This will check the filesystem, but since the file doesn't exist,
-        // it will default to FileDelete (not symlink)
-        recorder.on_unlink(1, OsStr::new("file.txt"));
+        recorder.on_unlink(1, OsStr::new("file.txt"), FileType::RegularFile);
 
         let opcode = queue.try_pop().unwrap();
         match opcode.into_op() {
@@ -514,6 +559,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_on_unlink_symlink() {
+        let (recorder, queue) = setup_test_recorder();
+
+        recorder.on_unlink(1, OsStr::new("link"), FileType::Symlink);
+
+        let opcode = queue.try_pop().unwrap();
+        match opcode.into_op() {
+            Operation::SymlinkDelete { path } => {
+                assert_eq!(path, PathBuf::from("link"));
+            }
+            _ => panic!("Expected SymlinkDelete operation"),
+        }
+    }
+
     #[test]
     fn test_on_mkdir() {
         let (recorder, queue) = setup_test_recorder();
@@ -627,6 +687,23 @@ mod tests {
         assert_eq!(op3.seq(), 3);
     }
 
+    #[test]
+    fn test_caller_stamped_on_opcodes() {
+        let (recorder, queue) = setup_test_recorder();
+
+        recorder.on_mkdir(1, OsStr::new("before"), 0o755, None);
+        let caller = Caller {
+            uid: 1000,
+            gid: 1000,
+            pid: 4242,
+        };
+        recorder.on_caller(caller);
+        recorder.on_mkdir(1, OsStr::new("after"), 0o755, None);
+
+        assert_eq!(queue.try_pop().unwrap().caller(), None);
+        assert_eq!(queue.try_pop().unwrap().caller(), Some(caller));
+    }
+
     #[test]
     fn test_unresolved_inode_skipped() {
         let (recorder, queue) = setup_test_recorder();
@@ -688,10 +765,10 @@ mod tests {
     fn test_filter_ignores_git_unlink() {
         let (recorder, queue) = setup_test_recorder_with_git_filter();
 
-        recorder.on_unlink(3, OsStr::new("index.lock"));
+        recorder.on_unlink(3, OsStr::new("index.lock"), FileType::RegularFile);
         assert!(queue.is_empty(), ".git unlink should be filtered");
 
-        recorder.on_unlink(1, OsStr::new("file.txt"));
+        recorder.on_unlink(1, OsStr::new("file.txt"), FileType::RegularFile);
         assert!(!queue.is_empty(), "regular unlink should be recorded");
     }
 
@@ -776,4 +853,229 @@ mod tests {
 
         assert!(!recorder.ignore_filters.is_empty());
     }
+
+    #[test]
+    fn test_rename_detects_directory_through_backing() {
+        use crate::backing_fs::MemBackingFs;
+        use std::path::Path;
+
+        let backing = std::sync::Arc::new(MemBackingFs::new());
+        backing.mkdir(Path::new("dir"), 0o755).unwrap();
+        backing
+            .rename(Path::new("dir"), Path::new("moved"))
+            .unwrap();
+
+        let (recorder, queue) = setup_test_recorder();
+        let recorder = recorder.with_backing(backing);
+
+        recorder.on_rename(1, OsStr::new("dir"), 1, OsStr::new("moved"));
+        match queue.try_pop().unwrap().into_op() {
+            Operation::DirRename { old_path, new_path } => {
+                assert_eq!(old_path, PathBuf::from("dir"));
+                assert_eq!(new_path, PathBuf::from("moved"));
+            }
+            other => panic!("Expected DirRename operation, got {:?}", other),
+        }
+
+        // Paths the backing store doesn't know about fall back to files.
+        recorder.on_rename(1, OsStr::new("file.txt"), 1, OsStr::new("b.txt"));
+        assert!(matches!(
+            queue.try_pop().unwrap().op(),
+            Operation::FileRename { .. }
+        ));
+    }
+
+    // -- Error paths through the full filesystem stack -----------------------
+
+    type FaultyStore = std::sync::Arc<FaultyBackingFs<std::sync::Arc<MemBackingFs>>>;
+
+    /// `ObservingFS<FdPassthroughFS>` over a fault-injecting in-memory store,
+    /// with a recorder feeding the returned queue.
+    fn setup_faulty_stack() -> (
+        ObservingFS<FdPassthroughFS<FaultyStore>>,
+        FaultyStore,
+        std::sync::Arc<OpcodeQueue>,
+    ) {
+        let mem = std::sync::Arc::new(MemBackingFs::new());
+        let faulty = std::sync::Arc::new(FaultyBackingFs::new(mem.clone()));
+        let fs = FdPassthroughFS::new(faulty.clone(), PathBuf::from("/nonexistent/mnt"));
+
+        let queue = OpcodeQueue::new();
+        let recorder = OpcodeRecorder::new(
+            fs.inode_map(),
+            PathBuf::from("/nonexistent/src"),
+            queue.sender(),
+        )
+        .with_backing(mem);
+
+        let mut observing = ObservingFS::new(fs);
+        observing.add_observer(std::sync::Arc::new(recorder));
+        (observing, faulty, queue)
+    }
+
+    const ROOT: u64 = 1;
+
+    #[test]
+    fn test_failed_create_not_recorded() {
+        let (mut fs, faulty, queue) = setup_faulty_stack();
+        faulty.inject(Fault::errno(FsMethod::OpenFile, libc::ENOSPC));
+
+        let err = fs
+            .create(ROOT, OsStr::new("a.txt"), 0o644, libc::O_RDWR)
+            .unwrap_err();
+
+        assert_eq!(err, libc::ENOSPC);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_failed_write_not_recorded() {
+        let (mut fs, faulty, queue) = setup_faulty_stack();
+        let (attr, fh) = fs
+            .create(ROOT, OsStr::new("a.txt"), 0o644, libc::O_RDWR)
+            .unwrap();
+        assert!(matches!(
+            queue.try_pop().unwrap().op(),
+            Operation::FileCreate { .. }
+        ));
+
+        faulty.inject(Fault::errno(FsMethod::Pwrite, libc::EIO));
+        assert_eq!(fs.write(attr.ino, fh, 0, b"hello"), Err(libc::EIO));
+        assert!(queue.is_empty());
+
+        faulty.clear();
+        faulty.inject(Fault::errno(FsMethod::Pwrite, libc::ENOSPC));
+        assert_eq!(fs.write(attr.ino, fh, 0, b"hello"), Err(libc::ENOSPC));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_transient_write_failure_records_only_retry() {
+        let (mut fs, faulty, queue) = setup_faulty_stack();
+        let (attr, fh) = fs
+            .create(ROOT, OsStr::new("a.txt"), 0o644, libc::O_RDWR)
+            .unwrap();
+        queue.drain();
+
+        faulty.inject(Fault::errno(FsMethod::Pwrite, libc::EIO).times(1));
+        assert_eq!(fs.write(attr.ino, fh, 0, b"hello"), Err(libc::EIO));
+        assert_eq!(fs.write(attr.ino, fh, 0, b"hello"), Ok(5));
+
+        let ops = queue.drain();
+        assert_eq!(ops.len(), 1);
+        match ops[0].op() {
+            Operation::FileWrite { path, data, .. } => {
+                assert_eq!(path, &PathBuf::from("a.txt"));
+                assert_eq!(data, b"hello");
+            }
+            other => panic!("Expected FileWrite operation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_short_write_records_written_bytes() {
+        let (mut fs, faulty, queue) = setup_faulty_stack();
+        let (attr, fh) = fs
+            .create(ROOT, OsStr::new("a.txt"), 0o644, libc::O_RDWR)
+            .unwrap();
+        queue.drain();
+
+        faulty.inject(Fault::short_io(FsMethod::Pwrite, 4));
+        assert_eq!(fs.write(attr.ino, fh, 0, b"hello world"), Ok(4));
+
+        match queue.try_pop().unwrap().into_op() {
+            Operation::FileWrite { offset, data, .. } => {
+                assert_eq!(offset, 0);
+                assert_eq!(data, b"hell");
+            }
+            other => panic!("Expected FileWrite operation, got {:?}", other),
+        }
+        assert_eq!(
+            faulty.inner().read_all(Path::new("a.txt")).unwrap(),
+            b"hell"
+        );
+    }
+
+    #[test]
+    fn test_failed_namespace_ops_not_recorded() {
+        let (mut fs, faulty, queue) = setup_faulty_stack();
+        let dir = fs.mkdir(ROOT, OsStr::new("dir"), 0o755).unwrap();
+        let (file, fh) = fs
+            .create(ROOT, OsStr::new("a.txt"), 0o644, libc::O_RDWR)
+            .unwrap();
+        fs.release(file.ino, fh).unwrap();
+        queue.drain();
+
+        faulty.inject(Fault::errno(FsMethod::Mkdir, libc::ENOSPC));
+        faulty.inject(Fault::errno(FsMethod::Rename, libc::EIO));
+        faulty.inject(Fault::errno(FsMethod::Unlink, libc::EIO));
+        faulty.inject(Fault::errno(FsMethod::Rmdir, libc::EBUSY));
+        faulty.inject(Fault::errno(FsMethod::Chmod, libc::EPERM));
+
+        assert_eq!(
+            fs.mkdir(ROOT, OsStr::new("other"), 0o755).unwrap_err(),
+            libc::ENOSPC
+        );
+        assert_eq!(
+            fs.rename(ROOT, OsStr::new("a.txt"), dir.ino, OsStr::new("b.txt")),
+            Err(libc::EIO)
+        );
+        assert_eq!(fs.unlink(ROOT, OsStr::new("a.txt")), Err(libc::EIO));
+        assert_eq!(fs.rmdir(ROOT, OsStr::new("dir")), Err(libc::EBUSY));
+        let chmod = SetAttr {
+            mode: Some(0o600),
+            ..SetAttr::default()
+        };
+        assert_eq!(fs.setattr(file.ino, &chmod).unwrap_err(), libc::EPERM);
+
+        assert!(queue.is_empty(), "recorded {:?}", queue.drain());
+
+        // Once the faults are gone the same operations are recorded.
+        faulty.clear();
+        fs.rename(ROOT, OsStr::new("a.txt"), dir.ino, OsStr::new("b.txt"))
+            .unwrap();
+        assert!(matches!(
+            queue.try_pop().unwrap().op(),
+            Operation::FileRename { .. }
+        ));
+    }
+
+    #[test]
+    fn test_successful_dir_rename_recorded_as_dir() {
+        let (mut fs, _faulty, queue) = setup_faulty_stack();
+        fs.mkdir(ROOT, OsStr::new("dir"), 0o755).unwrap();
+        queue.drain();
+
+        fs.rename(ROOT, OsStr::new("dir"), ROOT, OsStr::new("moved"))
+            .unwrap();
+
+        assert!(matches!(
+            queue.try_pop().unwrap().op(),
+            Operation::DirRename { .. }
+        ));
+    }
+
+    #[test]
+    fn test_symlink_unlink_recorded_as_symlink_delete() {
+        let (mut fs, _faulty, queue) = setup_faulty_stack();
+        fs.symlink(ROOT, OsStr::new("link"), Path::new("target.txt"))
+            .unwrap();
+        let (file, fh) = fs
+            .create(ROOT, OsStr::new("a.txt"), 0o644, libc::O_RDWR)
+            .unwrap();
+        fs.release(file.ino, fh).unwrap();
+        queue.drain();
+
+        fs.unlink(ROOT, OsStr::new("link")).unwrap();
+        fs.unlink(ROOT, OsStr::new("a.txt")).unwrap();
+
+        match queue.try_pop().unwrap().into_op() {
+            Operation::SymlinkDelete { path } => assert_eq!(path, PathBuf::from("link")),
+            other => panic!("Expected SymlinkDelete operation, got {:?}", other),
+        }
+        assert!(matches!(
+            queue.try_pop().unwrap().op(),
+            Operation::FileDelete { .. }
+        ));
+    }
 }
@@ -8,7 +8,7 @@ use log::debug;
 use std::path::{Path, PathBuf};
 
 use libpijul::alive_retrieve;
-use libpijul::change::{ChangeError, ChangeHeader};
+use libpijul::change::{Author, Change, ChangeError, ChangeHeader};
 use libpijul::changestore::filesystem::{Error as ChangeStoreError, FileSystem as ChangeStore};
 use libpijul::changestore::ChangeStore as ChangeStoreTrait;
 use libpijul::output::output_file;
@@ -24,6 +24,13 @@ use libpijul::{
 };
 use thiserror::Error;
 
+use super::blobs::{BlobPointer, LargeFilePolicy};
+use super::checkout::DirtyPolicy;
+use super::delta::BlockMap;
+use super::vault::Vault;
+use crate::identity::{Identity, AUTHOR_KEY, SIGNATURE_FIELD};
+use crate::storage::ChangeMessage;
+
 /// Constants matching pijul-repository
 pub const PRISTINE_DIR: &str = "pristine";
 pub const CHANGES_DIR: &str = "changes";
@@ -70,6 +77,42 @@ pub enum PijulError {
 
     #[error("Path conversion error: {0}")]
     PathConversion(String),
+
+    #[error("Change not found in channel: {0}")]
+    ChangeNotFound(String),
+
+    #[error("Ambiguous change prefix {0:?}; use more characters")]
+    AmbiguousChange(String),
+
+    #[error("Unrecorded changes in the working copy: {}", .0.join(", "))]
+    UnrecordedChanges(Vec<String>),
+
+    #[error("Apply error: {0}")]
+    Apply(String),
+
+    #[error("Unresolved conflicts in: {}", .0.join(", "))]
+    Conflicted(Vec<String>),
+
+    #[error("No such tag: {0}")]
+    TagNotFound(String),
+
+    #[error("Tag error: {0}")]
+    Tag(String),
+
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(String),
+
+    #[error("No lines of text in large or binary file: {0}")]
+    NotText(String),
+
+    #[error("Large-file store error: {0}")]
+    Blob(String),
+
+    #[error("Encryption key missing: {0}")]
+    KeyMissing(String),
+
+    #[error("Encryption error: {0}")]
+    Encryption(String),
 }
 
 impl From<ChangeStoreError> for PijulError {
@@ -103,7 +146,13 @@ pub struct PijulBackend {
     /// The pristine database handle
     pristine: Pristine,
     /// Current channel name
-    current_channel: String,
+    pub(super) current_channel: String,
+    /// Who recorded changes are authored and signed by
+    identity: Identity,
+    /// Which files are recorded as pointers into the blob store
+    large_files: LargeFilePolicy,
+    /// Key and decrypted change cache, if the store is encrypted
+    pub(super) vault: Option<Vault>,
 }
 
 impl PijulBackend {
@@ -163,11 +212,18 @@ impl PijulBackend {
             working_dir: working_dir.to_path_buf(),
             pristine,
             current_channel: channel_name,
+            identity: Identity::default(),
+            large_files: LargeFilePolicy::default(),
+            vault: None,
         })
     }
 
     /// Open an existing pijul repository.
     ///
+    /// An encrypted repository is unlocked with the key its
+    /// [`EncryptionSettings`](super::EncryptionSettings) point to, failing
+    /// with [`PijulError::KeyMissing`] if it can't be found.
+    ///
     /// # Arguments
     /// * `pijul_dir` - Path to the `.pijul/` directory
     /// * `working_dir` - Path to the working directory
@@ -193,6 +249,9 @@ impl PijulBackend {
             working_dir: working_dir.to_path_buf(),
             pristine,
             current_channel,
+            identity: Identity::default(),
+            large_files: LargeFilePolicy::default(),
+            vault: Vault::open(pijul_dir)?,
         })
     }
 
@@ -206,7 +265,7 @@ impl PijulBackend {
         &self.working_dir
     }
 
-    /// Get the path to the changes directory
+    /// Get the path to the changes directory, sealed in an encrypted store
     pub fn changes_dir(&self) -> PathBuf {
         self.pijul_dir.join(CHANGES_DIR)
     }
@@ -216,6 +275,60 @@ impl PijulBackend {
         &self.current_channel
     }
 
+    /// Record changes as `identity` from now on
+    pub fn set_identity(&mut self, identity: Identity) {
+        self.identity = identity;
+    }
+
+    /// Who changes are recorded as
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    /// Keep files `policy` calls large in the blob store from now on
+    pub fn set_large_files(&mut self, policy: LargeFilePolicy) {
+        self.large_files = policy;
+    }
+
+    /// Which files are kept in the blob store
+    pub fn large_files(&self) -> &LargeFilePolicy {
+        &self.large_files
+    }
+
+    /// Header for a change about to be recorded, authored by the identity
+    pub(super) fn header(&self, message: ChangeMessage) -> ChangeHeader {
+        let mut header = message.into_header();
+        header.authors = self.identity.author().map(Author).into_iter().collect();
+        header
+    }
+
+    /// Hash and store a change, signing the hash if the identity signs and
+    /// is one of the change's authors
+    pub(super) fn save_change(&self, change: &mut Change) -> Result<Hash, PijulError> {
+        let signs = self.identity.signs()
+            && self.identity.public_key().is_some_and(|key| {
+                change
+                    .hashed
+                    .header
+                    .authors
+                    .iter()
+                    .any(|author| author.0.get(AUTHOR_KEY) == Some(&key))
+            });
+        let hash = self
+            .get_change_store()
+            .save_change(change, |change, hash| {
+                if signs {
+                    change.unhashed = self
+                        .identity
+                        .sign(hash.to_base32().as_bytes())
+                        .map(|signature| serde_json::json!({ SIGNATURE_FIELD: signature }));
+                }
+                Ok::<_, ChangeStoreError>(())
+            })?;
+        self.seal_change(&hash)?;
+        Ok(hash)
+    }
+
     /// Get a reference to the pristine database
     pub fn pristine(&self) -> &Pristine {
         &self.pristine
@@ -236,22 +349,20 @@ impl PijulBackend {
         Ok(())
     }
 
-    /// Switch to a different channel
+    /// Switch to a different channel and check it out into the working copy
+    ///
+    /// Fails with [`PijulError::UnrecordedChanges`] if the working copy has
+    /// changes that aren't recorded yet; see
+    /// [`switch_channel_with`](Self::switch_channel_with) to stash or discard
+    /// them instead.
+    ///
+    /// This expects `working/` to hold what was recorded, as it does under
+    /// the mount: a tracked file missing from it counts as deleted and not
+    /// yet recorded. Callers that record through the `record_file_*`
+    /// methods without writing the files themselves should switch with
+    /// [`DirtyPolicy::Discard`].
     pub fn switch_channel(&mut self, name: &str) -> Result<(), PijulError> {
-        let mut txn = self.pristine.mut_txn_begin()?;
-
-        // Verify channel exists
-        let channel = txn
-            .load_channel(name)
-            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
-
-        if channel.is_none() {
-            return Err(PijulError::ChannelNotFound(name.to_string()));
-        }
-
-        txn.set_current_channel(name)?;
-        txn.commit()?;
-        self.current_channel = name.to_string();
+        self.switch_channel_with(name, DirtyPolicy::Refuse)?;
         Ok(())
     }
 
@@ -329,13 +440,14 @@ impl PijulBackend {
         path: &str,
         _mode: u32,
         content: &[u8],
-        message: &str,
+        message: impl Into<ChangeMessage>,
     ) -> Result<Option<Hash>, PijulError> {
         debug!(
             "PijulBackend::record_file_create path={:?} content_len={}",
             path,
             content.len()
         );
+        let content = self.stored_form(path, content.to_vec())?;
         let txn = self.arc_txn_begin()?;
         let channel = self.load_channel_ref(&txn)?;
 
@@ -362,7 +474,7 @@ impl PijulBackend {
         }
 
         // Use Memory working copy for new files
-        self.record_with_memory(txn, channel, path, content.to_vec(), message)
+        self.record_with_memory(txn, channel, path, content, message.into())
     }
 
     /// Record modification to an existing file
@@ -384,7 +496,7 @@ impl PijulBackend {
         path: &str,
         offset: u64,
         data: &[u8],
-        message: &str,
+        message: impl Into<ChangeMessage>,
     ) -> Result<Option<Hash>, PijulError> {
         debug!(
             "PijulBackend::record_file_write path={:?} offset={} data_len={}",
@@ -397,7 +509,41 @@ impl PijulBackend {
 
         // Get file position and current content
         let (file_pos, inode) = self.get_file_position(&txn, &channel, path)?;
-        let mut content = self.get_file_content_at(&txn, &channel, file_pos)?;
+        let stored = self.get_file_content_at(&txn, &channel, file_pos)?;
+
+        // Large files only re-chunk around the write
+        if let Some(pointer) = BlobPointer::parse(&stored) {
+            let pointer = self.blob_store().patch(&pointer, offset, data)?;
+            return self.diff_and_record(
+                txn,
+                channel,
+                path,
+                file_pos,
+                inode,
+                &pointer.to_bytes(),
+                message.into(),
+            );
+        }
+
+        // Binary files only replace the blocks the write touches, until the
+        // write makes them large: then they move to the blob store, once
+        let end = offset + data.len() as u64;
+        if let Some(map) = BlockMap::parse(&stored)
+            .filter(|map| !self.large_files().is_large(path, map.size.max(end)))
+        {
+            let (map, stored_bytes) = self.patch_blocks(&map, offset, data)?;
+            return self.record_block_delta(
+                txn,
+                channel,
+                path,
+                file_pos,
+                inode,
+                map,
+                stored_bytes,
+                message.into(),
+            );
+        }
+        let mut content = self.hydrate(stored)?;
 
         // Apply the write operation
         let offset = offset as usize;
@@ -413,7 +559,16 @@ impl PijulBackend {
         content[offset..offset + data.len()].copy_from_slice(data);
 
         // Diff and record
-        self.diff_and_record(txn, channel, path, file_pos, inode, &content, message)
+        let content = self.stored_form(path, content)?;
+        self.diff_and_record(
+            txn,
+            channel,
+            path,
+            file_pos,
+            inode,
+            &content,
+            message.into(),
+        )
     }
 
     /// Record file truncation
@@ -429,7 +584,7 @@ impl PijulBackend {
         &self,
         path: &str,
         new_size: u64,
-        message: &str,
+        message: impl Into<ChangeMessage>,
     ) -> Result<Option<Hash>, PijulError> {
         debug!(
             "PijulBackend::record_file_truncate path={:?} new_size={}",
@@ -440,13 +595,88 @@ impl PijulBackend {
 
         // Get file position and current content
         let (file_pos, inode) = self.get_file_position(&txn, &channel, path)?;
-        let mut content = self.get_file_content_at(&txn, &channel, file_pos)?;
+        let stored = self.get_file_content_at(&txn, &channel, file_pos)?;
+        // A large file that stays large keeps its chunks but the last
+        if let Some(pointer) =
+            BlobPointer::parse(&stored).filter(|_| self.large_files().is_large(path, new_size))
+        {
+            let pointer = self.blob_store().resize(&pointer, new_size)?;
+            return self.diff_and_record(
+                txn,
+                channel,
+                path,
+                file_pos,
+                inode,
+                &pointer.to_bytes(),
+                message.into(),
+            );
+        }
+        if let Some(map) =
+            BlockMap::parse(&stored).filter(|_| !self.large_files().is_large(path, new_size))
+        {
+            let (map, stored_bytes) = self.resize_blocks(&map, new_size)?;
+            return self.record_block_delta(
+                txn,
+                channel,
+                path,
+                file_pos,
+                inode,
+                map,
+                stored_bytes,
+                message.into(),
+            );
+        }
+        let mut content = self.hydrate(stored)?;
 
         // Truncate
         content.truncate(new_size as usize);
 
         // Diff and record
-        self.diff_and_record(txn, channel, path, file_pos, inode, &content, message)
+        let content = self.stored_form(path, content)?;
+        self.diff_and_record(
+            txn,
+            channel,
+            path,
+            file_pos,
+            inode,
+            &content,
+            message.into(),
+        )
+    }
+
+    /// Record new contents for an existing file
+    ///
+    /// # Arguments
+    /// * `path` - File path
+    /// * `content` - The file's full new content
+    /// * `message` - Commit message
+    ///
+    /// # Returns
+    /// The hash of the created change, or None if the content is unchanged
+    pub fn record_file_replace(
+        &self,
+        path: &str,
+        content: &[u8],
+        message: impl Into<ChangeMessage>,
+    ) -> Result<Option<Hash>, PijulError> {
+        debug!(
+            "PijulBackend::record_file_replace path={:?} content_len={}",
+            path,
+            content.len()
+        );
+        let content = self.stored_form(path, content.to_vec())?;
+        let txn = self.arc_txn_begin()?;
+        let channel = self.load_channel_ref(&txn)?;
+        let (file_pos, inode) = self.get_file_position(&txn, &channel, path)?;
+        self.diff_and_record(
+            txn,
+            channel,
+            path,
+            file_pos,
+            inode,
+            &content,
+            message.into(),
+        )
     }
 
     /// Record file deletion
@@ -460,7 +690,7 @@ impl PijulBackend {
     pub fn record_file_delete(
         &self,
         path: &str,
-        message: &str,
+        message: impl Into<ChangeMessage>,
     ) -> Result<Option<Hash>, PijulError> {
         debug!("PijulBackend::record_file_delete path={:?}", path);
         let txn = self.arc_txn_begin()?;
@@ -478,7 +708,8 @@ impl PijulBackend {
 
         // For deletion, diff against empty content
         // Note: diff_and_record takes ownership and commits the transaction
-        let result = self.diff_and_record(txn, channel, path, file_pos, inode, &[], message)?;
+        let result =
+            self.diff_and_record(txn, channel, path, file_pos, inode, &[], message.into())?;
 
         Ok(result)
     }
@@ -496,8 +727,16 @@ impl PijulBackend {
         &self,
         old_path: &str,
         new_path: &str,
-        message: &str,
+        message: impl Into<ChangeMessage>,
     ) -> Result<Hash, PijulError> {
+        debug!(
+            "PijulBackend::record_file_rename old_path={:?} new_path={:?}",
+            old_path, new_path
+        );
+        // Read the content before opening the write transaction; a large
+        // file moves as its pointer
+        let content = self.stored_file_content(old_path)?;
+
         let txn = self.arc_txn_begin()?;
         let channel = self.load_channel_ref(&txn)?;
 
@@ -508,8 +747,10 @@ impl PijulBackend {
                 .map_err(|e| PijulError::Transaction(format!("Failed to move file: {:?}", e)))?;
         }
 
-        // Record the change
-        let memory = Memory::new();
+        // Record only the moved file. The memory working copy holds nothing
+        // else, so recording from the root would see every other tracked file
+        // as deleted.
+        let memory = memory_with_file(new_path, content);
         let mut builder = RecordBuilder::new();
 
         builder
@@ -521,19 +762,14 @@ impl PijulBackend {
                 channel.clone(),
                 &memory,
                 &self.get_change_store(),
-                "",
+                new_path,
                 1,
             )
             .map_err(|e| PijulError::Recording(format!("{:?}", e)))?;
 
         let recorded = builder.finish();
 
-        let header = ChangeHeader {
-            message: message.to_string(),
-            authors: vec![],
-            description: None,
-            timestamp: jiff::Timestamp::now(),
-        };
+        let header = self.header(message.into());
 
         let change = {
             let t = txn.read();
@@ -543,8 +779,7 @@ impl PijulBackend {
         };
 
         let mut change = change;
-        let change_store = self.get_change_store();
-        let hash = change_store.save_change(&mut change, |_, _| Ok::<_, ChangeStoreError>(()))?;
+        let hash = self.save_change(&mut change)?;
 
         {
             let mut t = txn.write();
@@ -574,8 +809,27 @@ impl PijulBackend {
     /// # Returns
     /// The file content as bytes
     pub fn get_file_content(&self, path: &str) -> Result<Vec<u8>, PijulError> {
+        self.hydrate(self.stored_file_content(path)?)
+    }
+
+    /// File content at the channel head as recorded, pointers left as they
+    /// are
+    pub(super) fn stored_file_content(&self, path: &str) -> Result<Vec<u8>, PijulError> {
+        self.stored_file_content_in(&self.current_channel, path)
+    }
+
+    /// File content at the head of channel `name` as recorded
+    pub(super) fn stored_file_content_in(
+        &self,
+        name: &str,
+        path: &str,
+    ) -> Result<Vec<u8>, PijulError> {
         let txn = self.arc_txn_begin()?;
-        let channel = self.load_channel_ref(&txn)?;
+        let channel = txn
+            .read()
+            .load_channel(name)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            .ok_or_else(|| PijulError::ChannelNotFound(name.to_string()))?;
 
         // Get file position using follow_oldest_path
         let (file_pos, _ambiguous) = {
@@ -615,19 +869,27 @@ impl PijulBackend {
         Ok(result.is_ok())
     }
 
-    /// List all files in current channel
+    /// List all tracked files
+    ///
+    /// Walks the tracked tree, which every `record_file_*` method keeps in
+    /// step with what it records. Directories are left out since they only
+    /// exist implicitly as parents of files.
     ///
     /// # Returns
-    /// A vector of file paths
+    /// A sorted vector of file paths
     pub fn list_files(&self) -> Result<Vec<String>, PijulError> {
-        let _txn = self.arc_txn_begin()?;
-        let _channel = self.load_channel_ref(&_txn)?;
-        let files = Vec::new();
+        let txn = self.txn_begin()?;
+        let mut files = Vec::new();
 
-        // This is a simplified implementation - in a real scenario you'd walk the tree
-        // For now, just return an empty list (this can be improved later)
-        // TODO: Implement proper file listing from pristine
+        for entry in txn.iter_working_copy() {
+            let (_inode, path, is_dir) =
+                entry.map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+            if !is_dir {
+                files.push(path);
+            }
+        }
 
+        files.sort();
         Ok(files)
     }
 
@@ -636,11 +898,19 @@ impl PijulBackend {
     /// # Returns
     /// A vector of change hashes in chronological order
     pub fn list_changes(&self) -> Result<Vec<Hash>, PijulError> {
+        self.list_changes_in(&self.current_channel)
+    }
+
+    /// List all changes in the named channel
+    ///
+    /// # Returns
+    /// A vector of change hashes in chronological order
+    pub fn list_changes_in(&self, name: &str) -> Result<Vec<Hash>, PijulError> {
         let txn = self.txn_begin()?;
         let channel = txn
-            .load_channel(&self.current_channel)
+            .load_channel(name)
             .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
-            .ok_or_else(|| PijulError::ChannelNotFound(self.current_channel.clone()))?;
+            .ok_or_else(|| PijulError::ChannelNotFound(name.to_string()))?;
 
         let channel_ref = channel.read();
         let mut changes = Vec::new();
@@ -724,7 +994,7 @@ impl PijulBackend {
         file_pos: Position<libpijul::pristine::ChangeId>,
         inode: Inode,
         new_content: &[u8],
-        message: &str,
+        message: ChangeMessage,
     ) -> Result<Option<Hash>, PijulError> {
         debug!(
             "PijulBackend::diff_and_record path={:?} new_content_len={}",
@@ -772,13 +1042,7 @@ impl PijulBackend {
             recorded.actions.len()
         );
 
-        // Create the change header
-        let header = ChangeHeader {
-            message: message.to_string(),
-            authors: vec![],
-            description: None,
-            timestamp: jiff::Timestamp::now(),
-        };
+        let header = self.header(message);
 
         // Build the change
         let change = {
@@ -790,8 +1054,7 @@ impl PijulBackend {
 
         // Save to changestore
         let mut change = change;
-        let change_store = self.get_change_store();
-        let hash = change_store.save_change(&mut change, |_, _| Ok::<_, ChangeStoreError>(()))?;
+        let hash = self.save_change(&mut change)?;
 
         // Apply to pristine
         {
@@ -814,6 +1077,35 @@ impl PijulBackend {
         Ok(Some(hash))
     }
 
+    /// Record a binary file's new block map, counting the write in the delta
+    /// stats
+    fn record_block_delta(
+        &self,
+        txn: ArcTxn<MutTxn<()>>,
+        channel: ChannelRef<MutTxn<()>>,
+        path: &str,
+        file_pos: Position<libpijul::pristine::ChangeId>,
+        inode: Inode,
+        map: BlockMap,
+        stored_bytes: u64,
+        message: ChangeMessage,
+    ) -> Result<Option<Hash>, PijulError> {
+        let size = map.size;
+        let hash = self.diff_and_record(
+            txn,
+            channel,
+            path,
+            file_pos,
+            inode,
+            &map.to_bytes(),
+            message,
+        )?;
+        if hash.is_some() {
+            self.count_delta(size, stored_bytes)?;
+        }
+        Ok(hash)
+    }
+
     /// Record a change using Memory working copy (for new files)
     fn record_with_memory(
         &self,
@@ -821,31 +1113,15 @@ impl PijulBackend {
         channel: ChannelRef<MutTxn<()>>,
         path: &str,
         content: Vec<u8>,
-        message: &str,
+        message: ChangeMessage,
     ) -> Result<Option<Hash>, PijulError> {
         debug!(
             "PijulBackend::record_with_memory path={:?} content_len={}",
             path,
             content.len()
         );
-        // Create memory working copy
-        let memory = Memory::new();
-
-        // Populate directory structure
-        let mut current = String::new();
-        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
-        for (i, component) in components.iter().enumerate() {
-            if i < components.len() - 1 {
-                if !current.is_empty() {
-                    current.push('/');
-                }
-                current.push_str(component);
-                memory.add_dir(&current);
-            }
-        }
-
-        // Add the file with new content
-        memory.add_file(path, content);
+        // Create memory working copy holding just this file
+        let memory = memory_with_file(path, content);
 
         // Build and record the change
         let mut builder = RecordBuilder::new();
@@ -876,13 +1152,7 @@ impl PijulBackend {
             recorded.actions.len()
         );
 
-        // Create the change header
-        let header = ChangeHeader {
-            message: message.to_string(),
-            authors: vec![],
-            description: None,
-            timestamp: jiff::Timestamp::now(),
-        };
+        let header = self.header(message);
 
         // Build the change
         let change = {
@@ -894,8 +1164,7 @@ impl PijulBackend {
 
         // Save to changestore
         let mut change = change;
-        let change_store = self.get_change_store();
-        let hash = change_store.save_change(&mut change, |_, _| Ok::<_, ChangeStoreError>(()))?;
+        let hash = self.save_change(&mut change)?;
 
         // Apply to pristine
         {
@@ -923,11 +1192,30 @@ impl PijulBackend {
 
     /// Get a change store handle
     pub fn get_change_store(&self) -> ChangeStore {
-        let changes_dir = self.changes_dir();
-        ChangeStore::from_changes(changes_dir, 1024)
+        ChangeStore::from_changes(self.plain_changes_dir(), 1024)
     }
 }
 
+/// Build a memory working copy containing a single file and its parent directories
+fn memory_with_file(path: &str, content: Vec<u8>) -> Memory {
+    let memory = Memory::new();
+
+    let mut current = String::new();
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    for (i, component) in components.iter().enumerate() {
+        if i < components.len() - 1 {
+            if !current.is_empty() {
+                current.push('/');
+            }
+            current.push_str(component);
+            memory.add_dir(&current);
+        }
+    }
+
+    memory.add_file(path, content);
+    memory
+}
+
 /// Detect encoding for a file based on its content
 fn detect_encoding(content: &[u8]) -> Option<Encoding> {
     // Simple heuristic: if it contains null bytes, treat as binary
@@ -1273,4 +1561,55 @@ mod tests {
         let content = backend.get_file_content("test.txt").unwrap();
         assert_eq!(content, b"Mod");
     }
+
+    #[test]
+    fn test_list_files() {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        assert!(backend.list_files().unwrap().is_empty());
+
+        backend
+            .record_file_create("b.txt", 0, b"b", "Create b.txt")
+            .unwrap();
+        backend
+            .record_file_create("src/a.rs", 0, b"a", "Create src/a.rs")
+            .unwrap();
+        backend
+            .record_file_create("tmp.txt", 0, b"tmp", "Create tmp.txt")
+            .unwrap();
+        backend
+            .record_file_delete("tmp.txt", "Delete tmp.txt")
+            .unwrap();
+
+        // Sorted, files only, deleted files gone
+        assert_eq!(backend.list_files().unwrap(), vec!["b.txt", "src/a.rs"]);
+    }
+
+    #[test]
+    fn test_record_file_rename() {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+
+        backend
+            .record_file_create("old.txt", 0, b"moving", "Create old.txt")
+            .unwrap();
+        backend
+            .record_file_create("other.txt", 0, b"untouched", "Create other.txt")
+            .unwrap();
+        backend
+            .record_file_rename("old.txt", "new.txt", "Rename old.txt")
+            .unwrap();
+
+        assert_eq!(backend.list_changes().unwrap().len(), 3);
+        assert_eq!(backend.list_files().unwrap(), vec!["new.txt", "other.txt"]);
+        assert_eq!(backend.get_file_content("new.txt").unwrap(), b"moving");
+        // Files outside the rename must not be recorded as deleted
+        assert_eq!(backend.get_file_content("other.txt").unwrap(), b"untouched");
+    }
 }
@@ -0,0 +1,334 @@
+//! Line-level blame
+//!
+//! Every line of a file is a piece of some vertex in the Pijul graph, and
+//! every vertex was added by exactly one change. Outputting the file through
+//! a [`VertexBuffer`] that notes which change each piece came from therefore
+//! attributes each line to the change that wrote it. Since Ize records at
+//! write granularity, that is the write itself, with its exact time and, from
+//! the change's [`ChangeMetadata`](super::ChangeMetadata), the process behind
+//! it.
+//!
+//! A line is credited to the change of its first byte. Pijul diffs by line,
+//! so a line only spans vertices if an edit didn't end with a newline.
+//! Conflict marker lines belong to no change.
+//!
+//! Large and binary files are recorded as a pointer or block map, not as
+//! lines, so they cannot be blamed.
+
+use std::io;
+
+use chrono::{DateTime, Utc};
+use libpijul::changestore::ChangeStore;
+use libpijul::output::output_file;
+use libpijul::pristine::{ChangeId, Hash, Vertex};
+use libpijul::vertex_buffer::{VertexBuffer, Writer};
+use libpijul::{TxnT, TxnTExt};
+
+use super::backend::{PijulBackend, PijulError};
+use super::blobs::file_text;
+use super::query::ChangeInfo;
+
+/// A line of a file with the change that wrote it, as returned by
+/// [`PijulQuery::blame`](super::PijulQuery::blame)
+#[derive(Debug, Clone)]
+pub struct BlameLine {
+    /// Line number, from 1
+    pub number: usize,
+    /// The line, without its newline
+    pub content: String,
+    /// The change that wrote it; `None` for conflict markers
+    pub change: Option<ChangeInfo>,
+}
+
+impl BlameLine {
+    /// When the line was written
+    ///
+    /// The time of the write itself for changes recorded from opcodes, else
+    /// the change's timestamp.
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        let change = self.change.as_ref()?;
+        Some(
+            change
+                .metadata
+                .as_ref()
+                .map_or(change.timestamp, |metadata| metadata.time),
+        )
+    }
+
+    /// `(pid, uid)` of the process that wrote the line, if the mount knew it
+    pub fn process(&self) -> Option<(u32, u32)> {
+        let metadata = self.change.as_ref()?.metadata.as_ref()?;
+        metadata.pid.zip(metadata.uid)
+    }
+}
+
+/// One line of a file and the change that wrote it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlamedLine {
+    /// Change that wrote the line, `None` for conflict markers
+    pub change: Option<Hash>,
+    /// The line, without its newline
+    pub content: Vec<u8>,
+}
+
+/// Collects the pieces of a file along with the vertex each came from
+#[derive(Default)]
+struct Blamer {
+    pieces: Vec<(Option<ChangeId>, Vec<u8>)>,
+}
+
+impl VertexBuffer for Blamer {
+    fn output_line<E, F>(&mut self, vertex: Vertex<ChangeId>, contents: F) -> Result<(), E>
+    where
+        E: From<io::Error>,
+        F: FnOnce(&mut [u8]) -> Result<(), E>,
+    {
+        let mut buffer = vec![0; vertex.end - vertex.start];
+        contents(&mut buffer)?;
+        if !buffer.is_empty() {
+            self.pieces.push((Some(vertex.change), buffer));
+        }
+        Ok(())
+    }
+
+    fn output_conflict_marker<C: ChangeStore>(
+        &mut self,
+        marker: &str,
+        id: usize,
+        sides: Option<(&C, &[&Hash])>,
+    ) -> Result<(), io::Error> {
+        // Markers go on lines of their own, spelled as in the working copy
+        let mut buffer = Vec::new();
+        if self
+            .pieces
+            .last()
+            .is_some_and(|(_, piece)| !piece.ends_with(b"\n"))
+        {
+            buffer.push(b'\n');
+        }
+        Writer::new(&mut buffer).output_conflict_marker(marker, id, sides)?;
+        self.pieces.push((None, buffer));
+        Ok(())
+    }
+}
+
+impl PijulBackend {
+    /// The lines of `path` in the current channel, each with the change that
+    /// wrote it
+    pub fn blame(&self, path: &str) -> Result<Vec<BlamedLine>, PijulError> {
+        let change_store = self.get_change_store();
+        let txn = self.arc_txn_begin()?;
+        let channel = txn
+            .read()
+            .load_channel(&self.current_channel)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            .ok_or_else(|| PijulError::ChannelNotFound(self.current_channel.clone()))?;
+
+        let (file_pos, _ambiguous) = {
+            let t = txn.read();
+            (&*t)
+                .follow_oldest_path(&change_store, &channel, path)
+                .map_err(|_| PijulError::FileNotFound(path.to_string()))?
+        };
+
+        let mut blamer = Blamer::default();
+        output_file(&change_store, &txn, &channel, file_pos, &mut blamer)
+            .map_err(|e| PijulError::Diff(format!("Failed to output file: {:?}", e)))?;
+
+        // Change ids are local to this repository; blame in terms of hashes
+        let t = txn.read();
+        let mut pieces = Vec::with_capacity(blamer.pieces.len());
+        for (id, piece) in blamer.pieces {
+            let hash = match id {
+                Some(id) => t
+                    .get_external(&id)
+                    .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+                    .map(|hash| Hash::from(*hash)),
+                None => None,
+            };
+            pieces.push((hash, piece));
+        }
+
+        let recorded: Vec<u8> = pieces
+            .iter()
+            .flat_map(|(_, piece)| piece)
+            .copied()
+            .collect();
+        let mut lines = split_lines(pieces);
+        match file_text(&recorded) {
+            None => return Err(PijulError::NotText(path.to_string())),
+            // The escape line is no line of the file
+            Some(text) if text.len() < recorded.len() => {
+                lines.remove(0);
+            }
+            Some(_) => {}
+        }
+        Ok(lines)
+    }
+}
+
+/// Cut pieces into lines, crediting each line to its first piece
+fn split_lines(pieces: Vec<(Option<Hash>, Vec<u8>)>) -> Vec<BlamedLine> {
+    let mut lines = Vec::new();
+    let mut current: Option<BlamedLine> = None;
+    for (change, piece) in pieces {
+        for chunk in piece.split_inclusive(|byte| *byte == b'\n') {
+            let line = current.get_or_insert_with(|| BlamedLine {
+                change,
+                content: Vec::new(),
+            });
+            match chunk.strip_suffix(b"\n") {
+                Some(content) => {
+                    line.content.extend_from_slice(content);
+                    lines.extend(current.take());
+                }
+                None => line.content.extend_from_slice(chunk),
+            }
+        }
+    }
+    lines.extend(current);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    #[test]
+    fn test_blame_credits_each_line() {
+        let (_temp, backend) = setup();
+        let path = backend.working_dir().join("a.txt");
+
+        std::fs::write(&path, b"one\ntwo\n").unwrap();
+        let first = backend
+            .record_file_create("a.txt", 0, b"one\ntwo\n", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        std::fs::write(&path, b"one\nTWO\nthree\n").unwrap();
+        let second = backend
+            .record_file_replace("a.txt", b"one\nTWO\nthree\n", "Edit a.txt")
+            .unwrap()
+            .unwrap();
+
+        let blame = backend.blame("a.txt").unwrap();
+        let credited: Vec<(Option<Hash>, &[u8])> = blame
+            .iter()
+            .map(|line| (line.change, line.content.as_slice()))
+            .collect();
+        assert_eq!(
+            credited,
+            vec![
+                (Some(first), &b"one"[..]),
+                (Some(second), &b"TWO"[..]),
+                (Some(second), &b"three"[..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_query_blame_reports_time_and_process() {
+        use super::super::{OpcodeRecordingBackend, PijulQuery};
+        use crate::filesystems::Caller;
+        use crate::operations::{Opcode, Operation};
+
+        let (_temp, backend) = setup();
+        let backend = OpcodeRecordingBackend::new(backend);
+        let opcode = Opcode::with_timestamp(
+            3,
+            1_714_573_800_500_000_000,
+            Operation::FileCreate {
+                path: "a.txt".into(),
+                mode: 0o644,
+                content: b"hello\n".to_vec(),
+            },
+        )
+        .with_caller(Caller {
+            uid: 1000,
+            gid: 1000,
+            pid: 4242,
+        });
+        backend.apply_opcode(&opcode).unwrap();
+
+        let blame = PijulQuery::new(backend.pijul()).blame("a.txt").unwrap();
+        assert_eq!(blame.len(), 1);
+        assert_eq!((blame[0].number, blame[0].content.as_str()), (1, "hello"));
+        // The write's own time, not the header's whole seconds
+        assert_eq!(blame[0].timestamp().unwrap().timestamp_subsec_millis(), 500);
+        assert_eq!(blame[0].process(), Some((4242, 1000)));
+    }
+
+    #[test]
+    fn test_blame_missing_file() {
+        let (_temp, backend) = setup();
+        assert!(matches!(
+            backend.blame("nope.txt"),
+            Err(PijulError::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_blame_stand_ins() {
+        let (_temp, backend) = setup();
+        let created = backend
+            .record_file_create("notes.txt", 0, b"ize-blob 1\nsize 3\n", "Create notes.txt")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            backend.blame("notes.txt").unwrap(),
+            vec![
+                BlamedLine {
+                    change: Some(created),
+                    content: b"ize-blob 1".to_vec()
+                },
+                BlamedLine {
+                    change: Some(created),
+                    content: b"size 3".to_vec()
+                },
+            ]
+        );
+
+        let image: Vec<u8> = (0..40_000).map(|i| (i % 7) as u8).collect();
+        backend
+            .record_file_create("image.png", 0, &image, "Create image.png")
+            .unwrap();
+        assert!(matches!(
+            backend.blame("image.png"),
+            Err(PijulError::NotText(_))
+        ));
+    }
+
+    #[test]
+    fn test_split_lines() {
+        let a = Some(Hash::None);
+        let lines = split_lines(vec![
+            (a, b"first\nsec".to_vec()),
+            (None, b"ond\nlast".to_vec()),
+        ]);
+        assert_eq!(
+            lines,
+            vec![
+                BlamedLine {
+                    change: a,
+                    content: b"first".to_vec()
+                },
+                BlamedLine {
+                    change: a,
+                    content: b"second".to_vec()
+                },
+                BlamedLine {
+                    change: None,
+                    content: b"last".to_vec()
+                },
+            ]
+        );
+    }
+}
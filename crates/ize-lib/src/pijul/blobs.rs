@@ -0,0 +1,777 @@
+//! Content-addressed store for large files
+//!
+//! Recording a write diffs the whole file against its previous version,
+//! which is fine for source code and hopeless for a 500 MB video. Files the
+//! project's [`LargeFilePolicy`] calls large are instead split into
+//! content-defined chunks, each stored once under its BLAKE3 hash in
+//! `.pijul/blobs/`, and Pijul records a small text pointer listing them:
+//!
+//! ```text
+//! ize-blob 1
+//! size 524288000
+//! hash 8c1f...
+//! 3b9a... 65536
+//! 71de... 48213
+//! ```
+//!
+//! Chunk boundaries follow the content (a gear hash, as in FastCDC), so an
+//! edit in the middle of a file only adds the chunks around it, and the
+//! recorded change only touches their lines of the pointer. A write to a
+//! large file reads and re-chunks just those chunks, never the whole file;
+//! the pointer's `hash` covers the chunk list, so it needs no full read
+//! either. Reads through
+//! the backend — the head, points in history, snapshots and the working
+//! copy after checkout — see the file itself, never the pointer.
+//!
+//! A plain file that happens to start like a pointer or a block map is
+//! recorded with an `ize-plain 1` line in front, which reads take off again,
+//! so only stand-ins ize wrote are ever taken for one.
+
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::{PijulBackend, PijulError};
+use super::delta::{is_binary, BlockMap, BLOCK_MAGIC};
+use super::vault::Key;
+
+/// Directory under `.pijul/` holding chunks
+pub const BLOBS_DIR: &str = "blobs";
+
+/// First line of every pointer
+const POINTER_MAGIC: &str = "ize-blob 1";
+
+/// Line put in front of plain content that starts like a stand-in, so it is
+/// recorded as itself
+const PLAIN_MAGIC: &str = "ize-plain 1";
+
+/// What recorded content starting with one of these may stand for
+const STAND_IN_MAGICS: [&str; 3] = [POINTER_MAGIC, BLOCK_MAGIC, PLAIN_MAGIC];
+
+/// Chunk size bounds; boundaries fall on average every 64 KiB in between
+const MIN_CHUNK: usize = 16 * 1024;
+const MAX_CHUNK: usize = 256 * 1024;
+const BOUNDARY_MASK: u64 = (1 << 16) - 1;
+
+/// Gear hash table: one fixed pseudo-random word per byte value (SplitMix64)
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Which files are kept in the blob store, from the `[large_files]` section
+/// of project.toml
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargeFilePolicy {
+    /// Files of at least this many bytes are large
+    #[serde(default = "default_min_size")]
+    pub min_size: u64,
+    /// Non-empty files with these extensions are large whatever their size,
+    /// compared case-insensitively and without the dot
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+}
+
+fn default_min_size() -> u64 {
+    16 * 1024 * 1024
+}
+
+impl Default for LargeFilePolicy {
+    fn default() -> Self {
+        Self {
+            min_size: default_min_size(),
+            extensions: Vec::new(),
+        }
+    }
+}
+
+impl LargeFilePolicy {
+    /// Whether `path`, `size` bytes long, belongs in the blob store
+    pub fn is_large(&self, path: &str, size: u64) -> bool {
+        if size >= self.min_size {
+            return true;
+        }
+        let extension = Path::new(path).extension().and_then(|ext| ext.to_str());
+        size > 0
+            && extension.is_some_and(|ext| {
+                self.extensions
+                    .iter()
+                    .any(|wanted| wanted.eq_ignore_ascii_case(ext))
+            })
+    }
+}
+
+/// What Pijul records in place of a large file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobPointer {
+    /// Length of the file
+    pub size: u64,
+    /// BLAKE3 hash of the chunk list; each chunk is checked against its own
+    /// hash, so this pins down the whole file
+    pub hash: blake3::Hash,
+    /// The file's chunks in order, with their lengths
+    pub chunks: Vec<(blake3::Hash, u64)>,
+}
+
+impl BlobPointer {
+    /// Read a pointer back from recorded content; `None` if it isn't one
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        let text = std::str::from_utf8(content).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != POINTER_MAGIC {
+            return None;
+        }
+        let size = lines.next()?.strip_prefix("size ")?.parse().ok()?;
+        let hash = blake3::Hash::from_hex(lines.next()?.strip_prefix("hash ")?).ok()?;
+        let mut chunks = Vec::new();
+        for line in lines {
+            let (chunk, length) = line.split_once(' ')?;
+            chunks.push((blake3::Hash::from_hex(chunk).ok()?, length.parse().ok()?));
+        }
+        let total: u64 = chunks.iter().map(|(_, length)| length).sum();
+        (total == size).then_some(Self { size, hash, chunks })
+    }
+
+    /// The pointer to `chunks`, in order
+    fn from_chunks(chunks: Vec<(blake3::Hash, u64)>) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        for (chunk, length) in &chunks {
+            hasher.update(chunk.as_bytes());
+            hasher.update(&length.to_le_bytes());
+        }
+        Self {
+            size: chunks.iter().map(|(_, length)| length).sum(),
+            hash: hasher.finalize(),
+            chunks,
+        }
+    }
+
+    /// The pointer as recorded, one chunk per line
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut text = format!(
+            "{}\nsize {}\nhash {}\n",
+            POINTER_MAGIC,
+            self.size,
+            self.hash.to_hex()
+        );
+        for (chunk, length) in &self.chunks {
+            text.push_str(&format!("{} {}\n", chunk.to_hex(), length));
+        }
+        text.into_bytes()
+    }
+}
+
+/// Chunks on disk, each a file named by its BLAKE3 hash
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    dir: PathBuf,
+    /// Key chunks are sealed with, in an encrypted project
+    key: Option<Key>,
+}
+
+impl BlobStore {
+    /// The store rooted at `dir`, created on first write
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            key: None,
+        }
+    }
+
+    /// Seal chunks with `key`, and name them by a keyed hash
+    pub fn with_key(mut self, key: Option<Key>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Split `content` into chunks, store the ones not already present and
+    /// return the pointer to them
+    pub fn put(&self, content: &[u8]) -> Result<BlobPointer, PijulError> {
+        let mut chunks = Vec::new();
+        let mut rest = content;
+        while !rest.is_empty() {
+            let (chunk, tail) = rest.split_at(next_boundary(rest));
+            let (hash, _) = self.put_chunk(chunk)?;
+            chunks.push((hash, chunk.len() as u64));
+            rest = tail;
+        }
+        Ok(BlobPointer::from_chunks(chunks))
+    }
+
+    /// Reassemble the file `pointer` stands for, checking every hash
+    pub fn get(&self, pointer: &BlobPointer) -> Result<Vec<u8>, PijulError> {
+        if BlobPointer::from_chunks(pointer.chunks.clone()).hash != pointer.hash {
+            return Err(PijulError::Blob(format!(
+                "file {} doesn't match its chunks",
+                pointer.hash.to_hex()
+            )));
+        }
+        let mut content = Vec::with_capacity(pointer.size as usize);
+        for (hash, length) in &pointer.chunks {
+            content.extend_from_slice(&self.get_chunk(hash, *length)?);
+        }
+        Ok(content)
+    }
+
+    /// Write `data` at `offset` into the file `pointer` stands for, reading
+    /// and re-chunking only around the write
+    ///
+    /// Chunking restarts at the chunk holding `offset` and stops once a new
+    /// boundary past the write lands where an old chunk started; from there
+    /// the old chunks are kept. The result is the pointer [`put`](Self::put)
+    /// gives for the whole new file. Writing past the end fills the gap with
+    /// zeros, as the filesystem does.
+    pub fn patch(
+        &self,
+        pointer: &BlobPointer,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<BlobPointer, PijulError> {
+        if data.is_empty() {
+            return Ok(pointer.clone());
+        }
+        let end = offset + data.len() as u64;
+        let starts: Vec<u64> = pointer
+            .chunks
+            .iter()
+            .scan(0, |at, (_, length)| {
+                let start = *at;
+                *at += length;
+                Some(start)
+            })
+            .collect();
+        // The chunk holding `offset`, or the last one for a write past the
+        // end: it ends where the file did, not at a boundary
+        let first = starts
+            .partition_point(|&start| start <= offset)
+            .saturating_sub(1);
+
+        let mut chunks = pointer.chunks[..first].to_vec();
+        let mut pos = starts.get(first).copied().unwrap_or(0);
+        let mut next = first;
+        let mut pending = Vec::new();
+        let read_next = |pending: &mut Vec<u8>, next: &mut usize| -> Result<(), PijulError> {
+            let (hash, length) = &pointer.chunks[*next];
+            pending.extend_from_slice(&self.get_chunk(hash, *length)?);
+            *next += 1;
+            Ok(())
+        };
+
+        // The old bytes under the write, then the write itself
+        while next < pointer.chunks.len() && pos + (pending.len() as u64) < end {
+            read_next(&mut pending, &mut next)?;
+        }
+        if pos + (pending.len() as u64) < end {
+            pending.resize((end - pos) as usize, 0);
+        }
+        let at = (offset - pos) as usize;
+        pending[at..at + data.len()].copy_from_slice(data);
+
+        loop {
+            // A boundary is never looked for further than MAX_CHUNK ahead
+            while pending.len() < MAX_CHUNK && next < pointer.chunks.len() {
+                read_next(&mut pending, &mut next)?;
+            }
+            if pending.is_empty() {
+                break;
+            }
+            let length = next_boundary(&pending);
+            let (hash, _) = self.put_chunk(&pending[..length])?;
+            chunks.push((hash, length as u64));
+            pending.drain(..length);
+            pos += length as u64;
+            if pos >= end {
+                if let Ok(old) = starts.binary_search(&pos) {
+                    chunks.extend_from_slice(&pointer.chunks[old..]);
+                    break;
+                }
+            }
+        }
+        Ok(BlobPointer::from_chunks(chunks))
+    }
+
+    /// Cut or extend the file `pointer` stands for to `size` bytes, reading
+    /// at most the chunk the new end falls in
+    pub fn resize(&self, pointer: &BlobPointer, size: u64) -> Result<BlobPointer, PijulError> {
+        if size >= pointer.size {
+            return self.patch(
+                pointer,
+                pointer.size,
+                &vec![0; (size - pointer.size) as usize],
+            );
+        }
+        let mut chunks = Vec::new();
+        let mut at = 0;
+        for (hash, length) in &pointer.chunks {
+            if at + length <= size {
+                chunks.push((*hash, *length));
+            } else if at < size {
+                // A prefix of a chunk has no boundary in it, so it stays one
+                let mut chunk = self.get_chunk(hash, *length)?;
+                chunk.truncate((size - at) as usize);
+                let (hash, _) = self.put_chunk(&chunk)?;
+                chunks.push((hash, chunk.len() as u64));
+            }
+            at += length;
+        }
+        Ok(BlobPointer::from_chunks(chunks))
+    }
+
+    /// Store one chunk unless it is already there, returning its hash and
+    /// whether it was new
+    pub fn put_chunk(&self, chunk: &[u8]) -> Result<(blake3::Hash, bool), PijulError> {
+        let hash = blake3::hash(chunk);
+        let path = self.chunk_path(&hash);
+        if path.exists() {
+            return Ok((hash, false));
+        }
+        std::fs::create_dir_all(path.parent().expect("chunk paths have a parent"))?;
+        // Written aside and renamed, so a chunk is never seen half written
+        let partial = path.with_extension("partial");
+        match &self.key {
+            Some(key) => std::fs::write(&partial, key.seal(chunk)?)?,
+            None => std::fs::write(&partial, chunk)?,
+        }
+        std::fs::rename(&partial, &path)?;
+        Ok((hash, true))
+    }
+
+    /// Read one chunk, checking its length and hash
+    pub fn get_chunk(&self, hash: &blake3::Hash, length: u64) -> Result<Vec<u8>, PijulError> {
+        let chunk = self
+            .read_chunk_file(&self.chunk_path(hash))
+            .map_err(|e| PijulError::Blob(format!("chunk {} unreadable: {}", hash.to_hex(), e)))?;
+        if chunk.len() as u64 != length || blake3::hash(&chunk) != *hash {
+            return Err(PijulError::Blob(format!(
+                "chunk {} is corrupt",
+                hash.to_hex()
+            )));
+        }
+        Ok(chunk)
+    }
+
+    /// Number of chunks stored and the bytes they take
+    pub fn disk_usage(&self) -> Result<(usize, u64), PijulError> {
+        let mut usage = (0, 0);
+        for chunk in self.chunk_files()? {
+            usage.0 += 1;
+            usage.1 += std::fs::metadata(chunk)?.len();
+        }
+        Ok(usage)
+    }
+
+    /// Paths of every chunk in the store
+    pub fn chunk_files(&self) -> Result<Vec<PathBuf>, PijulError> {
+        let mut chunks = Vec::new();
+        if !self.dir.exists() {
+            return Ok(chunks);
+        }
+        for dir in std::fs::read_dir(&self.dir)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for chunk in std::fs::read_dir(dir.path())? {
+                let path = chunk?.path();
+                if path.extension().is_none() {
+                    chunks.push(path);
+                }
+            }
+        }
+        Ok(chunks)
+    }
+
+    /// Content of the chunk file at `path`, decrypted if the store is
+    /// encrypted
+    pub fn read_chunk_file(&self, path: &Path) -> Result<Vec<u8>, PijulError> {
+        let content = std::fs::read(path)?;
+        match &self.key {
+            Some(key) => key.open(&content),
+            None => Ok(content),
+        }
+    }
+
+    /// Chunks are spread over directories named by the first two hex digits
+    /// of their hash, keyed in an encrypted store
+    fn chunk_path(&self, hash: &blake3::Hash) -> PathBuf {
+        let name = match &self.key {
+            Some(key) => key.chunk_name(hash),
+            None => *hash,
+        };
+        let hex = name.to_hex();
+        self.dir.join(&hex[..2]).join(&hex[2..])
+    }
+}
+
+/// Length of the chunk `data` starts with
+fn next_boundary(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK {
+        return data.len();
+    }
+    let end = data.len().min(MAX_CHUNK);
+    let mut hash = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(end).skip(MIN_CHUNK) {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        if hash & BOUNDARY_MASK == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+impl PijulBackend {
+    /// The project's chunk store
+    pub fn blob_store(&self) -> BlobStore {
+        BlobStore::new(self.pijul_dir().join(BLOBS_DIR)).with_key(self.store_key().cloned())
+    }
+
+    /// What to record for `path` with `content`: a pointer if the file is
+    /// large, a block map if it is binary, else the content itself, escaped
+    /// if it starts like a stand-in
+    pub(super) fn stored_form(&self, path: &str, content: Vec<u8>) -> Result<Vec<u8>, PijulError> {
+        if self.large_files().is_large(path, content.len() as u64) {
+            Ok(self.blob_store().put(&content)?.to_bytes())
+        } else if is_binary(&content) {
+            Ok(self.put_blocks(&content)?.to_bytes())
+        } else if starts_like_stand_in(&content) {
+            let mut escaped = format!("{}\n", PLAIN_MAGIC).into_bytes();
+            escaped.extend_from_slice(&content);
+            Ok(escaped)
+        } else {
+            Ok(content)
+        }
+    }
+
+    /// The file recorded as `stored`, reassembled if it is a pointer or a
+    /// block map
+    pub(super) fn hydrate(&self, stored: Vec<u8>) -> Result<Vec<u8>, PijulError> {
+        if let Some(plain) = unescape(&stored) {
+            return Ok(plain.to_vec());
+        }
+        if let Some(pointer) = BlobPointer::parse(&stored) {
+            return self.blob_store().get(&pointer);
+        }
+        match BlockMap::parse(&stored) {
+            Some(map) => self.read_blocks(&map),
+            None => Ok(stored),
+        }
+    }
+
+    /// Whether the file at `on_disk` holds what was recorded as `stored`
+    ///
+    /// Pointers and block maps are checked by hashing the file piece by
+    /// piece against the hashes they list, without reading the blob store or
+    /// the whole file.
+    pub(super) fn matches_stored(&self, on_disk: &Path, stored: &[u8]) -> Result<bool, PijulError> {
+        let mut file = match std::fs::File::open(on_disk) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        let plain = unescape(stored).unwrap_or(stored);
+        let (size, pieces) = if let Some(pointer) = BlobPointer::parse(stored) {
+            (pointer.size, Some(pointer.chunks))
+        } else if let Some(map) = BlockMap::parse(stored) {
+            let blocks = (0..map.blocks.len())
+                .map(|index| (map.blocks[index], map.block_len(index)))
+                .collect();
+            (map.size, Some(blocks))
+        } else {
+            (plain.len() as u64, None)
+        };
+        if file.metadata()?.len() != size {
+            return Ok(false);
+        }
+
+        let Some(pieces) = pieces else {
+            let mut content = Vec::with_capacity(size as usize);
+            file.read_to_end(&mut content)?;
+            return Ok(content == plain);
+        };
+        let mut buffer = Vec::new();
+        for (hash, length) in pieces {
+            buffer.resize(length as usize, 0);
+            match file.read_exact(&mut buffer) {
+                Ok(()) => {}
+                // Shrunk since the size was checked
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+                Err(e) => return Err(e.into()),
+            }
+            if blake3::hash(&buffer) != hash {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Replace the stand-ins output into the working copy by the files they
+    /// stand for
+    pub(super) fn hydrate_working_copy(&self) -> Result<(), PijulError> {
+        let head_len = STAND_IN_MAGICS.iter().map(|magic| magic.len()).max();
+        for path in self.list_files()? {
+            let on_disk = self.working_dir().join(&path);
+            // Only files that start like one are looked at further
+            let mut head = Vec::new();
+            if let Ok(file) = std::fs::File::open(&on_disk) {
+                file.take(head_len.unwrap_or(0) as u64)
+                    .read_to_end(&mut head)?;
+            }
+            if !starts_like_stand_in(&head) {
+                continue;
+            }
+            // A file left alone by the checkout already holds its content,
+            // which may itself look like a stand-in: only what is exactly
+            // the recorded stand-in is replaced
+            let stored = self.stored_file_content(&path)?;
+            if !is_stand_in(&stored)
+                || std::fs::metadata(&on_disk)?.len() != stored.len() as u64
+                || std::fs::read(&on_disk)? != stored
+            {
+                continue;
+            }
+            std::fs::write(&on_disk, self.hydrate(stored)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Text a change inserted or removed, as lines of the file itself
+///
+/// `None` if every line is part of a pointer or block map, which stands for
+/// bytes no line of text holds. Escaped plain content loses its escape line.
+pub(super) fn file_text(recorded: &[u8]) -> Option<&[u8]> {
+    if let Some(plain) = unescape(recorded) {
+        return Some(plain);
+    }
+    let is_stand_in_line = |line: &[u8]| {
+        let Ok(line) = std::str::from_utf8(line) else {
+            return false;
+        };
+        let is_hash = |hex: &str| hex.len() == 64 && blake3::Hash::from_hex(hex).is_ok();
+        let is_number = |n: &str| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit());
+        match line.split_once(' ') {
+            _ if line == POINTER_MAGIC || line == BLOCK_MAGIC => true,
+            Some(("size" | "block", n)) => is_number(n),
+            Some(("hash", hex)) => is_hash(hex),
+            Some((hex, n)) => is_hash(hex) && is_number(n),
+            None => is_hash(line),
+        }
+    };
+    let mut lines = recorded
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .peekable();
+    if lines.peek().is_some() && lines.all(is_stand_in_line) {
+        None
+    } else {
+        Some(recorded)
+    }
+}
+
+/// Whether `content` starts like something ize records in place of a file
+fn starts_like_stand_in(content: &[u8]) -> bool {
+    STAND_IN_MAGICS
+        .iter()
+        .any(|magic| content.starts_with(magic.as_bytes()))
+}
+
+/// Whether `stored` is something ize recorded in place of a file
+fn is_stand_in(stored: &[u8]) -> bool {
+    unescape(stored).is_some()
+        || BlobPointer::parse(stored).is_some()
+        || BlockMap::parse(stored).is_some()
+}
+
+/// Plain content recorded escaped, without its escape line
+fn unescape(stored: &[u8]) -> Option<&[u8]> {
+    stored
+        .strip_prefix(PLAIN_MAGIC.as_bytes())
+        .and_then(|rest| rest.strip_prefix(b"\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::PijulQuery;
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    /// Deterministic bytes that don't repeat, so chunking has content to
+    /// follow
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks_are_shared_between_versions() {
+        let temp = TempDir::new().unwrap();
+        let store = BlobStore::new(temp.path());
+        let original = noise(1 << 20, 1);
+        let mut edited = original.clone();
+        edited[500_000] ^= 0xff;
+
+        let first = store.put(&original).unwrap();
+        let second = store.put(&edited).unwrap();
+        assert!(first.chunks.len() > 4);
+        assert!(first
+            .chunks
+            .iter()
+            .all(|(_, len)| *len as usize <= MAX_CHUNK));
+        let shared = second
+            .chunks
+            .iter()
+            .filter(|chunk| first.chunks.contains(chunk))
+            .count();
+        assert!(shared >= second.chunks.len() - 2);
+
+        assert_eq!(BlobPointer::parse(&first.to_bytes()), Some(first.clone()));
+        assert_eq!(store.get(&first).unwrap(), original);
+        assert_eq!(store.get(&second).unwrap(), edited);
+        assert_eq!(BlobPointer::parse(b"ize-blob 1\nsize 3\n"), None);
+    }
+
+    #[test]
+    fn test_patch_rechunks_only_around_the_write() {
+        let temp = TempDir::new().unwrap();
+        let store = BlobStore::new(temp.path());
+        let original = noise(2 << 20, 3);
+        let pointer = store.put(&original).unwrap();
+
+        // Chunks far from the write are never read
+        let (far, far_len) = pointer.chunks[pointer.chunks.len() / 2];
+        let far_content = store.get_chunk(&far, far_len).unwrap();
+        std::fs::remove_file(store.chunk_path(&far)).unwrap();
+
+        let mut edited = original.clone();
+        edited[1000..1007].copy_from_slice(b"patched");
+        let patched = store.patch(&pointer, 1000, b"patched").unwrap();
+        store.put_chunk(&far_content).unwrap();
+        assert_eq!(patched, store.put(&edited).unwrap());
+        assert_eq!(store.get(&patched).unwrap(), edited);
+
+        // Past the end, zero-filled, and cut back down
+        let mut grown = original.clone();
+        grown.resize(original.len() + 100, 0);
+        grown.extend_from_slice(b"tail");
+        let appended = store
+            .patch(&pointer, original.len() as u64 + 100, b"tail")
+            .unwrap();
+        assert_eq!(appended, store.put(&grown).unwrap());
+        let cut = store.resize(&appended, 300_000).unwrap();
+        assert_eq!(cut, store.put(&original[..300_000]).unwrap());
+
+        let mut tampered = patched.clone();
+        tampered.chunks.swap(0, 1);
+        assert!(store.get(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_large_files_are_recorded_as_pointers() {
+        let (_temp, mut backend) = setup();
+        backend.set_large_files(LargeFilePolicy {
+            min_size: 64 * 1024,
+            extensions: vec!["mp4".to_string()],
+        });
+        assert!(backend.large_files().is_large("clip.MP4", 10));
+        assert!(!backend.large_files().is_large("notes.txt", 10));
+
+        let content = noise(300_000, 7);
+        std::fs::write(backend.working_dir().join("data.bin"), &content).unwrap();
+        let created = backend
+            .record_file_create("data.bin", 0, &content, "Create data.bin")
+            .unwrap()
+            .unwrap();
+        backend
+            .record_file_write("data.bin", 1000, b"patched", "Patch data.bin")
+            .unwrap()
+            .unwrap();
+
+        let mut patched = content.clone();
+        patched[1000..1007].copy_from_slice(b"patched");
+        assert_eq!(backend.get_file_content("data.bin").unwrap(), patched);
+
+        // The change holds the pointer, not the data
+        let details = PijulQuery::new(&backend)
+            .get_change_details(&created)
+            .unwrap();
+        let (added, _) = details.line_counts();
+        assert!(added <= content.len() / MIN_CHUNK + 3);
+
+        // Points in history and snapshots read the file itself
+        assert_eq!(
+            backend
+                .file_content_after("data.bin", Some(&created))
+                .unwrap(),
+            content
+        );
+        let snapshot = backend.snapshot_at(Some(&created)).unwrap();
+        assert_eq!(snapshot.get_file_content("data.bin").unwrap(), content);
+    }
+
+    #[test]
+    fn test_plain_files_that_look_like_pointers_read_back_as_themselves() {
+        let (_temp, backend) = setup();
+        let elsewhere = TempDir::new().unwrap();
+        let pointer = BlobStore::new(elsewhere.path())
+            .put(&noise(300_000, 9))
+            .unwrap()
+            .to_bytes();
+        let escape_line = format!("{}\nnot escaped\n", PLAIN_MAGIC).into_bytes();
+
+        for (path, content) in [("pointer.txt", &pointer), ("plain.txt", &escape_line)] {
+            std::fs::write(backend.working_dir().join(path), content).unwrap();
+            let created = backend
+                .record_file_create(path, 0, content, "Create")
+                .unwrap()
+                .unwrap();
+            assert_eq!(&backend.get_file_content(path).unwrap(), content);
+            assert_eq!(
+                &backend.file_content_after(path, Some(&created)).unwrap(),
+                content
+            );
+
+            // Edits keep it as itself
+            backend
+                .record_file_write(path, content.len() as u64, b"more\n", "Append")
+                .unwrap()
+                .unwrap();
+            let mut appended = content.clone();
+            appended.extend_from_slice(b"more\n");
+            assert_eq!(backend.get_file_content(path).unwrap(), appended);
+
+            // A working file that already holds it is left alone
+            std::fs::write(backend.working_dir().join(path), &appended).unwrap();
+            backend.hydrate_working_copy().unwrap();
+            assert_eq!(
+                std::fs::read(backend.working_dir().join(path)).unwrap(),
+                appended
+            );
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! Checking channels out into the working copy
+//!
+//! Switching channels rewrites `working/` to match the target channel, so
+//! that later writes are recorded against the right baseline. Before that,
+//! the working copy is compared with the current channel's head: tracked
+//! files that differ or have gone missing, and untracked files the target
+//! would overwrite, count as unrecorded changes. [`DirtyPolicy`] decides
+//! whether they stop the switch, are stashed on a channel of their own, or
+//! are overwritten.
+//!
+//! Untracked files the target doesn't touch are left alone.
+//!
+//! All of this assumes `working/` is kept in step with what is recorded, as
+//! the mount keeps it. A caller that only records, and never writes the
+//! files, has every tracked file missing; [`DirtyPolicy::Discard`] is the
+//! policy for it, and the checkout then writes the target's files out.
+
+use std::io;
+
+use chrono::Utc;
+use libpijul::{MutTxnT, TxnT};
+use log::{debug, info};
+
+use super::backend::{PijulBackend, PijulError};
+
+/// Prefix of the channels unrecorded changes are stashed on
+pub const STASH_PREFIX: &str = "stash/";
+
+/// What to do with unrecorded changes when switching channels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DirtyPolicy {
+    /// Fail with [`PijulError::UnrecordedChanges`] and leave everything as is
+    #[default]
+    Refuse,
+    /// Record them on a new `stash/<channel>/<time>` channel forked from the
+    /// current one, then switch
+    Stash,
+    /// Overwrite them with the target channel's files
+    Discard,
+}
+
+impl PijulBackend {
+    /// Switch to channel `name`, checking it out into the working copy
+    ///
+    /// # Returns
+    /// The stash channel, if unrecorded changes were stashed
+    pub fn switch_channel_with(
+        &mut self,
+        name: &str,
+        policy: DirtyPolicy,
+    ) -> Result<Option<String>, PijulError> {
+        let from = self.current_channel.clone();
+        let dirty = self.dirty_paths(Some(name))?;
+        debug!(
+            "PijulBackend::switch_channel_with {} -> {} dirty={:?} policy={:?}",
+            from, name, dirty, policy
+        );
+
+        let stash = match policy {
+            _ if dirty.is_empty() => None,
+            DirtyPolicy::Refuse => return Err(PijulError::UnrecordedChanges(dirty)),
+            DirtyPolicy::Stash => Some(self.stash(&from, name, &dirty)?),
+            DirtyPolicy::Discard => {
+                info!("Discarding unrecorded changes to {:?}", dirty);
+                None
+            }
+        };
+
+        self.checkout(name)?;
+        Ok(stash)
+    }
+
+    /// Tracked files whose working-copy content differs from the current
+    /// channel's head, including ones missing from the working copy
+    pub fn unrecorded_changes(&self) -> Result<Vec<String>, PijulError> {
+        self.dirty_paths(None)
+    }
+
+    /// [`unrecorded_changes`](Self::unrecorded_changes), plus the untracked
+    /// files channel `target` would overwrite
+    ///
+    /// Files are compared one by one with what was recorded for them, so
+    /// neither channel is ever held in memory, and large and binary files
+    /// are hashed against their stand-ins instead of read back whole.
+    pub(super) fn dirty_paths(&self, target: Option<&str>) -> Result<Vec<String>, PijulError> {
+        let head = self.list_files_in(&self.current_channel)?;
+        let mut dirty = Vec::new();
+        for path in &head {
+            let recorded = self.stored_file_content(path)?;
+            if !self.matches_stored(&self.working_dir().join(path), &recorded)? {
+                dirty.push(path.clone());
+            }
+        }
+
+        // Untracked files only matter where the target would replace them
+        if let Some(target) = target {
+            for path in self.list_files_in(target)? {
+                let on_disk = self.working_dir().join(&path);
+                if head.binary_search(&path).is_ok() || !on_disk.exists() {
+                    continue;
+                }
+                let recorded = self.stored_file_content_in(target, &path)?;
+                if !self.matches_stored(&on_disk, &recorded)? {
+                    dirty.push(path);
+                }
+            }
+        }
+
+        dirty.sort();
+        Ok(dirty)
+    }
+
+    fn read_working_file(&self, path: &str) -> Result<Option<Vec<u8>>, PijulError> {
+        match std::fs::read(self.working_dir().join(path)) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record `paths` as they are in the working copy onto a fresh fork of
+    /// channel `from`
+    fn stash(&mut self, from: &str, to: &str, paths: &[String]) -> Result<String, PijulError> {
+        let stash = side_channel_name(STASH_PREFIX, from);
+        self.fork_channel(from, &stash)?;
+
+        // Point recording at the stash without touching the stored current
+        // channel, which only changes once the checkout has succeeded.
+        let previous = std::mem::replace(&mut self.current_channel, stash.clone());
+        let message = format!("Stash before switching to {}", to);
+        let result = self.record_working_files(paths, &message);
+        self.current_channel = previous;
+        result?;
+
+        info!("Stashed {} file(s) on {}", paths.len(), stash);
+        Ok(stash)
+    }
+
+    fn record_working_files(&self, paths: &[String], message: &str) -> Result<(), PijulError> {
+        for path in paths {
+            match self.read_working_file(path)? {
+                Some(content) if self.file_exists(path)? => {
+                    self.record_file_replace(path, &content, message)?;
+                }
+                Some(content) => {
+                    self.record_file_create(path, 0, &content, message)?;
+                }
+                None => {
+                    self.record_file_delete(path, message)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Output channel `name` into the working copy and make it current
+    fn checkout(&mut self, name: &str) -> Result<(), PijulError> {
+        let txn = self.arc_txn_begin()?;
+        let channel = txn
+            .read()
+            .load_channel(name)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            .ok_or_else(|| PijulError::ChannelNotFound(name.to_string()))?;
+
+        // Also deletes tracked files that aren't alive in `name`
+        libpijul::output::output_repository_no_pending(
+            &self.working_copy(),
+            &self.get_change_store(),
+            &txn,
+            &channel,
+            "",
+            true,
+            None,
+            1,
+            0,
+        )
+        .map_err(|e| PijulError::Transaction(format!("output {}: {:?}", name, e)))?;
+
+        txn.write().set_current_channel(name)?;
+        txn.commit()
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+
+        self.current_channel = name.to_string();
+        self.hydrate_working_copy()
+    }
+}
+
+/// `<prefix><from>/<time>`, the name of a channel set aside from `from`
+pub(super) fn side_channel_name(prefix: &str, from: &str) -> String {
+    format!(
+        "{}{}/{}",
+        prefix,
+        from,
+        Utc::now().format("%Y%m%dT%H%M%S%.3f")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    /// Write a file into the working copy and record it, as the mount does
+    fn create(backend: &PijulBackend, path: &str, content: &[u8]) {
+        let full = backend.working_dir().join(path);
+        std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+        std::fs::write(full, content).unwrap();
+        backend
+            .record_file_create(path, 0, content, &format!("Create {}", path))
+            .unwrap();
+    }
+
+    fn read(backend: &PijulBackend, path: &str) -> Option<Vec<u8>> {
+        std::fs::read(backend.working_dir().join(path)).ok()
+    }
+
+    /// `main` has a.txt; `feature` additionally has b.txt
+    fn two_channels() -> (TempDir, PijulBackend) {
+        let (temp, mut backend) = setup();
+        create(&backend, "a.txt", b"a\n");
+        backend.fork_channel("main", "feature").unwrap();
+        backend.switch_channel("feature").unwrap();
+        create(&backend, "b.txt", b"b\n");
+        (temp, backend)
+    }
+
+    #[test]
+    fn test_switch_checks_out_target_files() {
+        let (_temp, mut backend) = two_channels();
+
+        backend.switch_channel("main").unwrap();
+        assert_eq!(backend.current_channel(), "main");
+        assert_eq!(read(&backend, "a.txt").unwrap(), b"a\n");
+        assert!(read(&backend, "b.txt").is_none());
+
+        backend.switch_channel("feature").unwrap();
+        assert_eq!(read(&backend, "b.txt").unwrap(), b"b\n");
+
+        // Reopening picks up the switched channel
+        let reopened = PijulBackend::open(backend.pijul_dir(), backend.working_dir()).unwrap();
+        assert_eq!(reopened.current_channel(), "feature");
+    }
+
+    #[test]
+    fn test_switch_refuses_unrecorded_changes() {
+        let (_temp, mut backend) = two_channels();
+        std::fs::write(backend.working_dir().join("a.txt"), b"edited\n").unwrap();
+        std::fs::remove_file(backend.working_dir().join("b.txt")).unwrap();
+
+        assert_eq!(
+            backend.unrecorded_changes().unwrap(),
+            vec!["a.txt", "b.txt"]
+        );
+        match backend.switch_channel("main") {
+            Err(PijulError::UnrecordedChanges(paths)) => assert_eq!(paths, vec!["a.txt", "b.txt"]),
+            other => panic!("expected UnrecordedChanges, got {:?}", other),
+        }
+        assert_eq!(backend.current_channel(), "feature");
+        assert_eq!(read(&backend, "a.txt").unwrap(), b"edited\n");
+    }
+
+    #[test]
+    fn test_switch_refuses_to_overwrite_untracked_file() {
+        let (_temp, mut backend) = two_channels();
+        backend.switch_channel("main").unwrap();
+        std::fs::write(backend.working_dir().join("b.txt"), b"mine\n").unwrap();
+
+        assert!(backend.unrecorded_changes().unwrap().is_empty());
+        assert!(matches!(
+            backend.switch_channel("feature"),
+            Err(PijulError::UnrecordedChanges(paths)) if paths == vec!["b.txt"]
+        ));
+    }
+
+    #[test]
+    fn test_binary_files_are_checked_against_their_block_maps() {
+        let (_temp, mut backend) = two_channels();
+        let image: Vec<u8> = (0..40_000).map(|i| (i % 7) as u8).collect();
+        create(&backend, "image.png", &image);
+
+        // Clean or not, deciding never reads the blocks back
+        std::fs::remove_dir_all(backend.pijul_dir().join(crate::pijul::BLOBS_DIR)).unwrap();
+        assert!(backend.unrecorded_changes().unwrap().is_empty());
+
+        let mut edited = image.clone();
+        edited[20_000] = b'x';
+        std::fs::write(backend.working_dir().join("image.png"), &edited).unwrap();
+        assert_eq!(backend.unrecorded_changes().unwrap(), vec!["image.png"]);
+        assert!(matches!(
+            backend.switch_channel("main"),
+            Err(PijulError::UnrecordedChanges(paths)) if paths == vec!["image.png"]
+        ));
+    }
+
+    #[test]
+    fn test_switch_without_working_copy() {
+        let (_temp, mut backend) = setup();
+        backend
+            .record_file_create("a.txt", 0, b"a\n", "Create a.txt")
+            .unwrap();
+        backend.fork_channel("main", "feature").unwrap();
+
+        // Nothing was written, so a.txt looks deleted
+        assert!(matches!(
+            backend.switch_channel("feature"),
+            Err(PijulError::UnrecordedChanges(paths)) if paths == vec!["a.txt"]
+        ));
+        backend
+            .switch_channel_with("feature", DirtyPolicy::Discard)
+            .unwrap();
+        assert_eq!(read(&backend, "a.txt").unwrap(), b"a\n");
+    }
+
+    #[test]
+    fn test_switch_stashes_unrecorded_changes() {
+        let (_temp, mut backend) = two_channels();
+        std::fs::write(backend.working_dir().join("a.txt"), b"edited\n").unwrap();
+
+        let stash = backend
+            .switch_channel_with("main", DirtyPolicy::Stash)
+            .unwrap()
+            .unwrap();
+        assert!(stash.starts_with("stash/feature/"));
+        assert_eq!(backend.current_channel(), "main");
+        assert_eq!(read(&backend, "a.txt").unwrap(), b"a\n");
+
+        let stashed = backend.snapshot_channel(&stash).unwrap();
+        assert_eq!(stashed.get_file_content("a.txt").unwrap(), b"edited\n");
+        assert_eq!(stashed.get_file_content("b.txt").unwrap(), b"b\n");
+
+        // The channel the changes came from is untouched
+        let feature = backend.snapshot_channel("feature").unwrap();
+        assert_eq!(feature.get_file_content("a.txt").unwrap(), b"a\n");
+    }
+
+    #[test]
+    fn test_switch_discards_unrecorded_changes() {
+        let (_temp, mut backend) = two_channels();
+        std::fs::write(backend.working_dir().join("a.txt"), b"edited\n").unwrap();
+
+        let stash = backend
+            .switch_channel_with("main", DirtyPolicy::Discard)
+            .unwrap();
+        assert!(stash.is_none());
+        assert_eq!(read(&backend, "a.txt").unwrap(), b"a\n");
+        assert!(read(&backend, "b.txt").is_none());
+    }
+}
@@ -0,0 +1,334 @@
+//! Squashing recorded changes into checkpoints
+//!
+//! Recording at opcode granularity leaves thousands of small changes behind.
+//! A checkpoint replaces every change since the previous checkpoint with a
+//! single named change that has the same overall effect. The originals are
+//! either kept on an `archive/<channel>/<time>` channel forked beforehand or
+//! dropped, per [`CheckpointPolicy`].
+//!
+//! Checkpoints are ordinary changes whose description starts with
+//! [`CHECKPOINT_TAG`], which is how the previous one is found.
+//!
+//! ## How
+//!
+//! 1. Snapshot the channel head, which must match the working copy.
+//! 2. Unrecord, newest first, every change since the previous checkpoint.
+//! 3. Output into a throwaway in-memory working copy, which brings the
+//!    tracked tree back in step with the unrecorded channel.
+//! 4. Record the head snapshot against it as one change.
+//!
+//! All of this happens in one transaction; the working copy is untouched.
+
+use libpijul::change::ChangeHeader;
+use libpijul::changestore::ChangeStore as _;
+use libpijul::pristine::sanakirja::MutTxn;
+use libpijul::pristine::Hash;
+use libpijul::record::Builder as RecordBuilder;
+use libpijul::working_copy::memory::Memory;
+use libpijul::{Algorithm, ArcTxn, ChannelRef, MutTxnTExt, TxnT, DEFAULT_SEPARATOR};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+use super::backend::{PijulBackend, PijulError};
+use super::checkout::side_channel_name;
+use super::snapshot::Snapshot;
+
+/// First line of a checkpoint change's description
+pub const CHECKPOINT_TAG: &str = "ize-checkpoint";
+
+/// Prefix of the channels squashed changes are archived on
+pub const ARCHIVE_PREFIX: &str = "archive/";
+
+/// What happens to the changes a checkpoint squashes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckpointPolicy {
+    /// Keep them on a new `archive/<channel>/<time>` channel
+    #[default]
+    Archive,
+    /// Only keep the checkpoint
+    Drop,
+}
+
+/// Outcome of a checkpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointReport {
+    /// The checkpoint change
+    pub hash: Hash,
+    /// Number of changes it replaced
+    pub squashed: usize,
+    /// Channel holding the replaced changes, with [`CheckpointPolicy::Archive`]
+    pub archived_on: Option<String>,
+}
+
+impl PijulBackend {
+    /// Changes of the current channel recorded since its last checkpoint,
+    /// oldest first
+    pub fn changes_since_checkpoint(&self) -> Result<Vec<Hash>, PijulError> {
+        let log = self.list_changes()?;
+        let change_store = self.get_change_store();
+        let mut start = 0;
+        for (index, hash) in log.iter().enumerate().rev() {
+            let header = change_store.get_header(hash)?;
+            if is_checkpoint(header.description.as_deref()) {
+                start = index + 1;
+                break;
+            }
+        }
+        Ok(log[start..].to_vec())
+    }
+
+    /// Squash every change since the last checkpoint into one change
+    ///
+    /// Returns `None` if nothing was recorded since the last checkpoint.
+    pub fn checkpoint(
+        &mut self,
+        message: &str,
+        policy: CheckpointPolicy,
+    ) -> Result<Option<CheckpointReport>, PijulError> {
+        let squashed = self.changes_since_checkpoint()?;
+        if squashed.is_empty() {
+            return Ok(None);
+        }
+
+        // The squashed change is recorded from the head, so it must be what
+        // the working copy shows
+        let dirty = self.dirty_paths(None)?;
+        if !dirty.is_empty() {
+            return Err(PijulError::UnrecordedChanges(dirty));
+        }
+        let head = self.snapshot_channel(&self.current_channel)?;
+        debug!(
+            "PijulBackend::checkpoint channel={} squashing {} change(s) policy={:?}",
+            self.current_channel,
+            squashed.len(),
+            policy
+        );
+
+        let archived_on = match policy {
+            CheckpointPolicy::Archive => {
+                let archive = side_channel_name(ARCHIVE_PREFIX, &self.current_channel);
+                self.fork_channel(&self.current_channel, &archive)?;
+                Some(archive)
+            }
+            CheckpointPolicy::Drop => None,
+        };
+
+        let change_store = self.get_change_store();
+        let txn = self.arc_txn_begin()?;
+        let channel = txn
+            .read()
+            .load_channel(&self.current_channel)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            .ok_or_else(|| PijulError::ChannelNotFound(self.current_channel.clone()))?;
+
+        for hash in squashed.iter().rev() {
+            txn.write()
+                .unrecord(&change_store, &channel, hash, 0)
+                .map_err(|e| PijulError::Transaction(format!("unrecord {:?}: {:?}", hash, e)))?;
+        }
+        libpijul::output::output_repository_no_pending(
+            &Memory::new(),
+            &change_store,
+            &txn,
+            &channel,
+            "",
+            true,
+            None,
+            1,
+            0,
+        )
+        .map_err(|e| PijulError::Transaction(format!("output before checkpoint: {:?}", e)))?;
+
+        let mut header = self.header(message.into());
+        header.description = Some(format!(
+            "{}\n{} change(s) squashed",
+            CHECKPOINT_TAG,
+            squashed.len()
+        ));
+        let hash = self.record_state(&txn, &channel, head, header)?;
+
+        txn.commit()
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+
+        info!(
+            "Checkpoint {:?} squashed {} change(s) on {}",
+            hash,
+            squashed.len(),
+            self.current_channel
+        );
+        Ok(Some(CheckpointReport {
+            hash,
+            squashed: squashed.len(),
+            archived_on,
+        }))
+    }
+
+    /// Record the tree `state` as one change on top of `channel`, and apply
+    /// it there
+    ///
+    /// Tracked files missing from `state` are recorded as deleted. The
+    /// transaction is left for the caller to commit.
+    pub(super) fn record_state(
+        &self,
+        txn: &ArcTxn<MutTxn<()>>,
+        channel: &ChannelRef<MutTxn<()>>,
+        state: Snapshot,
+        header: ChangeHeader,
+    ) -> Result<Hash, PijulError> {
+        let change_store = self.get_change_store();
+        // Everything in the state, in a working copy of its own
+        let memory = Memory::new();
+        {
+            let mut t = txn.write();
+            for dir in state.list_dirs() {
+                memory.add_dir(&dir);
+                // Already tracked if the channel has it
+                let _ = t.add_dir(&dir, 0);
+            }
+            for (path, content) in state.into_files() {
+                let _ = t.add_file(&path, 0);
+                memory.add_file(&path, self.stored_form(&path, content)?);
+            }
+        }
+
+        let mut builder = RecordBuilder::new();
+        builder
+            .record(
+                txn.clone(),
+                Algorithm::default(),
+                false,
+                &DEFAULT_SEPARATOR,
+                channel.clone(),
+                &memory,
+                &change_store,
+                "",
+                1,
+            )
+            .map_err(|e| PijulError::Recording(format!("{:?}", e)))?;
+        let recorded = builder.finish();
+
+        let mut change = {
+            let t = txn.read();
+            recorded
+                .into_change(&*t, channel, header)
+                .map_err(|e| PijulError::Recording(format!("{:?}", e)))?
+        };
+        let hash = self.save_change(&mut change)?;
+        {
+            let mut t = txn.write();
+            libpijul::apply::apply_local_change(
+                &mut *t,
+                channel,
+                &change,
+                &hash,
+                &std::collections::HashMap::new(),
+            )
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+        }
+        Ok(hash)
+    }
+}
+
+/// Whether a change description marks a checkpoint
+pub fn is_checkpoint(description: Option<&str>) -> bool {
+    description.is_some_and(|d| d.lines().next() == Some(CHECKPOINT_TAG))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        std::fs::create_dir_all(backend.working_dir().join("src")).unwrap();
+        (temp, backend)
+    }
+
+    /// Write a file into the working copy and record it, as the mount does
+    fn write(backend: &PijulBackend, path: &str, content: &[u8]) {
+        let full = backend.working_dir().join(path);
+        let existed = full.exists();
+        std::fs::write(full, content).unwrap();
+        if existed {
+            backend.record_file_replace(path, content, "Write").unwrap();
+        } else {
+            backend
+                .record_file_create(path, 0, content, "Create")
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_squashes_changes() {
+        let (_temp, mut backend) = setup();
+        write(&backend, "a.txt", b"one\n");
+        write(&backend, "a.txt", b"one\ntwo\n");
+        write(&backend, "src/b.rs", b"fn b() {}\n");
+        let before = backend.list_changes().unwrap();
+
+        let report = backend
+            .checkpoint("First draft", CheckpointPolicy::Archive)
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.squashed, 3);
+        assert_eq!(backend.list_changes().unwrap(), vec![report.hash]);
+        assert_eq!(backend.get_file_content("a.txt").unwrap(), b"one\ntwo\n");
+        assert_eq!(
+            backend.get_file_content("src/b.rs").unwrap(),
+            b"fn b() {}\n"
+        );
+        assert!(backend.unrecorded_changes().unwrap().is_empty());
+
+        let archive = report.archived_on.unwrap();
+        assert!(archive.starts_with("archive/main/"));
+        assert_eq!(backend.list_changes_in(&archive).unwrap(), before);
+
+        // Nothing new since the checkpoint
+        assert!(backend.changes_since_checkpoint().unwrap().is_empty());
+        assert!(backend
+            .checkpoint("Again", CheckpointPolicy::Archive)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_checkpoint_only_squashes_since_previous() {
+        let (_temp, mut backend) = setup();
+        write(&backend, "a.txt", b"one\n");
+        let first = backend
+            .checkpoint("First", CheckpointPolicy::Drop)
+            .unwrap()
+            .unwrap();
+        assert!(first.archived_on.is_none());
+
+        write(&backend, "a.txt", b"one\ntwo\n");
+        std::fs::remove_file(backend.working_dir().join("a.txt")).unwrap();
+        backend.record_file_delete("a.txt", "Delete").unwrap();
+        write(&backend, "c.txt", b"c\n");
+        assert_eq!(backend.changes_since_checkpoint().unwrap().len(), 3);
+
+        let second = backend
+            .checkpoint("Second", CheckpointPolicy::Drop)
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.squashed, 3);
+        assert_eq!(
+            backend.list_changes().unwrap(),
+            vec![first.hash, second.hash]
+        );
+        assert!(!backend.file_exists("a.txt").unwrap());
+        assert_eq!(backend.get_file_content("c.txt").unwrap(), b"c\n");
+    }
+
+    #[test]
+    fn test_is_checkpoint() {
+        assert!(is_checkpoint(Some("ize-checkpoint\n3 change(s) squashed")));
+        assert!(!is_checkpoint(Some("something else")));
+        assert!(!is_checkpoint(None));
+    }
+}
@@ -0,0 +1,320 @@
+//! Unresolved conflicts and their markers
+//!
+//! A conflict lives in the channel: applying changes that compete (say, two
+//! insertions at the same place) leaves both sides in the graph. Outputting
+//! the channel writes every conflicted region into the file between markers:
+//!
+//! ```text
+//! >>>>>>> 1 [MNYNGT2V]
+//! one side's lines
+//! ======= 1 [QXGZOFDP]
+//! the other side's lines
+//! <<<<<<< 1
+//! ```
+//!
+//! The bracketed hashes name the changes behind each side. Resolving is just
+//! editing the file, markers included, into the wanted shape. Since the
+//! working copy is recorded by diffing it against the conflicted graph, the
+//! next recorded change (e.g. a write through the mount) is the resolution.
+
+use std::collections::BTreeMap;
+
+use super::backend::{PijulBackend, PijulError};
+use super::merge::Conflict;
+use super::snapshot::Snapshot;
+
+/// Opens a conflicted region; the first side follows
+pub const START_MARKER: &str = ">>>>>>>";
+/// Separates two sides of a conflicted region
+pub const SEPARATOR: &str = "=======";
+/// Closes a conflicted region
+pub const END_MARKER: &str = "<<<<<<<";
+
+/// A file of the current channel with unresolved conflicts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileConflict {
+    /// Path of the file
+    pub path: String,
+    /// Conflicts libpijul reported for the file
+    pub conflicts: Vec<Conflict>,
+    /// Marked regions in the file's content; empty for conflicts on the
+    /// file itself, such as name clashes
+    pub regions: Vec<ConflictRegion>,
+}
+
+/// One marked region of a conflicted file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictRegion {
+    /// 1-based line of the start marker
+    pub start_line: usize,
+    /// 1-based line of the end marker
+    pub end_line: usize,
+    /// The competing sides, in the order they appear
+    pub sides: Vec<ConflictSide>,
+}
+
+/// One side of a conflicted region
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConflictSide {
+    /// Hashes (as printed in the marker, possibly abbreviated) of the changes
+    /// this side comes from
+    pub changes: Vec<String>,
+    /// The side's lines, without line endings
+    pub lines: Vec<String>,
+}
+
+/// Find the conflict markers in a file's content
+///
+/// Regions nested inside another region are kept as lines of the enclosing
+/// side. An unterminated region is dropped.
+pub fn parse_conflict_markers(content: &[u8]) -> Vec<ConflictRegion> {
+    let text = String::from_utf8_lossy(content);
+    let mut regions = Vec::new();
+    let mut open: Option<ConflictRegion> = None;
+    let mut depth = 0usize;
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let marker = [START_MARKER, SEPARATOR, END_MARKER]
+            .into_iter()
+            .find(|marker| line.starts_with(marker));
+
+        match (marker, open.as_mut()) {
+            (Some(START_MARKER), None) => {
+                depth = 1;
+                open = Some(ConflictRegion {
+                    start_line: number,
+                    end_line: number,
+                    sides: vec![ConflictSide {
+                        changes: marker_changes(line),
+                        lines: Vec::new(),
+                    }],
+                });
+            }
+            (Some(START_MARKER), Some(region)) => {
+                depth += 1;
+                push_line(region, line);
+            }
+            (Some(SEPARATOR), Some(region)) if depth == 1 => {
+                region.sides.push(ConflictSide {
+                    changes: marker_changes(line),
+                    lines: Vec::new(),
+                });
+            }
+            (Some(END_MARKER), Some(region)) if depth == 1 => {
+                region.end_line = number;
+                regions.extend(open.take());
+                depth = 0;
+            }
+            (Some(END_MARKER), Some(region)) => {
+                depth -= 1;
+                push_line(region, line);
+            }
+            (_, Some(region)) => push_line(region, line),
+            (_, None) => {}
+        }
+    }
+
+    regions
+}
+
+fn push_line(region: &mut ConflictRegion, line: &str) {
+    if let Some(side) = region.sides.last_mut() {
+        side.lines.push(line.to_string());
+    }
+}
+
+/// The bracketed change hashes of a marker line, e.g. `>>>>>>> 1 [AB CD]`
+fn marker_changes(line: &str) -> Vec<String> {
+    let Some((_, rest)) = line.split_once('[') else {
+        return Vec::new();
+    };
+    let inside = rest.split_once(']').map_or(rest, |(inside, _)| inside);
+    inside.split_whitespace().map(str::to_string).collect()
+}
+
+impl PijulBackend {
+    /// Files of the current channel with unresolved conflicts, sorted by path
+    ///
+    /// Built from the channel itself, so it reflects what has been recorded
+    /// rather than the working copy.
+    pub fn conflicts(&self) -> Result<Vec<FileConflict>, PijulError> {
+        let head = self.snapshot_channel(&self.current_channel)?;
+
+        let mut by_path: BTreeMap<String, Vec<Conflict>> = BTreeMap::new();
+        for conflict in head.conflicts() {
+            by_path
+                .entry(conflict.path.clone())
+                .or_default()
+                .push(conflict.clone());
+        }
+
+        Ok(by_path
+            .into_iter()
+            .map(|(path, conflicts)| {
+                let regions = head
+                    .get_file_content(&path)
+                    .map(|content| parse_conflict_markers(&content))
+                    .unwrap_or_default();
+                FileConflict {
+                    path,
+                    conflicts,
+                    regions,
+                }
+            })
+            .collect())
+    }
+
+    /// Fail with [`PijulError::Conflicted`] if the current channel has
+    /// unresolved conflicts
+    pub fn ensure_resolved(&self) -> Result<(), PijulError> {
+        let head = self.snapshot_channel(&self.current_channel)?;
+        ensure_resolved_in(&head)
+    }
+}
+
+pub(super) fn ensure_resolved_in(snapshot: &Snapshot) -> Result<(), PijulError> {
+    let mut conflicted: Vec<String> = snapshot
+        .conflicts()
+        .iter()
+        .map(|conflict| conflict.path.clone())
+        .collect();
+    conflicted.dedup();
+    if conflicted.is_empty() {
+        Ok(())
+    } else {
+        Err(PijulError::Conflicted(conflicted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pijul::ConflictKind;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_conflict_markers() {
+        let content = b"before\n\
+            >>>>>>> 1 [MNYNGT2V QXGZOFDP]\n\
+            ours\n\
+            ======= 1 [AAAABBBB]\n\
+            theirs\n\
+            more theirs\n\
+            <<<<<<< 1\n\
+            after\n";
+        let regions = parse_conflict_markers(content);
+        assert_eq!(
+            regions,
+            vec![ConflictRegion {
+                start_line: 2,
+                end_line: 7,
+                sides: vec![
+                    ConflictSide {
+                        changes: vec!["MNYNGT2V".to_string(), "QXGZOFDP".to_string()],
+                        lines: vec!["ours".to_string()],
+                    },
+                    ConflictSide {
+                        changes: vec!["AAAABBBB".to_string()],
+                        lines: vec!["theirs".to_string(), "more theirs".to_string()],
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_and_unterminated_markers() {
+        let content = b">>>>>>> 1\n\
+            a\n\
+            >>>>>>> 2\n\
+            b\n\
+            ======= 2\n\
+            c\n\
+            <<<<<<< 2\n\
+            ======= 1\n\
+            d\n\
+            <<<<<<< 1\n\
+            >>>>>>> 3\n\
+            dangling\n";
+        let regions = parse_conflict_markers(content);
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].sides.len(), 2);
+        assert_eq!(regions[0].sides[0].lines.len(), 6);
+        assert_eq!(regions[0].sides[1].lines, vec!["d"]);
+        assert!(parse_conflict_markers(b"no markers\n").is_empty());
+    }
+
+    /// `main` and `exp` both append a line to a.txt; `exp` is merged into
+    /// `main`, which is checked out
+    fn conflicted() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let mut backend = PijulBackend::init(
+            &temp.path().join(".pijul"),
+            &temp.path().join("working"),
+            None,
+        )
+        .unwrap();
+        let write = |backend: &PijulBackend, content: &[u8], create: bool| {
+            std::fs::create_dir_all(backend.working_dir()).unwrap();
+            std::fs::write(backend.working_dir().join("a.txt"), content).unwrap();
+            if create {
+                backend.record_file_create("a.txt", 0, content, "Create a.txt")
+            } else {
+                backend.record_file_replace("a.txt", content, "Edit a.txt")
+            }
+            .unwrap();
+        };
+
+        write(&backend, b"a\n", true);
+        backend.fork_channel("main", "exp").unwrap();
+        backend.switch_channel("exp").unwrap();
+        write(&backend, b"a\nfrom exp\n", false);
+        backend.switch_channel("main").unwrap();
+        write(&backend, b"a\nfrom main\n", false);
+        backend.merge_channel("exp").unwrap();
+        (temp, backend)
+    }
+
+    #[test]
+    fn test_conflicts_lists_sides() {
+        let (_temp, backend) = conflicted();
+
+        let conflicts = backend.conflicts().unwrap();
+        assert_eq!(conflicts.len(), 1);
+        let file = &conflicts[0];
+        assert_eq!(file.path, "a.txt");
+        assert!(file.conflicts.iter().all(|c| c.kind == ConflictKind::Order));
+        assert_eq!(file.regions.len(), 1);
+
+        let mut sides: Vec<_> = file.regions[0]
+            .sides
+            .iter()
+            .map(|side| side.lines.join("\n"))
+            .collect();
+        sides.sort();
+        assert_eq!(sides, vec!["from exp", "from main"]);
+
+        assert!(matches!(
+            backend.ensure_resolved(),
+            Err(PijulError::Conflicted(paths)) if paths == vec!["a.txt"]
+        ));
+    }
+
+    #[test]
+    fn test_editing_the_file_resolves_the_conflict() {
+        let (_temp, backend) = conflicted();
+
+        // What a write through the mount amounts to
+        let resolved = b"a\nfrom main\nfrom exp\n";
+        std::fs::write(backend.working_dir().join("a.txt"), resolved).unwrap();
+        backend
+            .record_file_replace("a.txt", resolved, "Resolve a.txt")
+            .unwrap()
+            .unwrap();
+
+        assert!(backend.conflicts().unwrap().is_empty());
+        backend.ensure_resolved().unwrap();
+        assert_eq!(backend.get_file_content("a.txt").unwrap(), resolved);
+    }
+}
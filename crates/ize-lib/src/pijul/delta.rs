@@ -0,0 +1,472 @@
+//! Block-level deltas for binary files
+//!
+//! Line diffs make no sense for images, PDFs or spreadsheets: a one-byte
+//! edit can shift every "line" of a binary file. Binary files are instead
+//! recorded as a [`BlockMap`], one line per fixed-size block naming its
+//! BLAKE3 hash, with the blocks themselves kept in the [blob
+//! store](super::blobs):
+//!
+//! ```text
+//! ize-blocks 1
+//! size 40000
+//! block 16384
+//! 5f0c...
+//! e19a...
+//! 77b2...
+//! ```
+//!
+//! A `FileWrite` of `len` bytes at `offset` then only re-stores the blocks
+//! overlapping that range, without reading or diffing the rest of the file,
+//! and the recorded change replaces just their lines. Every such write is
+//! counted in `.pijul/deltas.toml`, which `ize status --stats` reports
+//! alongside the size of the stores.
+//!
+//! Text that starts with `ize-blocks 1` is escaped like text that starts
+//! like a blob pointer, so it is never read back as a map.
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::{PijulBackend, PijulError};
+use super::blobs::BlobPointer;
+
+/// First line of every block map
+pub(super) const BLOCK_MAGIC: &str = "ize-blocks 1";
+
+/// Size of every block but the last
+pub const BLOCK_SIZE: u64 = 16 * 1024;
+
+/// File under `.pijul/` counting block-level writes
+pub const DELTA_STATS_FILE: &str = "deltas.toml";
+
+/// How much of a file is looked at to tell whether it is binary
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// Whether `content` is binary: it has a NUL byte near the start, as git
+/// decides
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(BINARY_SNIFF_LEN).any(|&byte| byte == 0)
+}
+
+/// What Pijul records in place of a binary file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockMap {
+    /// Length of the file
+    pub size: u64,
+    /// Hash of each [`BLOCK_SIZE`] block, the last one possibly shorter
+    pub blocks: Vec<blake3::Hash>,
+}
+
+impl BlockMap {
+    /// Read a block map back from recorded content; `None` if it isn't one
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        let text = std::str::from_utf8(content).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != BLOCK_MAGIC {
+            return None;
+        }
+        let size = lines.next()?.strip_prefix("size ")?.parse().ok()?;
+        let block_size: u64 = lines.next()?.strip_prefix("block ")?.parse().ok()?;
+        if block_size != BLOCK_SIZE {
+            return None;
+        }
+        let blocks = lines
+            .map(|line| blake3::Hash::from_hex(line).ok())
+            .collect::<Option<Vec<_>>>()?;
+        (blocks.len() as u64 == block_count(size)).then_some(Self { size, blocks })
+    }
+
+    /// The block map as recorded, one block per line
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut text = format!(
+            "{}\nsize {}\nblock {}\n",
+            BLOCK_MAGIC, self.size, BLOCK_SIZE
+        );
+        for block in &self.blocks {
+            text.push_str(&block.to_hex());
+            text.push('\n');
+        }
+        text.into_bytes()
+    }
+
+    /// Length of block `index`
+    pub(super) fn block_len(&self, index: usize) -> u64 {
+        block_len(self.size, index)
+    }
+}
+
+fn block_count(size: u64) -> u64 {
+    size.div_ceil(BLOCK_SIZE)
+}
+
+fn block_len(size: u64, index: usize) -> u64 {
+    (size - index as u64 * BLOCK_SIZE).min(BLOCK_SIZE)
+}
+
+/// Running totals of block-level writes, kept in [`DELTA_STATS_FILE`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeltaStats {
+    /// Writes recorded as block deltas
+    pub writes: u64,
+    /// Size of the files those writes left, what recording each whole
+    /// would have stored
+    pub file_bytes: u64,
+    /// Bytes actually stored for them: new blocks plus changed map lines
+    pub stored_bytes: u64,
+}
+
+impl DeltaStats {
+    /// Bytes not stored thanks to block deltas
+    pub fn saved_bytes(&self) -> u64 {
+        self.file_bytes.saturating_sub(self.stored_bytes)
+    }
+}
+
+/// How the project's history is stored, as shown by `ize status --stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    /// Change files in the change store
+    pub change_files: usize,
+    /// Bytes they take
+    pub change_bytes: u64,
+    /// Chunks and blocks in the blob store
+    pub chunks: usize,
+    /// Bytes they take
+    pub chunk_bytes: u64,
+    /// Binary files at the head, recorded as block maps
+    pub binary_files: usize,
+    /// Their total size
+    pub binary_bytes: u64,
+    /// Large files at the head, recorded as pointers
+    pub large_files: usize,
+    /// Their total size
+    pub large_bytes: u64,
+    /// Block-level writes so far
+    pub deltas: DeltaStats,
+}
+
+impl PijulBackend {
+    /// Store `content` as blocks and return their map
+    pub(super) fn put_blocks(&self, content: &[u8]) -> Result<BlockMap, PijulError> {
+        let store = self.blob_store();
+        let blocks = content
+            .chunks(BLOCK_SIZE as usize)
+            .map(|block| store.put_chunk(block).map(|(hash, _)| hash))
+            .collect::<Result<_, _>>()?;
+        Ok(BlockMap {
+            size: content.len() as u64,
+            blocks,
+        })
+    }
+
+    /// The file `map` stands for
+    pub(super) fn read_blocks(&self, map: &BlockMap) -> Result<Vec<u8>, PijulError> {
+        let store = self.blob_store();
+        let mut content = Vec::with_capacity(map.size as usize);
+        for (index, hash) in map.blocks.iter().enumerate() {
+            content.extend_from_slice(&store.get_chunk(hash, map.block_len(index))?);
+        }
+        Ok(content)
+    }
+
+    /// Write `data` at `offset` into the file `map` stands for, touching only
+    /// the blocks in range
+    ///
+    /// Returns the new map and the bytes newly stored. Writing past the end
+    /// fills the gap with zeros, as the filesystem does.
+    pub(super) fn patch_blocks(
+        &self,
+        map: &BlockMap,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(BlockMap, u64), PijulError> {
+        if data.is_empty() {
+            return Ok((map.clone(), 0));
+        }
+        let end = offset + data.len() as u64;
+        let size = map.size.max(end);
+        let mut first = (offset / BLOCK_SIZE) as usize;
+        let mut last = ((end - 1) / BLOCK_SIZE) as usize;
+        if size > map.size {
+            // The old last block grows, and so does every block after it
+            first = first.min((map.size / BLOCK_SIZE) as usize);
+            last = block_count(size) as usize - 1;
+        }
+
+        let store = self.blob_store();
+        let mut blocks = map.blocks.clone();
+        blocks.resize(block_count(size) as usize, blake3::hash(&[]));
+        let mut stored = 0;
+        for (index, slot) in blocks.iter_mut().enumerate().take(last + 1).skip(first) {
+            let start = index as u64 * BLOCK_SIZE;
+            let len = block_len(size, index);
+            let mut block = if index < map.blocks.len() {
+                store.get_chunk(&map.blocks[index], map.block_len(index))?
+            } else {
+                Vec::new()
+            };
+            block.resize(len as usize, 0);
+
+            let from = offset.max(start);
+            let to = end.min(start + len);
+            if from < to {
+                block[(from - start) as usize..(to - start) as usize]
+                    .copy_from_slice(&data[(from - offset) as usize..(to - offset) as usize]);
+            }
+            let (hash, new) = store.put_chunk(&block)?;
+            if new {
+                stored += len;
+            }
+            *slot = hash;
+        }
+        // Each replaced line of the map is stored too
+        stored += (last + 1 - first) as u64 * (blake3::OUT_LEN as u64 * 2 + 1);
+        Ok((BlockMap { size, blocks }, stored))
+    }
+
+    /// Cut or extend the file `map` stands for to `size` bytes
+    pub(super) fn resize_blocks(
+        &self,
+        map: &BlockMap,
+        size: u64,
+    ) -> Result<(BlockMap, u64), PijulError> {
+        if size >= map.size {
+            return self.patch_blocks(map, map.size, &vec![0; (size - map.size) as usize]);
+        }
+        let mut blocks = map.blocks.clone();
+        blocks.truncate(block_count(size) as usize);
+        let mut stored = 0;
+        if size % BLOCK_SIZE != 0 {
+            // The new last block is cut short
+            let index = blocks.len() - 1;
+            let store = self.blob_store();
+            let mut block = store.get_chunk(&blocks[index], map.block_len(index))?;
+            block.truncate(block_len(size, index) as usize);
+            let (hash, new) = store.put_chunk(&block)?;
+            if new {
+                stored += block.len() as u64;
+            }
+            blocks[index] = hash;
+        }
+        Ok((BlockMap { size, blocks }, stored))
+    }
+
+    /// Block-level writes counted so far
+    pub fn delta_stats(&self) -> Result<DeltaStats, PijulError> {
+        let path = self.pijul_dir().join(DELTA_STATS_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| PijulError::Blob(format!("{}: {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DeltaStats::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Count a recorded block-level write leaving a file of `file_bytes`
+    pub(super) fn count_delta(&self, file_bytes: u64, stored_bytes: u64) -> Result<(), PijulError> {
+        let mut stats = self.delta_stats()?;
+        stats.writes += 1;
+        stats.file_bytes += file_bytes;
+        stats.stored_bytes += stored_bytes;
+        let content = toml::to_string_pretty(&stats)
+            .map_err(|e| PijulError::Blob(format!("{}: {}", DELTA_STATS_FILE, e)))?;
+        std::fs::write(self.pijul_dir().join(DELTA_STATS_FILE), content)?;
+        Ok(())
+    }
+
+    /// How the project's history is stored
+    pub fn storage_stats(&self) -> Result<StorageStats, PijulError> {
+        let mut stats = StorageStats {
+            deltas: self.delta_stats()?,
+            ..StorageStats::default()
+        };
+        (stats.chunks, stats.chunk_bytes) = self.blob_store().disk_usage()?;
+
+        let changes_dir = self.changes_dir();
+        if changes_dir.exists() {
+            for dir in std::fs::read_dir(&changes_dir)? {
+                let dir = dir?;
+                if !dir.file_type()?.is_dir() {
+                    continue;
+                }
+                for file in std::fs::read_dir(dir.path())? {
+                    let file = file?;
+                    stats.change_files += 1;
+                    stats.change_bytes += file.metadata()?.len();
+                }
+            }
+        }
+
+        for path in self.list_files()? {
+            let stored = self.stored_file_content(&path)?;
+            if let Some(map) = BlockMap::parse(&stored) {
+                stats.binary_files += 1;
+                stats.binary_bytes += map.size;
+            } else if let Some(pointer) = BlobPointer::parse(&stored) {
+                stats.large_files += 1;
+                stats.large_bytes += pointer.size;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::LargeFilePolicy;
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    /// A binary file of `len` bytes, different in every block
+    fn binary(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i / 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_block_map_patch_and_resize() {
+        let (_temp, backend) = setup();
+        let mut content = binary(40_000);
+        let map = backend.put_blocks(&content).unwrap();
+        assert_eq!(map.blocks.len(), 3);
+        assert_eq!(BlockMap::parse(&map.to_bytes()), Some(map.clone()));
+
+        // Inside one block
+        let (patched, stored) = backend.patch_blocks(&map, 20_000, b"hello").unwrap();
+        content[20_000..20_005].copy_from_slice(b"hello");
+        assert_eq!(backend.read_blocks(&patched).unwrap(), content);
+        assert_eq!(patched.blocks[0], map.blocks[0]);
+        assert_eq!(patched.blocks[2], map.blocks[2]);
+        assert!(stored <= BLOCK_SIZE + 65);
+
+        // Past the end, leaving a gap of zeros
+        let (grown, _) = backend.patch_blocks(&patched, 50_000, b"tail").unwrap();
+        content.resize(50_000, 0);
+        content.extend_from_slice(b"tail");
+        assert_eq!(backend.read_blocks(&grown).unwrap(), content);
+
+        let (cut, _) = backend.resize_blocks(&grown, 17_000).unwrap();
+        content.truncate(17_000);
+        assert_eq!(backend.read_blocks(&cut).unwrap(), content);
+        assert_eq!(cut.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_binary_writes_are_recorded_as_block_deltas() {
+        let (_temp, backend) = setup();
+        let mut content = binary(100_000);
+        assert!(is_binary(&content));
+        assert!(!is_binary(b"plain text\n"));
+
+        backend
+            .record_file_create("image.png", 0, &content, "Create image.png")
+            .unwrap()
+            .unwrap();
+        backend
+            .record_file_write("image.png", 70_000, b"\x00edit", "Edit image.png")
+            .unwrap()
+            .unwrap();
+        backend
+            .record_file_truncate("image.png", 90_000, "Truncate image.png")
+            .unwrap()
+            .unwrap();
+        content[70_000..70_005].copy_from_slice(b"\x00edit");
+        content.truncate(90_000);
+        assert_eq!(backend.get_file_content("image.png").unwrap(), content);
+
+        let stats = backend.storage_stats().unwrap();
+        assert_eq!(stats.binary_files, 1);
+        assert_eq!(stats.binary_bytes, 90_000);
+        assert_eq!(stats.deltas.writes, 2);
+        assert_eq!(stats.deltas.file_bytes, 100_000 + 90_000);
+        assert!(stats.deltas.saved_bytes() > 150_000);
+
+        // Text stays text
+        backend
+            .record_file_create("notes.txt", 0, b"plain\n", "Create notes.txt")
+            .unwrap();
+        assert_eq!(backend.storage_stats().unwrap().binary_files, 1);
+    }
+
+    #[test]
+    fn test_binary_file_growing_large_moves_to_blob_store() {
+        let (_temp, mut backend) = setup();
+        backend.set_large_files(LargeFilePolicy {
+            min_size: 64 * 1024,
+            extensions: Vec::new(),
+        });
+        let mut content = binary(40_000);
+        backend
+            .record_file_create("image.png", 0, &content, "Create image.png")
+            .unwrap()
+            .unwrap();
+        assert!(BlockMap::parse(&backend.stored_file_content("image.png").unwrap()).is_some());
+
+        backend
+            .record_file_write("image.png", 70_000, b"tail", "Grow image.png")
+            .unwrap()
+            .unwrap();
+        content.resize(70_000, 0);
+        content.extend_from_slice(b"tail");
+        let stored = backend.stored_file_content("image.png").unwrap();
+        assert!(BlobPointer::parse(&stored).is_some());
+        assert_eq!(backend.get_file_content("image.png").unwrap(), content);
+
+        // Later writes patch its chunks, not blocks
+        backend
+            .record_file_write("image.png", 10, b"\x00edit", "Edit image.png")
+            .unwrap()
+            .unwrap();
+        content[10..15].copy_from_slice(b"\x00edit");
+        assert_eq!(backend.get_file_content("image.png").unwrap(), content);
+        let stats = backend.storage_stats().unwrap();
+        assert_eq!((stats.binary_files, stats.large_files), (0, 1));
+        assert_eq!(stats.deltas.writes, 0);
+    }
+
+    #[test]
+    fn test_plain_files_that_look_like_block_maps_read_back_as_themselves() {
+        let (_temp, backend) = setup();
+        let (_elsewhere, other) = setup();
+        let map = other.put_blocks(&binary(40_000)).unwrap().to_bytes();
+        assert!(!is_binary(&map));
+
+        std::fs::write(backend.working_dir().join("map.txt"), &map).unwrap();
+        let created = backend
+            .record_file_create("map.txt", 0, &map, "Create map.txt")
+            .unwrap()
+            .unwrap();
+        assert_eq!(backend.get_file_content("map.txt").unwrap(), map);
+        assert_eq!(
+            backend
+                .file_content_after("map.txt", Some(&created))
+                .unwrap(),
+            map
+        );
+        let snapshot = backend.snapshot_at(Some(&created)).unwrap();
+        assert_eq!(snapshot.get_file_content("map.txt").unwrap(), map);
+
+        backend
+            .record_file_write("map.txt", map.len() as u64, b"more\n", "Append")
+            .unwrap()
+            .unwrap();
+        let mut appended = map.clone();
+        appended.extend_from_slice(b"more\n");
+        assert_eq!(backend.get_file_content("map.txt").unwrap(), appended);
+        assert_eq!(backend.storage_stats().unwrap().binary_files, 0);
+
+        // A working file that already holds it is left alone
+        std::fs::write(backend.working_dir().join("map.txt"), &appended).unwrap();
+        backend.hydrate_working_copy().unwrap();
+        assert_eq!(
+            std::fs::read(backend.working_dir().join("map.txt")).unwrap(),
+            appended
+        );
+    }
+}
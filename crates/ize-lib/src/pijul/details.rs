@@ -0,0 +1,371 @@
+//! What a single change did, file by file
+//!
+//! [`ChangeInfo`] summarizes a change; [`ChangeDetails`] lists its hunks,
+//! grouped by file, in terms a person auditing one recorded write can read:
+//! lines added and removed around a line number, files created, deleted and
+//! renamed.
+//!
+//! A change only holds the text it inserts. Removed text belongs to the
+//! changes that wrote it, so it is read back from the change store.
+//!
+//! Large and binary files are recorded as pointers and block maps, whose
+//! lines are no text of the file: edits to them show as
+//! [`HunkDetail::BinaryEdit`], and created or deleted ones list no lines.
+
+use std::collections::BTreeMap;
+
+use libpijul::change::{Atom, BaseHunk, Change, NewEdge};
+use libpijul::changestore::ChangeStore;
+use libpijul::pristine::{EdgeFlags, Hash, Vertex};
+
+use super::blobs::file_text;
+use super::query::ChangeInfo;
+
+/// A change and everything it did, as returned by
+/// [`PijulQuery::get_change_details`](super::PijulQuery::get_change_details)
+#[derive(Debug, Clone)]
+pub struct ChangeDetails {
+    /// Summary of the change, signature checked
+    pub info: ChangeInfo,
+    /// Changes this one depends on
+    pub dependencies: Vec<Hash>,
+    /// Affected files, sorted by path
+    pub files: Vec<FileChanges>,
+}
+
+/// The hunks of a change touching one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChanges {
+    /// The file, as named by the change
+    pub path: String,
+    /// Hunks in the order the change lists them
+    pub hunks: Vec<HunkDetail>,
+}
+
+/// One thing a change did to a file
+///
+/// Lines are decoded lossily and keep no newline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkDetail {
+    /// The file was created with these lines
+    FileAdded { lines: Vec<String> },
+    /// The file was deleted, losing these lines
+    FileDeleted { lines: Vec<String> },
+    /// A deleted file was brought back
+    FileUndeleted,
+    /// The file was renamed or moved from a file called `from`
+    Renamed { from: Option<String> },
+    /// Lines removed and added at `line` (from 1)
+    Edit {
+        line: usize,
+        removed: Vec<String>,
+        added: Vec<String>,
+    },
+    /// The content of a large or binary file changed
+    BinaryEdit,
+    /// A conflict was resolved, or a resolution undone
+    Resolution { line: Option<usize>, kind: String },
+}
+
+impl ChangeDetails {
+    /// Total `(added, removed)` lines, counting whole files created or
+    /// deleted
+    pub fn line_counts(&self) -> (usize, usize) {
+        self.files
+            .iter()
+            .flat_map(|file| &file.hunks)
+            .fold((0, 0), |(added, removed), hunk| match hunk {
+                HunkDetail::FileAdded { lines } => (added + lines.len(), removed),
+                HunkDetail::FileDeleted { lines } => (added, removed + lines.len()),
+                HunkDetail::Edit {
+                    removed: r,
+                    added: a,
+                    ..
+                } => (added + a.len(), removed + r.len()),
+                _ => (added, removed),
+            })
+    }
+}
+
+/// The hunks of `change`, grouped by file
+pub(super) fn file_changes<C: ChangeStore>(store: &C, change: &Change) -> Vec<FileChanges> {
+    // `None` for stand-ins
+    let file_lines = |atom: &Atom<Option<Hash>>| {
+        let bytes = atom_bytes(store, change, atom);
+        file_text(&bytes).map(lines)
+    };
+    let text = |atom: &Atom<Option<Hash>>| file_lines(atom).unwrap_or_default();
+    let mut files: BTreeMap<String, Vec<HunkDetail>> = BTreeMap::new();
+    for hunk in &change.hashed.changes {
+        let (path, detail) = match hunk {
+            BaseHunk::FileAdd { path, contents, .. } => (
+                path,
+                HunkDetail::FileAdded {
+                    lines: contents.as_ref().map(text).unwrap_or_default(),
+                },
+            ),
+            BaseHunk::FileDel { path, contents, .. } => (
+                path,
+                HunkDetail::FileDeleted {
+                    lines: contents.as_ref().map(text).unwrap_or_default(),
+                },
+            ),
+            BaseHunk::FileUndel { path, .. } => (path, HunkDetail::FileUndeleted),
+            BaseHunk::FileMove { path, del, .. } => (
+                path,
+                HunkDetail::Renamed {
+                    from: old_name(&atom_bytes(store, change, del)),
+                },
+            ),
+            BaseHunk::Edit {
+                change: atom,
+                local,
+                ..
+            } => {
+                let edit = match (atom, file_lines(atom)) {
+                    (_, None) => HunkDetail::BinaryEdit,
+                    (Atom::NewVertex(_), Some(added)) => edit(local.line, Vec::new(), added),
+                    (Atom::EdgeMap(map), Some(removed)) if map.edges.iter().all(is_deletion) => {
+                        edit(local.line, removed, Vec::new())
+                    }
+                    // Edges restoring deleted text
+                    (Atom::EdgeMap(_), Some(added)) => edit(local.line, Vec::new(), added),
+                };
+                (&local.path, edit)
+            }
+            BaseHunk::Replacement {
+                change: removed,
+                replacement,
+                local,
+                ..
+            } => (
+                &local.path,
+                match (file_lines(removed), file_lines(replacement)) {
+                    (Some(removed), Some(added)) => edit(local.line, removed, added),
+                    _ => HunkDetail::BinaryEdit,
+                },
+            ),
+            BaseHunk::SolveNameConflict { path, .. } => {
+                (path, resolution(None, "solved name conflict"))
+            }
+            BaseHunk::UnsolveNameConflict { path, .. } => {
+                (path, resolution(None, "unsolved name conflict"))
+            }
+            BaseHunk::SolveOrderConflict { local, .. } => (
+                &local.path,
+                resolution(Some(local.line), "solved order conflict"),
+            ),
+            BaseHunk::UnsolveOrderConflict { local, .. } => (
+                &local.path,
+                resolution(Some(local.line), "unsolved order conflict"),
+            ),
+            BaseHunk::ResurrectZombies { local, .. } => (
+                &local.path,
+                resolution(Some(local.line), "resurrected zombie lines"),
+            ),
+            BaseHunk::AddRoot { .. } | BaseHunk::DelRoot { .. } => continue,
+        };
+        let hunks = files.entry(path.clone()).or_default();
+        // One write touches many lines of a stand-in
+        if detail != HunkDetail::BinaryEdit || hunks.last() != Some(&HunkDetail::BinaryEdit) {
+            hunks.push(detail);
+        }
+    }
+    files
+        .into_iter()
+        .map(|(path, hunks)| FileChanges { path, hunks })
+        .collect()
+}
+
+fn edit(line: usize, removed: Vec<String>, added: Vec<String>) -> HunkDetail {
+    HunkDetail::Edit {
+        line,
+        removed,
+        added,
+    }
+}
+
+fn resolution(line: Option<usize>, kind: &str) -> HunkDetail {
+    HunkDetail::Resolution {
+        line,
+        kind: kind.to_string(),
+    }
+}
+
+fn is_deletion(edge: &NewEdge<Option<Hash>>) -> bool {
+    edge.flag.contains(EdgeFlags::DELETED)
+}
+
+/// The bytes of the vertices `atom` adds, or those its edges point to
+fn atom_bytes<C: ChangeStore>(store: &C, change: &Change, atom: &Atom<Option<Hash>>) -> Vec<u8> {
+    match atom {
+        Atom::NewVertex(vertex) => change
+            .contents
+            .get(vertex.start.us()..vertex.end.us())
+            .unwrap_or_default()
+            .to_vec(),
+        Atom::EdgeMap(map) => {
+            // Each vertex gets one edge per parent; read it once
+            let mut seen: Vec<Vertex<Option<Hash>>> = Vec::new();
+            let mut bytes = Vec::new();
+            for edge in &map.edges {
+                if seen.contains(&edge.to) {
+                    continue;
+                }
+                seen.push(edge.to);
+                bytes.extend(vertex_bytes(store, change, edge.to));
+            }
+            bytes
+        }
+    }
+}
+
+fn vertex_bytes<C: ChangeStore>(
+    store: &C,
+    change: &Change,
+    vertex: Vertex<Option<Hash>>,
+) -> Vec<u8> {
+    let range = vertex.start.us()..vertex.end.us();
+    if vertex.change.is_none() {
+        // Written by this very change
+        return change.contents.get(range).unwrap_or_default().to_vec();
+    }
+    let mut buffer = vec![0; range.len()];
+    match store.get_contents_ext(vertex, &mut buffer) {
+        Ok(read) => {
+            buffer.truncate(read);
+            buffer
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// The file name a name vertex holds, after its two bytes of inode metadata
+fn old_name(bytes: &[u8]) -> Option<String> {
+    bytes
+        .get(2..)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+}
+
+fn lines(bytes: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{PijulBackend, PijulQuery};
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    #[test]
+    fn test_details_of_create_and_edit() {
+        let (_temp, backend) = setup();
+        let path = backend.working_dir().join("a.txt");
+        std::fs::write(&path, b"one\ntwo\n").unwrap();
+        let created = backend
+            .record_file_create("a.txt", 0, b"one\ntwo\n", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        std::fs::write(&path, b"one\nTWO\n").unwrap();
+        let edited = backend
+            .record_file_replace("a.txt", b"one\nTWO\n", "Edit a.txt")
+            .unwrap()
+            .unwrap();
+        let query = PijulQuery::new(&backend);
+
+        let details = query.get_change_details(&created).unwrap();
+        assert_eq!(details.info.message, "Create a.txt");
+        assert_eq!(
+            details.files,
+            vec![FileChanges {
+                path: "a.txt".into(),
+                hunks: vec![HunkDetail::FileAdded {
+                    lines: vec!["one".into(), "two".into()]
+                }],
+            }]
+        );
+
+        let details = query.get_change_details(&edited).unwrap();
+        assert!(details.dependencies.contains(&created));
+        assert_eq!(
+            details.files[0].hunks,
+            vec![HunkDetail::Edit {
+                line: 2,
+                removed: vec!["two".into()],
+                added: vec!["TWO".into()],
+            }]
+        );
+        assert_eq!(details.line_counts(), (1, 1));
+    }
+
+    #[test]
+    fn test_details_of_rename_and_delete() {
+        let (_temp, backend) = setup();
+        backend
+            .record_file_create("old.txt", 0, b"moving\n", "Create old.txt")
+            .unwrap();
+        let renamed = backend
+            .record_file_rename("old.txt", "new.txt", "Rename old.txt")
+            .unwrap();
+        let deleted = backend
+            .record_file_delete("new.txt", "Delete new.txt")
+            .unwrap()
+            .unwrap();
+        let query = PijulQuery::new(&backend);
+
+        let details = query.get_change_details(&renamed).unwrap();
+        assert_eq!(details.files[0].path, "new.txt");
+        assert_eq!(
+            details.files[0].hunks,
+            vec![HunkDetail::Renamed {
+                from: Some("old.txt".into())
+            }]
+        );
+
+        let details = query.get_change_details(&deleted).unwrap();
+        assert_eq!(details.line_counts(), (0, 1));
+    }
+
+    #[test]
+    fn test_details_of_binary_edit() {
+        let (_temp, backend) = setup();
+        let image: Vec<u8> = (0..40_000).map(|i| (i % 7) as u8).collect();
+        let created = backend
+            .record_file_create("image.png", 0, &image, "Create image.png")
+            .unwrap()
+            .unwrap();
+        let edited = backend
+            .record_file_write("image.png", 20_000, b"\x00edit", "Edit image.png")
+            .unwrap()
+            .unwrap();
+        let query = PijulQuery::new(&backend);
+
+        let details = query.get_change_details(&created).unwrap();
+        assert_eq!(
+            details.files[0].hunks,
+            vec![HunkDetail::FileAdded { lines: Vec::new() }]
+        );
+
+        let details = query.get_change_details(&edited).unwrap();
+        assert_eq!(
+            details.files,
+            vec![FileChanges {
+                path: "image.png".into(),
+                hunks: vec![HunkDetail::BinaryEdit],
+            }]
+        );
+        assert_eq!(details.line_counts(), (0, 0));
+    }
+}
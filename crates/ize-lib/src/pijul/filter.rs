@@ -0,0 +1,203 @@
+//! Filtering a channel's changes
+//!
+//! `ize history` shows a whole channel; scripts and dashboards usually want a
+//! slice of it: the last day, one directory, one person. A [`LogFilter`]
+//! describes that slice and [`PijulQuery::log`](super::PijulQuery::log)
+//! returns the matching changes, newest first:
+//!
+//! ```ignore
+//! let filter = LogFilter::new()
+//!     .with_since(parse_timestamp("2024-05-01").unwrap())
+//!     .with_path("src/**/*.rs")?
+//!     .with_author("ada")
+//!     .with_limit(20);
+//! for change in PijulQuery::new(&backend).log(&filter)? {
+//!     println!("{}", serde_json::to_string(&change)?);
+//! }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use super::backend::PijulError;
+use super::grep::glob_matcher;
+use crate::identity::{AUTHOR_EMAIL, AUTHOR_NAME};
+
+/// Which changes of a channel to list
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    path: Option<Regex>,
+    author: Option<String>,
+    channel: Option<String>,
+    limit: Option<usize>,
+}
+
+impl LogFilter {
+    /// A filter letting every change of the current channel through
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only changes recorded at or after `since`
+    pub fn with_since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only changes recorded at or before `until`
+    pub fn with_until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Only changes touching a file matching `glob` (see [`glob_matcher`])
+    pub fn with_path(mut self, glob: &str) -> Result<Self, PijulError> {
+        self.path = Some(glob_matcher(glob)?);
+        Ok(self)
+    }
+
+    /// Only changes by an author whose name or email contains `author`,
+    /// ignoring case
+    pub fn with_author(mut self, author: &str) -> Self {
+        self.author = Some(author.to_lowercase());
+        self
+    }
+
+    /// List this channel instead of the current one
+    pub fn with_channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    /// Stop after the `limit` newest matching changes
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// The channel to list, if not the current one
+    pub fn channel(&self) -> Option<&str> {
+        self.channel.as_deref()
+    }
+
+    /// The most changes to list, if limited
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Whether the filter needs the paths a change touches, which takes
+    /// reading the whole change rather than its header
+    pub fn filters_paths(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Whether a change recorded at `time` is listed
+    pub fn covers_time(&self, time: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time <= until)
+    }
+
+    /// Whether a change with these author entries is listed
+    pub fn covers_authors<'a>(
+        &self,
+        authors: impl IntoIterator<Item = &'a BTreeMap<String, String>>,
+    ) -> bool {
+        let Some(wanted) = &self.author else {
+            return true;
+        };
+        authors.into_iter().any(|author| {
+            [AUTHOR_NAME, AUTHOR_EMAIL]
+                .iter()
+                .filter_map(|key| author.get(*key))
+                .any(|value| value.to_lowercase().contains(wanted.as_str()))
+        })
+    }
+
+    /// Whether a change touching `paths` is listed
+    pub fn covers_paths(&self, paths: &BTreeSet<String>) -> bool {
+        self.path
+            .as_ref()
+            .is_none_or(|glob| paths.iter().any(|path| glob.is_match(path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{PijulBackend, PijulQuery};
+    use super::*;
+    use crate::identity::Identity;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        std::fs::create_dir_all(backend.working_dir().join("src")).unwrap();
+        (temp, backend)
+    }
+
+    fn create(backend: &PijulBackend, path: &str) -> libpijul::pristine::Hash {
+        std::fs::write(backend.working_dir().join(path), b"x\n").unwrap();
+        backend
+            .record_file_create(path, 0, b"x\n", format!("Create {}", path))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_log_filters_by_path_author_and_limit() {
+        let (_temp, mut backend) = setup();
+        let notes = create(&backend, "notes.txt");
+        backend.set_identity(Identity::new("Ada Lovelace").with_email("ada@example.com"));
+        let lib = create(&backend, "src/lib.rs");
+        let main = create(&backend, "src/main.rs");
+        let query = PijulQuery::new(&backend);
+
+        let hashes = |filter: LogFilter| -> Vec<_> {
+            query
+                .log(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|change| change.hash)
+                .collect()
+        };
+        assert_eq!(hashes(LogFilter::new()), vec![main, lib, notes]);
+        assert_eq!(
+            hashes(LogFilter::new().with_path("*.rs").unwrap()),
+            vec![main, lib]
+        );
+        assert_eq!(
+            hashes(LogFilter::new().with_author("ADA@")),
+            vec![main, lib]
+        );
+        assert_eq!(hashes(LogFilter::new().with_author("grace")), vec![]);
+        assert_eq!(hashes(LogFilter::new().with_limit(1)), vec![main]);
+
+        let listed = query.log(&LogFilter::new().with_limit(1)).unwrap();
+        assert_eq!(listed[0].files_changed, 1);
+    }
+
+    #[test]
+    fn test_log_filters_by_time_and_channel() {
+        let (_temp, backend) = setup();
+        create(&backend, "a.txt");
+        let query = PijulQuery::new(&backend);
+        let recorded = query.list_changes_detailed().unwrap()[0].timestamp;
+
+        let around = LogFilter::new()
+            .with_since(recorded)
+            .with_until(recorded + chrono::Duration::seconds(1));
+        assert_eq!(query.log(&around).unwrap().len(), 1);
+        let before = LogFilter::new().with_until(recorded - chrono::Duration::seconds(1));
+        assert!(query.log(&before).unwrap().is_empty());
+
+        backend.create_channel("empty").unwrap();
+        let elsewhere = LogFilter::new().with_channel("empty");
+        assert!(query.log(&elsewhere).unwrap().is_empty());
+        assert!(query.log(&LogFilter::new().with_channel("nope")).is_err());
+    }
+}
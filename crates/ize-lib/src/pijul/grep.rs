@@ -0,0 +1,307 @@
+//! Searching file contents across history
+//!
+//! Every line a file ever held was inserted by some change, and the change
+//! files keep the inserted bytes. Searching those insertions finds text from
+//! every past version without reconstructing any of them, including text
+//! that has since been deleted. Pointers and block maps recorded for large
+//! and binary files hold no text of the file, so they are not searched.
+//!
+//! ```ignore
+//! let search = GrepQuery::new(r"fn parse_\w+")?
+//!     .with_path("src/**/*.rs")?
+//!     .with_since(parse_timestamp("2024-05-01").unwrap());
+//! for found in PijulQuery::new(&backend).grep(&search)? {
+//!     let hash = PijulQuery::format_hash(&found.change);
+//!     println!("{} {}:{}: {}", hash, found.path, found.line_number, found.line);
+//! }
+//! ```
+
+use chrono::{DateTime, Utc};
+use libpijul::change::{Atom, BaseHunk, Change, NewVertex};
+use libpijul::pristine::Hash;
+use regex::Regex;
+
+use super::backend::PijulError;
+use super::blobs::file_text;
+
+/// What to search for, and where
+#[derive(Debug, Clone)]
+pub struct GrepQuery {
+    pattern: Regex,
+    since: Option<DateTime<Utc>>,
+    path: Option<Regex>,
+    channel: Option<String>,
+}
+
+impl GrepQuery {
+    /// Search for lines matching the regular expression `pattern`
+    pub fn new(pattern: &str) -> Result<Self, PijulError> {
+        Ok(Self {
+            pattern: Regex::new(pattern).map_err(|e| PijulError::InvalidPattern(e.to_string()))?,
+            since: None,
+            path: None,
+            channel: None,
+        })
+    }
+
+    /// Only search changes recorded at or after `since`
+    pub fn with_since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only search files matching `glob` (see [`glob_matcher`])
+    pub fn with_path(mut self, glob: &str) -> Result<Self, PijulError> {
+        self.path = Some(glob_matcher(glob)?);
+        Ok(self)
+    }
+
+    /// Search this channel instead of the current one
+    pub fn with_channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    /// The channel to search, if not the current one
+    pub fn channel(&self) -> Option<&str> {
+        self.channel.as_deref()
+    }
+
+    /// Whether a change recorded at `time` is searched
+    pub fn covers_time(&self, time: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| time >= since)
+    }
+
+    /// Whether files at `path` are searched
+    pub fn covers_path(&self, path: &str) -> bool {
+        self.path.as_ref().is_none_or(|glob| glob.is_match(path))
+    }
+
+    /// Matching lines among those `change` inserted
+    pub(super) fn search(
+        &self,
+        hash: Hash,
+        timestamp: DateTime<Utc>,
+        change: &Change,
+    ) -> Vec<GrepMatch> {
+        let mut found = Vec::new();
+        for (path, first_line, bytes) in inserted_text(change) {
+            if !self.covers_path(path) {
+                continue;
+            }
+            let text = String::from_utf8_lossy(bytes);
+            for (index, line) in text.lines().enumerate() {
+                let line = line.trim_end_matches('\0');
+                if self.pattern.is_match(line) {
+                    found.push(GrepMatch {
+                        change: hash,
+                        timestamp,
+                        path: path.to_string(),
+                        line_number: first_line + index,
+                        line: line.to_string(),
+                    });
+                }
+            }
+        }
+        found
+    }
+}
+
+/// A line, as some change inserted it, that matches a [`GrepQuery`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepMatch {
+    /// The change that inserted the line
+    pub change: Hash,
+    /// When that change was recorded
+    pub timestamp: DateTime<Utc>,
+    /// The file, as named by the change
+    pub path: String,
+    /// Line number in the file right after the change, from 1
+    pub line_number: usize,
+    /// The line, without its newline
+    pub line: String,
+}
+
+/// `(path, first line number, bytes)` of each block of text `change` inserts
+/// into a file, leaving out stand-ins
+fn inserted_text(change: &Change) -> Vec<(&str, usize, &[u8])> {
+    let bytes = |vertex: &NewVertex<Option<Hash>>| -> &[u8] {
+        change
+            .contents
+            .get(vertex.start.us()..vertex.end.us())
+            .unwrap_or_default()
+    };
+    change
+        .hashed
+        .changes
+        .iter()
+        .filter_map(|hunk| match hunk {
+            BaseHunk::FileAdd {
+                contents: Some(Atom::NewVertex(vertex)),
+                path,
+                ..
+            } => Some((path.as_str(), 1, bytes(vertex))),
+            BaseHunk::Edit {
+                change: Atom::NewVertex(vertex),
+                local,
+                ..
+            }
+            | BaseHunk::Replacement {
+                replacement: Atom::NewVertex(vertex),
+                local,
+                ..
+            } => Some((local.path.as_str(), local.line, bytes(vertex))),
+            _ => None,
+        })
+        .filter_map(|(path, line, bytes)| Some((path, line, file_text(bytes)?)))
+        .collect()
+}
+
+/// Compile a path glob into a regular expression
+///
+/// `*` and `?` stay within one path component, `**` spans any number of
+/// them. A glob without a `/` matches file names in any directory, so `*.rs`
+/// finds `src/main.rs`.
+pub fn glob_matcher(glob: &str) -> Result<Regex, PijulError> {
+    let mut pattern = String::from(if glob.contains('/') { "^" } else { "(^|/)" });
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // "**/" also matches no directory at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).map_err(|e| PijulError::InvalidPattern(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{PijulBackend, PijulQuery};
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        std::fs::create_dir_all(backend.working_dir().join("src")).unwrap();
+        (temp, backend)
+    }
+
+    fn write(backend: &PijulBackend, path: &str, content: &[u8]) -> Hash {
+        let full = backend.working_dir().join(path);
+        let existed = full.exists();
+        std::fs::write(full, content).unwrap();
+        if existed {
+            backend.record_file_replace(path, content, "Write")
+        } else {
+            backend.record_file_create(path, 0, content, "Create")
+        }
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_grep_finds_deleted_text() {
+        let (_temp, backend) = setup();
+        let added = write(&backend, "src/lib.rs", b"fn keep() {}\nfn parse_old() {}\n");
+        write(&backend, "src/lib.rs", b"fn keep() {}\n");
+        let edited = write(&backend, "src/lib.rs", b"fn keep() {}\nfn parse_new() {}\n");
+        write(&backend, "notes.txt", b"parse_old was removed\n");
+        let query = PijulQuery::new(&backend);
+
+        let found = query
+            .grep(&GrepQuery::new(r"fn parse_\w+").unwrap())
+            .unwrap();
+        let found: Vec<(Hash, &str, usize, &str)> = found
+            .iter()
+            .map(|m| (m.change, m.path.as_str(), m.line_number, m.line.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (edited, "src/lib.rs", 2, "fn parse_new() {}"),
+                (added, "src/lib.rs", 2, "fn parse_old() {}"),
+            ]
+        );
+
+        let in_notes = GrepQuery::new("parse_old")
+            .unwrap()
+            .with_path("*.txt")
+            .unwrap();
+        assert_eq!(query.grep(&in_notes).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_grep_since_and_channel() {
+        let (_temp, backend) = setup();
+        write(&backend, "a.txt", b"needle\n");
+        let query = PijulQuery::new(&backend);
+        let recorded = query.list_changes_detailed().unwrap()[0].timestamp;
+
+        let later = GrepQuery::new("needle")
+            .unwrap()
+            .with_since(recorded + chrono::Duration::seconds(60));
+        assert!(query.grep(&later).unwrap().is_empty());
+
+        backend.create_channel("empty").unwrap();
+        let elsewhere = GrepQuery::new("needle").unwrap().with_channel("empty");
+        assert!(query.grep(&elsewhere).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_grep_skips_stand_ins() {
+        let (_temp, backend) = setup();
+        let image: Vec<u8> = (0..40_000).map(|i| (i % 7) as u8).collect();
+        write(&backend, "image.png", &image);
+        let mut edited = image.clone();
+        edited[20_000] = b'x';
+        write(&backend, "image.png", &edited);
+        write(&backend, "notes.txt", b"ize-blocks 1\nsize 3\n");
+
+        let stand_in_lines = GrepQuery::new(r"^(ize-|size |block |[0-9a-f]{64})").unwrap();
+        let found = PijulQuery::new(&backend).grep(&stand_in_lines).unwrap();
+        let found: Vec<(&str, usize, &str)> = found
+            .iter()
+            .map(|m| (m.path.as_str(), m.line_number, m.line.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("notes.txt", 1, "ize-blocks 1"), ("notes.txt", 2, "size 3")]
+        );
+    }
+
+    #[test]
+    fn test_glob_matcher() {
+        let rs = glob_matcher("*.rs").unwrap();
+        assert!(rs.is_match("main.rs"));
+        assert!(rs.is_match("src/main.rs"));
+        assert!(!rs.is_match("main.rsx"));
+
+        let src = glob_matcher("src/*.rs").unwrap();
+        assert!(src.is_match("src/main.rs"));
+        assert!(!src.is_match("src/bin/main.rs"));
+
+        let deep = glob_matcher("src/**/*.rs").unwrap();
+        assert!(deep.is_match("src/main.rs"));
+        assert!(deep.is_match("src/bin/main.rs"));
+        assert!(!deep.is_match("tests/main.rs"));
+
+        assert!(glob_matcher("a?c.txt").unwrap().is_match("abc.txt"));
+        assert!(glob_matcher("a.txt").unwrap().is_match("docs/a.txt"));
+        assert!(!glob_matcher("a.txt").unwrap().is_match("abtxt"));
+    }
+}
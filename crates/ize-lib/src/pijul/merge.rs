@@ -0,0 +1,316 @@
+//! Bringing changes from one channel into another
+//!
+//! Merging a channel and cherry-picking a single change both go through
+//! libpijul's `apply_change_rec`, which also applies whatever the change
+//! depends on. The current channel is then output into the working copy.
+//!
+//! Pijul never refuses an apply. Competing edits become conflicts inside the
+//! channel; output writes them into the files with conflict markers, and
+//! they are reported back as [`Conflict`]s.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use libpijul::pristine::Hash;
+use libpijul::{MutTxnTExt, TxnT};
+use log::debug;
+
+use super::backend::{PijulBackend, PijulError};
+use super::conflicts::ensure_resolved_in;
+
+/// How the sides of a conflict compete
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConflictKind {
+    /// Both sides inserted lines at the same place
+    Order,
+    /// One side deleted lines the other side edited around
+    Zombie,
+    /// Lines whose order became circular
+    Cyclic,
+    /// Two files were given the same name
+    Name,
+    /// One side deleted a file the other side edited
+    ZombieFile,
+    /// One file was given two names
+    MultipleNames,
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConflictKind::Order => "conflicting insertions",
+            ConflictKind::Zombie => "edit to deleted lines",
+            ConflictKind::Cyclic => "cyclic line order",
+            ConflictKind::Name => "name clash",
+            ConflictKind::ZombieFile => "edit to deleted file",
+            ConflictKind::MultipleNames => "file with several names",
+        })
+    }
+}
+
+/// A conflict in the current channel, as found when outputting it
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Conflict {
+    /// Path of the file the conflict is in
+    pub path: String,
+    /// What kind of conflict it is
+    pub kind: ConflictKind,
+    /// Line of the conflict marker in the working copy, for line conflicts
+    pub line: Option<usize>,
+}
+
+impl Conflict {
+    pub(super) fn from_output(conflict: &libpijul::output::Conflict) -> Self {
+        use libpijul::output::Conflict as C;
+        let (path, kind, line) = match conflict {
+            C::Order { path, line, .. } => (path, ConflictKind::Order, Some(*line)),
+            C::Zombie { path, line, .. } => (path, ConflictKind::Zombie, Some(*line)),
+            C::Cyclic { path, line, .. } => (path, ConflictKind::Cyclic, Some(*line)),
+            C::Name { path, .. } => (path, ConflictKind::Name, None),
+            C::ZombieFile { path, .. } => (path, ConflictKind::ZombieFile, None),
+            C::MultipleNames { path, .. } => (path, ConflictKind::MultipleNames, None),
+        };
+        Self {
+            path: path.clone(),
+            kind,
+            line,
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path, line, self.kind),
+            None => write!(f, "{}: {}", self.path, self.kind),
+        }
+    }
+}
+
+/// Outcome of merging a channel or applying a change
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplyReport {
+    /// Changes that were new to the channel, in the order they were applied;
+    /// includes dependencies pulled in along the way
+    pub applied: Vec<Hash>,
+    /// Conflicts in the channel afterwards, sorted by path and line
+    pub conflicts: Vec<Conflict>,
+}
+
+impl PijulBackend {
+    /// Apply every change of channel `from` that the current channel lacks
+    pub fn merge_channel(&mut self, from: &str) -> Result<ApplyReport, PijulError> {
+        let incoming = self.list_changes_in(from)?;
+        debug!(
+            "PijulBackend::merge_channel from={} into={} incoming={}",
+            from,
+            self.current_channel,
+            incoming.len()
+        );
+        self.apply_changes(&incoming)
+    }
+
+    /// Apply one change, and anything it depends on, to the current channel
+    ///
+    /// The change must be in the change store, i.e. recorded on some channel.
+    pub fn apply_change(&mut self, hash: &Hash) -> Result<ApplyReport, PijulError> {
+        debug!(
+            "PijulBackend::apply_change hash={:?} into={}",
+            hash, self.current_channel
+        );
+        self.apply_changes(std::slice::from_ref(hash))
+    }
+
+    fn apply_changes(&mut self, hashes: &[Hash]) -> Result<ApplyReport, PijulError> {
+        // Outputting afterwards would overwrite anything not yet recorded,
+        // and new conflicts are only readable once the old ones are gone
+        let dirty = self.dirty_paths(None)?;
+        if !dirty.is_empty() {
+            return Err(PijulError::UnrecordedChanges(dirty));
+        }
+        ensure_resolved_in(&self.snapshot_channel(&self.current_channel)?)?;
+
+        let before: HashSet<Hash> = self.list_changes()?.into_iter().collect();
+        let change_store = self.get_change_store();
+        let txn = self.arc_txn_begin()?;
+        let channel = txn
+            .read()
+            .load_channel(&self.current_channel)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            .ok_or_else(|| PijulError::ChannelNotFound(self.current_channel.clone()))?;
+
+        for hash in hashes.iter().filter(|hash| !before.contains(hash)) {
+            txn.write()
+                .apply_change_rec(&change_store, &mut *channel.write(), hash)
+                .map_err(|e| PijulError::Apply(format!("{:?}: {:?}", hash, e)))?;
+        }
+
+        let conflicts = libpijul::output::output_repository_no_pending(
+            &self.working_copy(),
+            &change_store,
+            &txn,
+            &channel,
+            "",
+            true,
+            None,
+            1,
+            0,
+        )
+        .map_err(|e| PijulError::Transaction(format!("output after apply: {:?}", e)))?;
+
+        txn.commit()
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+        self.hydrate_working_copy()?;
+
+        let applied = self
+            .list_changes()?
+            .into_iter()
+            .filter(|hash| !before.contains(hash))
+            .collect();
+        let mut conflicts: Vec<Conflict> = conflicts.iter().map(Conflict::from_output).collect();
+        conflicts.sort();
+        conflicts.dedup();
+
+        Ok(ApplyReport { applied, conflicts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    /// Write a file into the working copy and record it, as the mount does
+    fn write(backend: &PijulBackend, path: &str, content: &[u8]) -> Hash {
+        let full = backend.working_dir().join(path);
+        let existed = full.exists();
+        std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+        std::fs::write(full, content).unwrap();
+        let message = format!("Write {}", path);
+        let hash = if existed {
+            backend.record_file_replace(path, content, &message)
+        } else {
+            backend.record_file_create(path, 0, content, &message)
+        };
+        hash.unwrap().unwrap()
+    }
+
+    fn read(backend: &PijulBackend, path: &str) -> Option<String> {
+        std::fs::read_to_string(backend.working_dir().join(path)).ok()
+    }
+
+    /// `main` has a.txt; `exp` is forked from it and checked out
+    fn with_experiment() -> (TempDir, PijulBackend) {
+        let (temp, mut backend) = setup();
+        write(&backend, "a.txt", b"a\n");
+        backend.fork_channel("main", "exp").unwrap();
+        backend.switch_channel("exp").unwrap();
+        (temp, backend)
+    }
+
+    #[test]
+    fn test_merge_channel_brings_changes_back() {
+        let (_temp, mut backend) = with_experiment();
+        let b = write(&backend, "b.txt", b"b\n");
+        backend.switch_channel("main").unwrap();
+        assert!(read(&backend, "b.txt").is_none());
+
+        let report = backend.merge_channel("exp").unwrap();
+        assert_eq!(report.applied, vec![b]);
+        assert!(report.conflicts.is_empty());
+        assert_eq!(read(&backend, "b.txt").unwrap(), "b\n");
+        assert!(backend.list_changes().unwrap().contains(&b));
+
+        // Merging again has nothing left to apply
+        assert!(backend.merge_channel("exp").unwrap().applied.is_empty());
+    }
+
+    #[test]
+    fn test_apply_single_change() {
+        let (_temp, mut backend) = with_experiment();
+        write(&backend, "b.txt", b"b\n");
+        let c = write(&backend, "c.txt", b"c\n");
+        backend.switch_channel("main").unwrap();
+
+        let report = backend.apply_change(&c).unwrap();
+        assert_eq!(report.applied, vec![c]);
+        assert_eq!(read(&backend, "c.txt").unwrap(), "c\n");
+        assert!(read(&backend, "b.txt").is_none());
+    }
+
+    #[test]
+    fn test_apply_pulls_in_dependencies() {
+        let (_temp, mut backend) = with_experiment();
+        let created = write(&backend, "b.txt", b"one\n");
+        let edited = write(&backend, "b.txt", b"one\ntwo\n");
+        backend.switch_channel("main").unwrap();
+
+        let report = backend.apply_change(&edited).unwrap();
+        assert_eq!(report.applied.len(), 2);
+        assert!(report.applied.contains(&created));
+        assert!(report.applied.contains(&edited));
+        assert_eq!(read(&backend, "b.txt").unwrap(), "one\ntwo\n");
+    }
+
+    #[test]
+    fn test_merge_reports_conflicts() {
+        let (_temp, mut backend) = with_experiment();
+        write(&backend, "a.txt", b"a\nfrom exp\n");
+        backend.switch_channel("main").unwrap();
+        write(&backend, "a.txt", b"a\nfrom main\n");
+
+        let report = backend.merge_channel("exp").unwrap();
+        assert_eq!(report.applied.len(), 1);
+        assert!(!report.conflicts.is_empty());
+        assert!(report.conflicts.iter().all(|c| c.path == "a.txt"));
+
+        let content = read(&backend, "a.txt").unwrap();
+        assert!(content.contains("from exp"));
+        assert!(content.contains("from main"));
+        assert!(content.contains(">>>>>>>"));
+
+        // Nothing more comes in until the conflict is resolved
+        backend.switch_channel("exp").unwrap();
+        write(&backend, "c.txt", b"c\n");
+        backend.switch_channel("main").unwrap();
+        assert!(matches!(
+            backend.merge_channel("exp"),
+            Err(PijulError::Conflicted(paths)) if paths == vec!["a.txt"]
+        ));
+    }
+
+    #[test]
+    fn test_apply_refuses_unrecorded_changes() {
+        let (_temp, mut backend) = with_experiment();
+        let b = write(&backend, "b.txt", b"b\n");
+        backend.switch_channel("main").unwrap();
+        std::fs::write(backend.working_dir().join("a.txt"), b"edited\n").unwrap();
+
+        assert!(matches!(
+            backend.apply_change(&b),
+            Err(PijulError::UnrecordedChanges(paths)) if paths == vec!["a.txt"]
+        ));
+        assert!(!backend.list_changes().unwrap().contains(&b));
+    }
+
+    #[test]
+    fn test_conflict_display() {
+        let conflict = Conflict {
+            path: "src/lib.rs".to_string(),
+            kind: ConflictKind::Order,
+            line: Some(12),
+        };
+        assert_eq!(
+            conflict.to_string(),
+            "src/lib.rs:12: conflicting insertions"
+        );
+    }
+}
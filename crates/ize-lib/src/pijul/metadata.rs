@@ -0,0 +1,65 @@
+//! Change metadata in Pijul headers
+//!
+//! Pijul headers have no field for a [`ChangeMetadata`], so it goes into the
+//! description as the TOML table [`ChangeMetadata::to_description`] writes,
+//! and the change is timestamped with the opcode's time.
+//! [`PijulQuery`](super::PijulQuery) parses it back into
+//! [`ChangeInfo::metadata`](super::ChangeInfo::metadata).
+
+use libpijul::change::ChangeHeader;
+
+use crate::storage::{ChangeMessage, ChangeMetadata};
+
+impl ChangeMessage {
+    /// The Pijul header for the change, timestamped with the metadata's time
+    /// if there is one
+    pub(super) fn into_header(self) -> ChangeHeader {
+        let timestamp = self
+            .metadata
+            .as_ref()
+            .and_then(|metadata| {
+                jiff::Timestamp::new(
+                    metadata.time.timestamp(),
+                    metadata.time.timestamp_subsec_nanos() as i32,
+                )
+                .ok()
+            })
+            .unwrap_or_else(jiff::Timestamp::now);
+        ChangeHeader {
+            message: self.message,
+            authors: vec![],
+            description: self.metadata.as_ref().map(ChangeMetadata::to_description),
+            timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::{Opcode, Operation};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_header_uses_opcode_time() {
+        let opcode = Opcode::with_timestamp(
+            42,
+            1_714_573_800_123_456_789,
+            Operation::FileWrite {
+                path: PathBuf::from("src/main.rs"),
+                offset: 4096,
+                data: vec![0; 120],
+            },
+        );
+        let metadata = ChangeMetadata::from_opcode(&opcode);
+        let header = ChangeMessage::new("edit src/main.rs")
+            .with_metadata(metadata)
+            .into_header();
+        assert_eq!(header.message, "edit src/main.rs");
+        assert_eq!(header.timestamp.as_second(), 1_714_573_800);
+        assert!(header.description.is_some());
+
+        let plain = ChangeMessage::from("plain").into_header();
+        assert_eq!(plain.description, None);
+    }
+}
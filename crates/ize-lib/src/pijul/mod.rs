@@ -10,12 +10,46 @@
 //! ## Modules
 //!
 //! - [`backend`]: Core PijulBackend implementation
+//! - [`blame`]: Crediting each line of a file to the change that wrote it
+//! - [`blobs`]: Content-addressed chunk store for large files, recorded as pointers
+//! - [`checkout`]: Switching channels and updating the working copy to match
+//! - [`checkpoint`]: Squashing the changes since the last checkpoint into one
+//! - [`conflicts`]: Unresolved conflicts and their markers in working files
+//! - [`delta`]: Block-level deltas for binary files, and storage statistics
+//! - [`details`]: Per-file hunks of a single change
+//! - [`filter`]: Filtering a channel's changes by time, path, author and count
+//! - [`grep`]: Searching the text inserted by every change
+//! - [`merge`]: Merging channels and applying single changes
+//! - [`metadata`]: How change metadata (opcode, time, process) is stored in Pijul headers
 //! - [`operations`]: Opcode recording - converts filesystem operations into Pijul changes
+//! - [`past`]: Reading files at a point in history without forking the channel
 //! - [`query`]: Query operations for reading repository data
+//! - [`retention`]: Compacting old history and removing unused change files
+//! - [`snapshot`]: Historical, read-only views of a channel
+//! - [`tags`]: Named tags on channel states
+//! - [`undo`]: Unrecording recent changes, keeping them on a side channel
+//! - [`vault`]: Encryption at rest for change files and chunks
 
 pub mod backend;
+pub mod blame;
+pub mod blobs;
+pub mod checkout;
+pub mod checkpoint;
+pub mod conflicts;
+pub mod delta;
+pub mod details;
+pub mod filter;
+pub mod grep;
+pub mod merge;
+pub mod metadata;
 pub mod operations;
+pub mod past;
 pub mod query;
+pub mod retention;
+pub mod snapshot;
+pub mod tags;
+pub mod undo;
+pub mod vault;
 
 // Re-export key types from backend module
 pub use backend::{
@@ -23,8 +57,62 @@ pub use backend::{
     PRISTINE_DIR,
 };
 
+// Re-export key types from blame module
+pub use blame::{BlameLine, BlamedLine};
+
+// Re-export key types from blobs module
+pub use blobs::{BlobPointer, BlobStore, LargeFilePolicy, BLOBS_DIR};
+
+// Re-export key types from checkout module
+pub use checkout::{DirtyPolicy, STASH_PREFIX};
+
+// Re-export key types from checkpoint module
+pub use checkpoint::{CheckpointPolicy, CheckpointReport, ARCHIVE_PREFIX, CHECKPOINT_TAG};
+
+// Re-export key types from conflicts module
+pub use conflicts::{parse_conflict_markers, ConflictRegion, ConflictSide, FileConflict};
+
+// Re-export key types from delta module
+pub use delta::{is_binary, BlockMap, DeltaStats, StorageStats, BLOCK_SIZE, DELTA_STATS_FILE};
+
+// Re-export key types from details module
+pub use details::{ChangeDetails, FileChanges, HunkDetail};
+
+// Re-export key types from filter module
+pub use filter::LogFilter;
+
+// Re-export key types from grep module
+pub use grep::{glob_matcher, GrepMatch, GrepQuery};
+
+// Re-export key types from merge module
+pub use merge::{ApplyReport, Conflict, ConflictKind};
+
+// Re-export the message types changes are recorded with
+pub use crate::storage::{ChangeMessage, ChangeMetadata};
+
 // Re-export key types from operations module
 pub use operations::{OpcodeError, OpcodeRecordingBackend};
 
+// Re-export key types from past module
+pub use past::ChangeRef;
+
 // Re-export key types from query module
-pub use query::{ChangeInfo, FileInfo, PijulQuery};
+pub use query::{parse_timestamp, ChangeInfo, FileInfo, PijulQuery};
+
+// Re-export key types from retention module
+pub use retention::{GcReport, RetentionPolicy};
+
+// Re-export key types from snapshot module
+pub use snapshot::Snapshot;
+
+// Re-export key types from tags module
+pub use tags::{Tag, TAGS_FILE};
+
+// Re-export key types from undo module
+pub use undo::{UndoReport, UndoTarget, UNDONE_PREFIX};
+
+// Re-export key types from vault module
+pub use vault::{
+    default_keyfile, EncryptionSettings, Key, KeySource, ENCRYPTION_FILE, KEYS_DIR, KEY_FILE_VAR,
+    NEW_PASSPHRASE_VAR, PASSPHRASE_VAR,
+};
@@ -1,55 +1,39 @@
-//! Opcode recording operations for the Pijul backend
+//! Opcode recording operations
 //!
-//! This module provides `OpcodeRecordingBackend`, a pure adapter that translates
-//! filesystem opcodes into Pijul change recording operations. It has no knowledge
-//! of paths, repositories, or initialization - it only wraps a `PijulBackend` and
-//! delegates operations to it.
+//! This module provides `OpcodeRecordingBackend`, a pure adapter that records
+//! filesystem opcodes into a [`VersionStore`]. It has no knowledge of paths,
+//! repositories, or initialization - it only wraps a store and delegates
+//! operations to it. The store is the project's `PijulBackend` unless stated
+//! otherwise; this module also implements [`VersionStore`] for it.
 //!
 //! ## Architecture
 //!
 //! ```text
-//! Opcode -> OpcodeRecordingBackend -> PijulBackend -> libpijul
+//! Opcode -> OpcodeRecordingBackend<S> -> S: VersionStore
+//!                                        PijulBackend -> libpijul
 //! ```
 //!
 //! `OpcodeRecordingBackend` is responsible for:
-//! - Translating opcode types to appropriate PijulBackend method calls
-//! - Converting paths and parameters to the right format
-//! - Generating commit messages from opcode metadata
-//! - Error conversion from PijulError to OpcodeError
+//! - Generating a one-line message for each change, plus [`ChangeMetadata`]
+//!   recording the opcode's sequence number, time, kind and process
+//! - Error conversion from StorageError to OpcodeError
 //!
-//! All Pijul interaction happens through the wrapped `PijulBackend`.
-//! The calling code is responsible for creating and managing `PijulBackend` instances.
+//! `PijulBackend`'s [`VersionStore`] implementation translates each operation
+//! into the matching `record_file_*` call; versions are change hashes. Its
+//! `PijulError`s become `StorageError`s, those with no store-level meaning
+//! as `StorageError::Backend`.
+//! The calling code is responsible for creating and managing the store.
 
 use log::debug;
 
-/// Backend for applying opcodes to Pijul
-///
-/// This is a thin adapter that translates filesystem opcodes into calls
-/// to `PijulBackend`'s high-level API. It owns a `PijulBackend` and delegates
-/// all Pijul operations to it.
-///
-/// # Example
-///
-/// ```rust,ignore
-/// use ize_lib::pijul::{PijulBackend, OpcodeRecordingBackend};
-/// use ize_lib::operations::Opcode;
-///
-/// // Create or open a PijulBackend
-/// let pijul = PijulBackend::init(&pijul_dir, &working_dir, None)?;
-///
-/// // Wrap it in OpcodeRecordingBackend
-/// let mut backend = OpcodeRecordingBackend::new(pijul);
-///
-/// // Apply opcodes
-/// let hash = backend.apply_opcode(&opcode)?;
-/// ```
-use std::path::Path;
-
 use libpijul::pristine::Hash;
 use thiserror::Error;
 
-use super::PijulBackend;
+use super::{PijulBackend, PijulError, PijulQuery};
 use crate::operations::{Opcode, Operation};
+use crate::storage::{
+    path_to_str, ChangeMessage, ChangeMetadata, StorageError, VersionInfo, VersionStore,
+};
 
 /// Errors that can occur during opcode operations
 #[derive(Error, Debug)]
@@ -57,6 +41,9 @@ pub enum OpcodeError {
     #[error("Pijul error: {0}")]
     Pijul(String),
 
+    #[error("Storage error: {0}")]
+    Storage(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -67,46 +54,69 @@ pub enum OpcodeError {
     UnsupportedOperation(String),
 }
 
-impl From<super::PijulError> for OpcodeError {
-    fn from(e: super::PijulError) -> Self {
+impl From<PijulError> for OpcodeError {
+    fn from(e: PijulError) -> Self {
         OpcodeError::Pijul(format!("{}", e))
     }
 }
 
-/// Backend for applying opcodes to Pijul
+impl From<StorageError> for OpcodeError {
+    fn from(e: StorageError) -> Self {
+        match e {
+            StorageError::Io(e) => OpcodeError::Io(e),
+            StorageError::PathConversion(path) => OpcodeError::PathConversion(path),
+            StorageError::UnsupportedOperation(op) => OpcodeError::UnsupportedOperation(op),
+            e => OpcodeError::Storage(format!("{}", e)),
+        }
+    }
+}
+
+/// How a `PijulBackend` failure is reported through [`VersionStore`]
+impl From<PijulError> for StorageError {
+    fn from(e: PijulError) -> Self {
+        match e {
+            PijulError::Io(e) => StorageError::Io(e),
+            PijulError::FileNotFound(path) => StorageError::FileNotFound(path),
+            PijulError::ChangeNotFound(hash) => StorageError::VersionNotFound(hash),
+            PijulError::PathConversion(path) => StorageError::PathConversion(path),
+            e => StorageError::Backend(e.to_string()),
+        }
+    }
+}
+
+/// Backend for applying opcodes to a version store
 ///
-/// This is a thin adapter that translates filesystem opcodes into calls
-/// to `PijulBackend`'s high-level API. It owns a `PijulBackend` and delegates
-/// all Pijul operations to it.
+/// This is a thin adapter that turns filesystem opcodes into versions of the
+/// store it owns, `PijulBackend` by default.
 ///
 /// # Example
 ///
 /// ```rust,ignore
 /// use ize_lib::pijul::{PijulBackend, OpcodeRecordingBackend};
-/// use ize_lib::operations::Opcode;
+/// use ize_lib::storage::MemoryStore;
 ///
-/// // Create or open a PijulBackend
+/// // Record into a Pijul repository
 /// let pijul = PijulBackend::init(&pijul_dir, &working_dir, None)?;
-///
-/// // Wrap it in OpcodeRecordingBackend
-/// let mut backend = OpcodeRecordingBackend::new(pijul);
-///
-/// // Apply opcodes
+/// let backend = OpcodeRecordingBackend::new(pijul);
 /// let hash = backend.apply_opcode(&opcode)?;
+///
+/// // Or into anything else implementing VersionStore
+/// let backend = OpcodeRecordingBackend::new(MemoryStore::new());
+/// let version = backend.apply_opcode(&opcode)?;
 /// ```
-pub struct OpcodeRecordingBackend {
-    /// The underlying Pijul backend (our only interface to Pijul)
-    pijul: PijulBackend,
+pub struct OpcodeRecordingBackend<S: VersionStore = PijulBackend> {
+    /// The underlying store (our only interface to version history)
+    store: S,
 }
 
-impl OpcodeRecordingBackend {
-    /// Create a new OpcodeRecordingBackend wrapping a PijulBackend
+impl<S: VersionStore> OpcodeRecordingBackend<S> {
+    /// Create a new OpcodeRecordingBackend wrapping a store
     ///
     /// This is the only way to construct an `OpcodeRecordingBackend`.
-    /// The calling code must create the `PijulBackend` first.
+    /// The calling code must create the store first.
     ///
     /// # Arguments
-    /// * `pijul` - An initialized PijulBackend instance
+    /// * `store` - An initialized store, such as a `PijulBackend`
     ///
     /// # Example
     ///
@@ -119,35 +129,77 @@ impl OpcodeRecordingBackend {
     /// // Wrap it in the adapter
     /// let backend = OpcodeRecordingBackend::new(pijul);
     /// ```
-    pub fn new(pijul: PijulBackend) -> Self {
-        Self { pijul }
+    pub fn new(store: S) -> Self {
+        Self { store }
     }
 
-    /// Apply an opcode and record it as a Pijul change
-    ///
-    /// This method translates the opcode into the appropriate PijulBackend
-    /// method call and returns the hash of the created change.
+    /// Apply an opcode and record it as a new version of the store
     ///
     /// # Arguments
     /// * `opcode` - The opcode to apply
     ///
     /// # Returns
     ///
-    /// Returns `Some(Hash)` if a change was created, or `None` if no change
-    /// was needed (e.g., writing the same content).
+    /// Returns `Some(version)` (a change hash for Pijul) if a version was
+    /// recorded, or `None` if no change was needed (e.g., writing the same
+    /// content).
     ///
     /// # Errors
     ///
     /// Returns an error if the operation fails or is unsupported.
-    pub fn apply_opcode(&self, opcode: &Opcode) -> Result<Option<Hash>, OpcodeError> {
+    pub fn apply_opcode(&self, opcode: &Opcode) -> Result<Option<S::Version>, OpcodeError> {
         debug!(
             "OpcodeRecordingBackend::apply_opcode seq={} op={:?}",
             opcode.seq(),
             opcode.op()
         );
-        let message = format!("Opcode #{}: {:?}", opcode.seq(), opcode.op());
+        let message = ChangeMessage::new(change_message(opcode.op()))
+            .with_metadata(ChangeMetadata::from_opcode(opcode));
+        let result = self.store.record(opcode.op(), message);
+        debug!("OpcodeRecordingBackend: result={:?}", result);
+        result.map_err(Into::into)
+    }
+
+    /// Get a reference to the underlying store
+    pub fn store(&self) -> &S {
+        &self.store
+    }
 
-        match opcode.op() {
+    /// Get a mutable reference to the underlying store
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+}
+
+impl OpcodeRecordingBackend<PijulBackend> {
+    /// Get a reference to the underlying PijulBackend
+    ///
+    /// This provides access to all PijulBackend query and management methods:
+    /// - `get_file_content()`, `list_changes()` - Query operations
+    /// - `current_channel()`, `pijul_dir()`, `working_dir()` - Repository info
+    /// - `create_channel()`, `switch_channel()` - Channel management
+    pub fn pijul(&self) -> &PijulBackend {
+        &self.store
+    }
+
+    /// Get a mutable reference to the underlying PijulBackend
+    ///
+    /// This allows mutable operations like channel switching:
+    ///
+    /// ```rust,ignore
+    /// backend.pijul_mut().switch_channel("feature")?;
+    /// ```
+    pub fn pijul_mut(&mut self) -> &mut PijulBackend {
+        &mut self.store
+    }
+}
+
+/// The current channel as a version store: each version is a change
+impl VersionStore for PijulBackend {
+    type Version = Hash;
+
+    fn record(&self, op: &Operation, message: ChangeMessage) -> Result<Option<Hash>, StorageError> {
+        match op {
             Operation::FileCreate {
                 path,
                 mode,
@@ -155,60 +207,47 @@ impl OpcodeRecordingBackend {
             } => {
                 let path_str = path_to_str(path)?;
                 debug!(
-                    "OpcodeRecordingBackend: FileCreate path={:?} content_len={}",
+                    "PijulBackend: FileCreate path={:?} content_len={}",
                     path_str,
                     content.len()
                 );
-                let result = self
-                    .pijul
-                    .record_file_create(path_str, *mode, content, &message);
-                debug!("OpcodeRecordingBackend: FileCreate result={:?}", result);
-                result.map_err(Into::into)
+                Ok(self.record_file_create(path_str, *mode, content, message)?)
             }
 
             Operation::FileWrite { path, offset, data } => {
                 let path_str = path_to_str(path)?;
                 debug!(
-                    "OpcodeRecordingBackend: FileWrite path={:?} offset={} data_len={}",
+                    "PijulBackend: FileWrite path={:?} offset={} data_len={}",
                     path_str,
                     offset,
                     data.len()
                 );
-                let result = self
-                    .pijul
-                    .record_file_write(path_str, *offset, data, &message);
-                debug!("OpcodeRecordingBackend: FileWrite result={:?}", result);
-                result.map_err(Into::into)
+                Ok(self.record_file_write(path_str, *offset, data, message)?)
             }
 
             Operation::FileTruncate { path, new_size } => {
                 let path_str = path_to_str(path)?;
                 debug!(
-                    "OpcodeRecordingBackend: FileTruncate path={:?} new_size={}",
+                    "PijulBackend: FileTruncate path={:?} new_size={}",
                     path_str, new_size
                 );
-                let result = self
-                    .pijul
-                    .record_file_truncate(path_str, *new_size, &message);
-                debug!("OpcodeRecordingBackend: FileTruncate result={:?}", result);
-                result.map_err(Into::into)
+                Ok(self.record_file_truncate(path_str, *new_size, message)?)
             }
 
             Operation::FileDelete { path } => {
                 let path_str = path_to_str(path)?;
-                debug!("OpcodeRecordingBackend: FileDelete path={:?}", path_str);
-                let result = self.pijul.record_file_delete(path_str, &message);
-                debug!("OpcodeRecordingBackend: FileDelete result={:?}", result);
-                result.map_err(Into::into)
+                debug!("PijulBackend: FileDelete path={:?}", path_str);
+                Ok(self.record_file_delete(path_str, message)?)
             }
 
             Operation::FileRename { old_path, new_path } => {
                 let old_path_str = path_to_str(old_path)?;
                 let new_path_str = path_to_str(new_path)?;
-                self.pijul
-                    .record_file_rename(old_path_str, new_path_str, &message)
-                    .map(Some)
-                    .map_err(Into::into)
+                Ok(Some(self.record_file_rename(
+                    old_path_str,
+                    new_path_str,
+                    message,
+                )?))
             }
 
             // Unsupported operations
@@ -220,48 +259,83 @@ impl OpcodeRecordingBackend {
             | Operation::SetOwnership { .. }
             | Operation::SymlinkCreate { .. }
             | Operation::SymlinkDelete { .. }
-            | Operation::HardLinkCreate { .. } => Err(OpcodeError::UnsupportedOperation(format!(
-                "{:?}",
-                opcode.op()
-            ))),
+            | Operation::HardLinkCreate { .. } => {
+                Err(StorageError::UnsupportedOperation(format!("{:?}", op)))
+            }
         }
     }
 
-    /// Get a reference to the underlying PijulBackend
-    ///
-    /// This provides access to all PijulBackend query and management methods:
-    /// - `get_file_content()`, `list_changes()` - Query operations
-    /// - `current_channel()`, `pijul_dir()`, `working_dir()` - Repository info
-    /// - `create_channel()`, `switch_channel()` - Channel management
-    pub fn pijul(&self) -> &PijulBackend {
-        &self.pijul
+    fn file_at(&self, path: &str, version: Option<&Hash>) -> Result<Vec<u8>, StorageError> {
+        Ok(match version {
+            None => self.get_file_content(path)?,
+            Some(hash) => self.file_content_after(path, Some(hash))?,
+        })
     }
 
-    /// Get a mutable reference to the underlying PijulBackend
-    ///
-    /// This allows mutable operations like channel switching:
-    ///
-    /// ```rust,ignore
-    /// backend.pijul_mut().switch_channel("feature")?;
-    /// ```
-    pub fn pijul_mut(&mut self) -> &mut PijulBackend {
-        &mut self.pijul
+    fn files_at(&self, version: Option<&Hash>) -> Result<Vec<String>, StorageError> {
+        Ok(match version {
+            None => self.list_files()?,
+            Some(hash) => self.list_files_after(Some(hash))?,
+        })
+    }
+
+    fn history(&self, path: Option<&str>) -> Result<Vec<VersionInfo<Hash>>, StorageError> {
+        let query = PijulQuery::new(self);
+        let changes = match path {
+            None => query.list_changes_detailed()?,
+            Some(path) => query.file_history(path)?,
+        };
+        Ok(changes
+            .into_iter()
+            .map(|change| VersionInfo {
+                version: change.hash,
+                message: change.message,
+                time: change.timestamp,
+                metadata: change.metadata,
+            })
+            .collect())
     }
 }
 
-/// Convert a Path to a Pijul-compatible string
-///
-/// Pijul expects forward-slash separated paths, so we need to convert
-/// platform-specific paths to this format.
-fn path_to_str(path: &Path) -> Result<&str, OpcodeError> {
-    path.to_str()
-        .ok_or_else(|| OpcodeError::PathConversion(format!("Invalid UTF-8 in path: {:?}", path)))
+/// One-line summary of an operation, e.g. `edit src/main.rs (+120 bytes at 4096)`
+fn change_message(op: &Operation) -> String {
+    match op {
+        Operation::FileCreate { path, content, .. } => {
+            format!(
+                "create {} ({})",
+                path.display(),
+                bytes(content.len() as u64)
+            )
+        }
+        Operation::FileWrite { path, offset, data } => format!(
+            "edit {} (+{} at {})",
+            path.display(),
+            bytes(data.len() as u64),
+            offset
+        ),
+        Operation::FileTruncate { path, new_size } => {
+            format!("truncate {} to {}", path.display(), bytes(*new_size))
+        }
+        Operation::FileRename { old_path, new_path }
+        | Operation::DirRename { old_path, new_path } => {
+            format!("rename {} -> {}", old_path.display(), new_path.display())
+        }
+        _ => format!("{} {}", op.kind(), op.path().display()),
+    }
+}
+
+fn bytes(n: u64) -> String {
+    if n == 1 {
+        "1 byte".to_string()
+    } else {
+        format!("{} bytes", n)
+    }
 }
 
-impl std::fmt::Debug for OpcodeRecordingBackend {
+impl<S: VersionStore + std::fmt::Debug> std::fmt::Debug for OpcodeRecordingBackend<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("OpcodeRecordingBackend")
-            .field("pijul", &self.pijul)
+            .field("store", &self.store)
             .finish()
     }
 }
@@ -407,6 +481,79 @@ mod tests {
         assert_eq!(&content[10..15], b"World");
     }
 
+    #[test]
+    fn test_change_message() {
+        let path = || PathBuf::from("src/main.rs");
+        let cases = [
+            (
+                Operation::FileWrite {
+                    path: path(),
+                    offset: 4096,
+                    data: vec![0; 120],
+                },
+                "edit src/main.rs (+120 bytes at 4096)",
+            ),
+            (
+                Operation::FileCreate {
+                    path: path(),
+                    mode: 0o644,
+                    content: b"x".to_vec(),
+                },
+                "create src/main.rs (1 byte)",
+            ),
+            (
+                Operation::FileTruncate {
+                    path: path(),
+                    new_size: 0,
+                },
+                "truncate src/main.rs to 0 bytes",
+            ),
+            (Operation::FileDelete { path: path() }, "delete src/main.rs"),
+            (
+                Operation::FileRename {
+                    old_path: path(),
+                    new_path: PathBuf::from("src/lib.rs"),
+                },
+                "rename src/main.rs -> src/lib.rs",
+            ),
+        ];
+        for (op, expected) in cases {
+            assert_eq!(change_message(&op), expected);
+        }
+    }
+
+    #[test]
+    fn test_change_records_metadata() {
+        let (_temp, backend) = setup_test_repo();
+
+        let opcode = Opcode::with_timestamp(
+            7,
+            1_714_573_800_000_000_000,
+            Operation::FileCreate {
+                path: PathBuf::from("test.txt"),
+                mode: 0o644,
+                content: b"Hello".to_vec(),
+            },
+        )
+        .with_caller(crate::filesystems::Caller {
+            uid: 1000,
+            gid: 1000,
+            pid: 4242,
+        });
+        let hash = backend.apply_opcode(&opcode).unwrap().unwrap();
+
+        let info = super::super::PijulQuery::new(backend.pijul())
+            .get_change_info(&hash)
+            .unwrap();
+        assert_eq!(info.message, "create test.txt (5 bytes)");
+        assert_eq!(info.timestamp.timestamp(), 1_714_573_800);
+        let metadata = info.metadata.unwrap();
+        assert_eq!(metadata.seq, 7);
+        assert_eq!(metadata.kind, "create");
+        assert_eq!(metadata.uid, Some(1000));
+        assert_eq!(metadata.pid, Some(4242));
+    }
+
     #[test]
     fn test_unsupported_operations() {
         let (_temp, backend) = setup_test_repo();
@@ -502,6 +649,68 @@ mod tests {
         assert_eq!(changes.len(), 1, "Should have 1 change after first file");
     }
 
+    #[test]
+    fn test_records_into_any_store() {
+        let backend = OpcodeRecordingBackend::new(crate::storage::MemoryStore::new());
+
+        let opcode = Opcode::new(
+            1,
+            Operation::FileCreate {
+                path: PathBuf::from("test.txt"),
+                mode: 0o644,
+                content: b"Hello".to_vec(),
+            },
+        );
+        assert_eq!(backend.apply_opcode(&opcode).unwrap(), Some(1));
+        assert_eq!(backend.store().file_at("test.txt", None).unwrap(), b"Hello");
+
+        let history = backend.store().history(None).unwrap();
+        assert_eq!(history[0].message, "create test.txt (5 bytes)");
+        assert_eq!(history[0].metadata.as_ref().unwrap().seq, 1);
+
+        let delete = Opcode::new(
+            2,
+            Operation::FileDelete {
+                path: PathBuf::from("missing.txt"),
+            },
+        );
+        assert!(matches!(
+            backend.apply_opcode(&delete),
+            Err(OpcodeError::Storage(_))
+        ));
+    }
+
+    #[test]
+    fn test_pijul_version_store() {
+        let (_temp, backend) = setup_test_repo();
+        let store = backend.pijul();
+
+        let create = Operation::FileCreate {
+            path: PathBuf::from("test.txt"),
+            mode: 0o644,
+            content: b"v1".to_vec(),
+        };
+        let v1 = store
+            .record(&create, ChangeMessage::new("create"))
+            .unwrap()
+            .unwrap();
+        let write = Operation::FileWrite {
+            path: PathBuf::from("test.txt"),
+            offset: 0,
+            data: b"v2".to_vec(),
+        };
+        store.record(&write, ChangeMessage::new("edit")).unwrap();
+
+        assert_eq!(store.file_at("test.txt", Some(&v1)).unwrap(), b"v1");
+        assert_eq!(store.file_at("test.txt", None).unwrap(), b"v2");
+        assert_eq!(store.files_at(Some(&v1)).unwrap(), vec!["test.txt"]);
+
+        let history = store.history(Some("test.txt")).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].version, v1);
+        assert_eq!(history[1].message, "edit");
+    }
+
     #[test]
     fn test_file_delete_sequence() {
         let (_temp, backend) = setup_test_repo();
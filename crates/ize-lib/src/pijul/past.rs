@@ -0,0 +1,427 @@
+//! Reading files as they were, straight from the graph
+//!
+//! A [`Snapshot`](super::Snapshot) forks the channel and unrecords every
+//! later change, which costs as much as the history since the target. To
+//! read one file, or list the tree, at some point in the past, that is
+//! wasteful: the channel's graph still holds every vertex and edge, deleted
+//! ones included, and each edge names the change that introduced it.
+//!
+//! An edge was alive at log position `p` if
+//! - it is alive now and was introduced at or before `p`, or
+//! - it is deleted, by a change applied after `p`, and its target vertex was
+//!   added at or before `p`.
+//!
+//! Walking only those edges, from the root through name vertices to the
+//! file and then through its lines, gives the file as it was at `p`, inside
+//! a read-only transaction and without touching the channel.
+//!
+//! Lines that were in conflict at `p` come out one side after the other,
+//! without markers; take a snapshot to see conflicts.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use libpijul::changestore::filesystem::FileSystem as ChangeStore;
+use libpijul::changestore::ChangeStore as _;
+use libpijul::pristine::sanakirja::Txn;
+use libpijul::pristine::{
+    iter_adjacent, ChangeId, ChannelTxnT, EdgeFlags, GraphTxnT, Hash, InodeMetadata,
+    SerializedEdge, Vertex,
+};
+use libpijul::TxnT;
+use log::debug;
+
+use super::backend::{PijulBackend, PijulError};
+use super::snapshot::Snapshot;
+
+/// A point in a channel's history to read files at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeRef {
+    /// The latest change
+    Head,
+    /// Right after this change
+    Change(Hash),
+    /// Right after the last change recorded at or before this time
+    Time(DateTime<Utc>),
+    /// Anything [`PijulQuery::resolve_at`](super::PijulQuery::resolve_at)
+    /// accepts: a hash or unique prefix, a tag or a timestamp
+    Named(String),
+}
+
+impl From<Hash> for ChangeRef {
+    fn from(hash: Hash) -> Self {
+        ChangeRef::Change(hash)
+    }
+}
+
+impl From<DateTime<Utc>> for ChangeRef {
+    fn from(time: DateTime<Utc>) -> Self {
+        ChangeRef::Time(time)
+    }
+}
+
+impl From<&str> for ChangeRef {
+    fn from(spec: &str) -> Self {
+        ChangeRef::Named(spec.to_string())
+    }
+}
+
+impl PijulBackend {
+    /// Content of `path` right after `change` in the current channel
+    ///
+    /// `None` is the empty state before the first change, where no file
+    /// exists.
+    pub fn file_content_after(
+        &self,
+        path: &str,
+        change: Option<&Hash>,
+    ) -> Result<Vec<u8>, PijulError> {
+        let not_found = || PijulError::FileNotFound(path.to_string());
+        let Some(change) = change else {
+            return Err(not_found());
+        };
+        self.with_past(self.current_channel(), change, |past| {
+            let mut dir = Vertex::ROOT;
+            let mut components = path.split('/').filter(|c| !c.is_empty()).peekable();
+            while let Some(component) = components.next() {
+                let is_last = components.peek().is_none();
+                // Directories on the way, a file at the end
+                dir = past
+                    .entries(dir)?
+                    .into_iter()
+                    .find(|(name, _, is_dir)| name == component && *is_dir != is_last)
+                    .map(|(_, vertex, _)| vertex)
+                    .ok_or_else(not_found)?;
+            }
+            if dir == Vertex::ROOT {
+                return Err(not_found());
+            }
+            self.hydrate(past.file_content(dir)?)
+        })
+    }
+
+    /// Files right after `change` in the current channel, sorted by path
+    pub fn list_files_after(&self, change: Option<&Hash>) -> Result<Vec<String>, PijulError> {
+        let Some(change) = change else {
+            return Ok(Vec::new());
+        };
+        self.with_past(self.current_channel(), change, |past| {
+            Ok(past.files()?.into_iter().map(|(path, _)| path).collect())
+        })
+    }
+
+    /// The tracked tree right after `change` in the current channel
+    ///
+    /// Like [`snapshot_at`](Self::snapshot_at), but read from the graph: no
+    /// conflicts are reported, and directories only appear as parents of
+    /// files.
+    pub fn snapshot_after(&self, change: Option<&Hash>) -> Result<Snapshot, PijulError> {
+        let Some(change) = change else {
+            return Ok(Snapshot::default());
+        };
+        let files = self.with_past(self.current_channel(), change, |past| {
+            past.files()?
+                .into_iter()
+                .map(|(path, inode)| Ok((path, self.hydrate(past.file_content(inode)?)?)))
+                .collect::<Result<Vec<_>, PijulError>>()
+        })?;
+        Ok(Snapshot::from_files(Some(*change), files))
+    }
+
+    /// Files at the head of channel `name`, sorted by path
+    ///
+    /// Only names are read, never contents.
+    pub(super) fn list_files_in(&self, name: &str) -> Result<Vec<String>, PijulError> {
+        let Some(head) = self.list_changes_in(name)?.pop() else {
+            return Ok(Vec::new());
+        };
+        self.with_past(name, &head, |past| {
+            Ok(past.files()?.into_iter().map(|(path, _)| path).collect())
+        })
+    }
+
+    /// Run `read` over channel `name` as it was right after `change`
+    fn with_past<R>(
+        &self,
+        name: &str,
+        change: &Hash,
+        read: impl FnOnce(&Past) -> Result<R, PijulError>,
+    ) -> Result<R, PijulError> {
+        let txn = self.txn_begin()?;
+        let channel = txn
+            .load_channel(name)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            .ok_or_else(|| PijulError::ChannelNotFound(name.to_string()))?;
+        let channel = channel.read();
+
+        let mut position = None;
+        for entry in txn
+            .log(&*channel, 0)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+        {
+            let (at, (hash, _)) = entry.map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+            if Hash::from(*hash) == *change {
+                position = Some(at);
+                break;
+            }
+        }
+        let position =
+            position.ok_or_else(|| PijulError::ChangeNotFound(format!("{:?}", change)))?;
+        debug!(
+            "PijulBackend::with_past channel={} change={:?} position={}",
+            name, change, position
+        );
+
+        read(&Past {
+            txn: &txn,
+            graph: txn.graph(&*channel),
+            changes: txn.changes(&*channel),
+            store: self.get_change_store(),
+            position,
+        })
+    }
+}
+
+/// A channel's graph, seen as it was at one position of its log
+struct Past<'a> {
+    txn: &'a Txn,
+    graph: &'a <Txn as GraphTxnT>::Graph,
+    changes: &'a <Txn as ChannelTxnT>::Changeset,
+    store: ChangeStore,
+    position: u64,
+}
+
+impl Past<'_> {
+    /// Whether `change` had been applied at the position
+    fn applied(&self, change: ChangeId) -> bool {
+        if change == ChangeId::ROOT {
+            return true;
+        }
+        matches!(
+            self.txn.get_changeset(self.changes, &change),
+            Ok(Some(at)) if u64::from(*at) <= self.position
+        )
+    }
+
+    fn alive(&self, edge: &SerializedEdge) -> bool {
+        let target_existed = self.applied(edge.dest().change);
+        if edge.flag().contains(EdgeFlags::DELETED) {
+            target_existed && !self.applied(edge.introduced_by())
+        } else {
+            target_existed && self.applied(edge.introduced_by())
+        }
+    }
+
+    /// Children of `vertex` alive at the position, through folder edges
+    /// (names and inodes) or through content edges
+    fn children(
+        &self,
+        vertex: Vertex<ChangeId>,
+        folder: bool,
+    ) -> Result<Vec<Vertex<ChangeId>>, PijulError> {
+        let mut children = Vec::new();
+        for edge in iter_adjacent(
+            self.txn,
+            self.graph,
+            vertex,
+            EdgeFlags::empty(),
+            EdgeFlags::all(),
+        )
+        .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+        {
+            let edge = edge.map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+            let flag = edge.flag();
+            if flag.contains(EdgeFlags::PARENT)
+                || flag.contains(EdgeFlags::FOLDER) != folder
+                || !self.alive(edge)
+            {
+                continue;
+            }
+            let child = *self
+                .txn
+                .find_block(self.graph, edge.dest())
+                .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+            if !children.contains(&child) {
+                children.push(child);
+            }
+        }
+        Ok(children)
+    }
+
+    fn contents(&self, vertex: Vertex<ChangeId>) -> Result<Vec<u8>, PijulError> {
+        let mut buffer = vec![0; vertex.end - vertex.start];
+        if !buffer.is_empty() {
+            self.store
+                .get_contents(
+                    |id| {
+                        self.txn
+                            .get_external(&id)
+                            .ok()
+                            .flatten()
+                            .map(|hash| Hash::from(*hash))
+                    },
+                    vertex,
+                    &mut buffer,
+                )
+                .map_err(|e| PijulError::ChangeStore(format!("{:?}", e)))?;
+        }
+        Ok(buffer)
+    }
+
+    /// `(name, inode vertex, is_dir)` for each entry of a directory
+    fn entries(
+        &self,
+        dir: Vertex<ChangeId>,
+    ) -> Result<Vec<(String, Vertex<ChangeId>, bool)>, PijulError> {
+        let mut entries = Vec::new();
+        for name_vertex in self.children(dir, true)? {
+            // Two bytes of inode metadata, then the name
+            let name = self.contents(name_vertex)?;
+            if name.len() < 2 {
+                continue;
+            }
+            let is_dir = InodeMetadata::from_basename(&name[..2]).is_dir();
+            let name = String::from_utf8_lossy(&name[2..]).into_owned();
+            for inode in self.children(name_vertex, true)? {
+                entries.push((name.clone(), inode, is_dir));
+            }
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+
+    /// `(path, inode vertex)` of every file, sorted by path
+    fn files(&self) -> Result<Vec<(String, Vertex<ChangeId>)>, PijulError> {
+        let mut files = Vec::new();
+        let mut dirs = vec![(String::new(), Vertex::ROOT)];
+        while let Some((prefix, dir)) = dirs.pop() {
+            for (name, vertex, is_dir) in self.entries(dir)? {
+                let path = format!("{}{}", prefix, name);
+                if is_dir {
+                    dirs.push((format!("{}/", path), vertex));
+                } else {
+                    files.push((path, vertex));
+                }
+            }
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(files)
+    }
+
+    /// The lines below a file's inode vertex, in graph order
+    fn file_content(&self, inode: Vertex<ChangeId>) -> Result<Vec<u8>, PijulError> {
+        // Reversed depth-first postorder is a topological order
+        let mut postorder = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![(inode, false)];
+        while let Some((vertex, done)) = stack.pop() {
+            if done {
+                postorder.push(vertex);
+                continue;
+            }
+            if !visited.insert(vertex) {
+                continue;
+            }
+            stack.push((vertex, true));
+            for child in self.children(vertex, false)?.into_iter().rev() {
+                if !visited.contains(&child) {
+                    stack.push((child, false));
+                }
+            }
+        }
+
+        let mut content = Vec::new();
+        for vertex in postorder.into_iter().rev() {
+            content.extend(self.contents(vertex)?);
+        }
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        std::fs::create_dir_all(backend.working_dir().join("src")).unwrap();
+        (temp, backend)
+    }
+
+    fn write(backend: &PijulBackend, path: &str, content: &[u8]) -> Hash {
+        let full = backend.working_dir().join(path);
+        let existed = full.exists();
+        std::fs::write(full, content).unwrap();
+        if existed {
+            backend.record_file_replace(path, content, "Write")
+        } else {
+            backend.record_file_create(path, 0, content, "Create")
+        }
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_file_content_after_matches_snapshots() {
+        let (_temp, backend) = setup();
+        let first = write(&backend, "src/lib.rs", b"one\ntwo\n");
+        let second = write(&backend, "src/lib.rs", b"one\nTWO\nthree\n");
+        let third = write(&backend, "src/lib.rs", b"three\n");
+
+        for change in [first, second, third] {
+            let snapshot = backend.snapshot_at(Some(&change)).unwrap();
+            assert_eq!(
+                backend
+                    .file_content_after("src/lib.rs", Some(&change))
+                    .unwrap(),
+                snapshot.get_file_content("src/lib.rs").unwrap()
+            );
+        }
+        // The channel itself is untouched
+        assert_eq!(backend.list_changes().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_deleted_and_later_files() {
+        let (_temp, backend) = setup();
+        let a = write(&backend, "a.txt", b"a\n");
+        let b = write(&backend, "src/b.rs", b"b\n");
+        std::fs::remove_file(backend.working_dir().join("a.txt")).unwrap();
+        let deleted = backend
+            .record_file_delete("a.txt", "Delete a.txt")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(backend.list_files_after(Some(&a)).unwrap(), vec!["a.txt"]);
+        assert_eq!(
+            backend.list_files_after(Some(&b)).unwrap(),
+            vec!["a.txt", "src/b.rs"]
+        );
+        assert_eq!(
+            backend.list_files_after(Some(&deleted)).unwrap(),
+            vec!["src/b.rs"]
+        );
+        assert!(backend.list_files_after(None).unwrap().is_empty());
+
+        assert_eq!(
+            backend.file_content_after("a.txt", Some(&b)).unwrap(),
+            b"a\n"
+        );
+        assert!(matches!(
+            backend.file_content_after("a.txt", Some(&deleted)),
+            Err(PijulError::FileNotFound(_))
+        ));
+        assert!(matches!(
+            backend.file_content_after("src/b.rs", Some(&a)),
+            Err(PijulError::FileNotFound(_))
+        ));
+        assert!(matches!(
+            backend.file_content_after("src", Some(&b)),
+            Err(PijulError::FileNotFound(_))
+        ));
+    }
+}
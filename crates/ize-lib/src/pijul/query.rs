@@ -21,13 +21,26 @@
 //! }
 //! ```
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use libpijul::change::{BaseHunk, Change, ChangeHeader};
 use libpijul::changestore::ChangeStore;
 use libpijul::pristine::Hash;
 use libpijul::{Base32, TxnT, TxnTExt};
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeSet, HashMap};
 use std::path::PathBuf;
 
 use super::backend::{PijulBackend, PijulError};
+use super::blame::BlameLine;
+use super::checkpoint::is_checkpoint;
+use super::conflicts::FileConflict;
+use super::details::{file_changes, ChangeDetails};
+use super::filter::LogFilter;
+use super::grep::{GrepMatch, GrepQuery};
+use super::past::ChangeRef;
+use super::tags::Tag;
+use crate::identity::{SignatureStatus, AUTHOR_NAME, SIGNATURE_FIELD};
+use crate::storage::ChangeMetadata;
 
 /// Detailed information about a change/commit
 #[derive(Debug, Clone)]
@@ -44,6 +57,10 @@ pub struct ChangeInfo {
     pub authors: Vec<String>,
     /// Number of files affected by this change
     pub files_changed: usize,
+    /// Where the change came from, for changes recorded from opcodes
+    pub metadata: Option<ChangeMetadata>,
+    /// Whether the change is signed by its author
+    pub signature: SignatureStatus,
 }
 
 impl ChangeInfo {
@@ -83,6 +100,62 @@ impl ChangeInfo {
         }
     }
 
+    /// Whether this change is a checkpoint squashing earlier changes
+    pub fn is_checkpoint(&self) -> bool {
+        is_checkpoint(self.description.as_deref())
+    }
+
+    /// Build the info a change header carries; the signature is left
+    /// unchecked and `files_changed` at zero
+    fn from_header(hash: Hash, header: ChangeHeader) -> Self {
+        // Convert timestamp to DateTime
+        // jiff::Timestamp can be converted to seconds via as_second()
+        let timestamp = Utc
+            .timestamp_opt(header.timestamp.as_second(), 0)
+            .single()
+            .unwrap_or_else(Utc::now);
+
+        // Extract author names from the Author struct
+        let authors: Vec<String> = header
+            .authors
+            .iter()
+            .filter_map(|author| {
+                // Author is a BTreeMap wrapper, try to get "name" key
+                author.0.get(AUTHOR_NAME).cloned()
+            })
+            .collect();
+
+        Self {
+            hash,
+            message: header.message,
+            metadata: ChangeMetadata::parse(header.description.as_deref()),
+            description: header.description,
+            timestamp,
+            authors,
+            files_changed: 0,
+            signature: SignatureStatus::Unchecked,
+        }
+    }
+
+    /// Build the info for a whole change, verifying its signature
+    fn from_change(hash: Hash, change: Change) -> Self {
+        let signature = SignatureStatus::check(
+            change.hashed.header.authors.iter().map(|author| &author.0),
+            hash.to_base32().as_bytes(),
+            change
+                .unhashed
+                .as_ref()
+                .and_then(|unhashed| unhashed.get(SIGNATURE_FIELD))
+                .and_then(|signature| signature.as_str()),
+        );
+        let files_changed = touched_paths(&change).len();
+
+        let mut info = Self::from_header(hash, change.hashed.header);
+        info.files_changed = files_changed;
+        info.signature = signature;
+        info
+    }
+
     /// Get the first author name, or "Unknown" if none
     pub fn primary_author(&self) -> &str {
         self.authors
@@ -92,6 +165,43 @@ impl ChangeInfo {
     }
 }
 
+/// How a change appears in machine-readable output such as `ize log --format json`
+#[derive(Serialize)]
+struct ChangeRecord<'a> {
+    hash: String,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    timestamp: DateTime<Utc>,
+    authors: &'a [String],
+    files_changed: usize,
+    checkpoint: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a ChangeMetadata>,
+    signature: &'a SignatureStatus,
+}
+
+impl Serialize for ChangeInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ChangeRecord {
+            hash: self.hash_full(),
+            message: &self.message,
+            // Opcode metadata has its own field
+            description: self
+                .description
+                .as_deref()
+                .filter(|_| self.metadata.is_none()),
+            timestamp: self.timestamp,
+            authors: &self.authors,
+            files_changed: self.files_changed,
+            checkpoint: self.is_checkpoint(),
+            metadata: self.metadata.as_ref(),
+            signature: &self.signature,
+        }
+        .serialize(serializer)
+    }
+}
+
 /// Information about a file in the repository
 #[derive(Debug, Clone)]
 pub struct FileInfo {
@@ -160,36 +270,7 @@ impl<'a> PijulQuery<'a> {
 
             // Get the change header for metadata
             match change_store.get_header(&hash) {
-                Ok(header) => {
-                    // Convert timestamp to DateTime
-                    // jiff::Timestamp can be converted to seconds via as_second()
-                    let timestamp = Utc
-                        .timestamp_opt(header.timestamp.as_second(), 0)
-                        .single()
-                        .unwrap_or_else(Utc::now);
-
-                    // Extract author names from the Author struct
-                    let authors: Vec<String> = header
-                        .authors
-                        .iter()
-                        .filter_map(|author| {
-                            // Author is a BTreeMap wrapper, try to get "name" key
-                            author.0.get("name").cloned()
-                        })
-                        .collect();
-
-                    // TODO: Get actual file count from touched_files
-                    let files_changed = 0;
-
-                    changes.push(ChangeInfo {
-                        hash,
-                        message: header.message,
-                        description: header.description,
-                        timestamp,
-                        authors,
-                        files_changed,
-                    });
-                }
+                Ok(header) => changes.push(ChangeInfo::from_header(hash, header)),
                 Err(_) => {
                     // If we can't get the header, create a minimal entry
                     changes.push(ChangeInfo {
@@ -199,6 +280,8 @@ impl<'a> PijulQuery<'a> {
                         timestamp: Utc::now(),
                         authors: vec![],
                         files_changed: 0,
+                        metadata: None,
+                        signature: SignatureStatus::Unchecked,
                     });
                 }
             }
@@ -215,31 +298,193 @@ impl<'a> PijulQuery<'a> {
     }
 
     /// Get detailed information about a specific change
+    ///
+    /// Unlike the listings, this reads the whole change: `files_changed` is
+    /// filled in and the signature, if any, is verified.
     pub fn get_change_info(&self, hash: &Hash) -> Result<ChangeInfo, PijulError> {
+        let change = self
+            .backend
+            .get_change_store()
+            .get_change(hash)
+            .map_err(|e| PijulError::ChangeStore(format!("{:?}", e)))?;
+        Ok(ChangeInfo::from_change(*hash, change))
+    }
+
+    /// Everything a change did, file by file
+    ///
+    /// Reads the whole change like [`get_change_info`](Self::get_change_info),
+    /// plus the text it removed from the changes that wrote it.
+    pub fn get_change_details(&self, hash: &Hash) -> Result<ChangeDetails, PijulError> {
         let change_store = self.backend.get_change_store();
-        let header = change_store
-            .get_header(hash)
+        let change = change_store
+            .get_change(hash)
             .map_err(|e| PijulError::ChangeStore(format!("{:?}", e)))?;
+        let files = file_changes(&change_store, &change);
+        let dependencies = change.hashed.dependencies.clone();
+        Ok(ChangeDetails {
+            info: ChangeInfo::from_change(*hash, change),
+            dependencies,
+            files,
+        })
+    }
 
-        let timestamp = Utc
-            .timestamp_opt(header.timestamp.as_second(), 0)
-            .single()
-            .unwrap_or_else(Utc::now);
+    /// Changes matching `filter`, newest first
+    ///
+    /// Like [`get_change_info`](Self::get_change_info), each listed change is
+    /// read whole, so `files_changed` and the signature are filled in.
+    pub fn log(&self, filter: &LogFilter) -> Result<Vec<ChangeInfo>, PijulError> {
+        let channel = filter
+            .channel()
+            .unwrap_or_else(|| self.backend.current_channel());
+        let change_store = self.backend.get_change_store();
+        let mut changes = Vec::new();
+        for hash in self.backend.list_changes_in(channel)?.into_iter().rev() {
+            if filter.limit().is_some_and(|limit| changes.len() >= limit) {
+                break;
+            }
+            let header = change_store
+                .get_header(&hash)
+                .map_err(|e| PijulError::ChangeStore(format!("{:?}", e)))?;
+            if !filter.covers_authors(header.authors.iter().map(|author| &author.0))
+                || !filter.covers_time(ChangeInfo::from_header(hash, header).timestamp)
+            {
+                continue;
+            }
+            let change = change_store
+                .get_change(&hash)
+                .map_err(|e| PijulError::ChangeStore(format!("{:?}", e)))?;
+            if filter.covers_paths(&touched_paths(&change)) {
+                changes.push(ChangeInfo::from_change(hash, change));
+            }
+        }
+        Ok(changes)
+    }
 
-        let authors: Vec<String> = header
-            .authors
-            .iter()
-            .filter_map(|author| author.0.get("name").cloned())
-            .collect();
+    /// Changes of the current channel touching `path`, oldest first
+    ///
+    /// A change that renamed the file counts under its new name only.
+    pub fn file_history(&self, path: &str) -> Result<Vec<ChangeInfo>, PijulError> {
+        let change_store = self.backend.get_change_store();
+        let mut history = Vec::new();
+        for hash in self.backend.list_changes()? {
+            let change = change_store
+                .get_change(&hash)
+                .map_err(|e| PijulError::ChangeStore(format!("{:?}", e)))?;
+            if touched_paths(&change).contains(path) {
+                history.push(self.get_change_info(&hash)?);
+            }
+        }
+        Ok(history)
+    }
 
-        Ok(ChangeInfo {
-            hash: *hash,
-            message: header.message,
-            description: header.description,
-            timestamp,
-            authors,
-            files_changed: 0, // TODO: implement
-        })
+    /// Find the change in the current channel whose hash starts with `prefix`
+    ///
+    /// Accepts the short hashes shown by `ize history` as well as full ones.
+    /// A tag name stands for the last change of its state, and wins over a
+    /// hash prefix spelled the same.
+    pub fn find_change(&self, prefix: &str) -> Result<Hash, PijulError> {
+        let changes = self.backend.list_changes()?;
+        if let Some(tag) = self.backend.get_tag(prefix)? {
+            return changes
+                .into_iter()
+                .find(|hash| *hash == tag.change)
+                .ok_or_else(|| {
+                    PijulError::ChangeNotFound(format!(
+                        "tag {} (from channel {})",
+                        tag.name, tag.channel
+                    ))
+                });
+        }
+        match_prefix(prefix, changes)
+    }
+
+    /// Like [`find_change`](Self::find_change), but searching every channel
+    ///
+    /// Used to pick changes from other channels, e.g. for `ize apply`.
+    pub fn find_change_anywhere(&self, prefix: &str) -> Result<Hash, PijulError> {
+        if let Some(tag) = self.backend.get_tag(prefix)? {
+            return Ok(tag.change);
+        }
+        let mut hashes = Vec::new();
+        for channel in self.backend.list_channels()? {
+            hashes.extend(self.backend.list_changes_in(&channel)?);
+        }
+        hashes.sort_by_key(|hash| hash.to_base32());
+        hashes.dedup();
+        match_prefix(prefix, hashes)
+    }
+
+    /// Find the latest change recorded at or before `time`
+    ///
+    /// Returns `None` if every change in the channel is newer than `time`.
+    pub fn change_at_time(&self, time: DateTime<Utc>) -> Result<Option<Hash>, PijulError> {
+        Ok(self
+            .list_changes_detailed()?
+            .into_iter()
+            .rev()
+            .find(|change| change.timestamp <= time)
+            .map(|change| change.hash))
+    }
+
+    /// Resolve a user-supplied point in history to a change
+    ///
+    /// `spec` is either a timestamp understood by [`parse_timestamp`], a tag
+    /// name or a (possibly abbreviated) change hash. `None` means the state
+    /// before the first change.
+    pub fn resolve_at(&self, spec: &str) -> Result<Option<Hash>, PijulError> {
+        match parse_timestamp(spec) {
+            Some(time) => self.change_at_time(time),
+            None => self.find_change(spec).map(Some),
+        }
+    }
+
+    // === Point-in-time Queries ===
+
+    /// The change `at` stands for in the current channel; `None` is the
+    /// state before the first change
+    pub fn resolve_ref(&self, at: &ChangeRef) -> Result<Option<Hash>, PijulError> {
+        match at {
+            ChangeRef::Head => Ok(self.backend.list_changes()?.last().copied()),
+            ChangeRef::Change(hash) => Ok(Some(*hash)),
+            ChangeRef::Time(time) => self.change_at_time(*time),
+            ChangeRef::Named(spec) => self.resolve_at(spec),
+        }
+    }
+
+    /// Content of `path` at a point in history
+    ///
+    /// Read from the channel's graph as it was then, without forking the
+    /// channel; see [`past`](super::past).
+    pub fn file_content_at(&self, path: &str, at: &ChangeRef) -> Result<Vec<u8>, PijulError> {
+        self.backend
+            .file_content_after(path, self.resolve_ref(at)?.as_ref())
+    }
+
+    /// Content of `path` as of `time`
+    pub fn file_content_at_time(
+        &self,
+        path: &str,
+        time: DateTime<Utc>,
+    ) -> Result<Vec<u8>, PijulError> {
+        self.file_content_at(path, &ChangeRef::Time(time))
+    }
+
+    /// Files at a point in history, sorted by path
+    pub fn list_files_at(&self, at: &ChangeRef) -> Result<Vec<String>, PijulError> {
+        self.backend
+            .list_files_after(self.resolve_ref(at)?.as_ref())
+    }
+
+    // === Tag Queries ===
+
+    /// Every tag, sorted by name
+    pub fn list_tags(&self) -> Result<Vec<Tag>, PijulError> {
+        self.backend.list_tags()
+    }
+
+    /// Look up a tag by name
+    pub fn get_tag(&self, name: &str) -> Result<Option<Tag>, PijulError> {
+        self.backend.get_tag(name)
     }
 
     /// Get the number of changes in the current channel
@@ -265,14 +510,86 @@ impl<'a> PijulQuery<'a> {
         String::from_utf8(content).map_err(|e| PijulError::Diff(format!("Invalid UTF-8: {}", e)))
     }
 
-    /// List all files in the current channel
-    ///
-    /// Note: This is currently a stub and returns an empty list.
-    /// TODO: Implement proper file listing from pristine.
+    /// List all tracked files, sorted by path
     pub fn list_files(&self) -> Result<Vec<String>, PijulError> {
         self.backend.list_files()
     }
 
+    /// Every line of a file in the current channel, with the change that
+    /// wrote it
+    ///
+    /// Changes are read by header only, so their `signature` is unchecked.
+    pub fn blame(&self, path: &str) -> Result<Vec<BlameLine>, PijulError> {
+        let change_store = self.backend.get_change_store();
+        let mut changes: HashMap<Hash, ChangeInfo> = HashMap::new();
+        let mut lines = Vec::new();
+        for (index, line) in self.backend.blame(path)?.into_iter().enumerate() {
+            let change = match line.change {
+                Some(hash) => Some(match changes.get(&hash) {
+                    Some(info) => info.clone(),
+                    None => {
+                        let header = change_store
+                            .get_header(&hash)
+                            .map_err(|e| PijulError::ChangeStore(format!("{:?}", e)))?;
+                        let info = ChangeInfo::from_header(hash, header);
+                        changes.insert(hash, info.clone());
+                        info
+                    }
+                }),
+                None => None,
+            };
+            lines.push(BlameLine {
+                number: index + 1,
+                content: String::from_utf8_lossy(&line.content).into_owned(),
+                change,
+            });
+        }
+        Ok(lines)
+    }
+
+    /// Lines matching `search` among everything ever written to the channel,
+    /// newest change first
+    ///
+    /// Reads the text each change inserted rather than reconstructing file
+    /// versions, so deleted text is found too.
+    pub fn grep(&self, search: &GrepQuery) -> Result<Vec<GrepMatch>, PijulError> {
+        let channel = search
+            .channel()
+            .unwrap_or_else(|| self.backend.current_channel());
+        let change_store = self.backend.get_change_store();
+        let mut found = Vec::new();
+        for hash in self.backend.list_changes_in(channel)?.into_iter().rev() {
+            let header = change_store
+                .get_header(&hash)
+                .map_err(|e| PijulError::ChangeStore(format!("{:?}", e)))?;
+            let timestamp = ChangeInfo::from_header(hash, header).timestamp;
+            if !search.covers_time(timestamp) {
+                continue;
+            }
+            let change = change_store
+                .get_change(&hash)
+                .map_err(|e| PijulError::ChangeStore(format!("{:?}", e)))?;
+            found.extend(search.search(hash, timestamp, &change));
+        }
+        Ok(found)
+    }
+
+    // === Conflict Queries ===
+
+    /// Files of the current channel with unresolved conflicts, sorted by path
+    pub fn conflicts(&self) -> Result<Vec<FileConflict>, PijulError> {
+        self.backend.conflicts()
+    }
+
+    /// Unresolved conflicts in one file, or `None` if it has none
+    pub fn file_conflict(&self, path: &str) -> Result<Option<FileConflict>, PijulError> {
+        Ok(self
+            .backend
+            .conflicts()?
+            .into_iter()
+            .find(|conflict| conflict.path == path))
+    }
+
     // === Utility Methods ===
 
     /// Get a reference to the underlying backend
@@ -280,6 +597,11 @@ impl<'a> PijulQuery<'a> {
         self.backend
     }
 
+    /// Format a hash as the base32 string [`parse_hash`](Self::parse_hash) accepts
+    pub fn format_hash(hash: &Hash) -> String {
+        hash.to_base32()
+    }
+
     /// Parse a hash from a base32 string
     pub fn parse_hash(hash_str: &str) -> Result<Hash, PijulError> {
         Hash::from_base32(hash_str.as_bytes())
@@ -287,9 +609,87 @@ impl<'a> PijulQuery<'a> {
     }
 }
 
+/// Paths a change adds, edits, moves or deletes
+pub(super) fn touched_paths(change: &Change) -> BTreeSet<String> {
+    change
+        .hashed
+        .changes
+        .iter()
+        .filter_map(|hunk| match hunk {
+            BaseHunk::FileMove { path, .. }
+            | BaseHunk::FileDel { path, .. }
+            | BaseHunk::FileUndel { path, .. }
+            | BaseHunk::FileAdd { path, .. }
+            | BaseHunk::SolveNameConflict { path, .. }
+            | BaseHunk::UnsolveNameConflict { path, .. } => Some(path.clone()),
+            BaseHunk::Edit { local, .. }
+            | BaseHunk::Replacement { local, .. }
+            | BaseHunk::SolveOrderConflict { local, .. }
+            | BaseHunk::UnsolveOrderConflict { local, .. }
+            | BaseHunk::ResurrectZombies { local, .. } => Some(local.path.clone()),
+            BaseHunk::AddRoot { .. } | BaseHunk::DelRoot { .. } => None,
+        })
+        .collect()
+}
+
+/// The single hash among `hashes` that starts with `prefix`
+fn match_prefix(prefix: &str, hashes: Vec<Hash>) -> Result<Hash, PijulError> {
+    let prefix = prefix.trim();
+    if prefix.is_empty() {
+        return Err(PijulError::ChangeNotFound(prefix.to_string()));
+    }
+
+    let mut matches = hashes
+        .into_iter()
+        .filter(|hash| hash.to_base32().starts_with(prefix));
+    match (matches.next(), matches.next()) {
+        (Some(hash), None) => Ok(hash),
+        (Some(_), Some(_)) => Err(PijulError::AmbiguousChange(prefix.to_string())),
+        (None, _) => Err(PijulError::ChangeNotFound(prefix.to_string())),
+    }
+}
+
+/// Parse a point in time given on the command line
+///
+/// Accepted forms, without a zone meaning local time:
+/// - RFC 3339: `2024-05-01T14:30:00Z`, `2024-05-01T14:30:00+02:00`
+/// - `2024-05-01 14:30`, `2024-05-01 14:30:15` (or with `T`)
+/// - `2024-05-01` (local midnight)
+/// - `@1714573800` (seconds since the Unix epoch)
+pub fn parse_timestamp(input: &str) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+
+    if let Some(secs) = input.strip_prefix('@') {
+        return Utc.timestamp_opt(secs.parse().ok()?, 0).single();
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Some(time.with_timezone(&Utc));
+    }
+
+    const LOCAL_FORMATS: [&str; 4] = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ];
+    let naive = LOCAL_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::identity::Identity;
     use tempfile::TempDir;
 
     fn setup_test_backend() -> (TempDir, PijulBackend) {
@@ -342,6 +742,188 @@ mod tests {
         assert!(!query.file_exists("nonexistent.txt").unwrap());
     }
 
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = Utc.with_ymd_and_hms(2024, 5, 1, 14, 30, 0).unwrap();
+        assert_eq!(parse_timestamp("2024-05-01T14:30:00Z"), Some(expected));
+        assert_eq!(parse_timestamp("2024-05-01T16:30:00+02:00"), Some(expected));
+        assert_eq!(parse_timestamp("@1714573800"), Some(expected));
+
+        let local = Local
+            .with_ymd_and_hms(2024, 5, 1, 14, 30, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parse_timestamp("2024-05-01 14:30"), Some(local));
+        assert_eq!(parse_timestamp("2024-05-01T14:30:00"), Some(local));
+
+        let midnight = Local
+            .with_ymd_and_hms(2024, 5, 1, 0, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parse_timestamp("2024-05-01"), Some(midnight));
+    }
+
+    #[test]
+    fn test_parse_timestamp_rejects_hashes() {
+        assert_eq!(parse_timestamp("MNYNGT2VGEQZX"), None);
+        assert_eq!(parse_timestamp("2024"), None);
+        assert_eq!(parse_timestamp("@soon"), None);
+    }
+
+    #[test]
+    fn test_find_change_by_prefix() {
+        let (_temp, backend) = setup_test_backend();
+        let hash = backend
+            .record_file_create("a.txt", 0, b"a", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        let query = PijulQuery::new(&backend);
+
+        let full = hash.to_base32();
+        assert_eq!(query.find_change(&full).unwrap(), hash);
+        assert_eq!(query.find_change(&full[..7]).unwrap(), hash);
+        assert_eq!(query.resolve_at(&full[..7]).unwrap(), Some(hash));
+        assert!(matches!(
+            query.find_change("zzzzzzz"),
+            Err(PijulError::ChangeNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_find_change_anywhere() {
+        let (_temp, mut backend) = setup_test_backend();
+        backend.fork_channel("main", "other").unwrap();
+        backend.switch_channel("other").unwrap();
+        let hash = backend
+            .record_file_create("b.txt", 0, b"b", "Create b.txt")
+            .unwrap()
+            .unwrap();
+        std::fs::create_dir_all(backend.working_dir()).unwrap();
+        std::fs::write(backend.working_dir().join("b.txt"), b"b").unwrap();
+        backend.switch_channel("main").unwrap();
+        let query = PijulQuery::new(&backend);
+
+        let short = &hash.to_base32()[..7];
+        assert!(query.find_change(short).is_err());
+        assert_eq!(query.find_change_anywhere(short).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_tags_resolve_like_hashes() {
+        let (_temp, mut backend) = setup_test_backend();
+        let first = backend
+            .record_file_create("a.txt", 0, b"a", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        backend.create_tag("before-refactor", None).unwrap();
+        backend
+            .record_file_create("b.txt", 0, b"b", "Create b.txt")
+            .unwrap();
+
+        let query = PijulQuery::new(&backend);
+        assert_eq!(query.find_change("before-refactor").unwrap(), first);
+        assert_eq!(query.resolve_at("before-refactor").unwrap(), Some(first));
+        assert_eq!(query.list_tags().unwrap().len(), 1);
+
+        // A tag from a channel missing the change doesn't resolve there,
+        // except when looking everywhere
+        std::fs::create_dir_all(backend.working_dir()).unwrap();
+        std::fs::write(backend.working_dir().join("a.txt"), b"a").unwrap();
+        std::fs::write(backend.working_dir().join("b.txt"), b"b").unwrap();
+        backend.create_channel("empty").unwrap();
+        backend.switch_channel("empty").unwrap();
+        let query = PijulQuery::new(&backend);
+        assert!(matches!(
+            query.find_change("before-refactor"),
+            Err(PijulError::ChangeNotFound(_))
+        ));
+        assert_eq!(
+            query.find_change_anywhere("before-refactor").unwrap(),
+            first
+        );
+    }
+
+    #[test]
+    fn test_change_at_time() {
+        let (_temp, backend) = setup_test_backend();
+        let hash = backend
+            .record_file_create("a.txt", 0, b"a", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        let query = PijulQuery::new(&backend);
+
+        let recorded = query.get_change_info(&hash).unwrap().timestamp;
+        assert_eq!(query.change_at_time(recorded).unwrap(), Some(hash));
+        assert_eq!(
+            query
+                .change_at_time(recorded - chrono::Duration::seconds(60))
+                .unwrap(),
+            None
+        );
+        assert_eq!(query.resolve_at("@0").unwrap(), None);
+    }
+
+    #[test]
+    fn test_signed_change_is_verified() {
+        let (temp, mut backend) = setup_test_backend();
+        let unsigned = backend
+            .record_file_create("a.txt", 0, b"a", "Create a.txt")
+            .unwrap()
+            .unwrap();
+
+        let key = crate::identity::generate_key(&temp.path().join("ed25519.key")).unwrap();
+        let identity = Identity::new("Ada")
+            .with_email("ada@example.com")
+            .with_key(key, true);
+        let public_key = identity.public_key().unwrap();
+        backend.set_identity(identity);
+        let signed = backend
+            .record_file_create("b.txt", 0, b"b", "Create b.txt")
+            .unwrap()
+            .unwrap();
+        let query = PijulQuery::new(&backend);
+
+        let info = query.get_change_info(&unsigned).unwrap();
+        assert_eq!(info.signature, SignatureStatus::Unsigned);
+        assert_eq!(info.primary_author(), "Unknown");
+
+        let info = query.get_change_info(&signed).unwrap();
+        assert_eq!(info.signature, SignatureStatus::Valid { key: public_key });
+        assert_eq!(info.authors, vec!["Ada".to_string()]);
+        assert_eq!(info.files_changed, 1);
+
+        // Listings only read headers
+        let listed = query.list_changes_detailed().unwrap();
+        assert_eq!(listed[1].signature, SignatureStatus::Unchecked);
+    }
+
+    #[test]
+    fn test_file_history() {
+        let (_temp, backend) = setup_test_backend();
+        let created = backend
+            .record_file_create("a.txt", 0, b"one\n", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        std::fs::write(backend.working_dir().join("a.txt"), b"one\n").unwrap();
+        backend
+            .record_file_create("b.txt", 0, b"b\n", "Create b.txt")
+            .unwrap();
+        let edited = backend
+            .record_file_replace("a.txt", b"one\ntwo\n", "Edit a.txt")
+            .unwrap()
+            .unwrap();
+        let query = PijulQuery::new(&backend);
+
+        let history: Vec<Hash> = query
+            .file_history("a.txt")
+            .unwrap()
+            .into_iter()
+            .map(|change| change.hash)
+            .collect();
+        assert_eq!(history, vec![created, edited]);
+        assert!(query.file_history("c.txt").unwrap().is_empty());
+    }
+
     #[test]
     fn test_change_info_hash_short() {
         let info = ChangeInfo {
@@ -351,6 +933,8 @@ mod tests {
             timestamp: Utc::now(),
             authors: vec!["Test Author".to_string()],
             files_changed: 1,
+            metadata: None,
+            signature: SignatureStatus::Unchecked,
         };
 
         // Just verify it doesn't panic
@@ -366,6 +950,8 @@ mod tests {
             timestamp: Utc::now(),
             authors: vec![],
             files_changed: 0,
+            metadata: None,
+            signature: SignatureStatus::Unchecked,
         };
 
         assert_eq!(info.timestamp_relative(), "just now");
@@ -380,6 +966,8 @@ mod tests {
             timestamp: Utc::now(),
             authors: vec!["Alice".to_string(), "Bob".to_string()],
             files_changed: 0,
+            metadata: None,
+            signature: SignatureStatus::Unchecked,
         };
         assert_eq!(info_with_author.primary_author(), "Alice");
 
@@ -390,7 +978,57 @@ mod tests {
             timestamp: Utc::now(),
             authors: vec![],
             files_changed: 0,
+            metadata: None,
+            signature: SignatureStatus::Unchecked,
         };
         assert_eq!(info_without_author.primary_author(), "Unknown");
     }
+
+    #[test]
+    fn test_change_info_json() {
+        let (_temp, backend) = setup_test_backend();
+        let hash = backend
+            .record_file_create("a.txt", 0, b"a", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        let info = PijulQuery::new(&backend).get_change_info(&hash).unwrap();
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["hash"], hash.to_base32());
+        assert_eq!(json["message"], "Create a.txt");
+        assert_eq!(json["files_changed"], 1);
+        assert_eq!(json["checkpoint"], false);
+        assert_eq!(json["signature"]["status"], "unsigned");
+        assert!(json.get("metadata").is_none());
+    }
+
+    #[test]
+    fn test_file_content_at() {
+        let (_temp, backend) = setup_test_backend();
+        let first = backend
+            .record_file_create("a.txt", 0, b"one\n", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        std::fs::write(backend.working_dir().join("a.txt"), b"two\n").unwrap();
+        backend
+            .record_file_replace("a.txt", b"two\n", "Edit a.txt")
+            .unwrap();
+        backend.create_tag("v1", None).unwrap();
+        let query = PijulQuery::new(&backend);
+
+        let at = |at: ChangeRef| query.file_content_at("a.txt", &at).unwrap();
+        assert_eq!(at(ChangeRef::from(first)), b"one\n");
+        assert_eq!(at(ChangeRef::Head), b"two\n");
+        assert_eq!(at(ChangeRef::from("v1")), b"two\n");
+
+        let recorded = query.get_change_info(&first).unwrap().timestamp;
+        assert!(matches!(
+            query.file_content_at_time("a.txt", recorded - chrono::Duration::seconds(1)),
+            Err(PijulError::FileNotFound(_))
+        ));
+        assert_eq!(
+            query.list_files_at(&ChangeRef::from(first)).unwrap(),
+            vec!["a.txt"]
+        );
+    }
 }
@@ -0,0 +1,579 @@
+//! Retention policy and garbage collection
+//!
+//! Recording every write keeps history forever at its finest grain. A
+//! [`RetentionPolicy`] says how much of that grain to keep as changes age:
+//! by default every change of the last day, one per hour for the last 30
+//! days, and one per day before that.
+//!
+//! [`PijulBackend::gc`] applies it to the current channel. Consecutive changes
+//! falling in the same hour or day are compacted into one checkpoint with
+//! their combined effect; a tagged change always ends its group, so tags
+//! keep naming the same state. Since Pijul changes name their dependencies
+//! by hash, every change after the first compacted one is recorded again
+//! with its message, authors, time and metadata, under a new hash; a
+//! compacted change is authored by everyone whose changes it holds.
+//! Signatures cover the old hashes, so they are lost:
+//! [`GcReport::dropped_signatures`] lists the signed changes rewritten.
+//! Finally, change files no channel refers to any more are deleted from
+//! `changes_dir`.
+//!
+//! Changes kept on other channels, such as `archive/...` channels left by
+//! checkpoints, keep their files.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+use chrono::{DateTime, TimeZone, Utc};
+use libpijul::changestore::ChangeStore as _;
+use libpijul::pristine::{Hash, Merkle};
+use libpijul::working_copy::memory::Memory;
+use libpijul::{Base32, MutTxnT, MutTxnTExt, TxnT, TxnTExt};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+use super::backend::{PijulBackend, PijulError};
+use super::checkpoint::CHECKPOINT_TAG;
+use super::tags::Tag;
+use crate::identity::SIGNATURE_FIELD;
+use crate::storage::ChangeMessage;
+
+const HOUR: i64 = 3600;
+const DAY: i64 = 24 * HOUR;
+
+/// How long history keeps its full grain, stored in the `[retention]`
+/// section of project.toml
+///
+/// Ages are counted from the time `ize gc` runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Keep every change younger than this many seconds
+    #[serde(default = "default_keep_all_secs")]
+    pub keep_all_secs: u64,
+    /// Keep one change per hour for changes younger than this many seconds,
+    /// and one per day for older ones
+    #[serde(default = "default_keep_hourly_secs")]
+    pub keep_hourly_secs: u64,
+}
+
+fn default_keep_all_secs() -> u64 {
+    DAY as u64
+}
+
+fn default_keep_hourly_secs() -> u64 {
+    30 * DAY as u64
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_all_secs: default_keep_all_secs(),
+            keep_hourly_secs: default_keep_hourly_secs(),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// The hour or day, as `(length, index)`, a change recorded at `time`
+    /// is compacted into; `None` if it is kept as is
+    fn bucket(&self, time: DateTime<Utc>, now: DateTime<Utc>) -> Option<(i64, i64)> {
+        let age = (now - time).num_seconds();
+        let length = if age < self.keep_all_secs as i64 {
+            return None;
+        } else if age < self.keep_hourly_secs as i64 {
+            HOUR
+        } else {
+            DAY
+        };
+        Some((length, time.timestamp().div_euclid(length)))
+    }
+
+    /// Split a log, oldest first, into the groups of changes to compact
+    ///
+    /// Every change is in exactly one group, in log order; groups of one are
+    /// kept as they are. A change in `tagged` ends its group.
+    pub fn plan(
+        &self,
+        log: &[(Hash, DateTime<Utc>)],
+        tagged: &HashSet<Hash>,
+        now: DateTime<Utc>,
+    ) -> Vec<Vec<Hash>> {
+        let mut groups: Vec<Vec<Hash>> = Vec::new();
+        let mut open = None;
+        for (hash, time) in log {
+            let bucket = self.bucket(*time, now);
+            match groups.last_mut() {
+                Some(group) if bucket.is_some() && bucket == open => group.push(*hash),
+                _ => groups.push(vec![*hash]),
+            }
+            open = if tagged.contains(hash) { None } else { bucket };
+        }
+        groups
+    }
+}
+
+/// Outcome of [`PijulBackend::gc`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// Changes replaced by coarser ones
+    pub compacted: usize,
+    /// Number of changes they were compacted into
+    pub compacted_into: usize,
+    /// Later changes recorded again on top of those
+    pub rerecorded: usize,
+    /// Signed changes among those rewritten, whose signatures don't carry
+    /// over to their replacements
+    pub dropped_signatures: Vec<Hash>,
+    /// Change files deleted
+    pub removed_files: usize,
+    /// Bytes those files took
+    pub reclaimed_bytes: u64,
+}
+
+impl PijulBackend {
+    /// Compact the current channel per `policy` and delete change files no
+    /// channel uses
+    ///
+    /// With `dry_run`, nothing is changed and the report says what would be.
+    pub fn gc(&self, policy: &RetentionPolicy, dry_run: bool) -> Result<GcReport, PijulError> {
+        let now = Utc::now();
+        let change_store = self.get_change_store();
+        let mut log = Vec::new();
+        for hash in self.list_changes()? {
+            let header = change_store.get_header(&hash)?;
+            let time = Utc
+                .timestamp_opt(header.timestamp.as_second(), 0)
+                .single()
+                .unwrap_or(now);
+            log.push((hash, time));
+        }
+        let tags: Vec<Tag> = self
+            .list_tags()?
+            .into_iter()
+            .filter(|tag| tag.channel == self.current_channel)
+            .collect();
+        let tagged = tags.iter().map(|tag| tag.change).collect();
+        let groups = policy.plan(&log, &tagged, now);
+
+        let mut report = GcReport::default();
+        let first = groups.iter().position(|group| group.len() > 1);
+        if let Some(first) = first {
+            for group in groups[first..].iter() {
+                if group.len() > 1 {
+                    report.compacted += group.len();
+                    report.compacted_into += 1;
+                } else {
+                    report.rerecorded += 1;
+                }
+                for hash in group {
+                    let change = change_store.get_change(hash)?;
+                    if change
+                        .unhashed
+                        .as_ref()
+                        .is_some_and(|unhashed| unhashed.get(SIGNATURE_FIELD).is_some())
+                    {
+                        report.dropped_signatures.push(*hash);
+                    }
+                }
+            }
+        }
+        debug!(
+            "PijulBackend::gc channel={} compacting {} change(s) into {}, rerecording {}",
+            self.current_channel, report.compacted, report.compacted_into, report.rerecorded
+        );
+
+        let mut unused = self.unreferenced_change_files()?;
+        if dry_run {
+            // The files of rewritten changes go too, unless another channel
+            // has them
+            if let Some(first) = first {
+                let elsewhere = self.changes_on_other_channels()?;
+                for hash in groups[first..].iter().flatten() {
+                    if !elsewhere.contains(hash) {
+                        let path = change_file(&self.changes_dir(), hash);
+                        let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+                        unused.push((path, size));
+                    }
+                }
+            }
+        } else {
+            if let Some(first) = first {
+                // Rewritten changes are recorded from the channel, so
+                // anything only in the working copy would look undone
+                let dirty = self.dirty_paths(None)?;
+                if !dirty.is_empty() {
+                    return Err(PijulError::UnrecordedChanges(dirty));
+                }
+                self.compact(&groups[first..], &tags)?;
+                unused = self.unreferenced_change_files()?;
+            }
+            for (path, _) in &unused {
+                self.remove_change_file(path)?;
+                if let Some(dir) = path.parent() {
+                    // Only succeeds once the directory is empty
+                    let _ = std::fs::remove_dir(dir);
+                }
+            }
+        }
+        report.removed_files = unused.len();
+        report.reclaimed_bytes = unused.iter().map(|(_, size)| size).sum();
+
+        if !dry_run {
+            info!(
+                "GC on {}: compacted {} change(s) into {}, removed {} file(s), {} bytes",
+                self.current_channel,
+                report.compacted,
+                report.compacted_into,
+                report.removed_files,
+                report.reclaimed_bytes
+            );
+        }
+        Ok(report)
+    }
+
+    /// Replace the tail of the log made of `groups` by one change per group
+    fn compact(&self, groups: &[Vec<Hash>], tags: &[Tag]) -> Result<(), PijulError> {
+        let change_store = self.get_change_store();
+        let rewritten: Vec<Hash> = groups.iter().flatten().copied().collect();
+
+        // The state after each group, and the header to record it with,
+        // while the channel still has the originals
+        let mut steps = Vec::with_capacity(groups.len());
+        for group in groups {
+            let last = group[group.len() - 1];
+            let state = self.snapshot_after(Some(&last))?;
+            let original = change_store.get_header(&last)?;
+            let header = if group.len() == 1 {
+                original
+            } else {
+                let first = change_store.get_header(&group[0])?;
+                let mut header = ChangeMessage::new(format!(
+                    "{} changes from {} to {}",
+                    group.len(),
+                    first.timestamp.strftime("%Y-%m-%d %H:%M"),
+                    original.timestamp.strftime("%Y-%m-%d %H:%M")
+                ))
+                .into_header();
+                for hash in group {
+                    for author in change_store.get_header(hash)?.authors {
+                        if !header.authors.iter().any(|known| known.0 == author.0) {
+                            header.authors.push(author);
+                        }
+                    }
+                }
+                header.timestamp = original.timestamp;
+                header.description = Some(format!(
+                    "{}\n{} change(s) squashed",
+                    CHECKPOINT_TAG,
+                    group.len()
+                ));
+                header
+            };
+            steps.push((group, state, header));
+        }
+
+        let txn = self.arc_txn_begin()?;
+        let channel = txn
+            .read()
+            .load_channel(&self.current_channel)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            .ok_or_else(|| PijulError::ChannelNotFound(self.current_channel.clone()))?;
+
+        let moving: Vec<&Tag> = tags
+            .iter()
+            .filter(|tag| rewritten.contains(&tag.change))
+            .collect();
+        for tag in &moving {
+            txn.write()
+                .del_tags(&mut channel.write().tags, tag.position)
+                .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+        }
+        for hash in rewritten.iter().rev() {
+            txn.write()
+                .unrecord(&change_store, &channel, hash, 0)
+                .map_err(|e| PijulError::Transaction(format!("unrecord {:?}: {:?}", hash, e)))?;
+        }
+        libpijul::output::output_repository_no_pending(
+            &Memory::new(),
+            &change_store,
+            &txn,
+            &channel,
+            "",
+            true,
+            None,
+            1,
+            0,
+        )
+        .map_err(|e| PijulError::Transaction(format!("output before compaction: {:?}", e)))?;
+
+        let mut replaced_by = HashMap::new();
+        for (group, state, header) in steps {
+            let hash = self.record_state(&txn, &channel, state, header)?;
+            for old in group {
+                replaced_by.insert(*old, hash);
+            }
+        }
+
+        let mut moved = Vec::with_capacity(moving.len());
+        if !moving.is_empty() {
+            let mut states = HashMap::new();
+            {
+                let t = txn.read();
+                let channel_ref = channel.read();
+                for entry in t
+                    .log(&*channel_ref, 0)
+                    .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+                {
+                    let (position, (hash, merkle)) =
+                        entry.map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+                    states.insert(Hash::from(*hash), (position, Merkle::from(*merkle)));
+                }
+            }
+            for tag in moving {
+                let change = replaced_by[&tag.change];
+                let (position, state) = states[&change];
+                txn.write()
+                    .put_tags(&mut channel.write().tags, position, &state)
+                    .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+                moved.push(Tag {
+                    change,
+                    position,
+                    state,
+                    ..tag.clone()
+                });
+            }
+        }
+
+        txn.commit()
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+        self.save_tags(&moved)?;
+        Ok(())
+    }
+
+    /// `(path, size)` of the change files no channel uses
+    pub fn unreferenced_change_files(&self) -> Result<Vec<(PathBuf, u64)>, PijulError> {
+        let mut used = HashSet::new();
+        for channel in self.list_channels()? {
+            used.extend(self.list_changes_in(&channel)?);
+        }
+
+        let mut unused = Vec::new();
+        let changes_dir = self.changes_dir();
+        if !changes_dir.exists() {
+            return Ok(unused);
+        }
+        // Change files are stored as `<first 2 chars>/<rest>.change`
+        for dir in std::fs::read_dir(&changes_dir)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            let prefix = dir.file_name().to_string_lossy().into_owned();
+            for file in std::fs::read_dir(dir.path())? {
+                let file = file?;
+                let path = file.path();
+                if path.extension() != Some(OsStr::new("change")) {
+                    continue;
+                }
+                let Some(stem) = path.file_stem() else {
+                    continue;
+                };
+                let name = format!("{}{}", prefix, stem.to_string_lossy());
+                match Hash::from_base32(name.as_bytes()) {
+                    Some(hash) if !used.contains(&hash) => {
+                        unused.push((path, file.metadata()?.len()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        unused.sort();
+        Ok(unused)
+    }
+
+    fn changes_on_other_channels(&self) -> Result<HashSet<Hash>, PijulError> {
+        let mut changes = HashSet::new();
+        for channel in self.list_channels()? {
+            if channel != self.current_channel {
+                changes.extend(self.list_changes_in(&channel)?);
+            }
+        }
+        Ok(changes)
+    }
+}
+
+/// Where the change store keeps the file of `hash`
+pub(super) fn change_file(changes_dir: &std::path::Path, hash: &Hash) -> PathBuf {
+    let name = hash.to_base32();
+    let (prefix, rest) = name.split_at(2);
+    changes_dir.join(prefix).join(format!("{}.change", rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ChangeMetadata;
+    use super::*;
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    /// Record creating `path`, as if it happened at `time`
+    fn create_at(backend: &PijulBackend, path: &str, time: DateTime<Utc>) -> Hash {
+        let metadata = ChangeMetadata {
+            seq: 0,
+            time,
+            kind: "create".to_string(),
+            uid: None,
+            pid: None,
+        };
+        std::fs::write(backend.working_dir().join(path), path).unwrap();
+        backend
+            .record_file_create(
+                path,
+                0,
+                path.as_bytes(),
+                ChangeMessage::new(format!("Create {}", path)).with_metadata(metadata),
+            )
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_plan_buckets_by_age() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let at = |days: i64, hour: u32, minute: u32| {
+            Utc.with_ymd_and_hms(2024, 6, 1, hour, minute, 0).unwrap() - Duration::days(days)
+        };
+        let hashes: Vec<Hash> = (0..8)
+            .map(|i| {
+                let mut bytes = [0; 32];
+                bytes[0] = i;
+                Hash::Blake3(bytes)
+            })
+            .collect();
+        let log = vec![
+            (hashes[0], at(40, 9, 0)),
+            (hashes[1], at(40, 17, 0)), // same day
+            (hashes[2], at(39, 9, 0)),  // next day
+            (hashes[3], at(2, 9, 5)),
+            (hashes[4], at(2, 9, 55)), // same hour
+            (hashes[5], at(2, 9, 58)), // same hour, after a tag
+            (hashes[6], at(0, 11, 0)), // within a day: kept
+            (hashes[7], at(0, 11, 1)),
+        ];
+        let tagged = HashSet::from([hashes[4]]);
+
+        let groups = RetentionPolicy::default().plan(&log, &tagged, now);
+        assert_eq!(
+            groups,
+            vec![
+                vec![hashes[0], hashes[1]],
+                vec![hashes[2]],
+                vec![hashes[3], hashes[4]],
+                vec![hashes[5]],
+                vec![hashes[6]],
+                vec![hashes[7]],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gc_compacts_and_reclaims() {
+        let (_temp, backend) = setup();
+        let now = Utc::now();
+        let old_day = now - Duration::days(40);
+        let old_day = Utc
+            .timestamp_opt(old_day.timestamp().div_euclid(DAY) * DAY, 0)
+            .unwrap();
+        let old_hour = now - Duration::days(2);
+        let old_hour = Utc
+            .timestamp_opt(old_hour.timestamp().div_euclid(HOUR) * HOUR, 0)
+            .unwrap();
+
+        create_at(&backend, "a.txt", old_day + Duration::hours(1));
+        create_at(&backend, "b.txt", old_day + Duration::hours(2));
+        create_at(&backend, "c.txt", old_hour + Duration::seconds(10));
+        let tagged = create_at(&backend, "d.txt", old_hour + Duration::seconds(20));
+        backend.create_tag("four-files", None).unwrap();
+        create_at(&backend, "e.txt", now);
+        let files = backend.list_files().unwrap();
+
+        let policy = RetentionPolicy::default();
+        let planned = backend.gc(&policy, true).unwrap();
+        assert_eq!(backend.list_changes().unwrap().len(), 5);
+
+        let report = backend.gc(&policy, false).unwrap();
+        assert_eq!(report.compacted, 4);
+        assert_eq!(report.compacted_into, 2);
+        assert_eq!(report.rerecorded, 1);
+        assert_eq!(report.removed_files, 5);
+        assert!(report.reclaimed_bytes > 0);
+        assert_eq!(planned.removed_files, report.removed_files);
+
+        let log = backend.list_changes().unwrap();
+        assert_eq!(log.len(), 3);
+        assert_eq!(backend.list_files().unwrap(), files);
+        assert_eq!(backend.get_file_content("e.txt").unwrap(), b"e.txt");
+        assert!(backend.unreferenced_change_files().unwrap().is_empty());
+
+        // The tag moved to the change standing for its state
+        let tag = backend.get_tag("four-files").unwrap().unwrap();
+        assert_ne!(tag.change, tagged);
+        assert_eq!(tag.change, log[1]);
+        assert_eq!(
+            backend.list_files_after(Some(&tag.change)).unwrap(),
+            vec!["a.txt", "b.txt", "c.txt", "d.txt"]
+        );
+
+        // Nothing left to do
+        let again = backend.gc(&policy, false).unwrap();
+        assert_eq!(again, GcReport::default());
+    }
+
+    #[test]
+    fn test_gc_keeps_authors_and_reports_signatures() {
+        use super::super::PijulQuery;
+        use crate::identity::{generate_key, Identity, SignatureStatus};
+
+        let (temp, mut backend) = setup();
+        let old_day = Utc::now() - Duration::days(40);
+        let old_day = Utc
+            .timestamp_opt(old_day.timestamp().div_euclid(DAY) * DAY, 0)
+            .unwrap();
+        let key = generate_key(&temp.path().join("ed25519.key")).unwrap();
+
+        backend.set_identity(Identity::new("Ada").with_key(key, true));
+        let signed = create_at(&backend, "a.txt", old_day + Duration::hours(1));
+        backend.set_identity(Identity::new("Grace"));
+        create_at(&backend, "b.txt", old_day + Duration::hours(2));
+        let kept = create_at(&backend, "c.txt", Utc::now());
+
+        backend.set_identity(Identity::new("Runner"));
+        let report = backend.gc(&RetentionPolicy::default(), false).unwrap();
+        assert_eq!((report.compacted, report.rerecorded), (2, 1));
+        assert_eq!(report.dropped_signatures, vec![signed]);
+
+        let query = PijulQuery::new(&backend);
+        let log = backend.list_changes().unwrap();
+        let squashed = query.get_change_info(&log[0]).unwrap();
+        assert_eq!(
+            squashed.authors,
+            vec!["Ada".to_string(), "Grace".to_string()]
+        );
+        assert_eq!(squashed.signature, SignatureStatus::Unsigned);
+
+        // Rerecorded as it was, but under a new hash
+        assert_ne!(log[1], kept);
+        let rerecorded = query.get_change_info(&log[1]).unwrap();
+        assert_eq!(rerecorded.authors, vec!["Grace".to_string()]);
+        assert_eq!(rerecorded.message, "Create c.txt");
+        assert_eq!(rerecorded.metadata.unwrap().kind, "create");
+    }
+}
@@ -0,0 +1,438 @@
+//! Historical snapshots of a channel
+//!
+//! A [`Snapshot`] is the tracked tree as it was right after a given change:
+//! every file's content plus the directories that contain them. It is built
+//! entirely inside a Pijul transaction that is never committed, so taking a
+//! snapshot leaves no channel, checkout or tree update behind and is safe to
+//! do while the live mount keeps recording.
+//!
+//! ## How
+//!
+//! 1. Fork the channel inside a mutable transaction.
+//! 2. Unrecord, newest first, every change applied after the target.
+//! 3. Output the forked channel into an in-memory working copy.
+//! 4. Copy the files out and drop the transaction (aborting it).
+//!
+//! The whole tree is held in memory, which suits the source-sized projects
+//! Ize tracks today.
+//!
+//! `PijulBackend` also implements [`HistorySource`], which is how the live
+//! mount's `.ize/` directory reads changes and channels.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+use libpijul::pristine::Hash;
+use libpijul::working_copy::memory::Memory;
+use libpijul::working_copy::WorkingCopyRead;
+use libpijul::{Base32, MutTxnT, MutTxnTExt, TxnT};
+use log::debug;
+
+use super::backend::{PijulBackend, PijulError};
+use super::merge::Conflict;
+use super::query::PijulQuery;
+use crate::filesystems::{HistorySource, SnapshotFS};
+
+/// Name of the throwaway channel a snapshot is built in.
+///
+/// Only ever exists inside an uncommitted transaction.
+const SNAPSHOT_CHANNEL: &str = "ize-snapshot";
+
+/// The tracked tree at one point in a channel's history
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Change the snapshot was taken at; `None` means before the first change
+    change: Option<Hash>,
+    /// File contents keyed by `/`-separated path
+    files: BTreeMap<String, Vec<u8>>,
+    /// Directories, including ones that only exist as parents of files
+    dirs: BTreeSet<String>,
+    /// Conflicts in the tree, whose files carry conflict markers
+    conflicts: Vec<Conflict>,
+}
+
+impl Snapshot {
+    /// Build a snapshot from file contents, deriving parent directories
+    pub fn from_files(
+        change: Option<Hash>,
+        files: impl IntoIterator<Item = (String, Vec<u8>)>,
+    ) -> Self {
+        let mut snapshot = Self {
+            change,
+            ..Self::default()
+        };
+        for (path, content) in files {
+            snapshot.insert_file(path, content);
+        }
+        snapshot
+    }
+
+    fn insert_file(&mut self, path: String, content: Vec<u8>) {
+        let mut parent = path.as_str();
+        while let Some((dir, _)) = parent.rsplit_once('/') {
+            self.dirs.insert(dir.to_string());
+            parent = dir;
+        }
+        self.files.insert(path, content);
+    }
+
+    /// The change this snapshot reflects, or `None` for the empty initial state
+    pub fn change(&self) -> Option<&Hash> {
+        self.change.as_ref()
+    }
+
+    /// All files, sorted by path
+    pub fn list_files(&self) -> Vec<String> {
+        self.files.keys().cloned().collect()
+    }
+
+    /// All directories, sorted by path
+    pub fn list_dirs(&self) -> Vec<String> {
+        self.dirs.iter().cloned().collect()
+    }
+
+    /// Content of a file at this point in history
+    pub fn get_file_content(&self, path: &str) -> Result<Vec<u8>, PijulError> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| PijulError::FileNotFound(path.to_string()))
+    }
+
+    /// Check whether a file existed at this point in history
+    pub fn file_exists(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    /// Conflicts at this point in history, sorted by path and line
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Consume the snapshot, yielding `(path, content)` pairs sorted by path
+    pub fn into_files(self) -> impl Iterator<Item = (String, Vec<u8>)> {
+        self.files.into_iter()
+    }
+}
+
+impl PijulBackend {
+    /// Reconstruct the tracked tree as it was right after `change`
+    ///
+    /// `change` must be in the current channel's log; `None` yields the empty
+    /// state before the first change. Nothing is written to the repository.
+    pub fn snapshot_at(&self, change: Option<&Hash>) -> Result<Snapshot, PijulError> {
+        let Some(target) = change else {
+            return Ok(Snapshot::default());
+        };
+        self.snapshot_in(self.current_channel(), target)
+    }
+
+    /// Reconstruct the tracked tree at the latest change on channel `name`
+    ///
+    /// Unlike switching channels, this leaves the working copy alone.
+    pub fn snapshot_channel(&self, name: &str) -> Result<Snapshot, PijulError> {
+        match self.list_changes_in(name)?.last() {
+            Some(head) => self.snapshot_in(name, head),
+            None => Ok(Snapshot::default()),
+        }
+    }
+
+    fn snapshot_in(&self, name: &str, target: &Hash) -> Result<Snapshot, PijulError> {
+        let log = self.list_changes_in(name)?;
+        let position = log
+            .iter()
+            .position(|hash| hash == target)
+            .ok_or_else(|| PijulError::ChangeNotFound(format!("{:?}", target)))?;
+        let later = &log[position + 1..];
+        debug!(
+            "PijulBackend::snapshot_in channel={} target={:?} unrecording {} later change(s)",
+            name,
+            target,
+            later.len()
+        );
+
+        let txn = self.arc_txn_begin()?;
+        let change_store = self.get_change_store();
+
+        let channel = {
+            let mut t = txn.write();
+            let current = t
+                .load_channel(name)
+                .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+                .ok_or_else(|| PijulError::ChannelNotFound(name.to_string()))?;
+            t.fork(&current, SNAPSHOT_CHANNEL)
+                .map_err(|e| PijulError::Fork(format!("{:?}", e)))?
+        };
+
+        for hash in later.iter().rev() {
+            let mut t = txn.write();
+            t.unrecord(&change_store, &channel, hash, 0)
+                .map_err(|e| PijulError::Transaction(format!("unrecord {:?}: {:?}", hash, e)))?;
+        }
+
+        let memory = Memory::new();
+        let conflicts = libpijul::output::output_repository_no_pending(
+            &memory,
+            &change_store,
+            &txn,
+            &channel,
+            "",
+            true,
+            None,
+            1,
+            0,
+        )
+        .map_err(|e| PijulError::Transaction(format!("output snapshot: {:?}", e)))?;
+
+        // Dropping `txn` without committing discards the fork, the unrecords
+        // and the tree updates made by the output above.
+        drop(channel);
+        drop(txn);
+
+        let mut snapshot = Snapshot {
+            change: Some(*target),
+            conflicts: conflicts.iter().map(Conflict::from_output).collect(),
+            ..Snapshot::default()
+        };
+        snapshot.conflicts.sort();
+        snapshot.conflicts.dedup();
+        for path in memory.list_files() {
+            let meta = memory
+                .file_metadata(&path)
+                .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+            if meta.is_dir() {
+                snapshot.dirs.insert(path);
+            } else {
+                let mut content = Vec::new();
+                memory
+                    .read_file(&path, &mut content)
+                    .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+                snapshot.insert_file(path, self.hydrate(content)?);
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// History for the live mount
+// ---------------------------------------------------------------------------
+
+/// Serves the `.ize/` directory of a live mount straight from the repository.
+impl HistorySource for PijulBackend {
+    fn log(&self) -> io::Result<String> {
+        let changes = PijulQuery::new(self)
+            .list_changes_detailed_reverse()
+            .map_err(to_io_error)?;
+        Ok(changes
+            .iter()
+            .map(|c| {
+                format!(
+                    "{}  {}  {}\n",
+                    c.hash_full(),
+                    c.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                    c.message
+                )
+            })
+            .collect())
+    }
+
+    fn changes(&self) -> io::Result<Vec<String>> {
+        let hashes = self.list_changes().map_err(to_io_error)?;
+        Ok(hashes.iter().map(|h| h.to_base32()).collect())
+    }
+
+    fn channels(&self) -> io::Result<Vec<String>> {
+        self.list_channels().map_err(to_io_error)
+    }
+
+    fn channel_head(&self, channel: &str) -> io::Result<Option<String>> {
+        let hashes = self.list_changes_in(channel).map_err(to_io_error)?;
+        Ok(hashes.last().map(|h| h.to_base32()))
+    }
+
+    fn load_change(&self, change: &str, tree: &mut SnapshotFS) -> io::Result<()> {
+        let hash = PijulQuery::parse_hash(change)
+            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, change.to_string()))?;
+        let snapshot = self.snapshot_at(Some(&hash)).map_err(to_io_error)?;
+        if let Ok(info) = PijulQuery::new(self).get_change_info(&hash) {
+            tree.set_mtime(info.timestamp.into());
+        }
+        fill_tree(snapshot, tree);
+        Ok(())
+    }
+
+    fn load_channel(&self, channel: &str, tree: &mut SnapshotFS) -> io::Result<()> {
+        let snapshot = self.snapshot_channel(channel).map_err(to_io_error)?;
+        if let Some(head) = snapshot.change() {
+            if let Ok(info) = PijulQuery::new(self).get_change_info(head) {
+                tree.set_mtime(info.timestamp.into());
+            }
+        }
+        fill_tree(snapshot, tree);
+        Ok(())
+    }
+}
+
+fn fill_tree(snapshot: Snapshot, tree: &mut SnapshotFS) {
+    for dir in snapshot.list_dirs() {
+        tree.add_dir(&dir);
+    }
+    for (path, content) in snapshot.into_files() {
+        tree.add_file(&path, content);
+    }
+}
+
+fn to_io_error(err: PijulError) -> io::Error {
+    let kind = match err {
+        PijulError::ChannelNotFound(_)
+        | PijulError::ChangeNotFound(_)
+        | PijulError::FileNotFound(_) => io::ErrorKind::NotFound,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    #[test]
+    fn test_from_files_derives_dirs() {
+        let snapshot = Snapshot::from_files(
+            None,
+            vec![
+                ("src/bin/main.rs".to_string(), b"fn main() {}".to_vec()),
+                ("README".to_string(), b"hi".to_vec()),
+            ],
+        );
+        assert_eq!(snapshot.list_files(), vec!["README", "src/bin/main.rs"]);
+        assert_eq!(snapshot.list_dirs(), vec!["src", "src/bin"]);
+        assert_eq!(snapshot.get_file_content("README").unwrap(), b"hi");
+        assert!(matches!(
+            snapshot.get_file_content("missing"),
+            Err(PijulError::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_before_first_change_is_empty() {
+        let (_temp, backend) = setup();
+        backend
+            .record_file_create("a.txt", 0, b"a", "Create a.txt")
+            .unwrap();
+
+        let snapshot = backend.snapshot_at(None).unwrap();
+        assert!(snapshot.list_files().is_empty());
+        assert!(snapshot.change().is_none());
+    }
+
+    #[test]
+    fn test_snapshot_at_earlier_change() {
+        let (_temp, backend) = setup();
+        let first = backend
+            .record_file_create("a.txt", 0, b"one\n", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        backend
+            .record_file_write("a.txt", 4, b"two\n", "Append to a.txt")
+            .unwrap();
+        backend
+            .record_file_create("sub/b.txt", 0, b"later\n", "Create sub/b.txt")
+            .unwrap();
+
+        let snapshot = backend.snapshot_at(Some(&first)).unwrap();
+        assert_eq!(snapshot.change(), Some(&first));
+        assert_eq!(snapshot.list_files(), vec!["a.txt"]);
+        assert_eq!(snapshot.get_file_content("a.txt").unwrap(), b"one\n");
+
+        // The live channel and tree are untouched
+        assert_eq!(backend.list_changes().unwrap().len(), 3);
+        assert_eq!(backend.list_files().unwrap(), vec!["a.txt", "sub/b.txt"]);
+        assert_eq!(backend.get_file_content("a.txt").unwrap(), b"one\ntwo\n");
+        assert!(!backend
+            .list_channels()
+            .unwrap()
+            .contains(&SNAPSHOT_CHANNEL.to_string()));
+    }
+
+    #[test]
+    fn test_snapshot_at_latest_change_matches_channel() {
+        let (_temp, backend) = setup();
+        backend
+            .record_file_create("a.txt", 0, b"a", "Create a.txt")
+            .unwrap();
+        let last = backend
+            .record_file_create("dir/b.txt", 0, b"b", "Create dir/b.txt")
+            .unwrap()
+            .unwrap();
+
+        let snapshot = backend.snapshot_at(Some(&last)).unwrap();
+        assert_eq!(snapshot.list_files(), backend.list_files().unwrap());
+        assert_eq!(snapshot.list_dirs(), vec!["dir"]);
+        assert_eq!(snapshot.get_file_content("dir/b.txt").unwrap(), b"b");
+    }
+
+    #[test]
+    fn test_snapshot_of_unknown_change() {
+        let (_temp, backend) = setup();
+        assert!(matches!(
+            backend.snapshot_at(Some(&Hash::None)),
+            Err(PijulError::ChangeNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_channel_leaves_working_copy() {
+        let (_temp, backend) = setup();
+        backend
+            .record_file_create("a.txt", 0, b"a", "Create a.txt")
+            .unwrap();
+        backend.fork_channel("main", "side").unwrap();
+        backend
+            .record_file_create("b.txt", 0, b"b", "Create b.txt")
+            .unwrap();
+
+        let side = backend.snapshot_channel("side").unwrap();
+        assert_eq!(side.list_files(), vec!["a.txt"]);
+        let main = backend.snapshot_channel("main").unwrap();
+        assert_eq!(main.list_files(), vec!["a.txt", "b.txt"]);
+        assert_eq!(backend.current_channel(), "main");
+        assert!(matches!(
+            backend.snapshot_channel("missing"),
+            Err(PijulError::ChannelNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_history_source_lists_changes_and_heads() {
+        let (_temp, backend) = setup();
+        let first = backend
+            .record_file_create("a.txt", 0, b"a", "Create a.txt")
+            .unwrap()
+            .unwrap();
+
+        let source: &dyn HistorySource = &backend;
+        assert_eq!(source.changes().unwrap(), vec![first.to_base32()]);
+        assert_eq!(
+            source.channel_head("main").unwrap(),
+            Some(first.to_base32())
+        );
+        let log = source.log().unwrap();
+        assert!(log.starts_with(&first.to_base32()));
+        assert!(log.trim_end().ends_with("Create a.txt"));
+        assert_eq!(
+            source.channel_head("missing").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Named tags on channel states
+//!
+//! A tag names the state of a channel at one point of its log, like "before
+//! the refactor", without forking the channel. The state is marked in the
+//! channel's tag table, as `pijul tag` does, and the name is kept in
+//! `.pijul/tags.toml` along with the channel, the state's Merkle hash and the
+//! change the state ends with:
+//!
+//! ```toml
+//! [tag.before-refactor]
+//! channel = "main"
+//! position = 41
+//! change = "MNYNGT2VGEQZX..."
+//! state = "OE3WTBJCQ6F7Y..."
+//! created = "2024-05-01T14:30:00Z"
+//! message = "Last state with the old parser"
+//! ```
+//!
+//! Wherever a change hash is accepted, a tag name is too; see
+//! [`PijulQuery::resolve_at`](super::PijulQuery::resolve_at).
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use libpijul::pristine::{Hash, Merkle};
+use libpijul::{Base32, MutTxnT, TxnT, TxnTExt};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+use super::backend::{PijulBackend, PijulError};
+use super::query::parse_timestamp;
+
+/// File under `.pijul/` listing tags by name
+pub const TAGS_FILE: &str = "tags.toml";
+
+/// A named channel state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    /// Name of the tag
+    pub name: String,
+    /// Channel the state belongs to
+    pub channel: String,
+    /// Position of the state in the channel's log
+    pub position: u64,
+    /// Last change applied in the state
+    pub change: Hash,
+    /// The state's Merkle hash
+    pub state: Merkle,
+    /// When the tag was created
+    pub created: DateTime<Utc>,
+    /// Optional note
+    pub message: Option<String>,
+}
+
+/// How tags sit in [`TAGS_FILE`]
+#[derive(Default, Serialize, Deserialize)]
+struct TagFile {
+    #[serde(default)]
+    tag: BTreeMap<String, TagRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TagRecord {
+    channel: String,
+    position: u64,
+    change: String,
+    state: String,
+    created: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl TagRecord {
+    fn into_tag(self, name: String) -> Result<Tag, PijulError> {
+        let invalid = || PijulError::Tag(format!("{}: bad hash in {}", name, TAGS_FILE));
+        Ok(Tag {
+            change: Hash::from_base32(self.change.as_bytes()).ok_or_else(invalid)?,
+            state: Merkle::from_base32(self.state.as_bytes()).ok_or_else(invalid)?,
+            name,
+            channel: self.channel,
+            position: self.position,
+            created: self.created,
+            message: self.message,
+        })
+    }
+}
+
+impl From<&Tag> for TagRecord {
+    fn from(tag: &Tag) -> Self {
+        Self {
+            channel: tag.channel.clone(),
+            position: tag.position,
+            change: tag.change.to_base32(),
+            state: tag.state.to_base32(),
+            created: tag.created,
+            message: tag.message.clone(),
+        }
+    }
+}
+
+impl PijulBackend {
+    /// Tag the current channel's head as `name`
+    ///
+    /// Names are unique across channels. An empty channel has no state to
+    /// tag.
+    pub fn create_tag(&self, name: &str, message: Option<&str>) -> Result<Tag, PijulError> {
+        check_tag_name(name)?;
+        let mut tags = self.read_tags()?;
+        if tags.tag.contains_key(name) {
+            return Err(PijulError::Tag(format!("tag {} already exists", name)));
+        }
+
+        let txn = self.arc_txn_begin()?;
+        let channel = txn
+            .read()
+            .load_channel(&self.current_channel)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            .ok_or_else(|| PijulError::ChannelNotFound(self.current_channel.clone()))?;
+
+        let (position, change, state) = {
+            let t = txn.read();
+            let channel_ref = channel.read();
+            let mut head = None;
+            for entry in t
+                .log(&*channel_ref, 0)
+                .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            {
+                let (position, (hash, merkle)) =
+                    entry.map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+                head = Some((position, Hash::from(*hash), Merkle::from(*merkle)));
+            }
+            head.ok_or_else(|| {
+                PijulError::Tag(format!(
+                    "channel {} has no changes to tag",
+                    self.current_channel
+                ))
+            })?
+        };
+        debug!(
+            "PijulBackend::create_tag name={} channel={} position={} change={:?}",
+            name, self.current_channel, position, change
+        );
+
+        txn.write()
+            .put_tags(&mut channel.write().tags, position, &state)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+        txn.commit()
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+
+        let tag = Tag {
+            name: name.to_string(),
+            channel: self.current_channel.clone(),
+            position,
+            change,
+            state,
+            created: Utc::now(),
+            message: message.map(String::from),
+        };
+        tags.tag.insert(name.to_string(), TagRecord::from(&tag));
+        self.write_tags(&tags)?;
+        info!(
+            "Tagged {:?} on {} as {}",
+            change, self.current_channel, name
+        );
+        Ok(tag)
+    }
+
+    /// Look up a tag by name
+    pub fn get_tag(&self, name: &str) -> Result<Option<Tag>, PijulError> {
+        self.read_tags()?
+            .tag
+            .remove(name)
+            .map(|record| record.into_tag(name.to_string()))
+            .transpose()
+    }
+
+    /// Every tag, sorted by name
+    pub fn list_tags(&self) -> Result<Vec<Tag>, PijulError> {
+        self.read_tags()?
+            .tag
+            .into_iter()
+            .map(|(name, record)| record.into_tag(name))
+            .collect()
+    }
+
+    /// Delete a tag, returning it
+    ///
+    /// The state stays marked in its channel while other tags name it.
+    pub fn delete_tag(&self, name: &str) -> Result<Tag, PijulError> {
+        let mut tags = self.read_tags()?;
+        let tag = tags
+            .tag
+            .remove(name)
+            .ok_or_else(|| PijulError::TagNotFound(name.to_string()))?
+            .into_tag(name.to_string())?;
+
+        let shared = tags
+            .tag
+            .values()
+            .any(|other| other.channel == tag.channel && other.position == tag.position);
+        if !shared {
+            let txn = self.arc_txn_begin()?;
+            let channel = txn
+                .read()
+                .load_channel(&tag.channel)
+                .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+            // The channel may be gone; the name is all that's left to delete
+            if let Some(channel) = channel {
+                txn.write()
+                    .del_tags(&mut channel.write().tags, tag.position)
+                    .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+                txn.commit()
+                    .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+            }
+        }
+
+        self.write_tags(&tags)?;
+        info!("Deleted tag {}", name);
+        Ok(tag)
+    }
+
+    /// Replace the records of `updated` tags, as when history is rewritten
+    /// under them
+    pub(super) fn save_tags(&self, updated: &[Tag]) -> Result<(), PijulError> {
+        if updated.is_empty() {
+            return Ok(());
+        }
+        let mut tags = self.read_tags()?;
+        for tag in updated {
+            tags.tag.insert(tag.name.clone(), TagRecord::from(tag));
+        }
+        self.write_tags(&tags)
+    }
+
+    fn read_tags(&self) -> Result<TagFile, PijulError> {
+        let path = self.pijul_dir().join(TAGS_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| PijulError::Tag(format!("{}: {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(TagFile::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_tags(&self, tags: &TagFile) -> Result<(), PijulError> {
+        let content = toml::to_string_pretty(tags)
+            .map_err(|e| PijulError::Tag(format!("{}: {}", TAGS_FILE, e)))?;
+        std::fs::write(self.pijul_dir().join(TAGS_FILE), content)?;
+        Ok(())
+    }
+}
+
+/// Refuse names that couldn't be told apart from the other ways of naming a
+/// point in history
+fn check_tag_name(name: &str) -> Result<(), PijulError> {
+    let reason = if name.is_empty() {
+        "tag names can't be empty"
+    } else if name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        "tag names can't contain whitespace"
+    } else if name.starts_with('-') {
+        "tag names can't start with '-'"
+    } else if parse_timestamp(name).is_some() {
+        "tag names can't look like a point in time"
+    } else {
+        return Ok(());
+    };
+    Err(PijulError::Tag(format!("{:?}: {}", name, reason)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    #[test]
+    fn test_create_and_list_tags() {
+        let (_temp, backend) = setup();
+        assert!(matches!(
+            backend.create_tag("empty", None),
+            Err(PijulError::Tag(_))
+        ));
+
+        let first = backend
+            .record_file_create("a.txt", 0, b"one\n", "Create a.txt")
+            .unwrap()
+            .unwrap();
+        let tag = backend
+            .create_tag("before-refactor", Some("Old parser"))
+            .unwrap();
+        assert_eq!(tag.change, first);
+        assert_eq!(tag.channel, "main");
+        assert_eq!(tag.message.as_deref(), Some("Old parser"));
+
+        backend
+            .record_file_create("b.txt", 0, b"two\n", "Create b.txt")
+            .unwrap();
+        backend.create_tag("after", None).unwrap();
+        assert!(backend.create_tag("after", None).is_err());
+
+        let names: Vec<String> = backend
+            .list_tags()
+            .unwrap()
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        assert_eq!(names, vec!["after", "before-refactor"]);
+        assert_eq!(backend.get_tag("before-refactor").unwrap(), Some(tag));
+        assert_eq!(backend.get_tag("nope").unwrap(), None);
+    }
+
+    #[test]
+    fn test_delete_tag() {
+        let (_temp, backend) = setup();
+        backend
+            .record_file_create("a.txt", 0, b"one\n", "Create a.txt")
+            .unwrap();
+        backend.create_tag("v1", None).unwrap();
+        backend.create_tag("also-v1", None).unwrap();
+
+        assert_eq!(backend.delete_tag("v1").unwrap().name, "v1");
+        assert!(matches!(
+            backend.delete_tag("v1"),
+            Err(PijulError::TagNotFound(_))
+        ));
+        assert_eq!(backend.list_tags().unwrap().len(), 1);
+        backend.delete_tag("also-v1").unwrap();
+        assert!(backend.list_tags().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tag_names() {
+        assert!(check_tag_name("before-refactor").is_ok());
+        assert!(check_tag_name("release/1.0").is_ok());
+        assert!(check_tag_name("").is_err());
+        assert!(check_tag_name("two words").is_err());
+        assert!(check_tag_name("--force").is_err());
+        assert!(check_tag_name("2024-05-01").is_err());
+        assert!(check_tag_name("@1714573800").is_err());
+    }
+}
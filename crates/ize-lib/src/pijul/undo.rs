@@ -0,0 +1,224 @@
+//! Taking recorded changes back
+//!
+//! Undo unrecords the newest changes of the current channel and outputs the
+//! channel into the working copy, so an accidental `rm -rf` through the mount
+//! can be walked back. Before anything is unrecorded the channel is forked to
+//! `undone/<channel>/<time>`, which keeps every undone change: switching to
+//! it, merging it or applying single changes from it undoes the undo.
+
+use chrono::{DateTime, Utc};
+use libpijul::pristine::Hash;
+use libpijul::{MutTxnTExt, TxnT};
+use log::{debug, info};
+
+use super::backend::{PijulBackend, PijulError};
+use super::checkout::side_channel_name;
+use super::query::PijulQuery;
+
+/// Prefix of the channels undone changes are kept on
+pub const UNDONE_PREFIX: &str = "undone/";
+
+/// How far back to undo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoTarget {
+    /// The last `n` changes
+    Last(usize),
+    /// Every change recorded at or after this time
+    Since(DateTime<Utc>),
+    /// This change and every change applied after it
+    Change(Hash),
+}
+
+/// Outcome of an undo
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UndoReport {
+    /// Changes taken off the channel, newest first
+    pub undone: Vec<Hash>,
+    /// Channel still holding them; `None` if there was nothing to undo
+    pub kept_on: Option<String>,
+}
+
+impl PijulBackend {
+    /// Unrecord the changes selected by `target` from the current channel
+    /// and restore the working copy to match
+    pub fn undo(&mut self, target: UndoTarget) -> Result<UndoReport, PijulError> {
+        // Outputting afterwards would overwrite anything not yet recorded
+        let dirty = self.unrecorded_changes()?;
+        if !dirty.is_empty() {
+            return Err(PijulError::UnrecordedChanges(dirty));
+        }
+
+        let log = self.list_changes()?;
+        let first = match target {
+            UndoTarget::Last(n) => log.len().saturating_sub(n),
+            UndoTarget::Since(time) => PijulQuery::new(self)
+                .list_changes_detailed()?
+                .iter()
+                .position(|change| change.timestamp >= time)
+                .unwrap_or(log.len()),
+            UndoTarget::Change(hash) => log
+                .iter()
+                .position(|h| *h == hash)
+                .ok_or_else(|| PijulError::ChangeNotFound(format!("{:?}", hash)))?,
+        };
+        let undone: Vec<Hash> = log[first..].iter().rev().copied().collect();
+        debug!(
+            "PijulBackend::undo channel={} target={:?} undoing {} change(s)",
+            self.current_channel,
+            target,
+            undone.len()
+        );
+        if undone.is_empty() {
+            return Ok(UndoReport::default());
+        }
+
+        let kept_on = side_channel_name(UNDONE_PREFIX, &self.current_channel);
+        self.fork_channel(&self.current_channel, &kept_on)?;
+
+        let change_store = self.get_change_store();
+        let txn = self.arc_txn_begin()?;
+        let channel = txn
+            .read()
+            .load_channel(&self.current_channel)
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?
+            .ok_or_else(|| PijulError::ChannelNotFound(self.current_channel.clone()))?;
+
+        for hash in &undone {
+            txn.write()
+                .unrecord(&change_store, &channel, hash, 0)
+                .map_err(|e| PijulError::Transaction(format!("unrecord {:?}: {:?}", hash, e)))?;
+        }
+
+        // Also deletes files the undone changes created
+        libpijul::output::output_repository_no_pending(
+            &self.working_copy(),
+            &change_store,
+            &txn,
+            &channel,
+            "",
+            true,
+            None,
+            1,
+            0,
+        )
+        .map_err(|e| PijulError::Transaction(format!("output after undo: {:?}", e)))?;
+
+        txn.commit()
+            .map_err(|e| PijulError::Transaction(format!("{:?}", e)))?;
+
+        self.hydrate_working_copy()?;
+
+        info!("Undid {} change(s), kept on {}", undone.len(), kept_on);
+        Ok(UndoReport {
+            undone,
+            kept_on: Some(kept_on),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        std::fs::create_dir_all(backend.working_dir()).unwrap();
+        (temp, backend)
+    }
+
+    /// Create a file in the working copy and record it, as the mount does
+    fn create(backend: &PijulBackend, path: &str, content: &[u8]) -> Hash {
+        std::fs::write(backend.working_dir().join(path), content).unwrap();
+        backend
+            .record_file_create(path, 0, content, &format!("Create {}", path))
+            .unwrap()
+            .unwrap()
+    }
+
+    fn exists(backend: &PijulBackend, path: &str) -> bool {
+        backend.working_dir().join(path).exists()
+    }
+
+    #[test]
+    fn test_undo_last_changes() {
+        let (_temp, mut backend) = setup();
+        let a = create(&backend, "a.txt", b"a\n");
+        let b = create(&backend, "b.txt", b"b\n");
+        let c = create(&backend, "c.txt", b"c\n");
+
+        let report = backend.undo(UndoTarget::Last(2)).unwrap();
+        assert_eq!(report.undone, vec![c, b]);
+        assert_eq!(backend.list_changes().unwrap(), vec![a]);
+        assert!(exists(&backend, "a.txt"));
+        assert!(!exists(&backend, "b.txt"));
+        assert!(!exists(&backend, "c.txt"));
+
+        // The undone changes are still around
+        let kept_on = report.kept_on.unwrap();
+        assert!(kept_on.starts_with("undone/main/"));
+        assert_eq!(backend.list_changes_in(&kept_on).unwrap(), vec![a, b, c]);
+    }
+
+    #[test]
+    fn test_undo_restores_deleted_file() {
+        let (_temp, mut backend) = setup();
+        create(&backend, "a.txt", b"precious\n");
+        std::fs::remove_file(backend.working_dir().join("a.txt")).unwrap();
+        backend.record_file_delete("a.txt", "Delete a.txt").unwrap();
+
+        backend.undo(UndoTarget::Last(1)).unwrap();
+        assert_eq!(
+            std::fs::read(backend.working_dir().join("a.txt")).unwrap(),
+            b"precious\n"
+        );
+    }
+
+    #[test]
+    fn test_undo_back_to_change() {
+        let (_temp, mut backend) = setup();
+        let a = create(&backend, "a.txt", b"a\n");
+        let b = create(&backend, "b.txt", b"b\n");
+        let c = create(&backend, "c.txt", b"c\n");
+
+        let report = backend.undo(UndoTarget::Change(b)).unwrap();
+        assert_eq!(report.undone, vec![c, b]);
+        assert_eq!(backend.list_changes().unwrap(), vec![a]);
+    }
+
+    #[test]
+    fn test_undo_since() {
+        let (_temp, mut backend) = setup();
+        create(&backend, "a.txt", b"a\n");
+
+        let future = Utc::now() + chrono::Duration::hours(1);
+        assert_eq!(
+            backend.undo(UndoTarget::Since(future)).unwrap(),
+            UndoReport::default()
+        );
+
+        let past = Utc::now() - chrono::Duration::hours(1);
+        assert_eq!(
+            backend.undo(UndoTarget::Since(past)).unwrap().undone.len(),
+            1
+        );
+        assert!(backend.list_changes().unwrap().is_empty());
+        assert!(!exists(&backend, "a.txt"));
+    }
+
+    #[test]
+    fn test_undo_refuses_unrecorded_changes() {
+        let (_temp, mut backend) = setup();
+        create(&backend, "a.txt", b"a\n");
+        std::fs::write(backend.working_dir().join("a.txt"), b"edited\n").unwrap();
+
+        assert!(matches!(
+            backend.undo(UndoTarget::Last(1)),
+            Err(PijulError::UnrecordedChanges(_))
+        ));
+        assert_eq!(backend.list_changes().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,1019 @@
+//! Encryption at rest
+//!
+//! The change files under `.pijul/changes/` and the chunks under
+//! `.pijul/blobs/` hold every version of every file a project has seen. An
+//! encrypted project seals each of them with XChaCha20-Poly1305 under a
+//! 256-bit key, read from a key file or derived from a passphrase with
+//! Argon2id. `.pijul/encryption.toml` says where the key comes from and
+//! which key it is, so a missing or wrong key is reported as such:
+//!
+//! ```toml
+//! key_id = "3f9a0c51d2e47b86"
+//! keyfile = "/home/me/.config/ize/keys/0b1c7e2a-....key"
+//! ```
+//!
+//! A passphrase-encrypted project has a `salt` instead of a `keyfile`, and
+//! the passphrase is read from `IZE_PASSPHRASE`.
+//!
+//! libpijul reads change files straight from a directory, so opening an
+//! encrypted repository decrypts them into a cache in the user's runtime
+//! directory (`$XDG_RUNTIME_DIR`, memory-backed and private to the user;
+//! opening fails without one rather than decrypt to disk). The mount and the
+//! CLI share the cache, and opening only decrypts the changes it lacks; the
+//! last of them to close the store deletes it. A new change is sealed into
+//! `.pijul/changes/` as soon as it is saved. Chunks are decrypted as they
+//! are read, and stored under a keyed hash of their content so their names
+//! don't give it away.
+//!
+//! Rotating the key saves the new key and `encryption.toml` naming it, and
+//! the key it replaces under `rotating_from`, before resealing anything.
+//! Opening a store whose rotation was interrupted finishes it, reading each
+//! file with whichever of the two keys it was sealed with.
+//!
+//! Opcodes journaled to a [`LogStore`](crate::storage::LogStore) are sealed
+//! too: a store made with `LogStore::init_sealed` and the project's key
+//! seals each content and index line it appends.
+//!
+//! Not encrypted: the pristine, which holds file names and the shape of the
+//! history but no contents, and `working/`, which mirrors what the source
+//! directory shows anyway.
+
+use std::fs::File;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use libpijul::pristine::Hash;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::backend::{PijulBackend, PijulError, CHANGES_DIR};
+use super::blobs::{BlobStore, BLOBS_DIR};
+use super::retention::change_file;
+use crate::identity::{from_hex, to_hex, CONFIG_DIR};
+
+/// File under `.pijul/` describing the key an encrypted store is sealed with
+pub const ENCRYPTION_FILE: &str = "encryption.toml";
+
+/// Environment variable holding the passphrase of a passphrase-encrypted
+/// project
+pub const PASSPHRASE_VAR: &str = "IZE_PASSPHRASE";
+
+/// Environment variable holding the new passphrase while rotating to one,
+/// since [`PASSPHRASE_VAR`] holds the current one
+pub const NEW_PASSPHRASE_VAR: &str = "IZE_NEW_PASSPHRASE";
+
+/// Environment variable pointing at a project's key file, if it has moved
+pub const KEY_FILE_VAR: &str = "IZE_KEY_FILE";
+
+/// Directory under [`CONFIG_DIR`] holding generated key files
+pub const KEYS_DIR: &str = "keys";
+
+/// Start of every sealed file, followed by the key id, the nonce and the
+/// ciphertext
+const SEAL_MAGIC: &[u8] = b"ize-sealed 1\n";
+
+const KEY_ID_LEN: usize = 8;
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+
+/// Where a generated key file for the project `uuid` goes by default
+pub fn default_keyfile(uuid: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| {
+        dir.join(CONFIG_DIR)
+            .join(KEYS_DIR)
+            .join(format!("{}.key", uuid))
+    })
+}
+
+/// A 256-bit key sealing a store
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key([u8; 32]);
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Key({})", self.id())
+    }
+}
+
+impl Key {
+    fn generate() -> Result<Self, PijulError> {
+        let mut bytes = [0u8; 32];
+        random(&mut bytes)?;
+        Ok(Self(bytes))
+    }
+
+    /// The key `passphrase` stands for with `salt`, by Argon2id
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self, PijulError> {
+        let mut bytes = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut bytes)
+            .map_err(|e| PijulError::Encryption(format!("deriving key: {}", e)))?;
+        Ok(Self(bytes))
+    }
+
+    /// Read a key file, the key hex-encoded on one line
+    pub fn load(path: &Path) -> Result<Self, PijulError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(PijulError::KeyMissing(format!(
+                    "key file {} not found; set {} if it has moved",
+                    path.display(),
+                    KEY_FILE_VAR
+                )));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        from_hex(content.trim())
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .map(Self)
+            .ok_or_else(|| PijulError::Encryption(format!("{} is not a key file", path.display())))
+    }
+
+    /// Write the key to a new file at `path`, readable by the owner only
+    fn save(&self, path: &Path) -> Result<(), PijulError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+        std::io::Write::write_all(&mut file, format!("{}\n", to_hex(&self.0)).as_bytes())?;
+        Ok(())
+    }
+
+    /// Whether `sealed` says it was sealed with this key
+    fn sealed(&self, sealed: &[u8]) -> bool {
+        sealed.get(SEAL_MAGIC.len()..SEAL_MAGIC.len() + KEY_ID_LEN) == Some(&self.id_bytes()[..])
+    }
+
+    /// Short name of the key, hex: tells keys apart without giving them away
+    pub fn id(&self) -> String {
+        to_hex(&self.id_bytes())
+    }
+
+    fn id_bytes(&self) -> [u8; KEY_ID_LEN] {
+        let hash = blake3::keyed_hash(&self.0, b"ize key id");
+        let mut id = [0u8; KEY_ID_LEN];
+        id.copy_from_slice(&hash.as_bytes()[..KEY_ID_LEN]);
+        id
+    }
+
+    /// Encrypt `plain`
+    pub fn seal(&self, plain: &[u8]) -> Result<Vec<u8>, PijulError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        random(&mut nonce)?;
+        let mut sealed = SEAL_MAGIC.to_vec();
+        sealed.extend_from_slice(&self.id_bytes());
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plain,
+                    aad: &sealed,
+                },
+            )
+            .map_err(|_| PijulError::Encryption("sealing failed".to_string()))?;
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypt what [`seal`](Self::seal) made, failing if it was sealed
+    /// with another key or altered since
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, PijulError> {
+        let header = SEAL_MAGIC.len() + KEY_ID_LEN;
+        if sealed.len() < header + NONCE_LEN || !sealed.starts_with(SEAL_MAGIC) {
+            return Err(PijulError::Encryption("not a sealed file".to_string()));
+        }
+        let id = &sealed[SEAL_MAGIC.len()..header];
+        if id != self.id_bytes() {
+            return Err(PijulError::Encryption(format!(
+                "sealed with key {}, not {}",
+                to_hex(id),
+                self.id()
+            )));
+        }
+        let (nonce, ciphertext) = sealed[header..].split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &sealed[..header],
+                },
+            )
+            .map_err(|_| PijulError::Encryption("sealed file is corrupt".to_string()))
+    }
+
+    /// Name the chunk with content hash `hash` is stored under
+    pub(super) fn chunk_name(&self, hash: &blake3::Hash) -> blake3::Hash {
+        blake3::keyed_hash(&self.0, hash.as_bytes())
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new_from_slice(&self.0).expect("keys are 32 bytes")
+    }
+}
+
+/// Where the key for [`PijulBackend::encrypt`] or
+/// [`PijulBackend::rotate_key`] comes from
+pub enum KeySource {
+    /// The key in this file, generated there if the file doesn't exist
+    KeyFile(PathBuf),
+    /// The key derived from this passphrase, with a new salt
+    Passphrase(String),
+}
+
+impl KeySource {
+    /// The key, and the settings that find it again
+    fn key(&self) -> Result<(Key, EncryptionSettings), PijulError> {
+        match self {
+            KeySource::KeyFile(path) => {
+                let key = if path.exists() {
+                    Key::load(path)?
+                } else {
+                    let key = Key::generate()?;
+                    key.save(path)?;
+                    info!("Generated key file {}", path.display());
+                    key
+                };
+                let settings = EncryptionSettings {
+                    key_id: key.id(),
+                    keyfile: Some(std::fs::canonicalize(path)?),
+                    salt: None,
+                    rotating_from: None,
+                };
+                Ok((key, settings))
+            }
+            KeySource::Passphrase(passphrase) => {
+                let mut salt = [0u8; SALT_LEN];
+                random(&mut salt)?;
+                let key = Key::from_passphrase(passphrase, &salt)?;
+                let settings = EncryptionSettings {
+                    key_id: key.id(),
+                    keyfile: None,
+                    salt: Some(to_hex(&salt)),
+                    rotating_from: None,
+                };
+                Ok((key, settings))
+            }
+        }
+    }
+}
+
+/// Contents of [`ENCRYPTION_FILE`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptionSettings {
+    /// [`Key::id`] of the key the store is sealed with
+    pub key_id: String,
+    /// Key file the key is read from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyfile: Option<PathBuf>,
+    /// Salt the key is derived from the passphrase with, hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    /// Settings of the key being rotated away from, until every file is
+    /// sealed with this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotating_from: Option<Box<EncryptionSettings>>,
+}
+
+impl EncryptionSettings {
+    /// The settings of the store at `pijul_dir`; `None` if it isn't
+    /// encrypted
+    pub fn load(pijul_dir: &Path) -> Result<Option<Self>, PijulError> {
+        let path = pijul_dir.join(ENCRYPTION_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        toml::from_str(&std::fs::read_to_string(&path)?)
+            .map(Some)
+            .map_err(|e| PijulError::Encryption(format!("{}: {}", path.display(), e)))
+    }
+
+    fn save(&self, pijul_dir: &Path) -> Result<(), PijulError> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| PijulError::Encryption(format!("{}: {}", ENCRYPTION_FILE, e)))?;
+        write_private(&pijul_dir.join(ENCRYPTION_FILE), content.as_bytes())
+    }
+
+    /// The key the store is sealed with, from the key file (or
+    /// [`KEY_FILE_VAR`]) or the passphrase in [`PASSPHRASE_VAR`]
+    ///
+    /// Until a rotation finishes, the new key may still be staged next to
+    /// the key file, or its passphrase in [`NEW_PASSPHRASE_VAR`]; both are
+    /// tried when the usual place doesn't hold it.
+    pub fn key(&self) -> Result<Key, PijulError> {
+        let mut candidates: Box<dyn Iterator<Item = Result<Key, PijulError>>> =
+            match (&self.keyfile, &self.salt) {
+                (Some(keyfile), _) => {
+                    let path = std::env::var_os(KEY_FILE_VAR)
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| keyfile.clone());
+                    let paths = [path, staged_keyfile(keyfile)];
+                    Box::new(paths.into_iter().map(|path| Key::load(&path)))
+                }
+                (None, Some(salt)) => {
+                    let salt = from_hex(salt).ok_or_else(|| {
+                        PijulError::Encryption(format!("invalid salt in {}", ENCRYPTION_FILE))
+                    })?;
+                    let passphrases: Vec<_> = [PASSPHRASE_VAR, NEW_PASSPHRASE_VAR]
+                        .into_iter()
+                        .filter_map(|var| std::env::var(var).ok())
+                        .collect();
+                    if passphrases.is_empty() {
+                        return Err(PijulError::KeyMissing(format!(
+                            "the project is encrypted with a passphrase; set {} to it",
+                            PASSPHRASE_VAR
+                        )));
+                    }
+                    Box::new(
+                        passphrases
+                            .into_iter()
+                            .map(move |passphrase| Key::from_passphrase(&passphrase, &salt)),
+                    )
+                }
+                (None, None) => {
+                    return Err(PijulError::Encryption(format!(
+                        "{} names neither a key file nor a salt",
+                        ENCRYPTION_FILE
+                    )));
+                }
+            };
+
+        // Only the first place is complained about
+        let first = candidates
+            .next()
+            .expect("there is always a first place to look");
+        if matches!(&first, Ok(key) if key.id() == self.key_id) {
+            return first;
+        }
+        if let Some(key) = candidates.find_map(|key| key.ok().filter(|key| key.id() == self.key_id))
+        {
+            return Ok(key);
+        }
+        let key = first?;
+        Err(PijulError::Encryption(match self.keyfile {
+            Some(_) => format!(
+                "the project is sealed with key {}, not {}",
+                self.key_id,
+                key.id()
+            ),
+            None => "wrong passphrase".to_string(),
+        }))
+    }
+}
+
+/// An unlocked encrypted store
+pub(super) struct Vault {
+    key: Key,
+    settings: EncryptionSettings,
+    /// Where change files are decrypted for libpijul to read
+    cache: Cache,
+}
+
+impl Vault {
+    /// Unlock the store at `pijul_dir`, finishing an interrupted key
+    /// rotation; `None` if it isn't encrypted
+    pub(super) fn open(pijul_dir: &Path) -> Result<Option<Self>, PijulError> {
+        let Some(mut settings) = EncryptionSettings::load(pijul_dir)? else {
+            return Ok(None);
+        };
+        let key = settings.key()?;
+        if settings.rotating_from.is_some() {
+            info!(
+                "Finishing the interrupted rotation of {} to key {}",
+                pijul_dir.display(),
+                key.id()
+            );
+        }
+        finish_rotation(pijul_dir, &key, &mut settings)?;
+        let vault = Self {
+            key,
+            settings,
+            cache: Cache::open(pijul_dir)?,
+        };
+        vault.sync(&pijul_dir.join(CHANGES_DIR))?;
+        Ok(Some(vault))
+    }
+
+    /// Decrypt the sealed changes the cache lacks, and seal the cached ones
+    /// the store lacks (saved by a process that stopped before sealing them)
+    fn sync(&self, changes_dir: &Path) -> Result<(), PijulError> {
+        let mut decrypted = 0;
+        for relative in change_files(changes_dir)? {
+            let plain = self.cache.dir.join(&relative);
+            if !plain.exists() {
+                let sealed = std::fs::read(changes_dir.join(&relative))?;
+                write_private(&plain, &self.key.open(&sealed)?)?;
+                decrypted += 1;
+            }
+        }
+        for relative in change_files(&self.cache.dir)? {
+            let sealed = changes_dir.join(&relative);
+            if !sealed.exists() {
+                let plain = std::fs::read(self.cache.dir.join(&relative))?;
+                write_private(&sealed, &self.key.seal(&plain)?)?;
+            }
+        }
+        debug!(
+            "Vault::sync decrypted {} change(s) into {}",
+            decrypted,
+            self.cache.dir.display()
+        );
+        Ok(())
+    }
+}
+
+impl PijulBackend {
+    /// How the store is encrypted; `None` if it isn't
+    pub fn encryption(&self) -> Option<&EncryptionSettings> {
+        self.vault.as_ref().map(|vault| &vault.settings)
+    }
+
+    /// The key chunks and change files are sealed with, if encrypted
+    pub(super) fn store_key(&self) -> Option<&Key> {
+        self.vault.as_ref().map(|vault| &vault.key)
+    }
+
+    /// Directory libpijul reads change files from: the decrypted cache of
+    /// an encrypted store, else the change store itself
+    pub fn plain_changes_dir(&self) -> PathBuf {
+        match &self.vault {
+            Some(vault) => vault.cache.dir.clone(),
+            None => self.changes_dir(),
+        }
+    }
+
+    /// Seal the change just saved into the cache into the store
+    pub(super) fn seal_change(&self, hash: &Hash) -> Result<(), PijulError> {
+        if let Some(vault) = &self.vault {
+            let plain = std::fs::read(change_file(&vault.cache.dir, hash))?;
+            write_private(
+                &change_file(&self.changes_dir(), hash),
+                &vault.key.seal(&plain)?,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Delete a change file from the store, and its decrypted copy
+    pub(super) fn remove_change_file(&self, path: &Path) -> Result<(), PijulError> {
+        std::fs::remove_file(path)?;
+        if let Some(vault) = &self.vault {
+            if let Ok(relative) = path.strip_prefix(self.changes_dir()) {
+                match std::fs::remove_file(vault.cache.dir.join(relative)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encrypt the store with the key from `source`, sealing the history
+    /// recorded so far
+    pub fn encrypt(&mut self, source: &KeySource) -> Result<&EncryptionSettings, PijulError> {
+        if self.vault.is_some() {
+            return Err(PijulError::Encryption(
+                "the project is already encrypted; rotate its key instead".to_string(),
+            ));
+        }
+        let (key, settings) = source.key()?;
+        reseal(self.pijul_dir(), None, &key)?;
+        settings.save(self.pijul_dir())?;
+
+        let vault = Vault {
+            key,
+            settings,
+            cache: Cache::open(self.pijul_dir())?,
+        };
+        vault.sync(&self.changes_dir())?;
+        info!(
+            "Encrypted {} with key {}",
+            self.pijul_dir().display(),
+            vault.key.id()
+        );
+        Ok(&self.vault.insert(vault).settings)
+    }
+
+    /// Seal the store under a new key from `source`
+    ///
+    /// Giving the key file the store is sealed with now generates a new key
+    /// that replaces it once everything is sealed again.
+    pub fn rotate_key(&mut self, source: &KeySource) -> Result<&EncryptionSettings, PijulError> {
+        let Some(vault) = self.vault.take() else {
+            return Err(PijulError::Encryption(
+                "the project isn't encrypted".to_string(),
+            ));
+        };
+
+        let result = self.rotate_from(&vault, source);
+        let vault = match result {
+            Ok((key, settings)) => Vault {
+                key,
+                settings,
+                cache: vault.cache,
+            },
+            Err(e) => {
+                self.vault = Some(vault);
+                return Err(e);
+            }
+        };
+        info!(
+            "Rotated {} to key {}",
+            self.pijul_dir().display(),
+            vault.key.id()
+        );
+        Ok(&self.vault.insert(vault).settings)
+    }
+
+    fn rotate_from(
+        &self,
+        vault: &Vault,
+        source: &KeySource,
+    ) -> Result<(Key, EncryptionSettings), PijulError> {
+        // A new key for the current key file is staged next to it
+        let replaced = match (source, &vault.settings.keyfile) {
+            (KeySource::KeyFile(path), Some(current))
+                if std::fs::canonicalize(path).ok().as_ref() == Some(current) =>
+            {
+                Some(current.clone())
+            }
+            _ => None,
+        };
+        let (key, mut settings) = match &replaced {
+            Some(replaced) => {
+                let staged = staged_keyfile(replaced);
+                if staged.exists() {
+                    std::fs::remove_file(&staged)?;
+                }
+                KeySource::KeyFile(staged).key()?
+            }
+            None => source.key()?,
+        };
+        if key == vault.key {
+            return Err(PijulError::Encryption(format!(
+                "the project is already sealed with key {}",
+                key.id()
+            )));
+        }
+
+        // Saved before anything is resealed, so that whatever interrupts
+        // the rotation, opening the store finds both keys and finishes it
+        if let Some(replaced) = replaced {
+            settings.keyfile = Some(replaced);
+        }
+        settings.rotating_from = Some(Box::new(vault.settings.clone()));
+        settings.save(self.pijul_dir())?;
+
+        finish_rotation(self.pijul_dir(), &key, &mut settings)?;
+        Ok((key, settings))
+    }
+}
+
+/// Finish rotating the store at `pijul_dir` to `key`, as `settings` name
+/// it: reseal what is still sealed with the key it replaces, forget that
+/// key, then move a staged key file into place
+fn finish_rotation(
+    pijul_dir: &Path,
+    key: &Key,
+    settings: &mut EncryptionSettings,
+) -> Result<(), PijulError> {
+    if let Some(previous) = &settings.rotating_from {
+        let from = previous.key().map_err(|e| {
+            PijulError::Encryption(format!(
+                "finishing the rotation to key {} needs key {} as well: {}",
+                settings.key_id, previous.key_id, e
+            ))
+        })?;
+        reseal(pijul_dir, Some(&from), key)?;
+        settings.rotating_from = None;
+        settings.save(pijul_dir)?;
+    }
+    if let Some(keyfile) = &settings.keyfile {
+        let staged = staged_keyfile(keyfile);
+        if Key::load(&staged).is_ok_and(|staged| staged == *key) {
+            std::fs::rename(&staged, keyfile)?;
+        }
+    }
+    Ok(())
+}
+
+/// Seal every change file and chunk of the store at `pijul_dir` under `to`,
+/// reading them with `from` (`None` when they are plain)
+///
+/// Both stores are written aside and swapped in once complete, so an
+/// interruption leaves each as it was or fully resealed. Files already
+/// sealed with `to` are read with it, so a reseal can be run again.
+fn reseal(pijul_dir: &Path, from: Option<&Key>, to: &Key) -> Result<(), PijulError> {
+    let changes_dir = pijul_dir.join(CHANGES_DIR);
+    let blobs_dir = pijul_dir.join(BLOBS_DIR);
+    for dir in [&changes_dir, &blobs_dir] {
+        recover_swap(dir)?;
+    }
+
+    let (files, chunks) = seal_aside(pijul_dir, from, to)?;
+    swap_in(&changes_dir, &changes_dir.with_extension("next"))?;
+    let next_blobs = blobs_dir.with_extension("next");
+    if next_blobs.exists() {
+        swap_in(&blobs_dir, &next_blobs)?;
+    }
+    debug!(
+        "reseal {} change(s) and {} chunk(s) under key {}",
+        files,
+        chunks,
+        to.id()
+    );
+    Ok(())
+}
+
+/// Write the change files and chunks of the store at `pijul_dir`, sealed
+/// under `to`, into `changes.next` and `blobs.next`; how many of each
+fn seal_aside(
+    pijul_dir: &Path,
+    from: Option<&Key>,
+    to: &Key,
+) -> Result<(usize, usize), PijulError> {
+    let changes_dir = pijul_dir.join(CHANGES_DIR);
+    let next_changes = changes_dir.with_extension("next");
+    let blobs_dir = pijul_dir.join(BLOBS_DIR);
+    let next_blobs = blobs_dir.with_extension("next");
+    for dir in [&next_changes, &next_blobs] {
+        if dir.exists() {
+            std::fs::remove_dir_all(dir)?;
+        }
+    }
+    let unseal = |content: Vec<u8>| match from {
+        None => Ok(content),
+        Some(_) if to.sealed(&content) => to.open(&content),
+        Some(from) => from.open(&content),
+    };
+
+    let files = change_files(&changes_dir)?;
+    for relative in &files {
+        let plain = unseal(std::fs::read(changes_dir.join(relative))?)?;
+        write_private(&next_changes.join(relative), &to.seal(&plain)?)?;
+    }
+    std::fs::create_dir_all(&next_changes)?;
+
+    let blobs = BlobStore::new(&blobs_dir);
+    let next = BlobStore::new(&next_blobs).with_key(Some(to.clone()));
+    let chunks = blobs.chunk_files()?;
+    for path in &chunks {
+        next.put_chunk(&unseal(std::fs::read(path)?)?)?;
+    }
+    Ok((files.len(), chunks.len()))
+}
+
+/// Where a new key for `keyfile` is kept until a rotation to it finishes
+fn staged_keyfile(keyfile: &Path) -> PathBuf {
+    keyfile.with_extension("key.new")
+}
+
+/// Change files under `dir`, relative to it: `<first 2 chars>/<rest>.change`
+fn change_files(dir: &Path) -> Result<Vec<PathBuf>, PijulError> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    for prefix in std::fs::read_dir(dir)? {
+        let prefix = prefix?;
+        if !prefix.file_type()?.is_dir() {
+            continue;
+        }
+        for file in std::fs::read_dir(prefix.path())? {
+            let name = file?.file_name();
+            if Path::new(&name)
+                .extension()
+                .is_some_and(|ext| ext == "change")
+            {
+                files.push(Path::new(&prefix.file_name()).join(name));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Private directory the changes of a store are decrypted into, shared by
+/// every process of the user that has the store open
+struct Cache {
+    dir: PathBuf,
+    /// `<dir>.lock`, locked shared for as long as the cache is in use
+    lock: File,
+}
+
+impl Cache {
+    /// The cache of the store at `pijul_dir`, in the user's runtime
+    /// directory
+    fn open(pijul_dir: &Path) -> Result<Self, PijulError> {
+        let uid = nix::unistd::getuid().as_raw();
+        // Decrypted changes are only ever written to memory-backed storage
+        // private to the user, never to a disk or a shared directory
+        let runtime = dirs::runtime_dir().ok_or_else(|| {
+            PijulError::Encryption(
+                "no private runtime directory to decrypt changes into; \
+                 set XDG_RUNTIME_DIR to a directory only you can read"
+                    .to_string(),
+            )
+        })?;
+        let base = runtime.join(format!("ize-{}", uid));
+        match std::fs::DirBuilder::new().mode(0o700).create(&base) {
+            Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e.into()),
+            _ => {}
+        }
+        let meta = std::fs::symlink_metadata(&base)?;
+        if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+            return Err(PijulError::Encryption(format!(
+                "{} is not a directory private to you",
+                base.display()
+            )));
+        }
+
+        let store = std::fs::canonicalize(pijul_dir)?;
+        let name = blake3::hash(store.as_os_str().as_encoded_bytes()).to_hex();
+        let dir = base.join(&name[..16]);
+        // The lock file outlives the cache, so every process locks the same
+        // file; taken before the cache is created so a closing process
+        // can't delete it under us
+        let lock = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(dir.with_extension("lock"))?;
+        flock(&lock, libc::LOCK_SH)?;
+        match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e.into()),
+            _ => {}
+        }
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+        Ok(Self { dir, lock })
+    }
+}
+
+impl Drop for Cache {
+    /// Delete the cache if no other process has the store open: every
+    /// change in it is sealed into the store by now
+    fn drop(&mut self) {
+        if flock(&self.lock, libc::LOCK_EX | libc::LOCK_NB).is_ok() {
+            if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+                warn!("Could not remove {}: {}", self.dir.display(), e);
+            }
+        }
+    }
+}
+
+fn flock(file: &File, operation: libc::c_int) -> Result<(), PijulError> {
+    // SAFETY: the descriptor stays open for as long as `file` is borrowed
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Write `content` to `path` aside and rename it into place, readable by the
+/// owner only
+fn write_private(path: &Path, content: &[u8]) -> Result<(), PijulError> {
+    std::fs::create_dir_all(path.parent().expect("store paths have a parent"))?;
+    let partial = path.with_extension("partial");
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&partial)?;
+    std::io::Write::write_all(&mut file, content)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Put back the directory `current` if a [`swap_in`] was interrupted
+/// between its two renames, and drop the one it replaced
+fn recover_swap(current: &Path) -> Result<(), PijulError> {
+    let old = current.with_extension("old");
+    if !old.exists() {
+        return Ok(());
+    }
+    if current.exists() {
+        std::fs::remove_dir_all(&old)?;
+    } else {
+        std::fs::rename(&old, current)?;
+    }
+    Ok(())
+}
+
+/// Replace the directory `current` with `next`
+fn swap_in(current: &Path, next: &Path) -> Result<(), PijulError> {
+    let old = current.with_extension("old");
+    if current.exists() {
+        std::fs::rename(current, &old)?;
+    }
+    std::fs::rename(next, current)?;
+    if old.exists() {
+        std::fs::remove_dir_all(&old)?;
+    }
+    Ok(())
+}
+
+fn random(bytes: &mut [u8]) -> Result<(), PijulError> {
+    getrandom::getrandom(bytes)
+        .map_err(|e| PijulError::Encryption(format!("no randomness available: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PijulBackend) {
+        // Changes are only decrypted into a runtime directory
+        static RUNTIME_DIR: std::sync::Once = std::sync::Once::new();
+        RUNTIME_DIR.call_once(|| {
+            if dirs::runtime_dir().is_none() {
+                let dir = TempDir::new().unwrap().keep();
+                std::env::set_var("XDG_RUNTIME_DIR", dir);
+            }
+        });
+        let temp = TempDir::new().unwrap();
+        let pijul_dir = temp.path().join(".pijul");
+        let working_dir = temp.path().join("working");
+        let backend = PijulBackend::init(&pijul_dir, &working_dir, None).unwrap();
+        (temp, backend)
+    }
+
+    #[test]
+    fn test_sealing() {
+        let key = Key::generate().unwrap();
+        let sealed = key.seal(b"secret").unwrap();
+        assert!(sealed.starts_with(SEAL_MAGIC));
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+        assert_eq!(key.open(&sealed).unwrap(), b"secret");
+
+        assert!(Key::generate().unwrap().open(&sealed).is_err());
+        let mut altered = sealed.clone();
+        *altered.last_mut().unwrap() ^= 1;
+        assert!(key.open(&altered).is_err());
+
+        let salt = [7u8; SALT_LEN];
+        assert_eq!(
+            Key::from_passphrase("hunter2", &salt).unwrap(),
+            Key::from_passphrase("hunter2", &salt).unwrap()
+        );
+        assert_ne!(
+            Key::from_passphrase("hunter2", &salt).unwrap(),
+            Key::from_passphrase("hunter3", &salt).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_encrypted_store() {
+        let (temp, mut backend) = setup();
+        let large = vec![42u8; 64 * 1024];
+        let mut policy = backend.large_files().clone();
+        policy.min_size = 32 * 1024;
+        backend.set_large_files(policy.clone());
+        backend
+            .record_file_create("a.txt", 0o644, b"top secret\n", "create a")
+            .unwrap();
+        backend
+            .record_file_create("big.bin", 0o644, &large, "create big")
+            .unwrap();
+
+        let keyfile = temp.path().join("keys/project.key");
+        backend
+            .encrypt(&KeySource::KeyFile(keyfile.clone()))
+            .unwrap();
+        backend
+            .record_file_write("a.txt", 0, b"TOP", "shout")
+            .unwrap();
+
+        let sealed: Vec<_> = change_files(&backend.changes_dir()).unwrap();
+        assert_eq!(sealed.len(), 3);
+        for relative in &sealed {
+            let content = std::fs::read(backend.changes_dir().join(relative)).unwrap();
+            assert!(content.starts_with(SEAL_MAGIC));
+        }
+
+        // Reopened, the key is found from the settings
+        let pijul_dir = backend.pijul_dir().to_path_buf();
+        let working_dir = backend.working_dir().to_path_buf();
+        let cache = backend.plain_changes_dir();
+        assert_eq!(std::fs::metadata(&cache).unwrap().mode() & 0o777, 0o700);
+        std::fs::remove_dir_all(&cache).unwrap();
+        let mut reopened = PijulBackend::open(&pijul_dir, &working_dir).unwrap();
+        reopened.set_large_files(policy);
+        assert_eq!(reopened.get_file_content("a.txt").unwrap(), b"TOP secret\n");
+        assert_eq!(reopened.get_file_content("big.bin").unwrap(), large);
+
+        // A new key reseals everything; the old one no longer opens it
+        let old = Key::load(&keyfile).unwrap();
+        reopened
+            .rotate_key(&KeySource::KeyFile(keyfile.clone()))
+            .unwrap();
+        let new = Key::load(&keyfile).unwrap();
+        assert_ne!(old, new);
+        let content = std::fs::read(reopened.changes_dir().join(&sealed[0])).unwrap();
+        assert!(old.open(&content).is_err());
+        assert!(new.open(&content).is_ok());
+        assert_eq!(reopened.get_file_content("big.bin").unwrap(), large);
+
+        std::fs::remove_file(&keyfile).unwrap();
+        assert!(matches!(
+            PijulBackend::open(&pijul_dir, &working_dir),
+            Err(PijulError::KeyMissing(_))
+        ));
+
+        // The cache goes with the last backend that has the store open
+        drop(backend);
+        assert!(cache.exists());
+        drop(reopened);
+        assert!(!cache.exists());
+    }
+
+    #[test]
+    fn test_interrupted_rotation_is_finished_on_open() {
+        let large = vec![42u8; 64 * 1024];
+        // Stop after each step of a rotation that replaces the key file:
+        // staging the key, saving the settings, sealing aside, the two
+        // renames swapping the change store, swapping the blob store, and
+        // saving the settings without the old key
+        for stop in 0..=6 {
+            let (temp, mut backend) = setup();
+            let mut policy = backend.large_files().clone();
+            policy.min_size = 32 * 1024;
+            backend.set_large_files(policy.clone());
+            backend
+                .record_file_create("a.txt", 0o644, b"top secret\n", "create a")
+                .unwrap();
+            backend
+                .record_file_create("big.bin", 0o644, &large, "create big")
+                .unwrap();
+            backend
+                .encrypt(&KeySource::KeyFile(temp.path().join("project.key")))
+                .unwrap();
+            let pijul_dir = backend.pijul_dir().to_path_buf();
+            let working_dir = backend.working_dir().to_path_buf();
+            let changes_dir = backend.changes_dir();
+            let blobs_dir = pijul_dir.join(BLOBS_DIR);
+
+            let vault = backend.vault.take().unwrap();
+            let keyfile = vault.settings.keyfile.clone().unwrap();
+            let (new, mut settings) = KeySource::KeyFile(staged_keyfile(&keyfile)).key().unwrap();
+            settings.keyfile = Some(keyfile.clone());
+            settings.rotating_from = Some(Box::new(vault.settings.clone()));
+            if stop >= 1 {
+                settings.save(&pijul_dir).unwrap();
+            }
+            if stop >= 2 {
+                seal_aside(&pijul_dir, Some(&vault.key), &new).unwrap();
+            }
+            if stop >= 3 {
+                std::fs::rename(&changes_dir, changes_dir.with_extension("old")).unwrap();
+            }
+            if stop >= 4 {
+                std::fs::rename(changes_dir.with_extension("next"), &changes_dir).unwrap();
+            }
+            if stop >= 5 {
+                std::fs::remove_dir_all(changes_dir.with_extension("old")).unwrap();
+                swap_in(&blobs_dir, &blobs_dir.with_extension("next")).unwrap();
+            }
+            if stop >= 6 {
+                settings.rotating_from = None;
+                settings.save(&pijul_dir).unwrap();
+            }
+            let old = vault.key.clone();
+            drop(vault);
+            drop(backend);
+
+            let mut reopened = PijulBackend::open(&pijul_dir, &working_dir).unwrap();
+            reopened.set_large_files(policy);
+            let expected = if stop == 0 { &old } else { &new };
+            let settings = reopened.encryption().unwrap().clone();
+            assert_eq!(
+                settings.key_id,
+                expected.id(),
+                "stopped after step {}",
+                stop
+            );
+            assert_eq!(settings.rotating_from, None);
+            assert_eq!(Key::load(&keyfile).unwrap(), *expected);
+            assert_eq!(staged_keyfile(&keyfile).exists(), stop == 0);
+            assert!(!changes_dir.with_extension("old").exists());
+            for relative in change_files(&changes_dir).unwrap() {
+                let content = std::fs::read(changes_dir.join(relative)).unwrap();
+                assert!(expected.open(&content).is_ok());
+            }
+            assert_eq!(reopened.get_file_content("a.txt").unwrap(), b"top secret\n");
+            assert_eq!(reopened.get_file_content("big.bin").unwrap(), large);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Control socket of a running mount
+//!
+//! While a project is mounted, its working copy belongs to the `ize mount`
+//! process: the recorder holds the Pijul backend and the kernel caches what
+//! the mount served. Commands that need to rewrite the working copy, like
+//! `ize channel switch`, send a request over `meta/mount.sock` and let that
+//! process do the work.
+//!
+//! A request is a single line; the answer is `ok` or `err` followed by a
+//! message, which may span several lines, up to the end of the stream:
+//!
+//! ```text
+//! switch <refuse|stash|discard> <channel>
+//! merge <channel>
+//! apply <hash>
+//! undo <last <n>|since <rfc3339>|change <hash>>
+//! checkpoint <archive|drop> <message>
+//! ok <message>
+//! err <message>
+//! ```
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+use chrono::{DateTime, Utc};
+use libpijul::Base32;
+use log::{debug, warn};
+
+use crate::pijul::{CheckpointPolicy, DirtyPolicy, PijulQuery, RetentionPolicy, UndoTarget};
+
+/// File name of the control socket inside a project's `meta/` directory
+pub const CONTROL_SOCKET: &str = "mount.sock";
+
+/// A request to the process serving a mount
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlRequest {
+    /// Check out another channel into the working copy
+    SwitchChannel { name: String, policy: DirtyPolicy },
+    /// Merge another channel into the current one
+    MergeChannel { from: String },
+    /// Apply a single change, given by its full hash, to the current channel
+    ApplyChange { hash: String },
+    /// Unrecord recent changes from the current channel
+    Undo { target: UndoTarget },
+    /// Squash the changes since the last checkpoint; the message is sent on
+    /// one line, so line breaks become spaces
+    Checkpoint {
+        message: String,
+        policy: CheckpointPolicy,
+    },
+    /// Compact the current channel's history per a retention policy and
+    /// remove unused change files
+    Gc { policy: RetentionPolicy },
+}
+
+/// `Ok` with a message to show the user, or `Err` with the reason it failed
+pub type ControlResponse = Result<String, String>;
+
+impl ControlRequest {
+    fn encode(&self) -> String {
+        match self {
+            ControlRequest::SwitchChannel { name, policy } => {
+                let policy = match policy {
+                    DirtyPolicy::Refuse => "refuse",
+                    DirtyPolicy::Stash => "stash",
+                    DirtyPolicy::Discard => "discard",
+                };
+                format!("switch {} {}", policy, name)
+            }
+            ControlRequest::MergeChannel { from } => format!("merge {}", from),
+            ControlRequest::ApplyChange { hash } => format!("apply {}", hash),
+            ControlRequest::Undo { target } => match target {
+                UndoTarget::Last(n) => format!("undo last {}", n),
+                UndoTarget::Since(time) => format!("undo since {}", time.to_rfc3339()),
+                UndoTarget::Change(hash) => format!("undo change {}", hash.to_base32()),
+            },
+            ControlRequest::Checkpoint { message, policy } => {
+                let policy = match policy {
+                    CheckpointPolicy::Archive => "archive",
+                    CheckpointPolicy::Drop => "drop",
+                };
+                format!("checkpoint {} {}", policy, message.replace('\n', " "))
+            }
+            ControlRequest::Gc { policy } => {
+                format!("gc {} {}", policy.keep_all_secs, policy.keep_hourly_secs)
+            }
+        }
+    }
+
+    fn decode(line: &str) -> Result<Self, String> {
+        let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match (verb, rest) {
+            (_, "") => Err(format!("malformed request {:?}", line)),
+            ("switch", rest) => {
+                let (policy, name) = rest
+                    .split_once(' ')
+                    .filter(|(_, name)| !name.is_empty())
+                    .ok_or_else(|| format!("malformed request {:?}", line))?;
+                let policy = match policy {
+                    "refuse" => DirtyPolicy::Refuse,
+                    "stash" => DirtyPolicy::Stash,
+                    "discard" => DirtyPolicy::Discard,
+                    other => return Err(format!("unknown policy {:?}", other)),
+                };
+                Ok(ControlRequest::SwitchChannel {
+                    name: name.to_string(),
+                    policy,
+                })
+            }
+            ("merge", from) => Ok(ControlRequest::MergeChannel {
+                from: from.to_string(),
+            }),
+            ("apply", hash) => Ok(ControlRequest::ApplyChange {
+                hash: hash.to_string(),
+            }),
+            ("undo", rest) => {
+                let target = match rest.split_once(' ') {
+                    Some(("last", n)) => n.parse().map(UndoTarget::Last).ok(),
+                    Some(("since", time)) => DateTime::parse_from_rfc3339(time)
+                        .map(|time| UndoTarget::Since(time.with_timezone(&Utc)))
+                        .ok(),
+                    Some(("change", hash)) => {
+                        PijulQuery::parse_hash(hash).map(UndoTarget::Change).ok()
+                    }
+                    _ => None,
+                };
+                target
+                    .map(|target| ControlRequest::Undo { target })
+                    .ok_or_else(|| format!("malformed request {:?}", line))
+            }
+            ("checkpoint", rest) => {
+                let (policy, message) = rest.split_once(' ').unwrap_or((rest, ""));
+                let policy = match policy {
+                    "archive" => CheckpointPolicy::Archive,
+                    "drop" => CheckpointPolicy::Drop,
+                    other => return Err(format!("unknown policy {:?}", other)),
+                };
+                Ok(ControlRequest::Checkpoint {
+                    message: message.to_string(),
+                    policy,
+                })
+            }
+            ("gc", rest) => {
+                let policy = rest.split_once(' ').and_then(|(all, hourly)| {
+                    Some(RetentionPolicy {
+                        keep_all_secs: all.parse().ok()?,
+                        keep_hourly_secs: hourly.parse().ok()?,
+                    })
+                });
+                policy
+                    .map(|policy| ControlRequest::Gc { policy })
+                    .ok_or_else(|| format!("malformed request {:?}", line))
+            }
+            _ => Err(format!("malformed request {:?}", line)),
+        }
+    }
+}
+
+/// Send `request` to the mount listening on `socket` and wait for its answer
+///
+/// Fails with `NotFound` or `ConnectionRefused` if nothing is listening.
+pub fn send(socket: &Path, request: &ControlRequest) -> io::Result<ControlResponse> {
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{}", request.encode())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let response = response.trim_end_matches('\n');
+    match response.split_once(' ').unwrap_or((response, "")) {
+        ("ok", message) => Ok(Ok(message.to_string())),
+        ("err", message) => Ok(Err(message.to_string())),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected response {:?}", response),
+        )),
+    }
+}
+
+/// Listening end of the control socket
+pub struct ControlServer {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+/// Removes the control socket when dropped
+pub struct ControlHandle {
+    path: PathBuf,
+    _thread: JoinHandle<()>,
+}
+
+impl ControlServer {
+    /// Listen on `path`, replacing a socket left behind by a mount that died
+    ///
+    /// Fails with `AddrInUse` if another process is still listening there.
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is served by another process", path.display()),
+                ));
+            }
+            std::fs::remove_file(path)?;
+        }
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Answer requests with `handler` on a background thread, one at a time
+    pub fn spawn<H>(self, mut handler: H) -> ControlHandle
+    where
+        H: FnMut(ControlRequest) -> ControlResponse + Send + 'static,
+    {
+        let ControlServer { listener, path } = self;
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| serve_one(stream, &mut handler));
+                if let Err(e) = result {
+                    warn!("Control request failed: {}", e);
+                }
+            }
+        });
+        ControlHandle {
+            path,
+            _thread: thread,
+        }
+    }
+}
+
+fn serve_one<H>(stream: UnixStream, handler: &mut H) -> io::Result<()>
+where
+    H: FnMut(ControlRequest) -> ControlResponse,
+{
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let line = line.trim_end_matches('\n');
+    debug!("Control request: {:?}", line);
+
+    let response = ControlRequest::decode(line).and_then(&mut *handler);
+    let mut stream = stream;
+    match response {
+        Ok(message) => writeln!(stream, "ok {}", message),
+        Err(message) => writeln!(stream, "err {}", message),
+    }
+}
+
+impl ControlHandle {
+    /// Path of the socket being served
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ControlHandle {
+    fn drop(&mut self) {
+        // The listener thread stays blocked in accept; the process is about
+        // to exit, so only the socket file needs cleaning up.
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_request_round_trip() {
+        let request = ControlRequest::SwitchChannel {
+            name: "feature/x y".to_string(),
+            policy: DirtyPolicy::Stash,
+        };
+        assert_eq!(ControlRequest::decode(&request.encode()), Ok(request));
+        assert!(ControlRequest::decode("switch sometimes main").is_err());
+        assert!(ControlRequest::decode("reboot").is_err());
+
+        let merge = ControlRequest::MergeChannel {
+            from: "experiment".to_string(),
+        };
+        assert_eq!(ControlRequest::decode(&merge.encode()), Ok(merge));
+        let apply = ControlRequest::ApplyChange {
+            hash: "MNYNGT2VGEQZX".to_string(),
+        };
+        assert_eq!(ControlRequest::decode(&apply.encode()), Ok(apply));
+        assert!(ControlRequest::decode("merge ").is_err());
+
+        let since = DateTime::parse_from_rfc3339("2024-05-01T14:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        for target in [UndoTarget::Last(3), UndoTarget::Since(since)] {
+            let undo = ControlRequest::Undo { target };
+            assert_eq!(ControlRequest::decode(&undo.encode()), Ok(undo));
+        }
+        assert!(ControlRequest::decode("undo last many").is_err());
+
+        let checkpoint = ControlRequest::Checkpoint {
+            message: "Before the refactor".to_string(),
+            policy: CheckpointPolicy::Drop,
+        };
+        assert_eq!(ControlRequest::decode(&checkpoint.encode()), Ok(checkpoint));
+
+        let gc = ControlRequest::Gc {
+            policy: RetentionPolicy {
+                keep_all_secs: 3600,
+                ..RetentionPolicy::default()
+            },
+        };
+        assert_eq!(ControlRequest::decode(&gc.encode()), Ok(gc));
+        assert!(ControlRequest::decode("gc soon").is_err());
+    }
+
+    #[test]
+    fn test_send_and_serve() {
+        let temp = TempDir::new().unwrap();
+        let socket = temp.path().join(CONTROL_SOCKET);
+
+        let handle = ControlServer::bind(&socket)
+            .unwrap()
+            .spawn(|request| match request {
+                ControlRequest::SwitchChannel { name, .. } if name == "main" => {
+                    Ok("switched to main".to_string())
+                }
+                ControlRequest::SwitchChannel { name, .. } => {
+                    Err(format!("no channel {}\nsorry", name))
+                }
+                _ => Err("unsupported".to_string()),
+            });
+
+        let switch = |name: &str| ControlRequest::SwitchChannel {
+            name: name.to_string(),
+            policy: DirtyPolicy::Refuse,
+        };
+        assert_eq!(
+            send(&socket, &switch("main")).unwrap(),
+            Ok("switched to main".to_string())
+        );
+        assert_eq!(
+            send(&socket, &switch("nope")).unwrap(),
+            Err("no channel nope\nsorry".to_string())
+        );
+
+        // A second server can't take over a live socket
+        assert_eq!(
+            ControlServer::bind(&socket).err().map(|e| e.kind()),
+            Some(io::ErrorKind::AddrInUse)
+        );
+
+        drop(handle);
+        assert!(!socket.exists());
+        assert!(send(&socket, &switch("main")).is_err());
+    }
+
+    #[test]
+    fn test_bind_replaces_stale_socket() {
+        let temp = TempDir::new().unwrap();
+        let socket = temp.path().join(CONTROL_SOCKET);
+
+        // A socket file nobody listens on, as left by a killed mount
+        drop(UnixListener::bind(&socket).unwrap());
+        assert!(socket.exists());
+
+        let _server = ControlServer::bind(&socket).unwrap();
+    }
+}
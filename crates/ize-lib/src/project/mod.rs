@@ -4,12 +4,17 @@
 //! directory and the `ProjectManager` for managing multiple projects in
 //! the central store.
 
+pub mod control;
 mod manager;
 
 pub use manager::{ProjectInfo, ProjectManager};
 
-use crate::pijul::{PijulBackend, PijulError};
+use crate::identity::{Identity, IdentityConfig, IdentityError};
+use crate::pijul::{
+    CheckpointPolicy, DirtyPolicy, LargeFilePolicy, PijulBackend, PijulError, RetentionPolicy,
+};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -34,6 +39,9 @@ pub enum ProjectError {
 
     #[error("TOML serialize error: {0}")]
     TomlSerialize(#[from] toml::ser::Error),
+
+    #[error("Identity error: {0}")]
+    Identity(#[from] IdentityError),
 }
 
 /// An Ize project representing a tracked directory
@@ -48,6 +56,73 @@ pub struct IzeProject {
     pub source_dir: PathBuf,
     /// Project UUID
     pub uuid: String,
+    /// Settings for the live mount, from the `[mount]` section
+    pub mount: MountSettings,
+    /// Checkpoint settings, from the `[checkpoint]` section
+    pub checkpoint: CheckpointSettings,
+    /// How much history `ize gc` keeps, from the `[retention]` section
+    pub retention: RetentionPolicy,
+    /// Which files are kept in the blob store, from the `[large_files]`
+    /// section
+    pub large_files: LargeFilePolicy,
+    /// Identity overrides, from the `[identity]` section
+    ///
+    /// Changes are recorded as the global identity with these applied.
+    pub identity: IdentityConfig,
+}
+
+/// Live mount settings stored in the `[mount]` section of project.toml
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MountSettings {
+    /// List the virtual `.ize/` history directory in the mount root
+    ///
+    /// The directory is reachable by path either way.
+    #[serde(default)]
+    pub show_history_dir: bool,
+}
+
+/// Checkpoint settings stored in the `[checkpoint]` section of project.toml
+///
+/// The automatic triggers only apply while the project is mounted read-write.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CheckpointSettings {
+    /// Whether squashed changes are archived on a channel or dropped
+    #[serde(default)]
+    pub policy: CheckpointPolicy,
+    /// Checkpoint when the mount goes away
+    #[serde(default)]
+    pub on_unmount: bool,
+    /// Checkpoint once nothing has been written for this many seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_secs: Option<u64>,
+    /// Checkpoint every this many seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<u64>,
+}
+
+impl CheckpointSettings {
+    /// Which timed trigger, if any, calls for a checkpoint now
+    ///
+    /// `idle` is the time since the last recorded write and `elapsed` the
+    /// time since the last checkpoint (or since mounting). Callers check
+    /// separately that there is anything to squash.
+    pub fn due(&self, idle: Duration, elapsed: Duration) -> Option<&'static str> {
+        let reached = |secs: Option<u64>, time: Duration| {
+            secs.is_some_and(|secs| time >= Duration::from_secs(secs))
+        };
+        if reached(self.idle_secs, idle) {
+            Some("idle")
+        } else if reached(self.interval_secs, elapsed) {
+            Some("interval")
+        } else {
+            None
+        }
+    }
+
+    /// Whether any timed trigger is configured
+    pub fn is_timed(&self) -> bool {
+        self.idle_secs.is_some() || self.interval_secs.is_some()
+    }
 }
 
 impl IzeProject {
@@ -65,7 +140,8 @@ impl IzeProject {
         std::fs::create_dir_all(&meta_dir)?;
 
         // Initialize pijul via backend
-        let pijul = PijulBackend::init(&pijul_dir, &working_dir, None)?;
+        let mut pijul = PijulBackend::init(&pijul_dir, &working_dir, None)?;
+        pijul.set_identity(IdentityConfig::load_global()?.resolve()?);
 
         // Copy existing contents from source_dir to working_dir if source exists
         if source_dir.exists() && source_dir.is_dir() {
@@ -91,6 +167,11 @@ impl IzeProject {
             pijul: PijulSection {
                 default_channel: pijul.current_channel().to_string(),
             },
+            mount: MountSettings::default(),
+            checkpoint: CheckpointSettings::default(),
+            retention: RetentionPolicy::default(),
+            large_files: LargeFilePolicy::default(),
+            identity: IdentityConfig::default(),
         };
 
         // Write project metadata
@@ -103,6 +184,11 @@ impl IzeProject {
             meta_dir,
             source_dir: source_dir.to_path_buf(),
             uuid,
+            mount: metadata.mount,
+            checkpoint: metadata.checkpoint,
+            retention: metadata.retention,
+            large_files: metadata.large_files,
+            identity: metadata.identity,
         })
     }
 
@@ -117,7 +203,10 @@ impl IzeProject {
         let content = std::fs::read_to_string(&meta_path)?;
         let metadata: ProjectMetadata = toml::from_str(&content)?;
 
-        let pijul = PijulBackend::open(&pijul_dir, &working_dir)?;
+        let mut pijul = PijulBackend::open(&pijul_dir, &working_dir)?;
+        let identity = metadata.identity.relative_to(&meta_dir);
+        pijul.set_identity(resolve_identity(&identity)?);
+        pijul.set_large_files(metadata.large_files.clone());
 
         Ok(Self {
             project_dir: project_dir.to_path_buf(),
@@ -125,9 +214,41 @@ impl IzeProject {
             meta_dir,
             source_dir: PathBuf::from(&metadata.project.source_dir),
             uuid: metadata.project.uuid,
+            mount: metadata.mount,
+            checkpoint: metadata.checkpoint,
+            retention: metadata.retention,
+            large_files: metadata.large_files,
+            identity,
         })
     }
 
+    /// Replace the project's identity overrides and record as the result
+    /// from now on
+    ///
+    /// A relative key path is taken relative to the `meta/` directory.
+    pub fn set_identity(&mut self, identity: IdentityConfig) -> Result<(), ProjectError> {
+        let identity = identity.relative_to(&self.meta_dir);
+        self.pijul.set_identity(resolve_identity(&identity)?);
+
+        let meta_path = self.meta_dir.join("project.toml");
+        let mut metadata: ProjectMetadata = toml::from_str(&std::fs::read_to_string(&meta_path)?)?;
+        metadata.identity = identity.clone();
+        std::fs::write(&meta_path, toml::to_string_pretty(&metadata)?)?;
+        self.identity = identity;
+        Ok(())
+    }
+
+    /// Open another handle on the project's repository, recording the way
+    /// the project's own does: as its identity, with its large-file policy
+    ///
+    /// A mount records through such a handle, separate from `pijul`.
+    pub fn open_recording_backend(&self) -> Result<PijulBackend, ProjectError> {
+        let mut pijul = PijulBackend::open(self.pijul_dir(), self.working_dir())?;
+        pijul.set_identity(resolve_identity(&self.identity)?);
+        pijul.set_large_files(self.pijul.large_files().clone());
+        Ok(pijul)
+    }
+
     /// Get the working directory path (where actual files are stored)
     pub fn working_dir(&self) -> &Path {
         self.pijul.working_dir()
@@ -163,10 +284,26 @@ impl IzeProject {
         Ok(self.pijul.create_channel(name)?)
     }
 
-    /// Switch to a different channel
+    /// Switch to a different channel, checking it out into the working copy
     pub fn switch_channel(&mut self, name: &str) -> Result<(), ProjectError> {
         Ok(self.pijul.switch_channel(name)?)
     }
+
+    /// Switch channels, deciding what happens to unrecorded changes
+    ///
+    /// Returns the stash channel if changes were stashed.
+    pub fn switch_channel_with(
+        &mut self,
+        name: &str,
+        policy: DirtyPolicy,
+    ) -> Result<Option<String>, ProjectError> {
+        Ok(self.pijul.switch_channel_with(name, policy)?)
+    }
+
+    /// Path of the socket a running mount of this project listens on
+    pub fn control_socket(&self) -> PathBuf {
+        self.meta_dir.join(control::CONTROL_SOCKET)
+    }
 }
 
 impl std::fmt::Debug for IzeProject {
@@ -185,6 +322,16 @@ impl std::fmt::Debug for IzeProject {
 pub(crate) struct ProjectMetadata {
     pub project: ProjectSection,
     pub pijul: PijulSection,
+    #[serde(default)]
+    pub mount: MountSettings,
+    #[serde(default)]
+    pub checkpoint: CheckpointSettings,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    #[serde(default)]
+    pub large_files: LargeFilePolicy,
+    #[serde(default, skip_serializing_if = "IdentityConfig::is_empty")]
+    pub identity: IdentityConfig,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -201,6 +348,13 @@ pub(crate) struct PijulSection {
     pub default_channel: String,
 }
 
+/// The global identity with the project's `[identity]` overrides applied
+fn resolve_identity(overrides: &IdentityConfig) -> Result<Identity, ProjectError> {
+    Ok(IdentityConfig::load_global()?
+        .overridden_by(overrides)
+        .resolve()?)
+}
+
 /// Recursively copy directory contents from src to dst
 fn copy_dir_contents(src: &Path, dst: &Path) -> Result<(), std::io::Error> {
     if !dst.exists() {
@@ -297,6 +451,199 @@ mod tests {
         assert_eq!(project.source_dir(), source_dir);
     }
 
+    #[test]
+    fn test_project_open_reads_mount_settings() {
+        let temp = TempDir::new().unwrap();
+        let project_dir = temp.path().join("project");
+        let source_dir = temp.path().join("source");
+
+        std::fs::create_dir_all(&source_dir).unwrap();
+
+        let project = IzeProject::init(&project_dir, &source_dir).unwrap();
+        assert!(!project.mount.show_history_dir);
+        let meta_path = project.meta_dir.join("project.toml");
+        drop(project);
+
+        // Older project.toml files have no [mount] section at all
+        let content = std::fs::read_to_string(&meta_path).unwrap();
+        let without_mount = content.split("[mount]").next().unwrap().to_string();
+        std::fs::write(&meta_path, &without_mount).unwrap();
+        assert!(
+            !IzeProject::open(&project_dir)
+                .unwrap()
+                .mount
+                .show_history_dir
+        );
+
+        std::fs::write(
+            &meta_path,
+            format!("{}\n[mount]\nshow_history_dir = true\n", without_mount),
+        )
+        .unwrap();
+        assert!(
+            IzeProject::open(&project_dir)
+                .unwrap()
+                .mount
+                .show_history_dir
+        );
+    }
+
+    #[test]
+    fn test_project_open_reads_checkpoint_settings() {
+        let temp = TempDir::new().unwrap();
+        let project_dir = temp.path().join("project");
+        let source_dir = temp.path().join("source");
+
+        std::fs::create_dir_all(&source_dir).unwrap();
+
+        let project = IzeProject::init(&project_dir, &source_dir).unwrap();
+        assert_eq!(project.checkpoint, CheckpointSettings::default());
+        let meta_path = project.meta_dir.join("project.toml");
+        drop(project);
+
+        let content = std::fs::read_to_string(&meta_path).unwrap();
+        let without = content.split("[checkpoint]").next().unwrap().to_string();
+        std::fs::write(
+            &meta_path,
+            format!(
+                "{}\n[checkpoint]\npolicy = \"drop\"\non_unmount = true\nidle_secs = 300\n",
+                without
+            ),
+        )
+        .unwrap();
+
+        let checkpoint = IzeProject::open(&project_dir).unwrap().checkpoint;
+        assert_eq!(checkpoint.policy, CheckpointPolicy::Drop);
+        assert!(checkpoint.on_unmount);
+        assert_eq!(checkpoint.idle_secs, Some(300));
+        assert_eq!(checkpoint.interval_secs, None);
+    }
+
+    #[test]
+    fn test_project_open_reads_retention_and_large_files() {
+        let temp = TempDir::new().unwrap();
+        let project_dir = temp.path().join("project");
+        let source_dir = temp.path().join("source");
+        std::fs::create_dir_all(&source_dir).unwrap();
+
+        let project = IzeProject::init(&project_dir, &source_dir).unwrap();
+        assert_eq!(project.retention, RetentionPolicy::default());
+        let meta_path = project.meta_dir.join("project.toml");
+        drop(project);
+
+        let content = std::fs::read_to_string(&meta_path).unwrap();
+        assert!(content.contains("[retention]"));
+        let without = content.split("[retention]").next().unwrap().to_string();
+        std::fs::write(
+            &meta_path,
+            format!(
+                "{}\n[retention]\nkeep_all_secs = 3600\n\n[large_files]\nmin_size = 1024\nextensions = [\"mp4\"]\n",
+                without
+            ),
+        )
+        .unwrap();
+
+        let project = IzeProject::open(&project_dir).unwrap();
+        assert_eq!(project.pijul.large_files().min_size, 1024);
+        assert!(project.pijul.large_files().is_large("clip.mp4", 1));
+        let retention = project.retention;
+        assert_eq!(retention.keep_all_secs, 3600);
+        assert_eq!(
+            retention.keep_hourly_secs,
+            RetentionPolicy::default().keep_hourly_secs
+        );
+    }
+
+    #[test]
+    fn test_project_identity() {
+        let temp = TempDir::new().unwrap();
+        let project_dir = temp.path().join("project");
+        let source_dir = temp.path().join("source");
+        std::fs::create_dir_all(&source_dir).unwrap();
+
+        let mut project = IzeProject::init(&project_dir, &source_dir).unwrap();
+        assert!(project.identity.is_empty());
+
+        crate::identity::generate_key(&project.meta_dir.join("ed25519.key")).unwrap();
+        project
+            .set_identity(IdentityConfig {
+                name: Some("Ada".to_string()),
+                key: Some(PathBuf::from("ed25519.key")),
+                sign: Some(true),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(project.pijul.identity().signs());
+        drop(project);
+
+        let project = IzeProject::open(&project_dir).unwrap();
+        assert_eq!(project.identity.name.as_deref(), Some("Ada"));
+        assert_eq!(
+            project.identity.key,
+            Some(project.meta_dir.join("ed25519.key"))
+        );
+        assert!(project.pijul.identity().signs());
+    }
+
+    #[test]
+    fn test_recording_backend_signs_as_project_identity() {
+        use crate::operations::{Opcode, Operation};
+        use crate::pijul::{OpcodeRecordingBackend, PijulQuery};
+        use crate::SignatureStatus;
+
+        let temp = TempDir::new().unwrap();
+        let project_dir = temp.path().join("project");
+        let source_dir = temp.path().join("source");
+        std::fs::create_dir_all(&source_dir).unwrap();
+
+        let mut project = IzeProject::init(&project_dir, &source_dir).unwrap();
+        crate::identity::generate_key(&project.meta_dir.join("ed25519.key")).unwrap();
+        project
+            .set_identity(IdentityConfig {
+                name: Some("Ada".to_string()),
+                key: Some(PathBuf::from("ed25519.key")),
+                sign: Some(true),
+                ..Default::default()
+            })
+            .unwrap();
+
+        // What the mount does: record opcodes through a handle of its own
+        let backend = OpcodeRecordingBackend::new(project.open_recording_backend().unwrap());
+        let opcode = Opcode::new(
+            1,
+            Operation::FileCreate {
+                path: PathBuf::from("notes.txt"),
+                mode: 0o644,
+                content: b"hello".to_vec(),
+            },
+        );
+        let hash = backend.apply_opcode(&opcode).unwrap().unwrap();
+
+        let info = PijulQuery::new(&project.pijul)
+            .get_change_info(&hash)
+            .unwrap();
+        assert_eq!(info.authors, vec!["Ada"]);
+        assert!(matches!(info.signature, SignatureStatus::Valid { .. }));
+    }
+
+    #[test]
+    fn test_checkpoint_due() {
+        let minutes = |m| Duration::from_secs(m * 60);
+        let settings = CheckpointSettings {
+            idle_secs: Some(300),
+            interval_secs: Some(3600),
+            ..CheckpointSettings::default()
+        };
+        assert!(settings.is_timed());
+        assert_eq!(settings.due(minutes(1), minutes(10)), None);
+        assert_eq!(settings.due(minutes(5), minutes(10)), Some("idle"));
+        assert_eq!(settings.due(minutes(1), minutes(60)), Some("interval"));
+
+        let manual = CheckpointSettings::default();
+        assert!(!manual.is_timed());
+        assert_eq!(manual.due(minutes(600), minutes(600)), None);
+    }
+
     #[test]
     fn test_channel_operations() {
         let temp = TempDir::new().unwrap();
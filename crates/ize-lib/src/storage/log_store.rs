@@ -0,0 +1,811 @@
+//! Versions in an append-only log on disk
+//!
+//! A store directory holds two files, both only ever appended to:
+//!
+//! ```text
+//! <dir>/
+//!   content.log     # file contents, back to back
+//!   index.jsonl     # one line per version: the files it touched and
+//!                   # where their new content sits in content.log
+//! ```
+//!
+//! A write only appends the bytes it wrote: the file's new content is
+//! recorded as a patch of its previous one. Truncations and renames append
+//! nothing, patching the previous content (under the old name, for a
+//! rename) without new bytes. After [`MAX_PATCH_CHAIN`] patches in a row a
+//! file's content is stored whole again, so reads stay bounded.
+//!
+//! Content is written before the index line that points at it, so a crash
+//! leaves at worst unreferenced bytes at the end of the content log or a torn
+//! last index line, which [`LogStore::open`] drops. A write that fails while
+//! the store is open is cut back the same way before anything else is
+//! appended. The index is read into memory on open; reads seek straight into
+//! the content log.
+//!
+//! A store made with [`LogStore::init_sealed`], as an encrypted project's
+//! is, seals every content and index line with the project's [`Key`] before
+//! appending it, and names the key in a `sealed` file. It only opens with
+//! [`LogStore::open_sealed`] and that key.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    apply_operation, listed_path, path_to_str, ChangeMessage, StorageError, VersionInfo,
+    VersionStore, MAX_FILE_SIZE,
+};
+use crate::identity::{from_hex, to_hex};
+use crate::operations::Operation;
+use crate::pijul::Key;
+
+/// File holding every recorded content
+pub const CONTENT_LOG: &str = "content.log";
+
+/// File holding one JSON line per version
+pub const INDEX_LOG: &str = "index.jsonl";
+
+/// File naming the key a sealed store is sealed with
+pub const SEALED_FILE: &str = "sealed";
+
+/// Patches read through to reach a file's content before it is stored
+/// whole again
+pub const MAX_PATCH_CHAIN: usize = 32;
+
+/// Offset and length of a content in the content log
+type Extent = (u64, u64);
+
+/// Where a file's content is in the content log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    /// All of it, at one extent
+    Whole(Extent),
+    /// The content `path` had right after `version`, zero-extended or cut
+    /// to `len` bytes, with the bytes at `data` written at `offset`
+    Patch {
+        path: String,
+        version: u64,
+        offset: u64,
+        data: Extent,
+        len: u64,
+    },
+}
+
+/// One line of the index
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexEntry {
+    #[serde(flatten)]
+    info: VersionInfo<u64>,
+    /// Path the version is listed under in a file's history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    /// Each touched file's new content, `None` once deleted
+    files: BTreeMap<String, Option<Stored>>,
+}
+
+/// A [`VersionStore`] in an append-only log, numbering versions from 1
+#[derive(Debug)]
+pub struct LogStore {
+    dir: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    content: File,
+    index: File,
+    /// Length of each file up to the last version fully written
+    content_len: u64,
+    index_len: u64,
+    entries: Vec<IndexEntry>,
+    /// Each path's contents in version order, `None` from a deletion on
+    timeline: BTreeMap<String, Vec<(u64, Option<Stored>)>>,
+    /// Key contents and index lines are sealed with, in a sealed store
+    key: Option<Key>,
+}
+
+impl LogStore {
+    /// Create an empty store in `dir`, which must not hold one already
+    pub fn init(dir: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::create(dir.as_ref())?;
+        Self::open(dir)
+    }
+
+    /// Create an empty store in `dir` sealed with `key`
+    pub fn init_sealed(dir: impl AsRef<Path>, key: Key) -> Result<Self, StorageError> {
+        let dir = dir.as_ref();
+        Self::create(dir)?;
+        fs::write(dir.join(SEALED_FILE), format!("{}\n", key.id()))?;
+        Self::open_sealed(dir, key)
+    }
+
+    fn create(dir: &Path) -> Result<(), StorageError> {
+        fs::create_dir_all(dir)?;
+        for name in [CONTENT_LOG, INDEX_LOG] {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(dir.join(name))?;
+        }
+        Ok(())
+    }
+
+    /// Whether `dir` holds a store
+    pub fn is_valid(dir: impl AsRef<Path>) -> bool {
+        let dir = dir.as_ref();
+        dir.join(CONTENT_LOG).is_file() && dir.join(INDEX_LOG).is_file()
+    }
+
+    /// Open the store in `dir`, dropping a torn last index line
+    ///
+    /// Fails on a sealed store.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::open_with(dir.as_ref(), None)
+    }
+
+    /// Open the store in `dir` sealed with `key`, dropping a torn last index
+    /// line
+    pub fn open_sealed(dir: impl AsRef<Path>, key: Key) -> Result<Self, StorageError> {
+        Self::open_with(dir.as_ref(), Some(key))
+    }
+
+    fn open_with(dir: &Path, key: Option<Key>) -> Result<Self, StorageError> {
+        let dir = dir.to_path_buf();
+        // Checked before reading the index: a line the key can't open would
+        // be dropped as torn
+        let sealed_with = match fs::read_to_string(dir.join(SEALED_FILE)) {
+            Ok(id) => Some(id.trim().to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        match (&sealed_with, &key) {
+            (None, None) => {}
+            (Some(id), Some(key)) if *id == key.id() => {}
+            (Some(id), _) => {
+                return Err(StorageError::Backend(format!(
+                    "{} is sealed with key {}",
+                    dir.display(),
+                    id
+                )))
+            }
+            (None, Some(_)) => {
+                return Err(StorageError::Backend(format!(
+                    "{} is not sealed",
+                    dir.display()
+                )))
+            }
+        }
+        let content = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(dir.join(CONTENT_LOG))?;
+        let mut index = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(dir.join(INDEX_LOG))?;
+        let content_len = content.metadata()?.len();
+
+        let mut entries = Vec::new();
+        let mut good_len = 0u64;
+        let mut torn = false;
+        let mut reader = BufReader::new(&mut index);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                break;
+            }
+            let parsed = if line.ends_with('\n') {
+                parse_line(line.trim_end_matches('\n'), key.as_ref())
+            } else {
+                None
+            };
+            match parsed {
+                Some(entry) if !torn => {
+                    good_len += read as u64;
+                    entries.push(entry);
+                }
+                _ if torn => {
+                    return Err(StorageError::Corrupt(format!(
+                        "unreadable index line {}",
+                        entries.len() + 1
+                    )))
+                }
+                _ => torn = true,
+            }
+        }
+        drop(reader);
+        if torn {
+            log::warn!(
+                "Dropping torn last line of {}",
+                dir.join(INDEX_LOG).display()
+            );
+            index.set_len(good_len)?;
+        }
+
+        let in_log = |(offset, len): &Extent| {
+            offset
+                .checked_add(*len)
+                .is_some_and(|end| end <= content_len)
+        };
+        let mut timeline: BTreeMap<String, Vec<(u64, Option<Stored>)>> = BTreeMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let expected = i as u64 + 1;
+            if entry.info.version != expected {
+                return Err(StorageError::Corrupt(format!(
+                    "index line {} is version {}",
+                    expected, entry.info.version
+                )));
+            }
+            for (path, stored) in &entry.files {
+                // Patches only build on earlier versions, so reading one
+                // always ends at a whole content
+                let valid = match stored {
+                    None => true,
+                    Some(Stored::Whole(extent)) => in_log(extent) && extent.1 <= MAX_FILE_SIZE,
+                    Some(Stored::Patch {
+                        version, data, len, ..
+                    }) => in_log(data) && *version < expected && *len <= MAX_FILE_SIZE,
+                };
+                if !valid {
+                    return Err(StorageError::Corrupt(format!(
+                        "version {} stores {} outside {} or on a later version",
+                        expected, path, CONTENT_LOG
+                    )));
+                }
+                timeline
+                    .entry(path.clone())
+                    .or_default()
+                    .push((expected, stored.clone()));
+            }
+        }
+
+        Ok(Self {
+            dir,
+            state: Mutex::new(State {
+                content,
+                index,
+                content_len,
+                index_len: good_len,
+                entries,
+                timeline,
+                key,
+            }),
+        })
+    }
+
+    /// Directory the store lives in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl State {
+    /// Check `version` names a recorded version; `None` means the latest
+    fn resolve(&self, version: Option<&u64>) -> Result<u64, StorageError> {
+        match version {
+            None => Ok(self.entries.len() as u64),
+            Some(&v) if v >= 1 && v <= self.entries.len() as u64 => Ok(v),
+            Some(v) => Err(StorageError::VersionNotFound(v.to_string())),
+        }
+    }
+
+    /// Where `path`'s content sat right after `version`
+    fn stored_at(&self, path: &str, version: u64) -> Option<&Stored> {
+        let states = self.timeline.get(path)?;
+        let after = states.partition_point(|(v, _)| *v <= version);
+        after.checked_sub(1).and_then(|i| states[i].1.as_ref())
+    }
+
+    /// The whole content under `stored`, and the `(offset, data, len)` of
+    /// the patches on top of it, last first
+    fn chain(
+        &self,
+        mut stored: &Stored,
+    ) -> Result<(Extent, Vec<(u64, Extent, u64)>), StorageError> {
+        let mut patches = Vec::new();
+        loop {
+            match stored {
+                Stored::Whole(extent) => return Ok((*extent, patches)),
+                Stored::Patch {
+                    path,
+                    version,
+                    offset,
+                    data,
+                    len,
+                } => {
+                    patches.push((*offset, *data, *len));
+                    stored = self.stored_at(path, *version).ok_or_else(|| {
+                        StorageError::Corrupt(format!(
+                            "patch of {} at version {}, where it has no content",
+                            path, version
+                        ))
+                    })?;
+                }
+            }
+        }
+    }
+
+    /// The content `stored` stands for
+    fn content(&self, stored: &Stored) -> Result<Vec<u8>, StorageError> {
+        let (whole, patches) = self.chain(stored)?;
+        let mut content = self.read(whole)?;
+        for (offset, data, len) in patches.into_iter().rev() {
+            content.resize(len as usize, 0);
+            let data = self.read(data)?;
+            let range = usize::try_from(offset)
+                .ok()
+                .and_then(|start| Some(start..start.checked_add(data.len())?))
+                .filter(|range| range.end <= content.len())
+                .ok_or_else(|| StorageError::Corrupt("patch past the end of its file".into()))?;
+            content[range].copy_from_slice(&data);
+        }
+        Ok(content)
+    }
+
+    /// Append a version's content and index line, cutting both files back
+    /// to their last version if either write fails
+    fn append(&mut self, blob: &[u8], line: &[u8]) -> Result<(), StorageError> {
+        // A write that failed earlier may have left bytes the cut back
+        // couldn't remove; they would end up between versions
+        self.cut_back()?;
+        let written = self.write(blob, line);
+        if written.is_err() {
+            if let Err(e) = self.cut_back() {
+                log::warn!("Could not cut back a failed write: {}", e);
+            }
+        } else {
+            self.content_len += blob.len() as u64;
+            self.index_len += line.len() as u64;
+        }
+        written
+    }
+
+    fn write(&mut self, blob: &[u8], line: &[u8]) -> Result<(), StorageError> {
+        self.content.write_all(blob)?;
+        self.content.sync_data()?;
+        self.index.write_all(line)?;
+        self.index.sync_data()?;
+        Ok(())
+    }
+
+    /// Truncate anything past the last version fully written
+    fn cut_back(&self) -> Result<(), StorageError> {
+        for (file, len) in [
+            (&self.content, self.content_len),
+            (&self.index, self.index_len),
+        ] {
+            if file.metadata()?.len() != len {
+                file.set_len(len)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, (offset, len): Extent) -> Result<Vec<u8>, StorageError> {
+        let mut content = vec![0; len as usize];
+        self.content.read_exact_at(&mut content, offset)?;
+        match &self.key {
+            Some(key) if !content.is_empty() => Ok(key.open(&content)?),
+            _ => Ok(content),
+        }
+    }
+
+    /// `content` as it is appended to the content log; nothing stays nothing
+    fn seal(&self, content: &[u8]) -> Result<Vec<u8>, StorageError> {
+        match &self.key {
+            Some(key) if !content.is_empty() => Ok(key.seal(content)?),
+            _ => Ok(content.to_vec()),
+        }
+    }
+
+    /// The index line for `entry`, newline included
+    fn index_line(&self, entry: &IndexEntry) -> Result<Vec<u8>, StorageError> {
+        let json = serde_json::to_vec(entry).map_err(std::io::Error::from)?;
+        let mut line = match &self.key {
+            Some(key) => to_hex(&key.seal(&json)?).into_bytes(),
+            None => json,
+        };
+        line.push(b'\n');
+        Ok(line)
+    }
+}
+
+/// The entry an index line holds, `None` if it can't be read
+fn parse_line(line: &str, key: Option<&Key>) -> Option<IndexEntry> {
+    match key {
+        Some(key) => serde_json::from_slice(&key.open(&from_hex(line)?).ok()?).ok(),
+        None => serde_json::from_str(line).ok(),
+    }
+}
+
+impl VersionStore for LogStore {
+    type Version = u64;
+
+    fn record(&self, op: &Operation, message: ChangeMessage) -> Result<Option<u64>, StorageError> {
+        let mut state = self.state.lock();
+        let latest = state.entries.len() as u64;
+        let current = |path: &str| {
+            state
+                .stored_at(path, latest)
+                .map(|stored| state.content(stored))
+                .transpose()
+        };
+
+        let mut changed = Vec::new();
+        for (path, content) in apply_operation(op, current)? {
+            if current(path.as_str())? != content {
+                changed.push((path, content));
+            }
+        }
+        if changed.is_empty() {
+            return Ok(None);
+        }
+
+        // What the operation patches its file's content with, and under
+        // which name that content is; deletions and creations have no base
+        let patch = match op {
+            Operation::FileWrite { path, offset, data } => {
+                Some((path_to_str(path)?, *offset, data.as_slice()))
+            }
+            Operation::FileTruncate { path, .. } => Some((path_to_str(path)?, 0, &[][..])),
+            Operation::FileRename { old_path, .. } => Some((path_to_str(old_path)?, 0, &[][..])),
+            _ => None,
+        }
+        .filter(|(base, _, _)| {
+            state.stored_at(base, latest).is_some_and(|stored| {
+                state
+                    .chain(stored)
+                    .is_ok_and(|(_, patches)| patches.len() < MAX_PATCH_CHAIN)
+            })
+        });
+
+        let mut files = BTreeMap::new();
+        let mut blob = Vec::new();
+        for (path, content) in changed {
+            let Some(content) = content else {
+                files.insert(path, None);
+                continue;
+            };
+            let at = state.content_len + blob.len() as u64;
+            let len = content.len() as u64;
+            let stored = match patch {
+                Some((base, offset, data)) if (data.len() as u64) < len => {
+                    let data = state.seal(data)?;
+                    blob.extend_from_slice(&data);
+                    Stored::Patch {
+                        path: base.to_string(),
+                        version: latest,
+                        offset,
+                        data: (at, data.len() as u64),
+                        len,
+                    }
+                }
+                _ => {
+                    let content = state.seal(&content)?;
+                    blob.extend_from_slice(&content);
+                    Stored::Whole((at, content.len() as u64))
+                }
+            };
+            files.insert(path, Some(stored));
+        }
+        let version = latest + 1;
+        let entry = IndexEntry {
+            info: VersionInfo::new(version, message),
+            path: listed_path(op)
+                .and_then(|path| path.to_str())
+                .map(String::from),
+            files,
+        };
+        let line = state.index_line(&entry)?;
+        state.append(&blob, &line)?;
+
+        for (path, stored) in &entry.files {
+            state
+                .timeline
+                .entry(path.clone())
+                .or_default()
+                .push((version, stored.clone()));
+        }
+        state.entries.push(entry);
+        Ok(Some(version))
+    }
+
+    fn file_at(&self, path: &str, version: Option<&u64>) -> Result<Vec<u8>, StorageError> {
+        let state = self.state.lock();
+        let version = state.resolve(version)?;
+        let stored = state
+            .stored_at(path, version)
+            .ok_or_else(|| StorageError::FileNotFound(path.to_string()))?;
+        state.content(stored)
+    }
+
+    fn files_at(&self, version: Option<&u64>) -> Result<Vec<String>, StorageError> {
+        let state = self.state.lock();
+        let version = state.resolve(version)?;
+        Ok(state
+            .timeline
+            .keys()
+            .filter(|path| state.stored_at(path, version).is_some())
+            .cloned()
+            .collect())
+    }
+
+    fn history(&self, path: Option<&str>) -> Result<Vec<VersionInfo<u64>>, StorageError> {
+        Ok(self
+            .state
+            .lock()
+            .entries
+            .iter()
+            .filter(|entry| path.is_none() || entry.path.as_deref() == path)
+            .map(|entry| entry.info.clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create(path: &str, content: &[u8]) -> Operation {
+        Operation::FileCreate {
+            path: PathBuf::from(path),
+            mode: 0o644,
+            content: content.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_record_reopen_and_read_back() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().join("store");
+        assert!(!LogStore::is_valid(&dir));
+
+        let store = LogStore::init(&dir).unwrap();
+        assert!(LogStore::is_valid(&dir));
+        assert!(LogStore::init(&dir).is_err());
+
+        store
+            .record(&create("a.txt", b"hello"), ChangeMessage::new("create a"))
+            .unwrap();
+        store
+            .record(&create("b.txt", b"bee"), ChangeMessage::new("create b"))
+            .unwrap();
+        let write = Operation::FileWrite {
+            path: PathBuf::from("a.txt"),
+            offset: 5,
+            data: b", world".to_vec(),
+        };
+        assert_eq!(
+            store.record(&write, ChangeMessage::new("write a")).unwrap(),
+            Some(3)
+        );
+        let delete = Operation::FileDelete {
+            path: PathBuf::from("b.txt"),
+        };
+        store
+            .record(&delete, ChangeMessage::new("delete b"))
+            .unwrap();
+        drop(store);
+
+        let store = LogStore::open(&dir).unwrap();
+        assert_eq!(store.file_at("a.txt", None).unwrap(), b"hello, world");
+        assert_eq!(store.file_at("a.txt", Some(&2)).unwrap(), b"hello");
+        assert_eq!(store.files_at(Some(&2)).unwrap(), vec!["a.txt", "b.txt"]);
+        assert_eq!(store.files_at(None).unwrap(), vec!["a.txt"]);
+        assert!(matches!(
+            store.file_at("b.txt", None),
+            Err(StorageError::FileNotFound(_))
+        ));
+        assert!(matches!(
+            store.files_at(Some(&5)),
+            Err(StorageError::VersionNotFound(_))
+        ));
+
+        let history: Vec<_> = store
+            .history(Some("a.txt"))
+            .unwrap()
+            .into_iter()
+            .map(|info| info.message)
+            .collect();
+        assert_eq!(history, vec!["create a", "write a"]);
+
+        // Recording carries on from where the reopened log left off
+        assert_eq!(
+            store
+                .record(&create("c.txt", b"sea"), ChangeMessage::new("create c"))
+                .unwrap(),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_open_drops_torn_index_line() {
+        let temp = TempDir::new().unwrap();
+        let store = LogStore::init(temp.path()).unwrap();
+        store
+            .record(&create("a.txt", b"hello"), ChangeMessage::new("create a"))
+            .unwrap();
+        drop(store);
+
+        let mut index = OpenOptions::new()
+            .append(true)
+            .open(temp.path().join(INDEX_LOG))
+            .unwrap();
+        index.write_all(b"{\"version\":2,\"mess").unwrap();
+        drop(index);
+
+        let store = LogStore::open(temp.path()).unwrap();
+        assert_eq!(store.history(None).unwrap().len(), 1);
+        assert_eq!(store.file_at("a.txt", None).unwrap(), b"hello");
+        assert_eq!(
+            store
+                .record(&create("b.txt", b"bee"), ChangeMessage::new("create b"))
+                .unwrap(),
+            Some(2)
+        );
+        drop(store);
+        assert_eq!(
+            LogStore::open(temp.path())
+                .unwrap()
+                .history(None)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_failed_append_is_cut_back() {
+        let temp = TempDir::new().unwrap();
+        let content = temp.path().join(CONTENT_LOG);
+        let index = temp.path().join(INDEX_LOG);
+        let store = LogStore::init(temp.path()).unwrap();
+        store
+            .record(&create("a.txt", b"hello"), ChangeMessage::new("create a"))
+            .unwrap();
+        let content_len = fs::metadata(&content).unwrap().len();
+
+        // The content is written, then the index line can't be
+        let writable =
+            std::mem::replace(&mut store.state.lock().index, File::open(&index).unwrap());
+        assert!(store
+            .record(&create("b.txt", b"bee"), ChangeMessage::new("create b"))
+            .is_err());
+        assert_eq!(fs::metadata(&content).unwrap().len(), content_len);
+        store.state.lock().index = writable;
+
+        // Half an index line is left behind by a failure the cut back missed
+        OpenOptions::new()
+            .append(true)
+            .open(&index)
+            .unwrap()
+            .write_all(b"{\"version\":2,\"mess")
+            .unwrap();
+        assert_eq!(
+            store
+                .record(&create("c.txt", b"sea"), ChangeMessage::new("create c"))
+                .unwrap(),
+            Some(2)
+        );
+        drop(store);
+
+        let store = LogStore::open(temp.path()).unwrap();
+        assert_eq!(store.history(None).unwrap().len(), 2);
+        assert_eq!(store.file_at("a.txt", None).unwrap(), b"hello");
+        assert_eq!(store.file_at("c.txt", None).unwrap(), b"sea");
+        assert!(matches!(
+            store.file_at("b.txt", None),
+            Err(StorageError::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_writes_renames_and_truncations_append_only_new_bytes() {
+        let temp = TempDir::new().unwrap();
+        let content_len = || fs::metadata(temp.path().join(CONTENT_LOG)).unwrap().len();
+        let store = LogStore::init(temp.path()).unwrap();
+        let mut expected = vec![b'x'; 1000];
+        store
+            .record(&create("a.txt", &expected), ChangeMessage::new("create a"))
+            .unwrap();
+
+        let write = Operation::FileWrite {
+            path: PathBuf::from("a.txt"),
+            offset: 1200,
+            data: b"tail".to_vec(),
+        };
+        store.record(&write, ChangeMessage::new("write a")).unwrap();
+        let rename = Operation::FileRename {
+            old_path: PathBuf::from("a.txt"),
+            new_path: PathBuf::from("b.txt"),
+        };
+        store
+            .record(&rename, ChangeMessage::new("rename a"))
+            .unwrap();
+        let truncate = Operation::FileTruncate {
+            path: PathBuf::from("b.txt"),
+            new_size: 1202,
+        };
+        store
+            .record(&truncate, ChangeMessage::new("truncate b"))
+            .unwrap();
+        assert_eq!(content_len(), 1000 + 4);
+        expected.resize(1200, 0);
+        expected.extend_from_slice(b"ta");
+
+        // A long run of patches is stored whole again
+        for i in 0..MAX_PATCH_CHAIN as u64 {
+            let write = Operation::FileWrite {
+                path: PathBuf::from("b.txt"),
+                offset: i,
+                data: b"y".to_vec(),
+            };
+            store.record(&write, ChangeMessage::new("write b")).unwrap();
+            expected[i as usize] = b'y';
+        }
+        assert_eq!(
+            content_len(),
+            1000 + 4 + MAX_PATCH_CHAIN as u64 - 1 + expected.len() as u64
+        );
+        drop(store);
+
+        let store = LogStore::open(temp.path()).unwrap();
+        assert_eq!(store.file_at("b.txt", None).unwrap(), expected);
+        let mut written = vec![b'x'; 1000];
+        written.resize(1200, 0);
+        written.extend_from_slice(b"tail");
+        assert_eq!(store.file_at("a.txt", Some(&2)).unwrap(), written);
+        assert_eq!(store.file_at("b.txt", Some(&3)).unwrap(), written);
+        assert_eq!(store.files_at(Some(&3)).unwrap(), vec!["b.txt"]);
+    }
+
+    #[test]
+    fn test_sealed_store_keeps_nothing_readable() {
+        let temp = TempDir::new().unwrap();
+        let key = Key::from_passphrase("correct horse", b"log store salt").unwrap();
+        let store = LogStore::init_sealed(temp.path(), key.clone()).unwrap();
+        store
+            .record(
+                &create("secret.txt", b"attack at dawn"),
+                ChangeMessage::new("create secret.txt"),
+            )
+            .unwrap();
+        let write = Operation::FileWrite {
+            path: PathBuf::from("secret.txt"),
+            offset: 10,
+            data: b"dusk".to_vec(),
+        };
+        store
+            .record(&write, ChangeMessage::new("write secret.txt"))
+            .unwrap();
+        drop(store);
+
+        let on_disk = [
+            fs::read(temp.path().join(CONTENT_LOG)).unwrap(),
+            fs::read(temp.path().join(INDEX_LOG)).unwrap(),
+        ]
+        .concat();
+        for plain in [&b"attack"[..], b"dusk", b"secret"] {
+            assert!(!on_disk.windows(plain.len()).any(|window| window == plain));
+        }
+
+        assert!(LogStore::open(temp.path()).is_err());
+        let other = Key::from_passphrase("wrong horse", b"log store salt").unwrap();
+        assert!(LogStore::open_sealed(temp.path(), other).is_err());
+        let store = LogStore::open_sealed(temp.path(), key).unwrap();
+        assert_eq!(
+            store.file_at("secret.txt", None).unwrap(),
+            b"attack at dusk"
+        );
+        assert_eq!(store.history(None).unwrap().len(), 2);
+    }
+}
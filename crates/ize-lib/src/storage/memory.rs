@@ -0,0 +1,165 @@
+//! Versions kept in memory
+//!
+//! Every version holds the whole tree, sharing the files it didn't change
+//! with the version before. Nothing survives the process, which is what
+//! tests of the recording path want.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::{apply_operation, listed_path, ChangeMessage, StorageError, VersionInfo, VersionStore};
+use crate::operations::Operation;
+
+type Tree = BTreeMap<String, Arc<Vec<u8>>>;
+
+/// A [`VersionStore`] in memory, numbering versions from 1
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    versions: Mutex<Vec<Recorded>>,
+}
+
+#[derive(Debug)]
+struct Recorded {
+    info: VersionInfo<u64>,
+    /// Path the version is listed under in a file's history
+    path: Option<String>,
+    tree: Tree,
+}
+
+impl MemoryStore {
+    /// An empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The tree right after `version`, or now with `None`
+    fn tree_at<T>(
+        &self,
+        version: Option<&u64>,
+        read: impl FnOnce(&Tree) -> T,
+    ) -> Result<T, StorageError> {
+        let versions = self.versions.lock();
+        let empty = Tree::new();
+        let tree = match version {
+            None => versions.last().map_or(&empty, |recorded| &recorded.tree),
+            Some(&version) => version
+                .checked_sub(1)
+                .and_then(|index| versions.get(index as usize))
+                .map(|recorded| &recorded.tree)
+                .ok_or_else(|| StorageError::VersionNotFound(version.to_string()))?,
+        };
+        Ok(read(tree))
+    }
+}
+
+impl VersionStore for MemoryStore {
+    type Version = u64;
+
+    fn record(&self, op: &Operation, message: ChangeMessage) -> Result<Option<u64>, StorageError> {
+        let mut versions = self.versions.lock();
+        let mut tree = versions
+            .last()
+            .map(|recorded| recorded.tree.clone())
+            .unwrap_or_default();
+
+        let touched = apply_operation(op, |path| Ok(tree.get(path).map(|c| c.to_vec())))?;
+        let mut changed = false;
+        for (path, content) in touched {
+            match content {
+                Some(content) => {
+                    if tree.get(&path).map(|c| c.as_slice()) != Some(&content[..]) {
+                        tree.insert(path, Arc::new(content));
+                        changed = true;
+                    }
+                }
+                None => changed |= tree.remove(&path).is_some(),
+            }
+        }
+        if !changed {
+            return Ok(None);
+        }
+
+        let version = versions.len() as u64 + 1;
+        versions.push(Recorded {
+            info: VersionInfo::new(version, message),
+            path: listed_path(op)
+                .and_then(|path| path.to_str())
+                .map(String::from),
+            tree,
+        });
+        Ok(Some(version))
+    }
+
+    fn file_at(&self, path: &str, version: Option<&u64>) -> Result<Vec<u8>, StorageError> {
+        self.tree_at(version, |tree| tree.get(path).map(|c| c.to_vec()))?
+            .ok_or_else(|| StorageError::FileNotFound(path.to_string()))
+    }
+
+    fn files_at(&self, version: Option<&u64>) -> Result<Vec<String>, StorageError> {
+        self.tree_at(version, |tree| tree.keys().cloned().collect())
+    }
+
+    fn history(&self, path: Option<&str>) -> Result<Vec<VersionInfo<u64>>, StorageError> {
+        Ok(self
+            .versions
+            .lock()
+            .iter()
+            .filter(|recorded| path.is_none() || recorded.path.as_deref() == path)
+            .map(|recorded| recorded.info.clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_record_and_read_back() {
+        let store = MemoryStore::new();
+        let create = Operation::FileCreate {
+            path: PathBuf::from("a.txt"),
+            mode: 0o644,
+            content: b"one".to_vec(),
+        };
+        let v1 = store.record(&create, ChangeMessage::new("create")).unwrap();
+        assert_eq!(v1, Some(1));
+
+        // Writing what's already there records nothing
+        let same = Operation::FileWrite {
+            path: PathBuf::from("a.txt"),
+            offset: 0,
+            data: b"one".to_vec(),
+        };
+        assert_eq!(
+            store.record(&same, ChangeMessage::new("noop")).unwrap(),
+            None
+        );
+
+        let rename = Operation::FileRename {
+            old_path: PathBuf::from("a.txt"),
+            new_path: PathBuf::from("b.txt"),
+        };
+        store.record(&rename, ChangeMessage::new("rename")).unwrap();
+
+        assert_eq!(store.files_at(Some(&1)).unwrap(), vec!["a.txt"]);
+        assert_eq!(store.files_at(None).unwrap(), vec!["b.txt"]);
+        assert_eq!(store.file_at("a.txt", Some(&1)).unwrap(), b"one");
+        assert!(matches!(
+            store.file_at("a.txt", None),
+            Err(StorageError::FileNotFound(_))
+        ));
+        assert!(matches!(
+            store.files_at(Some(&3)),
+            Err(StorageError::VersionNotFound(_))
+        ));
+
+        let history = store.history(Some("b.txt")).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].message, "rename");
+        assert_eq!(store.history(None).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,178 @@
+//! Messages and metadata recorded with each version
+//!
+//! Versions recorded from opcodes carry where they came from: the opcode's
+//! sequence number, when it happened, what kind of operation it was and, when
+//! the mount knew it, the process behind it. Stores that keep a free-form
+//! description, like Pijul's change headers, keep it there as a TOML table:
+//!
+//! ```toml
+//! [opcode]
+//! seq = 42
+//! time = "2024-05-01T14:30:00.123456789Z"
+//! kind = "write"
+//! uid = 1000
+//! pid = 4242
+//! ```
+//!
+//! Descriptions that are not such a table, like those of checkpoints, have
+//! no metadata.
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::operations::Opcode;
+
+/// Where a change recorded from an opcode came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeMetadata {
+    /// Sequence number of the opcode
+    pub seq: u64,
+    /// When the operation happened
+    pub time: DateTime<Utc>,
+    /// Operation type, as named by [`Operation::kind`](crate::operations::Operation::kind)
+    pub kind: String,
+    /// User ID of the process that performed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Process ID of the process that performed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+}
+
+/// How metadata sits in a description
+#[derive(Serialize, Deserialize)]
+struct Described<T> {
+    opcode: T,
+}
+
+impl ChangeMetadata {
+    /// Metadata for the change recording `opcode`
+    pub fn from_opcode(opcode: &Opcode) -> Self {
+        let caller = opcode.caller();
+        Self {
+            seq: opcode.seq(),
+            time: Utc.timestamp_nanos(opcode.timestamp() as i64),
+            kind: opcode.op().kind().to_string(),
+            uid: caller.map(|caller| caller.uid),
+            pid: caller.map(|caller| caller.pid),
+        }
+    }
+
+    /// Read metadata back from a change description
+    ///
+    /// Returns `None` for descriptions that don't hold any.
+    pub fn parse(description: Option<&str>) -> Option<Self> {
+        toml::from_str::<Described<Self>>(description?)
+            .ok()
+            .map(|described| described.opcode)
+    }
+
+    /// The description holding this metadata
+    pub fn to_description(&self) -> String {
+        toml::to_string(&Described { opcode: self }).expect("metadata serializes to TOML")
+    }
+}
+
+/// Message and optional metadata for a change about to be recorded
+///
+/// [`VersionStore::record`](super::VersionStore::record) takes one, and the
+/// `record_file_*` methods of [`PijulBackend`](crate::pijul::PijulBackend)
+/// anything that converts into one, so plain strings keep working.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeMessage {
+    /// One-line summary
+    pub message: String,
+    /// Where the change came from
+    pub metadata: Option<ChangeMetadata>,
+}
+
+impl ChangeMessage {
+    /// A message without metadata
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            metadata: None,
+        }
+    }
+
+    /// Attach metadata
+    pub fn with_metadata(mut self, metadata: ChangeMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+}
+
+impl From<&str> for ChangeMessage {
+    fn from(message: &str) -> Self {
+        Self::new(message)
+    }
+}
+
+impl From<&String> for ChangeMessage {
+    fn from(message: &String) -> Self {
+        Self::new(message.as_str())
+    }
+}
+
+impl From<String> for ChangeMessage {
+    fn from(message: String) -> Self {
+        Self::new(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystems::Caller;
+    use crate::operations::Operation;
+    use std::path::PathBuf;
+
+    fn write_opcode() -> Opcode {
+        Opcode::with_timestamp(
+            42,
+            1_714_573_800_123_456_789,
+            Operation::FileWrite {
+                path: PathBuf::from("src/main.rs"),
+                offset: 4096,
+                data: vec![0; 120],
+            },
+        )
+    }
+
+    #[test]
+    fn test_metadata_round_trips_through_description() {
+        let opcode = write_opcode().with_caller(Caller {
+            uid: 1000,
+            gid: 1000,
+            pid: 4242,
+        });
+        let metadata = ChangeMetadata::from_opcode(&opcode);
+        assert_eq!(metadata.seq, 42);
+        assert_eq!(metadata.kind, "write");
+        assert_eq!(metadata.time.timestamp(), 1_714_573_800);
+        assert_eq!(metadata.time.timestamp_subsec_nanos(), 123_456_789);
+        assert_eq!((metadata.uid, metadata.pid), (Some(1000), Some(4242)));
+
+        let description = metadata.to_description();
+        assert!(description.starts_with("[opcode]\n"));
+        assert_eq!(ChangeMetadata::parse(Some(&description)), Some(metadata));
+    }
+
+    #[test]
+    fn test_metadata_without_caller() {
+        let metadata = ChangeMetadata::from_opcode(&write_opcode());
+        let description = metadata.to_description();
+        assert!(!description.contains("uid"));
+        assert_eq!(ChangeMetadata::parse(Some(&description)).unwrap().pid, None);
+    }
+
+    #[test]
+    fn test_other_descriptions_have_no_metadata() {
+        assert_eq!(ChangeMetadata::parse(None), None);
+        assert_eq!(ChangeMetadata::parse(Some("just some words")), None);
+        assert_eq!(
+            ChangeMetadata::parse(Some("ize-checkpoint\n3 change(s) squashed")),
+            None
+        );
+    }
+}
@@ -1,43 +1,269 @@
-use std::io::Result;
+//! Pluggable versioned storage
+//!
+//! A [`VersionStore`] records file operations as versions and reads files
+//! back as they were at any of them. [`OpcodeRecordingBackend`] records
+//! opcodes into any store, so the mount isn't tied to Pijul:
+//!
+//! ```text
+//! Opcode -> OpcodeRecordingBackend<S> -> S: VersionStore
+//! ```
+//!
+//! - [`PijulBackend`](crate::pijul::PijulBackend): the project's Pijul
+//!   repository; versions are change hashes
+//! - [`memory::MemoryStore`]: versions kept in memory, for tests
+//! - [`log_store::LogStore`]: an append-only content log and version index on
+//!   disk, sealed with the project's key in an encrypted project
+//!
+//! Versions are recorded with a [`ChangeMessage`], and stores report their
+//! own failures as [`StorageError::Backend`].
+//!
+//! [`OpcodeRecordingBackend`]: crate::pijul::OpcodeRecordingBackend
+
+pub mod log_store;
+pub mod memory;
+pub mod message;
+
+pub use log_store::{LogStore, CONTENT_LOG, INDEX_LOG, MAX_PATCH_CHAIN, SEALED_FILE};
+pub use memory::MemoryStore;
+pub use message::{ChangeMessage, ChangeMetadata};
+
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::Path;
 
-/// A storage engine for the Ize filesystem
-pub trait Storage {
-    /// Write data to storage
-    fn write(&mut self, path: &str, data: &[u8]) -> Result<()>;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::operations::Operation;
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Store error: {0}")]
+    Backend(String),
+
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+
+    #[error("No such version: {0}")]
+    VersionNotFound(String),
 
-    /// Read data from storage
-    fn read(&self, path: &str) -> Result<Vec<u8>>;
+    #[error("Path conversion error: {0}")]
+    PathConversion(String),
 
-    /// Delete data from storage
-    fn delete(&mut self, path: &str) -> Result<()>;
+    #[error("Unsupported operation: {0}")]
+    UnsupportedOperation(String),
+
+    #[error("Corrupt store: {0}")]
+    Corrupt(String),
+
+    #[error("File too large: {0}")]
+    TooLarge(String),
 }
 
-// Implementation of the Storage trait for initialization
-pub struct StorageManager;
+/// Largest file [`apply_operation`] builds in memory
+pub const MAX_FILE_SIZE: u64 = 4 << 30;
+
+/// A store of file versions
+///
+/// Versions are recorded one operation at a time and form a single line of
+/// history. Methods take `&self` so a store can be shared behind the mount's
+/// lock; stores keep whatever they mutate behind their own.
+pub trait VersionStore {
+    /// Names a recorded version
+    type Version: Clone + fmt::Debug + PartialEq;
+
+    /// Record `op` as a new version; `None` if it changed nothing
+    fn record(
+        &self,
+        op: &Operation,
+        message: ChangeMessage,
+    ) -> Result<Option<Self::Version>, StorageError>;
+
+    /// Content of `path` right after `version`, or now with `None`
+    fn file_at(&self, path: &str, version: Option<&Self::Version>)
+        -> Result<Vec<u8>, StorageError>;
+
+    /// Files right after `version`, or now with `None`, sorted by path
+    fn files_at(&self, version: Option<&Self::Version>) -> Result<Vec<String>, StorageError>;
+
+    /// Recorded versions, oldest first; only those touching `path` if given
+    ///
+    /// A rename counts under its new name only.
+    fn history(&self, path: Option<&str>) -> Result<Vec<VersionInfo<Self::Version>>, StorageError>;
+}
 
-impl StorageManager {
-    /// Initialize a new database with the specified storage engine
-    pub fn init<P: AsRef<Path>>(_path: P) -> Result<()> {
-        // TODO: Implement storage initialization
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Storage backend not implemented yet",
-        ))
+/// A recorded version, as listed by [`VersionStore::history`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionInfo<V> {
+    /// The version
+    pub version: V,
+    /// One-line summary
+    pub message: String,
+    /// When it was recorded: the opcode's time if there is metadata
+    pub time: DateTime<Utc>,
+    /// Where it came from, for versions recorded from opcodes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ChangeMetadata>,
+}
+
+impl<V> VersionInfo<V> {
+    /// The entry for `version` recorded with `message`
+    pub fn new(version: V, message: ChangeMessage) -> Self {
+        Self {
+            version,
+            time: message
+                .metadata
+                .as_ref()
+                .map_or_else(Utc::now, |metadata| metadata.time),
+            message: message.message,
+            metadata: message.metadata,
+        }
     }
+}
+
+/// Path of `path` as stored: UTF-8 with `/` separators
+pub fn path_to_str(path: &Path) -> Result<&str, StorageError> {
+    path.to_str()
+        .ok_or_else(|| StorageError::PathConversion(format!("Invalid UTF-8 in path: {:?}", path)))
+}
 
-    /// Check if the database at the specified path is valid
-    pub fn is_valid<P: AsRef<Path>>(_path: P) -> Result<bool> {
-        // TODO: Implement storage validation
-        Ok(false)
+/// What `op` does to the files it touches, given their current content:
+/// each touched path with its new content, `None` once deleted
+///
+/// Writes and truncations past the end fill with zeros, as the filesystem
+/// does, up to [`MAX_FILE_SIZE`]. Only file operations are supported.
+pub fn apply_operation(
+    op: &Operation,
+    current: impl Fn(&str) -> Result<Option<Vec<u8>>, StorageError>,
+) -> Result<BTreeMap<String, Option<Vec<u8>>>, StorageError> {
+    let existing =
+        |path: &str| current(path)?.ok_or_else(|| StorageError::FileNotFound(path.to_string()));
+    let mut touched = BTreeMap::new();
+    match op {
+        Operation::FileCreate { path, content, .. } => {
+            touched.insert(path_to_str(path)?.to_string(), Some(content.clone()));
+        }
+        Operation::FileWrite { path, offset, data } => {
+            let path = path_to_str(path)?;
+            let mut content = existing(path)?;
+            let end = file_len(path, offset.checked_add(data.len() as u64))?;
+            let start = end - data.len();
+            if content.len() < end {
+                content.resize(end, 0);
+            }
+            content[start..end].copy_from_slice(data);
+            touched.insert(path.to_string(), Some(content));
+        }
+        Operation::FileTruncate { path, new_size } => {
+            let path = path_to_str(path)?;
+            let mut content = existing(path)?;
+            content.resize(file_len(path, Some(*new_size))?, 0);
+            touched.insert(path.to_string(), Some(content));
+        }
+        Operation::FileDelete { path } => {
+            let path = path_to_str(path)?;
+            existing(path)?;
+            touched.insert(path.to_string(), None);
+        }
+        Operation::FileRename { old_path, new_path } => {
+            let old_path = path_to_str(old_path)?;
+            let content = existing(old_path)?;
+            touched.insert(old_path.to_string(), None);
+            touched.insert(path_to_str(new_path)?.to_string(), Some(content));
+        }
+        Operation::DirCreate { .. }
+        | Operation::DirDelete { .. }
+        | Operation::DirRename { .. }
+        | Operation::SetPermissions { .. }
+        | Operation::SetTimestamps { .. }
+        | Operation::SetOwnership { .. }
+        | Operation::SymlinkCreate { .. }
+        | Operation::SymlinkDelete { .. }
+        | Operation::HardLinkCreate { .. } => {
+            return Err(StorageError::UnsupportedOperation(format!("{:?}", op)));
+        }
     }
+    Ok(touched)
+}
+
+/// `len` as the length of a file in memory, if it is no more than
+/// [`MAX_FILE_SIZE`]; `None` is a length that overflowed
+fn file_len(path: &str, len: Option<u64>) -> Result<usize, StorageError> {
+    len.filter(|len| *len <= MAX_FILE_SIZE)
+        .and_then(|len| usize::try_from(len).ok())
+        .ok_or_else(|| StorageError::TooLarge(format!("{} past {} bytes", path, MAX_FILE_SIZE)))
+}
+
+/// The path a history entry is listed under: the new name of a rename
+fn listed_path(op: &Operation) -> Option<&Path> {
+    match op {
+        Operation::FileCreate { path, .. }
+        | Operation::FileWrite { path, .. }
+        | Operation::FileTruncate { path, .. }
+        | Operation::FileDelete { path } => Some(path),
+        Operation::FileRename { new_path, .. } => Some(new_path),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_apply_operation() {
+        let current = |path: &str| Ok((path == "a.txt").then(|| b"hello".to_vec()));
+        let write = Operation::FileWrite {
+            path: PathBuf::from("a.txt"),
+            offset: 7,
+            data: b"!".to_vec(),
+        };
+        assert_eq!(
+            apply_operation(&write, current).unwrap(),
+            BTreeMap::from([("a.txt".to_string(), Some(b"hello\0\0!".to_vec()))])
+        );
+
+        let rename = Operation::FileRename {
+            old_path: PathBuf::from("a.txt"),
+            new_path: PathBuf::from("b.txt"),
+        };
+        assert_eq!(
+            apply_operation(&rename, current).unwrap(),
+            BTreeMap::from([
+                ("a.txt".to_string(), None),
+                ("b.txt".to_string(), Some(b"hello".to_vec())),
+            ])
+        );
+
+        let too_far = Operation::FileWrite {
+            path: PathBuf::from("a.txt"),
+            offset: u64::MAX,
+            data: b"!".to_vec(),
+        };
+        assert!(matches!(
+            apply_operation(&too_far, current),
+            Err(StorageError::TooLarge(_))
+        ));
+        let too_long = Operation::FileTruncate {
+            path: PathBuf::from("a.txt"),
+            new_size: MAX_FILE_SIZE + 1,
+        };
+        assert!(matches!(
+            apply_operation(&too_long, current),
+            Err(StorageError::TooLarge(_))
+        ));
 
-    /// Open an existing storage backend at the specified path
-    pub fn open<P: AsRef<Path>>(_path: P) -> Result<()> {
-        // TODO: Implement storage opening
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Storage backend not implemented yet",
-        ))
+        let delete = Operation::FileDelete {
+            path: PathBuf::from("nope.txt"),
+        };
+        assert!(matches!(
+            apply_operation(&delete, current),
+            Err(StorageError::FileNotFound(_))
+        ));
     }
 }
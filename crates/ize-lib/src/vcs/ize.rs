@@ -0,0 +1,54 @@
+//! Filter for Ize's own virtual history directory.
+
+use std::path::Path;
+
+use super::{path_starts_with_dir, IgnoreFilter};
+
+/// Filters the `.ize/` directory a live mount synthesizes at its root.
+///
+/// Nothing under `.ize/` exists in the working copy, so it must never be
+/// recorded even if an operation on it were to reach an observer.
+pub struct IzeHistoryFilter;
+
+impl IgnoreFilter for IzeHistoryFilter {
+    fn name(&self) -> &str {
+        "Ize"
+    }
+
+    fn dir_name(&self) -> &str {
+        ".ize"
+    }
+
+    /// Always active: the directory is virtual and never found on disk.
+    fn is_present(&self, _base_path: &Path) -> bool {
+        true
+    }
+
+    fn should_ignore(&self, rel_path: &Path) -> bool {
+        path_starts_with_dir(rel_path, ".ize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ize_should_ignore() {
+        let filter = IzeHistoryFilter;
+
+        assert!(filter.should_ignore(Path::new(".ize")));
+        assert!(filter.should_ignore(Path::new(".ize/log")));
+        assert!(filter.should_ignore(Path::new(".ize/changes/ABC/src/main.rs")));
+        assert!(!filter.should_ignore(Path::new("src/.ize")));
+        assert!(!filter.should_ignore(Path::new(".izerc")));
+        assert!(!filter.should_ignore(Path::new("")));
+    }
+
+    #[test]
+    fn test_ize_always_present() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(IzeHistoryFilter.is_present(tmp.path()));
+        assert_eq!(IzeHistoryFilter.dir_name(), ".ize");
+    }
+}
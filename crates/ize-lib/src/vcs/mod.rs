@@ -17,10 +17,12 @@ use std::ffi::OsStr;
 use std::path::Path;
 
 mod git;
+mod ize;
 mod jujutsu;
 mod pijul;
 
 pub use git::GitBackend;
+pub use ize::IzeHistoryFilter;
 pub use jujutsu::JujutsuBackend;
 pub use pijul::PijulBackend;
 
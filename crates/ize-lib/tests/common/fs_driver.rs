@@ -0,0 +1,361 @@
+//! In-process filesystem driver for Ize
+//!
+//! Drives `ObservingFS<FdPassthroughFS<_>>` without a FUSE mount, so POSIX
+//! call sequences can be scripted in ordinary (non-root, non-ignored) tests.
+//!
+//! fuser's `Request` and `Reply*` types can only be built by fuser itself,
+//! so the driver calls the request-free [`FsOps`] surface that the mounted
+//! filesystem's `Filesystem` impl forwards to one-for-one.  Every call and
+//! its reply is kept in a transcript, and an `OpcodeRecorder` observer feeds
+//! an `OpcodeQueue` the test can inspect.
+//!
+//! ```ignore
+//! let mut driver = FsDriver::new();
+//! driver.write_file("a.txt", b"hello").unwrap();
+//! driver.rename("a.txt", "b.txt").unwrap();
+//! assert!(matches!(driver.take_ops()[..], [FileCreate { .. }, FileWrite { .. }, FileRename { .. }]));
+//! ```
+
+use fuser::FileAttr;
+use ize_lib::backing_fs::{BackingFs, MemBackingFs};
+use ize_lib::filesystems::{FdPassthroughFS, FsOps, ObservingFS, SetAttr};
+use ize_lib::operations::{OpcodeQueue, OpcodeRecorder, Operation};
+use std::ffi::OsStr;
+use std::fmt::Debug;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// FUSE root inode.
+const ROOT_INO: u64 = 1;
+
+/// Result of a driver call: the value FUSE would have replied with, or errno.
+pub type Reply<T> = Result<T, libc::c_int>;
+
+/// An open file as seen by a FUSE client: its inode and file handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handle {
+    pub ino: u64,
+    pub fh: u64,
+}
+
+/// One request/reply pair in the driver transcript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exchange {
+    /// Human-readable request, e.g. `rename("a", "b")`.
+    pub request: String,
+    /// `None` on success, otherwise the errno replied.
+    pub errno: Option<libc::c_int>,
+}
+
+/// Drives an observed fd-passthrough filesystem through path-level calls.
+pub struct FsDriver {
+    fs: ObservingFS<FdPassthroughFS<Arc<dyn BackingFs>>>,
+    queue: Arc<OpcodeQueue>,
+    transcript: Vec<Exchange>,
+}
+
+impl FsDriver {
+    /// Create a driver over a fresh in-memory backing store.
+    pub fn new() -> Self {
+        Self::with_backing(Arc::new(MemBackingFs::new()))
+    }
+
+    /// Create a driver over an existing backing store.
+    ///
+    /// The recorder does its metadata lookups through the same store.
+    pub fn with_backing(backing: Arc<dyn BackingFs>) -> Self {
+        let fs = FdPassthroughFS::new(Arc::clone(&backing), PathBuf::from("/ize-driver"));
+        let queue = OpcodeQueue::new();
+        let recorder =
+            OpcodeRecorder::new(fs.inode_map(), PathBuf::from("/ize-driver"), queue.sender())
+                .with_backing(backing);
+
+        let mut fs = ObservingFS::new(fs);
+        fs.add_observer(Arc::new(recorder));
+
+        Self {
+            fs,
+            queue,
+            transcript: Vec::new(),
+        }
+    }
+
+    /// Direct access to the filesystem, for calls the path helpers don't cover.
+    pub fn fs(&mut self) -> &mut ObservingFS<FdPassthroughFS<Arc<dyn BackingFs>>> {
+        &mut self.fs
+    }
+
+    /// Every request made so far with its reply.
+    pub fn transcript(&self) -> &[Exchange] {
+        &self.transcript
+    }
+
+    /// Remove and return all opcodes recorded so far, in order.
+    pub fn take_ops(&self) -> Vec<Operation> {
+        self.queue
+            .drain()
+            .into_iter()
+            .map(|opcode| opcode.into_op())
+            .collect()
+    }
+
+    /// Number of opcodes waiting in the queue.
+    pub fn pending_ops(&self) -> usize {
+        self.queue.len()
+    }
+
+    // -- Path resolution ------------------------------------------------------
+
+    /// Resolve `path` to an inode by looking up each component from the root,
+    /// as the kernel would.
+    pub fn lookup(&mut self, path: impl AsRef<Path>) -> Reply<FileAttr> {
+        let path = path.as_ref();
+        let mut attr = self.fs.getattr(ROOT_INO, None)?;
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => attr = self.fs.lookup(attr.ino, name)?,
+                Component::CurDir => {}
+                _ => return Err(libc::EINVAL),
+            }
+        }
+        Ok(attr)
+    }
+
+    /// Resolve the parent directory of `path` and return its inode with the
+    /// final component.
+    fn parent_of<'p>(&mut self, path: &'p Path) -> Reply<(u64, &'p OsStr)> {
+        let name = path.file_name().ok_or(libc::EINVAL)?;
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let attr = self.lookup(parent)?;
+        Ok((attr.ino, name))
+    }
+
+    /// Append `result` to the transcript and pass it through.
+    fn log<T>(&mut self, request: String, result: Reply<T>) -> Reply<T> {
+        self.transcript.push(Exchange {
+            request,
+            errno: result.as_ref().err().copied(),
+        });
+        result
+    }
+
+    // -- POSIX-style calls ----------------------------------------------------
+
+    /// `stat(path)`.
+    pub fn stat(&mut self, path: impl AsRef<Path>) -> Reply<FileAttr> {
+        let path = path.as_ref();
+        let result = self.lookup(path);
+        self.log(call("stat", &[&path]), result)
+    }
+
+    /// `open(path, O_CREAT | flags, mode)` for a path that must not exist yet.
+    pub fn create(&mut self, path: impl AsRef<Path>, mode: u32, flags: i32) -> Reply<Handle> {
+        let path = path.as_ref();
+        let result = self.parent_of(path).and_then(|(parent, name)| {
+            let (attr, fh) = self.fs.create(parent, name, mode, flags)?;
+            Ok(Handle { ino: attr.ino, fh })
+        });
+        self.log(call("create", &[&path, &mode, &flags]), result)
+    }
+
+    /// `open(path, flags)` for an existing file.
+    pub fn open(&mut self, path: impl AsRef<Path>, flags: i32) -> Reply<Handle> {
+        let path = path.as_ref();
+        let result = self.lookup(path).and_then(|attr| {
+            let fh = self.fs.open(attr.ino, flags)?;
+            Ok(Handle { ino: attr.ino, fh })
+        });
+        self.log(call("open", &[&path, &flags]), result)
+    }
+
+    /// `pwrite(handle, data, offset)`.
+    pub fn write(&mut self, handle: Handle, offset: i64, data: &[u8]) -> Reply<u32> {
+        let result = self.fs.write(handle.ino, handle.fh, offset, data);
+        self.log(call("write", &[&handle.fh, &offset, &data.len()]), result)
+    }
+
+    /// `pread(handle, size, offset)`.
+    pub fn read(&mut self, handle: Handle, offset: i64, size: u32) -> Reply<Vec<u8>> {
+        let result = self.fs.read(handle.ino, handle.fh, offset, size);
+        self.log(call("read", &[&handle.fh, &offset, &size]), result)
+    }
+
+    /// `close(handle)`: flush, then release.
+    pub fn close(&mut self, handle: Handle) -> Reply<()> {
+        let result = self
+            .fs
+            .flush(handle.ino, handle.fh)
+            .and_then(|()| self.fs.release(handle.ino, handle.fh));
+        self.log(call("close", &[&handle.fh]), result)
+    }
+
+    /// `ftruncate(handle, size)`.
+    pub fn ftruncate(&mut self, handle: Handle, size: u64) -> Reply<FileAttr> {
+        let attr = SetAttr {
+            size: Some(size),
+            fh: Some(handle.fh),
+            ..SetAttr::default()
+        };
+        let result = self.fs.setattr(handle.ino, &attr);
+        self.log(call("ftruncate", &[&handle.fh, &size]), result)
+    }
+
+    /// `truncate(path, size)`.
+    pub fn truncate(&mut self, path: impl AsRef<Path>, size: u64) -> Reply<FileAttr> {
+        self.setattr(
+            "truncate",
+            path.as_ref(),
+            SetAttr {
+                size: Some(size),
+                ..SetAttr::default()
+            },
+        )
+    }
+
+    /// `chmod(path, mode)`.
+    pub fn chmod(&mut self, path: impl AsRef<Path>, mode: u32) -> Reply<FileAttr> {
+        self.setattr(
+            "chmod",
+            path.as_ref(),
+            SetAttr {
+                mode: Some(mode),
+                ..SetAttr::default()
+            },
+        )
+    }
+
+    fn setattr(&mut self, name: &str, path: &Path, attr: SetAttr) -> Reply<FileAttr> {
+        let result = self
+            .lookup(path)
+            .and_then(|found| self.fs.setattr(found.ino, &attr));
+        self.log(call(name, &[&path, &attr]), result)
+    }
+
+    /// `mkdir(path, mode)`.
+    pub fn mkdir(&mut self, path: impl AsRef<Path>, mode: u32) -> Reply<FileAttr> {
+        let path = path.as_ref();
+        let result = self
+            .parent_of(path)
+            .and_then(|(parent, name)| self.fs.mkdir(parent, name, mode));
+        self.log(call("mkdir", &[&path, &mode]), result)
+    }
+
+    /// `unlink(path)`.
+    pub fn unlink(&mut self, path: impl AsRef<Path>) -> Reply<()> {
+        let path = path.as_ref();
+        let result = self
+            .parent_of(path)
+            .and_then(|(parent, name)| self.fs.unlink(parent, name));
+        self.log(call("unlink", &[&path]), result)
+    }
+
+    /// `rmdir(path)`.
+    pub fn rmdir(&mut self, path: impl AsRef<Path>) -> Reply<()> {
+        let path = path.as_ref();
+        let result = self
+            .parent_of(path)
+            .and_then(|(parent, name)| self.fs.rmdir(parent, name));
+        self.log(call("rmdir", &[&path]), result)
+    }
+
+    /// `rename(from, to)`.
+    pub fn rename(&mut self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Reply<()> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let result = self.parent_of(from).and_then(|(parent, name)| {
+            let (newparent, newname) = self.parent_of(to)?;
+            self.fs.rename(parent, name, newparent, newname)
+        });
+        self.log(call("rename", &[&from, &to]), result)
+    }
+
+    /// `symlink(target, path)`.
+    pub fn symlink(&mut self, target: impl AsRef<Path>, path: impl AsRef<Path>) -> Reply<FileAttr> {
+        let (target, path) = (target.as_ref(), path.as_ref());
+        let result = self
+            .parent_of(path)
+            .and_then(|(parent, name)| self.fs.symlink(parent, name, target));
+        self.log(call("symlink", &[&target, &path]), result)
+    }
+
+    /// `readlink(path)`.
+    pub fn readlink(&mut self, path: impl AsRef<Path>) -> Reply<PathBuf> {
+        let path = path.as_ref();
+        let result = self.lookup(path).and_then(|attr| {
+            let target = self.fs.readlink(attr.ino)?;
+            Ok(PathBuf::from(OsStr::from_bytes(&target)))
+        });
+        self.log(call("readlink", &[&path]), result)
+    }
+
+    /// List the names in a directory, without `.` and `..`, sorted.
+    pub fn readdir(&mut self, path: impl AsRef<Path>) -> Reply<Vec<String>> {
+        let path = path.as_ref();
+        let result = self.lookup(path).and_then(|attr| {
+            let fh = self.fs.opendir(attr.ino, libc::O_RDONLY)?;
+            let entries = self.fs.readdir(attr.ino, fh, 0);
+            self.fs.releasedir(attr.ino, fh)?;
+            let mut names: Vec<String> = entries?
+                .into_iter()
+                .map(|e| e.name.to_string_lossy().into_owned())
+                .filter(|n| n != "." && n != "..")
+                .collect();
+            names.sort();
+            Ok(names)
+        });
+        self.log(call("readdir", &[&path]), result)
+    }
+
+    // -- Compound helpers -----------------------------------------------------
+
+    /// Create `path` (truncating it if it exists), write `data`, and close it.
+    ///
+    /// Like the kernel without atomic `O_TRUNC`, an existing file is
+    /// truncated with a `setattr` before it is opened.
+    pub fn write_file(&mut self, path: impl AsRef<Path>, data: &[u8]) -> Reply<()> {
+        let path = path.as_ref();
+        let handle = match self.lookup(path) {
+            Ok(_) => {
+                self.truncate(path, 0)?;
+                self.open(path, libc::O_WRONLY)?
+            }
+            Err(_) => self.create(path, 0o644, libc::O_WRONLY)?,
+        };
+        let mut offset = 0usize;
+        while offset < data.len() {
+            let written = self.write(handle, offset as i64, &data[offset..])?;
+            if written == 0 {
+                return Err(libc::EIO);
+            }
+            offset += written as usize;
+        }
+        self.close(handle)
+    }
+
+    /// Open `path` read-only, read it to the end, and close it.
+    pub fn read_file(&mut self, path: impl AsRef<Path>) -> Reply<Vec<u8>> {
+        let handle = self.open(path, libc::O_RDONLY)?;
+        let mut content = Vec::new();
+        loop {
+            let chunk = self.read(handle, content.len() as i64, 64 * 1024)?;
+            if chunk.is_empty() {
+                break;
+            }
+            content.extend_from_slice(&chunk);
+        }
+        self.close(handle)?;
+        Ok(content)
+    }
+}
+
+impl Default for FsDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Format a request for the transcript, e.g. `rename("a", "b")`.
+fn call(name: &str, args: &[&dyn Debug]) -> String {
+    let args: Vec<String> = args.iter().map(|a| format!("{:?}", a)).collect();
+    format!("{}({})", name, args.join(", "))
+}
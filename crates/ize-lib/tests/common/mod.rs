@@ -4,7 +4,9 @@
 //! duplicate setup code and create focused, maintainable tests.
 
 pub mod filesystem_harness;
+pub mod fs_driver;
 pub mod harness;
+pub mod model_checker;
 pub mod op_harness;
 
 // Re-export commonly used items
@@ -16,6 +18,10 @@ pub use filesystem_harness::{
     FilesystemTestContext, FilesystemTestHarness, FilesystemTestHarnessBuilder,
 };
 
+pub use fs_driver::{Exchange, FsDriver, Handle, Reply};
+
+pub use model_checker::{model_op, model_ops, ModelChecker, ModelOp};
+
 pub use op_harness::{MockStorage, OpQueueContext, OpQueueHarness, OpQueueHarnessBuilder};
 
 // Re-export the test macro
@@ -0,0 +1,310 @@
+//! Differential model checker: working copy vs. opcode stream vs. Pijul
+//!
+//! The failure we fear most is silent divergence: the directory on disk says
+//! one thing and Pijul's pristine says another. `ModelChecker` drives a real
+//! directory through `FsDriver` (so every opcode comes from the same
+//! `ObservingFS` + `OpcodeRecorder` stack a mount uses), feeds the resulting
+//! opcodes into an `OpcodeRecordingBackend`, and after every step compares
+//! `PijulBackend::list_files` / `get_file_content` against the directory.
+//!
+//! The directory is the reference model. Operations that fail there (unlink
+//! of a missing file, exclusive create of an existing one) are expected and
+//! must simply produce no opcodes.
+//!
+//! Use [`model_ops`] as a proptest strategy; shrinking then reduces any
+//! divergence to a minimal `Vec<ModelOp>`.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ize_lib::backing_fs::LibcBackingFs;
+use ize_lib::operations::{Opcode, Operation};
+use ize_lib::pijul::{OpcodeRecordingBackend, PijulBackend};
+use proptest::prelude::*;
+use tempfile::TempDir;
+
+use super::fs_driver::FsDriver;
+
+/// Paths the generated operations choose from.
+///
+/// A small pool keeps sequences colliding on the same files, which is where
+/// the interesting interleavings (write after rename, recreate after unlink)
+/// come from.
+pub const MODEL_PATHS: &[&str] = &["a.txt", "b.txt", "sub/c.txt", "sub/d.bin"];
+
+/// Directory pre-created in every model so `sub/` paths are reachable.
+///
+/// Directory opcodes aren't recorded by the Pijul backend yet; directories
+/// come into existence there as parents of files.
+const MODEL_DIR: &str = "sub";
+
+/// Placeholder file that keeps [`MODEL_DIR`] tracked in Pijul.
+const MODEL_DIR_KEEP: &str = "sub/.keep";
+
+/// One filesystem-level step, with paths given as indices into [`MODEL_PATHS`].
+#[derive(Clone, PartialEq, Eq)]
+pub enum ModelOp {
+    /// Exclusive create, then write `content` from offset 0
+    Create { file: usize, content: Vec<u8> },
+    /// Open for writing and write `data` at `offset`
+    Write {
+        file: usize,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    /// Truncate (or extend) to `size`
+    Truncate { file: usize, size: u64 },
+    /// Unlink
+    Unlink { file: usize },
+    /// Rename `from` over `to`
+    Rename { from: usize, to: usize },
+}
+
+impl fmt::Debug for ModelOp {
+    // Print paths and printable content so shrunk failures read as a script.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelOp::Create { file, content } => {
+                write!(f, "create({}, {:?})", MODEL_PATHS[*file], bytes(content))
+            }
+            ModelOp::Write { file, offset, data } => write!(
+                f,
+                "write({}, @{}, {:?})",
+                MODEL_PATHS[*file],
+                offset,
+                bytes(data)
+            ),
+            ModelOp::Truncate { file, size } => {
+                write!(f, "truncate({}, {})", MODEL_PATHS[*file], size)
+            }
+            ModelOp::Unlink { file } => write!(f, "unlink({})", MODEL_PATHS[*file]),
+            ModelOp::Rename { from, to } => {
+                write!(f, "rename({}, {})", MODEL_PATHS[*from], MODEL_PATHS[*to])
+            }
+        }
+    }
+}
+
+fn bytes(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
+
+/// File contents: short runs over a tiny alphabet, so diffs hit line
+/// boundaries and the occasional NUL flips a file between text and binary.
+fn content() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(prop::sample::select(b"ab\n\0".to_vec()), 0..24)
+}
+
+fn file() -> impl Strategy<Value = usize> {
+    0..MODEL_PATHS.len()
+}
+
+/// Strategy for a single [`ModelOp`].
+pub fn model_op() -> impl Strategy<Value = ModelOp> {
+    prop_oneof![
+        (file(), content()).prop_map(|(file, content)| ModelOp::Create { file, content }),
+        (
+            file(),
+            0u64..48,
+            content().prop_filter("non-empty", |d| !d.is_empty())
+        )
+            .prop_map(|(file, offset, data)| ModelOp::Write { file, offset, data }),
+        (file(), 0u64..48).prop_map(|(file, size)| ModelOp::Truncate { file, size }),
+        file().prop_map(|file| ModelOp::Unlink { file }),
+        (file(), file())
+            .prop_filter("distinct paths", |(from, to)| from != to)
+            .prop_map(|(from, to)| ModelOp::Rename { from, to }),
+    ]
+}
+
+/// Strategy for a sequence of up to `max_len` operations.
+pub fn model_ops(max_len: usize) -> impl Strategy<Value = Vec<ModelOp>> {
+    prop::collection::vec(model_op(), 1..=max_len)
+}
+
+/// Reference directory, recording stack, and Pijul repository in lockstep.
+pub struct ModelChecker {
+    _temp: TempDir,
+    work_dir: PathBuf,
+    driver: FsDriver,
+    backend: OpcodeRecordingBackend,
+    seq: u64,
+    applied: Vec<Operation>,
+}
+
+impl ModelChecker {
+    /// Create an empty model with [`MODEL_DIR`] in place in both the
+    /// directory and Pijul.
+    pub fn new() -> Self {
+        let temp = TempDir::new().expect("create temp dir");
+        let work_dir = temp.path().join("work");
+        let pijul_dir = temp.path().join(".pijul");
+        fs::create_dir_all(&work_dir).expect("create work dir");
+
+        let backing = LibcBackingFs::open_dir(&work_dir).expect("open work dir");
+        let driver = FsDriver::with_backing(Arc::new(backing));
+        let pijul = PijulBackend::init(&pijul_dir, &work_dir, None).expect("init pijul");
+
+        let mut checker = Self {
+            _temp: temp,
+            work_dir,
+            driver,
+            backend: OpcodeRecordingBackend::new(pijul),
+            seq: 0,
+            applied: Vec::new(),
+        };
+
+        checker
+            .driver
+            .mkdir(MODEL_DIR, 0o755)
+            .expect("mkdir model dir");
+        checker.driver.take_ops();
+        checker
+            .driver
+            .write_file(MODEL_DIR_KEEP, b"keep\n")
+            .expect("write keep file");
+        checker.sync().expect("record keep file");
+        checker
+    }
+
+    /// Apply `op` to the directory, record its opcodes, and compare.
+    ///
+    /// Returns a description of the first mismatch, if any.
+    pub fn step(&mut self, op: &ModelOp) -> Result<(), String> {
+        let path = |i: &usize| MODEL_PATHS[*i];
+
+        // Errors here are the reference model's answer, not a failure.
+        let _ = match op {
+            ModelOp::Create { file, content } => self
+                .driver
+                .create(path(file), 0o644, libc::O_WRONLY | libc::O_EXCL)
+                .and_then(|handle| {
+                    let written = if content.is_empty() {
+                        Ok(0)
+                    } else {
+                        self.driver.write(handle, 0, content)
+                    };
+                    self.driver.close(handle)?;
+                    written.map(drop)
+                }),
+            ModelOp::Write { file, offset, data } => self
+                .driver
+                .open(path(file), libc::O_WRONLY)
+                .and_then(|handle| {
+                    let written = self.driver.write(handle, *offset as i64, data);
+                    self.driver.close(handle)?;
+                    written.map(drop)
+                }),
+            ModelOp::Truncate { file, size } => self.driver.truncate(path(file), *size).map(drop),
+            ModelOp::Unlink { file } => self.driver.unlink(path(file)),
+            ModelOp::Rename { from, to } => self.driver.rename(path(from), path(to)),
+        };
+
+        self.sync()?;
+        self.check()
+    }
+
+    /// Run `ops` in order, stopping at the first divergence.
+    ///
+    /// The error names the failing step and includes the opcodes applied so
+    /// far, which is usually enough to see where Pijul went wrong.
+    pub fn run(&mut self, ops: &[ModelOp]) -> Result<(), String> {
+        for (i, op) in ops.iter().enumerate() {
+            self.step(op).map_err(|e| {
+                format!(
+                    "step {} {:?} diverged: {}\nopcodes applied:\n{}",
+                    i,
+                    op,
+                    e,
+                    self.applied
+                        .iter()
+                        .map(|op| format!("  {:?}", op))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Feed pending opcodes from the recorder into Pijul.
+    fn sync(&mut self) -> Result<(), String> {
+        for op in self.driver.take_ops() {
+            self.seq += 1;
+            let opcode = Opcode::new(self.seq, op.clone());
+            self.applied.push(op);
+            self.backend
+                .apply_opcode(&opcode)
+                .map_err(|e| format!("apply_opcode #{} failed: {}", self.seq, e))?;
+        }
+        Ok(())
+    }
+
+    /// Compare Pijul's view with the directory.
+    pub fn check(&self) -> Result<(), String> {
+        let mut expected = Vec::new();
+        collect_files(&self.work_dir, &self.work_dir, &mut expected);
+        expected.sort();
+
+        let pijul = self.backend.pijul();
+        let listed = pijul
+            .list_files()
+            .map_err(|e| format!("list_files failed: {}", e))?;
+        if listed != expected {
+            return Err(format!(
+                "file list differs: pijul={:?} disk={:?}",
+                listed, expected
+            ));
+        }
+
+        for path in &expected {
+            let on_disk = fs::read(self.work_dir.join(path))
+                .map_err(|e| format!("read {} from disk: {}", path, e))?;
+            let recorded = pijul
+                .get_file_content(path)
+                .map_err(|e| format!("get_file_content({}) failed: {}", path, e))?;
+            if recorded != on_disk {
+                return Err(format!(
+                    "content of {} differs: pijul={:?} disk={:?}",
+                    path,
+                    bytes(&recorded),
+                    bytes(&on_disk)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The driver, for inspecting the transcript after a failure.
+    pub fn driver(&self) -> &FsDriver {
+        &self.driver
+    }
+}
+
+impl Default for ModelChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Collect regular files under `dir` as `/`-separated paths relative to `root`.
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).expect("read model dir") {
+        let entry = entry.expect("read model dir entry");
+        let path = entry.path();
+        let file_type = entry.file_type().expect("model entry type");
+        if file_type.is_dir() {
+            collect_files(root, &path, out);
+        } else if file_type.is_file() {
+            let relative = path.strip_prefix(root).expect("path under model root");
+            let parts: Vec<_> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            out.push(parts.join("/"));
+        }
+    }
+}
@@ -3,3 +3,5 @@
 //! These tests verify complete features and workflows.
 
 pub mod operation_capture_test;
+pub mod pijul_model_test;
+pub mod posix_sequence_test;
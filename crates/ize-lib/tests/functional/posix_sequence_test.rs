@@ -0,0 +1,271 @@
+//! Scripted POSIX sequences through the in-process driver.
+//!
+//! These run the real `ObservingFS<FdPassthroughFS<_>>` stack and the
+//! `OpcodeRecorder` without a FUSE mount, checking both what the filesystem
+//! replies and which opcodes it emits.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ize_lib::backing_fs::{Fault, FaultyBackingFs, FsMethod, LibcBackingFs, MemBackingFs};
+use ize_lib::operations::Operation;
+
+use crate::common::FsDriver;
+
+fn p(path: &str) -> PathBuf {
+    PathBuf::from(path)
+}
+
+#[test]
+fn test_create_write_read_back() {
+    let mut driver = FsDriver::new();
+
+    let handle = driver.create("hello.txt", 0o644, libc::O_RDWR).unwrap();
+    assert_eq!(driver.write(handle, 0, b"hello world").unwrap(), 11);
+    assert_eq!(driver.read(handle, 6, 5).unwrap(), b"world");
+    driver.close(handle).unwrap();
+
+    assert_eq!(driver.stat("hello.txt").unwrap().size, 11);
+    assert_eq!(
+        driver.take_ops(),
+        vec![
+            Operation::FileCreate {
+                path: p("hello.txt"),
+                mode: 0o644,
+                content: Vec::new(),
+            },
+            Operation::FileWrite {
+                path: p("hello.txt"),
+                offset: 0,
+                data: b"hello world".to_vec(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_rewrite_truncates_first() {
+    let mut driver = FsDriver::new();
+    driver.write_file("a.txt", b"first version").unwrap();
+    driver.take_ops();
+
+    driver.write_file("a.txt", b"second").unwrap();
+
+    assert_eq!(driver.read_file("a.txt").unwrap(), b"second");
+    assert_eq!(
+        driver.take_ops(),
+        vec![
+            Operation::FileTruncate {
+                path: p("a.txt"),
+                new_size: 0,
+            },
+            Operation::FileWrite {
+                path: p("a.txt"),
+                offset: 0,
+                data: b"second".to_vec(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_rename_file_and_directory() {
+    let mut driver = FsDriver::new();
+    driver.mkdir("src", 0o755).unwrap();
+    driver.write_file("src/lib.rs", b"fn main() {}").unwrap();
+    driver.take_ops();
+
+    driver.rename("src/lib.rs", "src/main.rs").unwrap();
+    driver.rename("src", "crate").unwrap();
+
+    assert_eq!(driver.readdir("").unwrap(), vec!["crate"]);
+    assert_eq!(driver.readdir("crate").unwrap(), vec!["main.rs"]);
+    assert_eq!(driver.read_file("crate/main.rs").unwrap(), b"fn main() {}");
+    assert_eq!(
+        driver.take_ops(),
+        vec![
+            Operation::FileRename {
+                old_path: p("src/lib.rs"),
+                new_path: p("src/main.rs"),
+            },
+            Operation::DirRename {
+                old_path: p("src"),
+                new_path: p("crate"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_rename_over_existing_file() {
+    let mut driver = FsDriver::new();
+    driver.write_file("new", b"new").unwrap();
+    driver.write_file("old", b"old").unwrap();
+    driver.take_ops();
+
+    // The atomic-save pattern editors use.
+    driver.rename("new", "old").unwrap();
+
+    assert_eq!(driver.read_file("old").unwrap(), b"new");
+    assert_eq!(driver.stat("new").unwrap_err(), libc::ENOENT);
+    assert_eq!(
+        driver.take_ops(),
+        vec![Operation::FileRename {
+            old_path: p("new"),
+            new_path: p("old"),
+        }]
+    );
+}
+
+#[test]
+fn test_unlink_keeps_open_handle_readable() {
+    let mut driver = FsDriver::new();
+    driver.write_file("tmp", b"scratch").unwrap();
+    let handle = driver.open("tmp", libc::O_RDONLY).unwrap();
+    driver.take_ops();
+
+    driver.unlink("tmp").unwrap();
+
+    assert_eq!(driver.stat("tmp").unwrap_err(), libc::ENOENT);
+    assert_eq!(driver.read(handle, 0, 64).unwrap(), b"scratch");
+    driver.close(handle).unwrap();
+    assert_eq!(
+        driver.take_ops(),
+        vec![Operation::FileDelete { path: p("tmp") }]
+    );
+}
+
+#[test]
+fn test_failed_calls_reply_errno_and_record_nothing() {
+    let mut driver = FsDriver::new();
+    driver.mkdir("dir", 0o755).unwrap();
+    driver.write_file("dir/file", b"x").unwrap();
+    driver.take_ops();
+
+    assert_eq!(
+        driver
+            .create("dir/file", 0o644, libc::O_WRONLY | libc::O_EXCL)
+            .unwrap_err(),
+        libc::EEXIST
+    );
+    assert_eq!(driver.rmdir("dir").unwrap_err(), libc::ENOTEMPTY);
+    assert_eq!(driver.unlink("dir").unwrap_err(), libc::EISDIR);
+    assert_eq!(driver.unlink("missing").unwrap_err(), libc::ENOENT);
+    assert_eq!(driver.rename("missing", "other").unwrap_err(), libc::ENOENT);
+    assert_eq!(driver.mkdir("dir", 0o755).unwrap_err(), libc::EEXIST);
+
+    let read_only = driver.open("dir/file", libc::O_RDONLY).unwrap();
+    assert_eq!(
+        driver.write(read_only, 0, b"nope").unwrap_err(),
+        libc::EBADF
+    );
+    driver.close(read_only).unwrap();
+
+    assert_eq!(driver.pending_ops(), 0, "{:?}", driver.take_ops());
+}
+
+#[test]
+fn test_chmod_and_truncate_emit_metadata_ops() {
+    let mut driver = FsDriver::new();
+    driver
+        .write_file("script.sh", b"#!/bin/sh\nexit 0\n")
+        .unwrap();
+    driver.take_ops();
+
+    assert_eq!(driver.chmod("script.sh", 0o755).unwrap().perm, 0o755);
+    assert_eq!(driver.truncate("script.sh", 9).unwrap().size, 9);
+
+    assert_eq!(
+        driver.take_ops(),
+        vec![
+            Operation::SetPermissions {
+                path: p("script.sh"),
+                mode: 0o755,
+            },
+            Operation::FileTruncate {
+                path: p("script.sh"),
+                new_size: 9,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_symlink_records_target() {
+    let mut driver = FsDriver::new();
+    driver.write_file("notes.md", b"# notes").unwrap();
+    driver.take_ops();
+
+    driver.symlink("notes.md", "latest").unwrap();
+
+    assert_eq!(driver.readlink("latest").unwrap(), p("notes.md"));
+    assert_eq!(
+        driver.symlink("elsewhere", "latest").unwrap_err(),
+        libc::EEXIST
+    );
+    assert_eq!(
+        driver.take_ops(),
+        vec![Operation::SymlinkCreate {
+            path: p("latest"),
+            target: p("notes.md"),
+        }]
+    );
+}
+
+#[test]
+fn test_transcript_records_replies() {
+    let mut driver = FsDriver::new();
+    driver.mkdir("d", 0o755).unwrap();
+    let _ = driver.rmdir("missing");
+
+    let transcript = driver.transcript();
+    assert_eq!(transcript.len(), 2);
+    assert_eq!(transcript[0].request, "mkdir(\"d\", 493)");
+    assert_eq!(transcript[0].errno, None);
+    assert_eq!(transcript[1].request, "rmdir(\"missing\")");
+    assert_eq!(transcript[1].errno, Some(libc::ENOENT));
+}
+
+#[test]
+fn test_disk_full_mid_write() {
+    let faulty = Arc::new(FaultyBackingFs::new(MemBackingFs::new()));
+    let mut driver = FsDriver::with_backing(faulty.clone());
+    let handle = driver.create("big.bin", 0o644, libc::O_WRONLY).unwrap();
+    driver.take_ops();
+
+    faulty.inject(Fault::errno(FsMethod::Pwrite, libc::ENOSPC).after(1));
+    assert_eq!(driver.write(handle, 0, b"chunk-1").unwrap(), 7);
+    assert_eq!(
+        driver.write(handle, 7, b"chunk-2").unwrap_err(),
+        libc::ENOSPC
+    );
+    driver.close(handle).unwrap();
+
+    assert_eq!(
+        driver.take_ops(),
+        vec![Operation::FileWrite {
+            path: p("big.bin"),
+            offset: 0,
+            data: b"chunk-1".to_vec(),
+        }]
+    );
+}
+
+#[test]
+fn test_same_sequence_over_real_directory() {
+    let tmp = tempfile::tempdir().unwrap();
+    let backing = LibcBackingFs::open_dir(tmp.path()).unwrap();
+    let mut driver = FsDriver::with_backing(Arc::new(backing));
+
+    driver.mkdir("docs", 0o755).unwrap();
+    driver.write_file("docs/readme.md", b"# ize\n").unwrap();
+    driver.rename("docs/readme.md", "docs/README.md").unwrap();
+
+    assert_eq!(
+        std::fs::read(tmp.path().join("docs/README.md")).unwrap(),
+        b"# ize\n"
+    );
+    let ops = driver.take_ops();
+    assert!(matches!(ops[0], Operation::DirCreate { .. }));
+    assert!(matches!(ops.last(), Some(Operation::FileRename { .. })));
+}
@@ -1,18 +1,30 @@
 use anyhow::{Context, Result};
 use clap::Parser;
 use env_logger::Env;
-use ize_lib::cli::commands::{ChannelAction, Cli, Commands};
-use ize_lib::filesystems::observing::ObservingFS;
-use ize_lib::filesystems::passthrough::PassthroughFS;
-use ize_lib::operations::{OpcodeQueue, OpcodeRecorder};
-use ize_lib::{IzeProject, OpcodeRecordingBackend, PijulBackend, ProjectManager};
+use fuser::Notifier;
+use ize_lib::backing_fs::LibcBackingFs;
+use ize_lib::cli::commands::{ChannelAction, Cli, Commands, IdentityAction, KeyAction, TagAction};
+use ize_lib::filesystems::observing::{MutationGate, ObservingFS};
+use ize_lib::filesystems::passthrough_fd::{invalidate_kernel_cache, FdPassthroughFS, InodeMap};
+use ize_lib::filesystems::{HistoryFS, SnapshotFS, HISTORY_DIR};
+use ize_lib::identity::{self, IdentityConfig, IdentityError, SignatureStatus};
+use ize_lib::operations::{Opcode, OpcodeQueue, OpcodeRecorder};
+use ize_lib::pijul::{
+    default_keyfile, parse_timestamp, ApplyReport, CheckpointPolicy, CheckpointReport, DirtyPolicy,
+    EncryptionSettings, GcReport, GrepQuery, HunkDetail, KeySource, LogFilter, PijulQuery,
+    UndoReport, UndoTarget, NEW_PASSPHRASE_VAR, PASSPHRASE_VAR,
+};
+use ize_lib::project::control::{self, ControlRequest, ControlResponse, ControlServer};
+use ize_lib::vcs::IzeHistoryFilter;
+use ize_lib::{IzeProject, OpcodeRecordingBackend, PijulBackend, PijulError, ProjectManager};
 use log::{error, info, warn};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -28,21 +40,52 @@ fn main() -> Result<()> {
     let unmount_on_exit = cli.unmount_on_exit;
 
     match cli.command {
-        Commands::Init { directory, channel } => {
-            cmd_init(&directory, channel.as_deref())?;
+        Commands::Init {
+            directory,
+            channel,
+            encrypt,
+            keyfile,
+            passphrase,
+        } => {
+            cmd_init(&directory, channel.as_deref(), encrypt, keyfile, passphrase)?;
+        }
+        Commands::Mount {
+            directory,
+            read_only,
+            at: Some(at),
+            project,
+            foreground,
+        } => {
+            if read_only {
+                info!("--read-only is implied by --at");
+            }
+            cmd_mount_at(&directory, &at, project, foreground, unmount_on_exit)?;
         }
         Commands::Mount {
             directory,
             read_only,
+            at: None,
+            show_history_dir,
             foreground,
+            ..
         } => {
-            cmd_mount(&directory, read_only, foreground, unmount_on_exit)?;
+            cmd_mount(
+                &directory,
+                read_only,
+                show_history_dir,
+                foreground,
+                unmount_on_exit,
+            )?;
         }
         Commands::Unmount { directory } => {
             cmd_unmount(&directory)?;
         }
-        Commands::Status { directory, verbose } => {
-            cmd_status(directory.as_deref(), verbose)?;
+        Commands::Status {
+            directory,
+            verbose,
+            stats,
+        } => {
+            cmd_status(directory.as_deref(), verbose, stats)?;
         }
         Commands::List { format } => {
             cmd_list(&format)?;
@@ -51,29 +94,94 @@ fn main() -> Result<()> {
             file_path,
             limit,
             verbose,
+            directory,
         } => {
-            info!("Viewing history for file {:?}", file_path);
-            println!("File history viewing not yet implemented");
-
-            if verbose {
-                println!("Verbose mode enabled");
-            }
-
-            if let Some(limit_val) = limit {
-                println!("Showing up to {} versions", limit_val);
-            }
+            cmd_history(&file_path, limit, verbose, directory)?;
+        }
+        Commands::Log {
+            since,
+            until,
+            path,
+            author,
+            channel,
+            limit,
+            format,
+            directory,
+        } => {
+            let filter = log_filter(
+                since.as_deref(),
+                until.as_deref(),
+                path.as_deref(),
+                author.as_deref(),
+                channel,
+                limit,
+            )?;
+            cmd_log(&filter, &format, directory)?;
+        }
+        Commands::Show { hash, directory } => {
+            cmd_show(&hash, directory)?;
+        }
+        Commands::Blame {
+            file_path,
+            directory,
+        } => {
+            cmd_blame(&file_path, directory)?;
+        }
+        Commands::Grep {
+            pattern,
+            since,
+            path,
+            channel,
+            directory,
+        } => {
+            cmd_grep(
+                &pattern,
+                since.as_deref(),
+                path.as_deref(),
+                channel,
+                directory,
+            )?;
         }
         Commands::Restore {
             file_path,
             version,
             force,
+            directory,
         } => {
-            info!("Restoring file {:?} to version {}", file_path, version);
-            println!("File restoration not yet implemented");
-
-            if force {
-                println!("Force mode enabled, skipping confirmation");
-            }
+            cmd_restore(&file_path, &version, force, directory)?;
+        }
+        Commands::Apply { hash, directory } => {
+            cmd_apply(&hash, directory)?;
+        }
+        Commands::Undo {
+            target,
+            since,
+            directory,
+        } => {
+            cmd_undo(target.as_deref(), since.as_deref(), directory)?;
+        }
+        Commands::Checkpoint {
+            message,
+            archive,
+            drop,
+            directory,
+        } => {
+            cmd_checkpoint(&message, archive, drop, directory)?;
+        }
+        Commands::Gc { dry_run, directory } => {
+            cmd_gc(dry_run, directory)?;
+        }
+        Commands::Conflicts { directory } => {
+            cmd_conflicts(directory)?;
+        }
+        Commands::Tag { action } => {
+            cmd_tag(action)?;
+        }
+        Commands::Identity { action } => {
+            cmd_identity(action)?;
+        }
+        Commands::Key { action } => {
+            cmd_key(action)?;
         }
         Commands::Channel { action } => {
             cmd_channel(action)?;
@@ -90,7 +198,13 @@ fn main() -> Result<()> {
 }
 
 /// Initialize a directory for version control
-fn cmd_init(directory: &PathBuf, channel: Option<&str>) -> Result<()> {
+fn cmd_init(
+    directory: &PathBuf,
+    channel: Option<&str>,
+    encrypt: bool,
+    keyfile: Option<PathBuf>,
+    passphrase: bool,
+) -> Result<()> {
     info!("Initializing directory {:?} for version control", directory);
 
     // Check if directory exists
@@ -117,10 +231,35 @@ fn cmd_init(directory: &PathBuf, channel: Option<&str>) -> Result<()> {
         ));
     }
 
-    let project = manager
+    // Asked for before anything is created, so a mistyped passphrase
+    // leaves nothing behind
+    let passphrase = if passphrase {
+        Some(new_passphrase(PASSPHRASE_VAR)?)
+    } else {
+        None
+    };
+
+    let mut project = manager
         .create_project(directory)
         .with_context(|| format!("Failed to initialize project for {:?}", directory))?;
 
+    if encrypt {
+        let source = match (passphrase, keyfile) {
+            (Some(passphrase), _) => KeySource::Passphrase(passphrase),
+            (None, Some(keyfile)) => KeySource::KeyFile(keyfile),
+            (None, None) => {
+                KeySource::KeyFile(default_keyfile(project.uuid()).ok_or_else(|| {
+                    anyhow::anyhow!("No config directory on this platform; give --keyfile")
+                })?)
+            }
+        };
+        if let Err(e) = project.pijul.encrypt(&source) {
+            manager.delete_project_by_uuid(&project.uuid)?;
+            return Err(e)
+                .with_context(|| format!("Failed to encrypt project for {:?}", directory));
+        }
+    }
+
     // If a custom channel was requested, switch to it
     if let Some(channel_name) = channel {
         if channel_name != "main" {
@@ -135,6 +274,12 @@ fn cmd_init(directory: &PathBuf, channel: Option<&str>) -> Result<()> {
     println!("✓ Initialized ize for '{}'", canonical.display());
     println!("  Project UUID: {}", project.uuid());
     println!("  Channel: {}", project.current_channel());
+    if let Some(settings) = project.pijul.encryption() {
+        println!("  Encrypted: {}", describe_key(settings));
+        if settings.keyfile.is_some() {
+            println!("  Keep the key file safe: the history can't be read without it");
+        }
+    }
     println!();
     println!("Next steps:");
     println!("  Mount with: ize mount {}", directory.display());
@@ -147,6 +292,7 @@ fn cmd_init(directory: &PathBuf, channel: Option<&str>) -> Result<()> {
 fn cmd_mount(
     directory: &PathBuf,
     read_only: bool,
+    show_history_dir: bool,
     foreground: bool,
     unmount_on_exit: bool,
 ) -> Result<()> {
@@ -183,34 +329,24 @@ fn cmd_mount(
     let mp_copy = mountpoint.clone();
 
     // Create the passthrough filesystem
-    // Note: We're using the working directory as the source
-    let passthrough = if read_only {
-        PassthroughFS::new_read_only(project.working_dir().to_path_buf(), mp_copy.clone())?
-    } else {
-        PassthroughFS::new(project.working_dir().to_path_buf(), mp_copy.clone())?
-    };
+    // Note: We're using the working directory as the source. The directory fd
+    // is opened before mounting so backing I/O never re-enters FUSE.
+    let backing = LibcBackingFs::open_dir(project.working_dir()).with_context(|| {
+        format!(
+            "Failed to open working directory: {:?}",
+            project.working_dir()
+        )
+    })?;
+    let mut passthrough = FdPassthroughFS::new(backing, mp_copy.clone());
+    passthrough.set_read_only(read_only);
+
+    // A second backend handle serves the read-only `.ize/` history directory
+    let history = PijulBackend::open(project.pijul_dir(), project.working_dir())
+        .with_context(|| "Failed to open Pijul repository for history")?;
+    let show_history_dir = show_history_dir || project.mount.show_history_dir;
 
     if unmount_on_exit || foreground {
-        info!("Will unmount filesystem on exit");
-
-        // Set up signal handler for SIGINT and SIGTERM
-        let mp_for_handler = mp_copy.clone();
-        ctrlc::set_handler(move || {
-            info!("Received interrupt signal, unmounting filesystem");
-            match Command::new("fusermount")
-                .arg("-u")
-                .arg(&mp_for_handler)
-                .status()
-            {
-                Ok(status) if status.success() => {
-                    info!("Successfully unmounted filesystem")
-                }
-                Ok(status) => error!("Failed to unmount filesystem, exit code: {}", status),
-                Err(e) => error!("Failed to execute unmount command: {}", e),
-            }
-            std::process::exit(0);
-        })
-        .expect("Error setting signal handler");
+        unmount_on_signal(mp_copy.clone());
     }
 
     println!("✓ Mounting '{}' with ize", source_dir.display());
@@ -219,91 +355,442 @@ fn cmd_mount(
     if !read_only {
         println!("  Recording changes to Pijul");
     }
+    println!(
+        "  History: {}/{}{}",
+        source_dir.display(),
+        HISTORY_DIR,
+        if show_history_dir { "" } else { " (hidden)" }
+    );
     if foreground {
         println!("  Running in foreground (Ctrl+C to unmount)");
     }
 
-    if read_only {
-        // Read-only mode: mount passthrough directly, no opcode recording
-        passthrough
-            .mount()
-            .with_context(|| "Failed to mount filesystem")?;
-    } else {
-        // Read-write mode: set up opcode queue and recording
-        let queue = OpcodeQueue::new();
+    let inode_map = passthrough.inode_map();
+    let mut observing_fs = ObservingFS::new(passthrough);
+    let gate = observing_fs.gate();
 
-        // Get the inode map for path resolution
-        let inode_map = passthrough.inode_map();
+    // The recording backend is shared with the control socket, which switches
+    // channels on it so recording continues against the new channel. It
+    // records as the project's identity, checkpoints included.
+    let pijul = project
+        .open_recording_backend()
+        .with_context(|| "Failed to open Pijul repository for recording")?;
+    let backend = Arc::new(Mutex::new(OpcodeRecordingBackend::new(pijul)));
+    let queue = OpcodeQueue::new();
+    let running = Arc::new(AtomicBool::new(true));
+    let last_write = Arc::new(Mutex::new(Instant::now()));
 
-        // Create the opcode recorder
+    let control = ControlServer::bind(&project.control_socket())
+        .with_context(|| "Failed to open the mount's control socket")?;
+
+    if !read_only {
+        // Read-write mode: record every successful mutation
         let recorder = OpcodeRecorder::new(
-            inode_map,
+            inode_map.clone(),
             project.working_dir().to_path_buf(),
             queue.sender(),
-        );
-
-        // Wrap passthrough with observing filesystem
-        let mut observing_fs = ObservingFS::new(passthrough);
+        )
+        .with_ignore_filters(vec![Box::new(IzeHistoryFilter)]);
         observing_fs.add_observer(Arc::new(recorder));
 
-        // Flag for shutdown
-        let running = Arc::new(AtomicBool::new(true));
-
         // Spawn the opcode consumer thread
         let consumer_running = running.clone();
         let consumer_queue = queue.clone();
-        let pijul_dir = project.pijul_dir().to_path_buf();
-        let working_dir = project.working_dir().to_path_buf();
+        let consumer_backend = backend.clone();
+        let consumer_last_write = last_write.clone();
 
         let _consumer_handle = thread::spawn(move || {
             info!("Opcode consumer thread started");
 
-            // Open the Pijul backend for recording
-            let pijul = match PijulBackend::open(&pijul_dir, &working_dir) {
-                Ok(p) => p,
-                Err(e) => {
-                    error!("Failed to open PijulBackend: {}", e);
-                    return;
-                }
-            };
-            let backend = OpcodeRecordingBackend::new(pijul);
-
             while consumer_running.load(Ordering::SeqCst) {
-                // Try to pop with a short timeout by polling
-                if let Some(opcode) = consumer_queue.try_pop() {
-                    match backend.apply_opcode(&opcode) {
-                        Ok(Some(hash)) => {
-                            info!("Recorded opcode #{} -> {:?}", opcode.seq(), hash);
-                        }
-                        Ok(None) => {
-                            // No change needed (e.g., writing same content)
-                            info!("Opcode #{} resulted in no change", opcode.seq());
-                        }
-                        Err(e) => {
-                            warn!("Failed to apply opcode #{}: {}", opcode.seq(), e);
+                // Pop under the lock so a channel switch never sees an opcode
+                // that is neither queued nor applied
+                let applied = {
+                    let backend = consumer_backend.lock().unwrap();
+                    match consumer_queue.try_pop() {
+                        Some(opcode) => {
+                            apply_opcode_logged(&backend, &opcode);
+                            *consumer_last_write.lock().unwrap() = Instant::now();
+                            true
                         }
+                        None => false,
                     }
-                } else {
+                };
+                if !applied {
                     // Brief sleep to avoid busy-waiting
-                    thread::sleep(std::time::Duration::from_millis(10));
+                    thread::sleep(Duration::from_millis(10));
                 }
             }
 
             info!("Opcode consumer thread shutting down");
         });
+    }
+
+    // Mount with `.ize/` layered on top so history reads never reach
+    // the recorder
+    let mut session = HistoryFS::new(observing_fs, history)
+        .with_visible(show_history_dir)
+        .into_session()
+        .with_context(|| "Failed to mount filesystem")?;
+
+    let mount_control = Arc::new(MountControl {
+        read_only,
+        backend,
+        queue,
+        gate,
+        notifier: session.notifier(),
+        inode_map,
+    });
+    let handler = mount_control.clone();
+    let _control = control.spawn(move |request| handler.handle(request));
+
+    let checkpoint = project.checkpoint.clone();
+    if !read_only && checkpoint.is_timed() {
+        let auto_running = running.clone();
+        let auto_control = mount_control.clone();
+        let settings = checkpoint.clone();
+        thread::spawn(move || {
+            let mut last_checkpoint = Instant::now();
+            while auto_running.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_secs(1));
+                let written = *last_write.lock().unwrap();
+                if written <= last_checkpoint {
+                    continue;
+                }
+                if let Some(reason) = settings.due(written.elapsed(), last_checkpoint.elapsed()) {
+                    auto_control.checkpoint_now(reason, settings.policy);
+                    last_checkpoint = Instant::now();
+                }
+            }
+        });
+    }
+
+    // Blocks until unmounted
+    session
+        .run()
+        .with_context(|| "Failed to mount filesystem")?;
 
-        // Mount the observing filesystem (this blocks until unmounted)
-        observing_fs
-            .mount()
-            .with_context(|| "Failed to mount filesystem")?;
+    // Signal consumer to stop
+    running.store(false, Ordering::SeqCst);
 
-        // Signal consumer to stop
-        running.store(false, Ordering::SeqCst);
+    if !read_only && checkpoint.on_unmount {
+        mount_control.checkpoint_now("unmount", checkpoint.policy);
     }
 
     Ok(())
 }
 
+/// Apply one opcode to the recording backend, logging the outcome
+fn apply_opcode_logged(backend: &OpcodeRecordingBackend, opcode: &Opcode) {
+    match backend.apply_opcode(opcode) {
+        Ok(Some(hash)) => {
+            info!("Recorded opcode #{} -> {:?}", opcode.seq(), hash);
+        }
+        Ok(None) => {
+            // No change needed (e.g., writing same content)
+            info!("Opcode #{} resulted in no change", opcode.seq());
+        }
+        Err(e) => {
+            warn!("Failed to apply opcode #{}: {}", opcode.seq(), e);
+        }
+    }
+}
+
+/// What the control socket needs to rewrite the working copy of a live mount
+struct MountControl {
+    /// Refuse every request: they all change the working copy or history
+    read_only: bool,
+    backend: Arc<Mutex<OpcodeRecordingBackend>>,
+    queue: OpcodeQueue,
+    gate: MutationGate,
+    notifier: Notifier,
+    inode_map: InodeMap,
+}
+
+impl MountControl {
+    fn handle(&self, request: ControlRequest) -> ControlResponse {
+        if self.read_only {
+            return Err(
+                "The project is mounted read-only\nUnmount it, or remount without --read-only"
+                    .to_string(),
+            );
+        }
+        match request {
+            ControlRequest::SwitchChannel { name, policy } => self.run_paused(|pijul| {
+                pijul
+                    .switch_channel_with(&name, policy)
+                    .map(|stash| describe_switch(&name, stash))
+                    .map_err(|e| describe_switch_error(&name, &e))
+            }),
+            ControlRequest::MergeChannel { from } => self.run_paused(|pijul| {
+                let into = pijul.current_channel().to_string();
+                pijul
+                    .merge_channel(&from)
+                    .map(|report| describe_merge(&from, &into, &report))
+                    .map_err(|e| describe_apply_error(&format!("channel '{}'", from), &e))
+            }),
+            ControlRequest::Undo { target } => self.run_paused(|pijul| {
+                pijul
+                    .undo(target)
+                    .map(|report| describe_undo(&report))
+                    .map_err(|e| describe_undo_error(&e))
+            }),
+            ControlRequest::Checkpoint { message, policy } => self.quiesced(|pijul| {
+                pijul
+                    .checkpoint(&message, policy)
+                    .map(|report| describe_checkpoint(report.as_ref()))
+                    .map_err(|e| describe_checkpoint_error(&e))
+            }),
+            ControlRequest::Gc { policy } => self.quiesced(|pijul| {
+                pijul
+                    .gc(&policy, false)
+                    .map(|report| describe_gc(&report, false))
+                    .map_err(|e| describe_gc_error(&e))
+            }),
+            ControlRequest::ApplyChange { hash } => {
+                let parsed = PijulQuery::parse_hash(&hash).map_err(|e| e.to_string())?;
+                self.run_paused(|pijul| {
+                    pijul
+                        .apply_change(&parsed)
+                        .map(|report| describe_apply(&hash, &report))
+                        .map_err(|e| describe_apply_error(&hash, &e))
+                })
+            }
+        }
+    }
+
+    /// Run `op` on the recording backend with writes through the mount held
+    /// back, after recording whatever they already queued
+    fn quiesced<T>(&self, op: impl FnOnce(&mut PijulBackend) -> T) -> T {
+        let _paused = self.gate.pause();
+        let mut backend = self.backend.lock().unwrap();
+        while let Some(opcode) = self.queue.try_pop() {
+            apply_opcode_logged(&backend, &opcode);
+        }
+        op(backend.pijul_mut())
+    }
+
+    /// Like [`quiesced`](Self::quiesced), for operations that rewrite the
+    /// working copy behind the kernel's back
+    fn run_paused<T>(&self, op: impl FnOnce(&mut PijulBackend) -> T) -> T {
+        let result = self.quiesced(op);
+
+        // Only once writes may resume: the kernel can wait on requests in flight
+        let invalidated = invalidate_kernel_cache(&self.notifier, &self.inode_map);
+        info!("Invalidated {} cached inode(s)", invalidated);
+
+        result
+    }
+
+    /// Checkpoint on one of the `[checkpoint]` triggers, logging the outcome
+    fn checkpoint_now(&self, reason: &str, policy: CheckpointPolicy) {
+        let message = format!("Automatic checkpoint ({})", reason);
+        match self.quiesced(|pijul| pijul.checkpoint(&message, policy)) {
+            Ok(Some(report)) => info!(
+                "Checkpoint on {}: squashed {} change(s)",
+                reason, report.squashed
+            ),
+            Ok(None) => {}
+            Err(e) => warn!("Checkpoint on {} failed: {}", reason, e),
+        }
+    }
+}
+
+/// Success message for a channel switch
+fn describe_switch(name: &str, stash: Option<String>) -> String {
+    match stash {
+        Some(stash) => format!(
+            "Switched to channel '{}' (unrecorded changes stashed on '{}')",
+            name, stash
+        ),
+        None => format!("Switched to channel '{}'", name),
+    }
+}
+
+/// Failure message for a channel switch, with a hint for unrecorded changes
+fn describe_switch_error(name: &str, err: &PijulError) -> String {
+    match err {
+        PijulError::UnrecordedChanges(paths) => format!(
+            "Not switching to channel '{}': unrecorded changes to {}\n\
+             Use --stash to keep them on a separate channel or --force to discard them",
+            name,
+            paths.join(", ")
+        ),
+        e => format!("Failed to switch to channel '{}': {}", name, e),
+    }
+}
+
+/// Success message for `ize channel merge`
+fn describe_merge(from: &str, into: &str, report: &ApplyReport) -> String {
+    let summary = format!(
+        "Merged {} change(s) from '{}' into '{}'",
+        report.applied.len(),
+        from,
+        into
+    );
+    with_conflicts(summary, report)
+}
+
+/// Success message for `ize apply`
+fn describe_apply(hash: &str, report: &ApplyReport) -> String {
+    let summary = match report.applied.len() {
+        0 => format!("{} is already applied", hash),
+        1 => format!("Applied {}", hash),
+        n => format!("Applied {} with {} change(s) it depends on", hash, n - 1),
+    };
+    with_conflicts(summary, report)
+}
+
+fn with_conflicts(summary: String, report: &ApplyReport) -> String {
+    if report.conflicts.is_empty() {
+        return summary;
+    }
+    let mut message = format!(
+        "{}\n{} conflict(s), marked in the working files (see `ize conflicts`):",
+        summary,
+        report.conflicts.len()
+    );
+    for conflict in &report.conflicts {
+        message.push_str(&format!("\n  {}", conflict));
+    }
+    message
+}
+
+/// Failure message for a merge or apply of `what`
+fn describe_apply_error(what: &str, err: &PijulError) -> String {
+    match err {
+        PijulError::UnrecordedChanges(paths) => format!(
+            "Not applying {}: unrecorded changes to {}",
+            what,
+            paths.join(", ")
+        ),
+        PijulError::Conflicted(paths) => format!(
+            "Not applying {}: unresolved conflicts in {}\n\
+             Edit the files to resolve them first; `ize conflicts` lists them",
+            what,
+            paths.join(", ")
+        ),
+        e => format!("Failed to apply {}: {}", what, e),
+    }
+}
+
+/// Mount a read-only view of a project's history
+///
+/// `spec` is resolved against the project's current channel; the view is
+/// built in memory from the pristine, so the working copy and any live mount
+/// are left alone.
+fn cmd_mount_at(
+    mount_point: &PathBuf,
+    spec: &str,
+    project_dir: Option<PathBuf>,
+    foreground: bool,
+    unmount_on_exit: bool,
+) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(project_dir)?;
+    let project = get_project(&manager, &source_dir)?;
+
+    let mount_point = std::fs::canonicalize(mount_point)
+        .with_context(|| format!("Mount point does not exist: {:?}", mount_point))?;
+    if !mount_point.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Mount point is not a directory: {:?}",
+            mount_point
+        ));
+    }
+    if mount_point == source_dir {
+        return Err(anyhow::anyhow!(
+            "Cannot mount history over the tracked directory itself: {:?}\n\
+             Choose an empty directory elsewhere",
+            mount_point
+        ));
+    }
+    if is_fuse_mounted(&mount_point)? {
+        return Err(anyhow::anyhow!(
+            "Mount point is already in use: {:?}",
+            mount_point
+        ));
+    }
+
+    let query = PijulQuery::new(&project.pijul);
+    let change = query
+        .resolve_at(spec)
+        .with_context(|| format!("Failed to resolve {:?} to a change", spec))?;
+    info!("Resolved {:?} to {:?}", spec, change);
+
+    let snapshot = project
+        .pijul
+        .snapshot_at(change.as_ref())
+        .with_context(|| "Failed to reconstruct history")?;
+
+    let info = change
+        .map(|hash| query.get_change_info(&hash))
+        .transpose()?;
+    let mtime = info
+        .as_ref()
+        .map(|info| SystemTime::from(info.timestamp))
+        .unwrap_or(UNIX_EPOCH);
+
+    let mut fs = SnapshotFS::new(mount_point.clone()).with_mtime(mtime);
+    let file_count = snapshot.list_files().len();
+    for dir in snapshot.list_dirs() {
+        fs.add_dir(&dir);
+    }
+    for (path, content) in snapshot.into_files() {
+        fs.add_file(&path, content);
+    }
+
+    if unmount_on_exit || foreground {
+        unmount_on_signal(mount_point.clone());
+    }
+
+    println!(
+        "✓ Mounting '{}' read-only at '{}'",
+        source_dir.display(),
+        mount_point.display()
+    );
+    println!("  Channel: {}", project.current_channel());
+    match &info {
+        Some(info) => println!(
+            "  Change: {} {} ({})",
+            info.hash_short(),
+            info.message,
+            info.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+        None => println!("  Change: (none, before the first recorded change)"),
+    }
+    println!("  Files: {}", file_count);
+    if foreground {
+        println!("  Running in foreground (Ctrl+C to unmount)");
+    }
+
+    fs.mount().with_context(|| "Failed to mount filesystem")?;
+
+    Ok(())
+}
+
+/// Install a SIGINT/SIGTERM handler that unmounts `mount_point` and exits
+fn unmount_on_signal(mount_point: PathBuf) {
+    info!("Will unmount filesystem on exit");
+
+    ctrlc::set_handler(move || {
+        info!("Received interrupt signal, unmounting filesystem");
+        match Command::new("fusermount")
+            .arg("-u")
+            .arg(&mount_point)
+            .status()
+        {
+            Ok(status) if status.success() => {
+                info!("Successfully unmounted filesystem")
+            }
+            Ok(status) => error!("Failed to unmount filesystem, exit code: {}", status),
+            Err(e) => error!("Failed to execute unmount command: {}", e),
+        }
+        std::process::exit(0);
+    })
+    .expect("Error setting signal handler");
+}
+
 /// Unmount a tracked directory
 fn cmd_unmount(directory: &PathBuf) -> Result<()> {
     let source_dir = std::fs::canonicalize(directory)
@@ -337,7 +824,7 @@ fn cmd_unmount(directory: &PathBuf) -> Result<()> {
 }
 
 /// Show status of a tracked directory
-fn cmd_status(directory: Option<&Path>, verbose: bool) -> Result<()> {
+fn cmd_status(directory: Option<&Path>, verbose: bool, stats: bool) -> Result<()> {
     let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
 
     let source_dir = if let Some(dir) = directory {
@@ -374,6 +861,40 @@ fn cmd_status(directory: Option<&Path>, verbose: bool) -> Result<()> {
         println!("Pijul directory: {}", project.pijul_dir().display());
     }
 
+    if stats {
+        let stats = project.pijul.storage_stats()?;
+        println!();
+        println!("Storage:");
+        println!(
+            "  Changes: {} file(s), {}",
+            stats.change_files,
+            format_bytes(stats.change_bytes)
+        );
+        println!(
+            "  Blocks and chunks: {}, {}",
+            stats.chunks,
+            format_bytes(stats.chunk_bytes)
+        );
+        println!(
+            "  Binary files: {} ({}), recorded as block maps",
+            stats.binary_files,
+            format_bytes(stats.binary_bytes)
+        );
+        println!(
+            "  Large files: {} ({}), recorded as pointers",
+            stats.large_files,
+            format_bytes(stats.large_bytes)
+        );
+        let deltas = &stats.deltas;
+        println!(
+            "  Block-level writes: {}, {} stored for {} of files ({} saved)",
+            deltas.writes,
+            format_bytes(deltas.stored_bytes),
+            format_bytes(deltas.file_bytes),
+            format_bytes(deltas.saved_bytes())
+        );
+    }
+
     Ok(())
 }
 
@@ -465,15 +986,33 @@ fn cmd_channel(action: ChannelAction) -> Result<()> {
                 }
             }
         }
-        ChannelAction::Switch { name, directory } => {
+        ChannelAction::Switch {
+            name,
+            stash,
+            force,
+            directory,
+        } => {
             let source_dir = get_source_dir(directory)?;
             let mut project = get_project(&manager, &source_dir)?;
 
-            project
-                .switch_channel(&name)
-                .with_context(|| format!("Failed to switch to channel '{}'", name))?;
+            let policy = if stash {
+                DirtyPolicy::Stash
+            } else if force {
+                DirtyPolicy::Discard
+            } else {
+                DirtyPolicy::Refuse
+            };
 
-            println!("✓ Switched to channel '{}'", name);
+            let request = ControlRequest::SwitchChannel {
+                name: name.clone(),
+                policy,
+            };
+            run_on_working_copy(&mut project, &source_dir, request, |pijul| {
+                pijul
+                    .switch_channel_with(&name, policy)
+                    .map(|stash| describe_switch(&name, stash))
+                    .map_err(|e| describe_switch_error(&name, &e))
+            })?;
         }
         ChannelAction::Fork {
             name,
@@ -494,11 +1033,933 @@ fn cmd_channel(action: ChannelAction) -> Result<()> {
 
             println!("✓ Forked '{}' to '{}'", from_channel, name);
         }
+        ChannelAction::Merge { from, directory } => {
+            let source_dir = get_source_dir(directory)?;
+            let mut project = get_project(&manager, &source_dir)?;
+
+            let request = ControlRequest::MergeChannel { from: from.clone() };
+            run_on_working_copy(&mut project, &source_dir, request, |pijul| {
+                let into = pijul.current_channel().to_string();
+                pijul
+                    .merge_channel(&from)
+                    .map(|report| describe_merge(&from, &into, &report))
+                    .map_err(|e| describe_apply_error(&format!("channel '{}'", from), &e))
+            })?;
+        }
     }
 
     Ok(())
 }
 
+/// List the changes touching a file, newest first
+fn cmd_history(
+    file_path: &Path,
+    limit: Option<usize>,
+    verbose: bool,
+    directory: Option<PathBuf>,
+) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let project = get_project(&manager, &source_dir)?;
+
+    let path = file_path.strip_prefix(&source_dir).unwrap_or(file_path);
+    let path = path.to_string_lossy();
+    let history = PijulQuery::new(&project.pijul)
+        .file_history(&path)
+        .with_context(|| format!("Failed to read history of {}", path))?;
+    if history.is_empty() {
+        println!("No recorded changes to {}", path);
+        return Ok(());
+    }
+
+    for change in history.iter().rev().take(limit.unwrap_or(usize::MAX)) {
+        let mark = match change.signature {
+            SignatureStatus::Valid { .. } => " ✓",
+            SignatureStatus::Invalid => " ✗",
+            _ => "",
+        };
+        println!(
+            "{}  {:<12}  {}{}  {}",
+            change.hash_short(),
+            change.timestamp_relative(),
+            change.primary_author(),
+            mark,
+            change.message
+        );
+        if verbose {
+            println!("    Change:    {}", change.hash_full());
+            println!("    Date:      {}", change.timestamp.to_rfc3339());
+            println!("    Signature: {}", change.signature);
+            if let Some(metadata) = &change.metadata {
+                if let (Some(uid), Some(pid)) = (metadata.uid, metadata.pid) {
+                    println!("    Process:   pid {} (uid {})", pid, uid);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Build the filter for `ize log` from its options
+fn log_filter(
+    since: Option<&str>,
+    until: Option<&str>,
+    path: Option<&str>,
+    author: Option<&str>,
+    channel: Option<String>,
+    limit: Option<usize>,
+) -> Result<LogFilter> {
+    let time = |spec: &str| {
+        parse_timestamp(spec).ok_or_else(|| anyhow::anyhow!("Not a point in time: '{}'", spec))
+    };
+    let mut filter = LogFilter::new();
+    if let Some(since) = since {
+        filter = filter.with_since(time(since)?);
+    }
+    if let Some(until) = until {
+        filter = filter.with_until(time(until)?);
+    }
+    if let Some(glob) = path {
+        filter = filter.with_path(glob)?;
+    }
+    if let Some(author) = author {
+        filter = filter.with_author(author);
+    }
+    if let Some(channel) = channel {
+        filter = filter.with_channel(channel);
+    }
+    if let Some(limit) = limit {
+        filter = filter.with_limit(limit);
+    }
+    Ok(filter)
+}
+
+/// List a channel's changes as a table, a JSON array or JSON lines
+fn cmd_log(filter: &LogFilter, format: &str, directory: Option<PathBuf>) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let project = get_project(&manager, &source_dir)?;
+
+    let changes = PijulQuery::new(&project.pijul)
+        .log(filter)
+        .with_context(|| "Failed to read the change log")?;
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&changes)?),
+        "jsonl" => {
+            for change in &changes {
+                println!("{}", serde_json::to_string(change)?);
+            }
+        }
+        _ => {
+            if changes.is_empty() {
+                println!("No matching changes");
+            }
+            for change in &changes {
+                println!(
+                    "{}  {}  {:<16}  {:>3} file(s)  {}",
+                    change.hash_short(),
+                    change.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                    change.primary_author(),
+                    change.files_changed,
+                    change.message
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print a change and its hunks
+fn cmd_show(hash: &str, directory: Option<PathBuf>) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let project = get_project(&manager, &source_dir)?;
+
+    let query = PijulQuery::new(&project.pijul);
+    let hash = query
+        .find_change_anywhere(hash)
+        .with_context(|| format!("No change matching '{}'", hash))?;
+    let details = query
+        .get_change_details(&hash)
+        .with_context(|| format!("Failed to read change {}", PijulQuery::format_hash(&hash)))?;
+
+    let info = &details.info;
+    println!("Change:    {}", info.hash_full());
+    println!("Author:    {}", info.primary_author());
+    println!("Date:      {}", info.timestamp.to_rfc3339());
+    println!("Signature: {}", info.signature);
+    if let Some(metadata) = &info.metadata {
+        println!(
+            "Opcode:    #{} {} at {}",
+            metadata.seq,
+            metadata.kind,
+            metadata.time.to_rfc3339()
+        );
+        if let (Some(uid), Some(pid)) = (metadata.uid, metadata.pid) {
+            println!("Process:   pid {} (uid {})", pid, uid);
+        }
+    }
+    for dependency in &details.dependencies {
+        println!("Depends:   {}", PijulQuery::format_hash(dependency));
+    }
+    println!();
+    println!("    {}", info.message);
+
+    for file in &details.files {
+        println!();
+        println!("{}", file.path);
+        for hunk in &file.hunks {
+            match hunk {
+                HunkDetail::FileAdded { lines } => {
+                    println!("  created");
+                    print_lines("+", lines);
+                }
+                HunkDetail::FileDeleted { lines } => {
+                    println!("  deleted");
+                    print_lines("-", lines);
+                }
+                HunkDetail::FileUndeleted => println!("  undeleted"),
+                HunkDetail::Renamed { from } => match from {
+                    Some(from) => println!("  renamed from {}", from),
+                    None => println!("  moved"),
+                },
+                HunkDetail::Edit {
+                    line,
+                    removed,
+                    added,
+                } => {
+                    println!("  @@ line {} @@", line);
+                    print_lines("-", removed);
+                    print_lines("+", added);
+                }
+                HunkDetail::BinaryEdit => println!("  binary content changed"),
+                HunkDetail::Resolution { line, kind } => match line {
+                    Some(line) => println!("  {} at line {}", kind, line),
+                    None => println!("  {}", kind),
+                },
+            }
+        }
+    }
+
+    let (added, removed) = details.line_counts();
+    println!();
+    println!(
+        "{} file(s) changed, {} line(s) added, {} removed",
+        details.files.len(),
+        added,
+        removed
+    );
+    Ok(())
+}
+
+fn print_lines(sign: &str, lines: &[String]) {
+    for line in lines {
+        println!("  {}{}", sign, line);
+    }
+}
+
+/// Credit each line of a file to the change that wrote it
+fn cmd_blame(file_path: &Path, directory: Option<PathBuf>) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let project = get_project(&manager, &source_dir)?;
+
+    let path = file_path.strip_prefix(&source_dir).unwrap_or(file_path);
+    let path = path.to_string_lossy();
+    let lines = PijulQuery::new(&project.pijul)
+        .blame(&path)
+        .with_context(|| format!("Failed to blame {}", path))?;
+
+    let width = lines.len().to_string().len();
+    for line in &lines {
+        let Some(change) = &line.change else {
+            // Conflict markers
+            println!("{:<65} {:>width$}| {}", "", line.number, line.content);
+            continue;
+        };
+        let time = line
+            .timestamp()
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+            .unwrap_or_default();
+        let process = line
+            .process()
+            .map(|(pid, uid)| format!("pid {} uid {}", pid, uid))
+            .unwrap_or_default();
+        println!(
+            "{} {} {:<20} {:<12.12} {:>width$}| {}",
+            change.hash_short(),
+            time,
+            process,
+            change.primary_author(),
+            line.number,
+            line.content,
+        );
+    }
+    Ok(())
+}
+
+/// Search the text every change inserted
+fn cmd_grep(
+    pattern: &str,
+    since: Option<&str>,
+    path: Option<&str>,
+    channel: Option<String>,
+    directory: Option<PathBuf>,
+) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let project = get_project(&manager, &source_dir)?;
+
+    let mut search = GrepQuery::new(pattern)?;
+    if let Some(since) = since {
+        search = search.with_since(
+            parse_timestamp(since)
+                .ok_or_else(|| anyhow::anyhow!("Not a point in time: '{}'", since))?,
+        );
+    }
+    if let Some(glob) = path {
+        search = search.with_path(glob)?;
+    }
+    if let Some(channel) = channel {
+        search = search.with_channel(channel);
+    }
+
+    let found = PijulQuery::new(&project.pijul)
+        .grep(&search)
+        .with_context(|| "Failed to search history")?;
+    for found in &found {
+        let hash = PijulQuery::format_hash(&found.change);
+        println!(
+            "{} {}:{}: {}",
+            &hash[..hash.len().min(7)],
+            found.path,
+            found.line_number,
+            found.line
+        );
+    }
+    if found.is_empty() {
+        println!("No matches for '{}'", pattern);
+    }
+    Ok(())
+}
+
+/// Put a file back the way it was at some point in history
+fn cmd_restore(
+    file_path: &Path,
+    version: &str,
+    force: bool,
+    directory: Option<PathBuf>,
+) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let project = get_project(&manager, &source_dir)?;
+
+    let path = file_path.strip_prefix(&source_dir).unwrap_or(file_path);
+    let path = path.to_string_lossy().into_owned();
+    let content = match PijulQuery::new(&project.pijul).file_content_at(&path, &version.into()) {
+        Err(PijulError::FileNotFound(_)) => {
+            return Err(anyhow::anyhow!("{} did not exist at {}", path, version))
+        }
+        result => result.with_context(|| format!("Failed to read {} at {:?}", path, version))?,
+    };
+
+    if !force {
+        print!("Overwrite {} with its content at {}? [y/N] ", path, version);
+        use std::io::Write;
+        std::io::stdout().flush()?;
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    if is_fuse_mounted(&source_dir)? {
+        // Written through the mount, which records it like any other write
+        fs::write(source_dir.join(&path), &content)
+            .with_context(|| format!("Failed to write {}", path))?;
+    } else {
+        let working = project.working_dir().join(&path);
+        if let Some(parent) = working.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tracked = project.pijul.file_exists(&path)?;
+        fs::write(&working, &content).with_context(|| format!("Failed to write {}", path))?;
+        let message = format!("restore {} from {}", path, version);
+        if tracked {
+            project
+                .pijul
+                .record_file_replace(&path, &content, message)?;
+        } else {
+            project
+                .pijul
+                .record_file_create(&path, 0o644, &content, message)?;
+        }
+    }
+
+    println!("✓ Restored {} as of {}", path, version);
+    Ok(())
+}
+
+/// Create, list, delete or show tags
+fn cmd_tag(action: TagAction) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+
+    match action {
+        TagAction::Create {
+            name,
+            message,
+            directory,
+        } => {
+            let source_dir = get_source_dir(directory)?;
+            let project = get_project(&manager, &source_dir)?;
+            let tag = project
+                .pijul
+                .create_tag(&name, message.as_deref())
+                .with_context(|| format!("Failed to create tag '{}'", name))?;
+            println!(
+                "✓ Tagged {} on channel '{}' as '{}'",
+                PijulQuery::format_hash(&tag.change),
+                tag.channel,
+                tag.name
+            );
+        }
+        TagAction::List { directory } => {
+            let source_dir = get_source_dir(directory)?;
+            let project = get_project(&manager, &source_dir)?;
+            let tags = PijulQuery::new(&project.pijul).list_tags()?;
+            if tags.is_empty() {
+                println!("No tags. Create one with: ize tag create <NAME>");
+                return Ok(());
+            }
+
+            let width = tags.iter().map(|tag| tag.name.len()).max().unwrap_or(0);
+            for tag in tags {
+                let hash = PijulQuery::format_hash(&tag.change);
+                println!(
+                    "{:<width$}  {}  {:<12}  {}",
+                    tag.name,
+                    &hash[..hash.len().min(7)],
+                    tag.channel,
+                    tag.message.as_deref().unwrap_or(""),
+                    width = width
+                );
+            }
+        }
+        TagAction::Delete { name, directory } => {
+            let source_dir = get_source_dir(directory)?;
+            let project = get_project(&manager, &source_dir)?;
+            project
+                .pijul
+                .delete_tag(&name)
+                .with_context(|| format!("Failed to delete tag '{}'", name))?;
+            println!("✓ Deleted tag '{}'", name);
+        }
+        TagAction::Show { name, directory } => {
+            let source_dir = get_source_dir(directory)?;
+            let project = get_project(&manager, &source_dir)?;
+            let query = PijulQuery::new(&project.pijul);
+            let tag = query
+                .get_tag(&name)?
+                .ok_or_else(|| PijulError::TagNotFound(name.clone()))?;
+
+            println!("Tag:     {}", tag.name);
+            println!("Channel: {} (state {})", tag.channel, tag.position);
+            println!("Created: {}", tag.created.to_rfc3339());
+            if let Some(message) = &tag.message {
+                println!("Message: {}", message);
+            }
+            let info = query.get_change_info(&tag.change)?;
+            println!(
+                "Change:  {} {} ({})",
+                info.hash_full(),
+                info.message,
+                info.timestamp.to_rfc3339()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Show or change the identity changes are recorded as
+fn cmd_identity(action: IdentityAction) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let global_path = IdentityConfig::global_path().ok_or(IdentityError::NoConfigDir)?;
+
+    match action {
+        IdentityAction::Show { directory } => {
+            let source_dir = get_source_dir(directory)?;
+            let (identity, overridden) = match manager.find_by_source_dir(&source_dir)? {
+                Some(project) => (
+                    project.pijul.identity().clone(),
+                    !project.identity.is_empty(),
+                ),
+                None => (IdentityConfig::load_global()?.resolve()?, false),
+            };
+
+            println!("Identity: {}", identity);
+            match identity.public_key() {
+                Some(key) => println!(
+                    "Key:      {} (signing {})",
+                    key,
+                    if identity.signs() { "on" } else { "off" }
+                ),
+                None => println!("Key:      none"),
+            }
+            println!(
+                "From:     {}{}",
+                global_path.display(),
+                if overridden {
+                    " with the project's [identity] overrides"
+                } else {
+                    ""
+                }
+            );
+        }
+        IdentityAction::Set {
+            name,
+            email,
+            key,
+            generate_key,
+            sign,
+            no_sign,
+            project,
+            directory,
+        } => {
+            let mut target = if project {
+                Some(get_project(&manager, &get_source_dir(directory)?)?)
+            } else {
+                None
+            };
+            let mut config = match &target {
+                Some(project) => project.identity.clone(),
+                None => IdentityConfig::load_global()?,
+            };
+
+            config.name = name.or(config.name);
+            config.email = email.or(config.email);
+            config.key = key.or(config.key);
+            if generate_key {
+                let key_dir = match &target {
+                    Some(project) => project.meta_dir.clone(),
+                    None => global_path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+                };
+                let key_path = key_dir.join(identity::KEY_FILE);
+                identity::generate_key(&key_path).with_context(|| "Failed to generate a key")?;
+                println!("✓ Generated key {}", key_path.display());
+                config.key = Some(key_path);
+            }
+            if sign {
+                config.sign = Some(true);
+            } else if no_sign {
+                config.sign = Some(false);
+            }
+
+            match target.as_mut() {
+                Some(project) => {
+                    project.set_identity(config)?;
+                    println!("✓ Project now records as {}", project.pijul.identity());
+                }
+                None => {
+                    let identity = config.resolve()?;
+                    config.save(&global_path)?;
+                    println!("✓ Now recording as {}", identity);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Show or rotate a project's encryption key
+fn cmd_key(action: KeyAction) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+
+    match action {
+        KeyAction::Show { directory } => {
+            let source_dir = get_source_dir(directory)?;
+            let project = get_project(&manager, &source_dir)?;
+            match project.pijul.encryption() {
+                Some(settings) => println!("Encrypted: {}", describe_key(settings)),
+                None => println!("Not encrypted"),
+            }
+        }
+        KeyAction::Rotate {
+            keyfile,
+            passphrase,
+            directory,
+        } => {
+            let source_dir = get_source_dir(directory)?;
+            // The mount holds the current key and would seal new changes
+            // with it
+            if is_fuse_mounted(&source_dir)? {
+                return Err(anyhow::anyhow!(
+                    "{} is mounted; unmount it before rotating its key\n\
+                     Unmount with: ize unmount {:?}",
+                    source_dir.display(),
+                    source_dir
+                ));
+            }
+            let mut project = get_project(&manager, &source_dir)?;
+            let Some(settings) = project.pijul.encryption() else {
+                return Err(anyhow::anyhow!("{} is not encrypted", source_dir.display()));
+            };
+
+            let source = if passphrase {
+                KeySource::Passphrase(new_passphrase(NEW_PASSPHRASE_VAR)?)
+            } else {
+                let keyfile = keyfile
+                    .or_else(|| settings.keyfile.clone())
+                    .or_else(|| default_keyfile(project.uuid()))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("No config directory on this platform; give --keyfile")
+                    })?;
+                KeySource::KeyFile(keyfile)
+            };
+            let settings = project
+                .pijul
+                .rotate_key(&source)
+                .with_context(|| "Failed to rotate the key")?;
+            println!("✓ History sealed again: {}", describe_key(settings));
+        }
+    }
+    Ok(())
+}
+
+/// Which key a project is sealed with, and where it is read from
+fn describe_key(settings: &EncryptionSettings) -> String {
+    match &settings.keyfile {
+        Some(keyfile) => format!("key {} in {}", settings.key_id, keyfile.display()),
+        None => format!(
+            "key {} from a passphrase, read from ${}",
+            settings.key_id, PASSPHRASE_VAR
+        ),
+    }
+}
+
+/// A new passphrase: from `var` if it is set, else asked for twice
+fn new_passphrase(var: &str) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(var) {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password("New passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(anyhow::anyhow!("The passphrase can't be empty"));
+    }
+    if rpassword::prompt_password("Repeat passphrase: ")? != passphrase {
+        return Err(anyhow::anyhow!("The passphrases don't match"));
+    }
+    Ok(passphrase)
+}
+
+/// Apply a change from any channel to the current one
+fn cmd_apply(hash: &str, directory: Option<PathBuf>) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let mut project = get_project(&manager, &source_dir)?;
+
+    let hash = PijulQuery::new(&project.pijul)
+        .find_change_anywhere(hash)
+        .with_context(|| format!("No change matching '{}'", hash))?;
+
+    let full = PijulQuery::format_hash(&hash);
+    let request = ControlRequest::ApplyChange { hash: full.clone() };
+    run_on_working_copy(&mut project, &source_dir, request, |pijul| {
+        pijul
+            .apply_change(&hash)
+            .map(|report| describe_apply(&full, &report))
+            .map_err(|e| describe_apply_error(&full, &e))
+    })
+}
+
+/// Squash the changes since the last checkpoint
+fn cmd_checkpoint(
+    message: &str,
+    archive: bool,
+    drop: bool,
+    directory: Option<PathBuf>,
+) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let mut project = get_project(&manager, &source_dir)?;
+
+    let policy = if archive {
+        CheckpointPolicy::Archive
+    } else if drop {
+        CheckpointPolicy::Drop
+    } else {
+        project.checkpoint.policy
+    };
+
+    let request = ControlRequest::Checkpoint {
+        message: message.to_string(),
+        policy,
+    };
+    run_on_working_copy(&mut project, &source_dir, request, |pijul| {
+        pijul
+            .checkpoint(message, policy)
+            .map(|report| describe_checkpoint(report.as_ref()))
+            .map_err(|e| describe_checkpoint_error(&e))
+    })
+}
+
+/// Success message for `ize checkpoint`
+fn describe_checkpoint(report: Option<&CheckpointReport>) -> String {
+    let Some(report) = report else {
+        return "Nothing recorded since the last checkpoint".to_string();
+    };
+    let summary = format!(
+        "Checkpoint {} replaces {} change(s)",
+        PijulQuery::format_hash(&report.hash),
+        report.squashed
+    );
+    match &report.archived_on {
+        Some(archive) => format!("{} (originals kept on '{}')", summary, archive),
+        None => summary,
+    }
+}
+
+/// Failure message for `ize checkpoint`
+fn describe_checkpoint_error(err: &PijulError) -> String {
+    match err {
+        PijulError::UnrecordedChanges(paths) => format!(
+            "Not checkpointing: unrecorded changes to {}",
+            paths.join(", ")
+        ),
+        e => format!("Failed to checkpoint: {}", e),
+    }
+}
+
+/// Compact old history per the project's retention policy
+fn cmd_gc(dry_run: bool, directory: Option<PathBuf>) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let mut project = get_project(&manager, &source_dir)?;
+    let policy = project.retention.clone();
+
+    if dry_run {
+        // Nothing is written, so the mount needn't be asked
+        let report = project
+            .pijul
+            .gc(&policy, true)
+            .map_err(|e| anyhow::anyhow!(describe_gc_error(&e)))?;
+        println!("{}", describe_gc(&report, true));
+        return Ok(());
+    }
+
+    let request = ControlRequest::Gc {
+        policy: policy.clone(),
+    };
+    run_on_working_copy(&mut project, &source_dir, request, |pijul| {
+        pijul
+            .gc(&policy, false)
+            .map(|report| describe_gc(&report, false))
+            .map_err(|e| describe_gc_error(&e))
+    })
+}
+
+/// Outcome of `ize gc`, or what it would do with `--dry-run`
+fn describe_gc(report: &GcReport, dry_run: bool) -> String {
+    let (compacted, removed) = if dry_run {
+        ("Would compact", "would remove")
+    } else {
+        ("Compacted", "removed")
+    };
+    let mut summary = if report.compacted == 0 {
+        "No history old enough to compact".to_string()
+    } else {
+        format!(
+            "{} {} change(s) into {} ({} later change(s) recorded again)",
+            compacted, report.compacted, report.compacted_into, report.rerecorded
+        )
+    };
+    summary.push_str(&format!(
+        "; {} {} unused change file(s), {}",
+        removed,
+        report.removed_files,
+        format_bytes(report.reclaimed_bytes)
+    ));
+    if !report.dropped_signatures.is_empty() {
+        summary.push_str(&format!(
+            "; {} signed change(s) rewritten without their signature",
+            report.dropped_signatures.len()
+        ));
+    }
+    summary
+}
+
+/// Failure message for `ize gc`
+fn describe_gc_error(err: &PijulError) -> String {
+    match err {
+        PijulError::UnrecordedChanges(paths) => {
+            format!("Not compacting: unrecorded changes to {}", paths.join(", "))
+        }
+        e => format!("Failed to collect garbage: {}", e),
+    }
+}
+
+/// A byte count in the largest unit that keeps it above 1
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Undo recent changes on the current channel
+fn cmd_undo(target: Option<&str>, since: Option<&str>, directory: Option<PathBuf>) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let mut project = get_project(&manager, &source_dir)?;
+
+    let target = match (target, since) {
+        (_, Some(since)) => UndoTarget::Since(
+            parse_timestamp(since)
+                .ok_or_else(|| anyhow::anyhow!("Not a point in time: '{}'", since))?,
+        ),
+        (Some(target), None) => match target.parse::<usize>() {
+            Ok(count) => UndoTarget::Last(count),
+            Err(_) => UndoTarget::Change(
+                PijulQuery::new(&project.pijul)
+                    .find_change(target)
+                    .with_context(|| format!("No change matching '{}'", target))?,
+            ),
+        },
+        (None, None) => UndoTarget::Last(1),
+    };
+
+    let request = ControlRequest::Undo { target };
+    run_on_working_copy(&mut project, &source_dir, request, |pijul| {
+        pijul
+            .undo(target)
+            .map(|report| describe_undo(&report))
+            .map_err(|e| describe_undo_error(&e))
+    })
+}
+
+/// Success message for `ize undo`
+fn describe_undo(report: &UndoReport) -> String {
+    match &report.kept_on {
+        Some(kept_on) => format!(
+            "Undid {} change(s); they are kept on channel '{}'\n\
+             To bring them back: ize channel merge {}",
+            report.undone.len(),
+            kept_on,
+            kept_on
+        ),
+        None => "Nothing to undo".to_string(),
+    }
+}
+
+/// Failure message for `ize undo`
+fn describe_undo_error(err: &PijulError) -> String {
+    match err {
+        PijulError::UnrecordedChanges(paths) => {
+            format!("Not undoing: unrecorded changes to {}", paths.join(", "))
+        }
+        e => format!("Failed to undo: {}", e),
+    }
+}
+
+/// List the current channel's unresolved conflicts, side by side
+fn cmd_conflicts(directory: Option<PathBuf>) -> Result<()> {
+    let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
+    let source_dir = get_source_dir(directory)?;
+    let project = get_project(&manager, &source_dir)?;
+
+    let conflicts = PijulQuery::new(&project.pijul)
+        .conflicts()
+        .with_context(|| "Failed to read conflicts")?;
+    if conflicts.is_empty() {
+        println!("✓ No conflicts on channel '{}'", project.current_channel());
+        return Ok(());
+    }
+
+    println!(
+        "{} file(s) with conflicts on channel '{}':",
+        conflicts.len(),
+        project.current_channel()
+    );
+    for file in conflicts {
+        println!();
+        if file.regions.is_empty() {
+            for conflict in &file.conflicts {
+                println!("  {}", conflict);
+            }
+            continue;
+        }
+        for region in &file.regions {
+            println!(
+                "  {}:{}-{}: {} sides",
+                file.path,
+                region.start_line,
+                region.end_line,
+                region.sides.len()
+            );
+            for side in &region.sides {
+                println!("    [{}]", side.changes.join(" "));
+                for line in side.lines.iter().take(CONFLICT_PREVIEW_LINES) {
+                    println!("      {}", line);
+                }
+                if side.lines.len() > CONFLICT_PREVIEW_LINES {
+                    println!(
+                        "      … {} more line(s)",
+                        side.lines.len() - CONFLICT_PREVIEW_LINES
+                    );
+                }
+            }
+        }
+    }
+
+    println!();
+    println!("Edit the files, markers included, to resolve; the edit is recorded as usual.");
+    Ok(())
+}
+
+/// Lines of each conflict side shown by `ize conflicts`
+const CONFLICT_PREVIEW_LINES: usize = 3;
+
+/// Run a command that rewrites the working copy and print its outcome
+///
+/// A running mount owns the working copy, so if the project is mounted the
+/// mount is sent `request` instead of running `local` here.
+fn run_on_working_copy(
+    project: &mut IzeProject,
+    source_dir: &PathBuf,
+    request: ControlRequest,
+    local: impl FnOnce(&mut PijulBackend) -> ControlResponse,
+) -> Result<()> {
+    let result = if is_fuse_mounted(source_dir)? {
+        control::send(&project.control_socket(), &request).with_context(|| {
+            format!(
+                "{} is mounted but its mount isn't accepting requests\n\
+                 Unmount with: ize unmount {:?}",
+                source_dir.display(),
+                source_dir
+            )
+        })?
+    } else {
+        local(&mut project.pijul)
+    };
+
+    match result {
+        Ok(message) => {
+            println!("✓ {}", message);
+            Ok(())
+        }
+        Err(message) => Err(anyhow::anyhow!(message)),
+    }
+}
+
 /// Remove a project from tracking
 fn cmd_remove(directory: &PathBuf, force: bool) -> Result<()> {
     let manager = ProjectManager::new().with_context(|| "Failed to create project manager")?;
@@ -615,6 +2076,14 @@ fn cmd_export_pijul(source: &PathBuf, target: &PathBuf) -> Result<()> {
     let pijul_target = target.join(".pijul");
     copy_dir_recursive(&pijul_source, &pijul_target)
         .with_context(|| format!("Failed to copy .pijul directory from {:?}", pijul_source))?;
+    if project.pijul.encryption().is_some() {
+        // Pijul reads plain change files
+        let changes_target = pijul_target.join(ize_lib::pijul::CHANGES_DIR);
+        fs::remove_dir_all(&changes_target)?;
+        copy_dir_recursive(&project.pijul.plain_changes_dir(), &changes_target)?;
+        fs::remove_file(pijul_target.join(ize_lib::pijul::ENCRYPTION_FILE))?;
+        println!("Note: change files are exported decrypted");
+    }
 
     // Copy working directory contents to target root
     let working_source = project.working_dir();